@@ -0,0 +1,81 @@
+//! Measures a full ammo table JSON round-trip, comparing the direct `EscadraString` deserializer
+//! against deserializing through an intermediate `String`.
+//!
+//! Run with `cargo bench --bench ammo_table`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use highfleet::v1_163::{Ammo, EscadraString};
+use serde::Deserialize;
+
+const AMMO_COUNT: usize = 64;
+const ITERATIONS: u32 = 2000;
+
+/// Deserializes the way `EscadraString` used to, through `#[serde(from = "String")]`.
+#[derive(Deserialize)]
+#[serde(from = "String")]
+struct ViaString(#[allow(dead_code)] EscadraString);
+
+impl From<String> for ViaString {
+    fn from(value: String) -> Self {
+        Self(EscadraString::from(value))
+    }
+}
+
+fn ammo_json(index: usize) -> String {
+    format!(
+        r#"{{
+            "reticle": 1, "padding_4h": 0,
+            "item_name": "AMMO_{index}", "shell_kind": "Incendiary", "shell_kind2": "@INCENDIARY",
+            "milimeterage": "57mm", "magazine_image": "shell_57mm_incendiary_{index}",
+            "sign_ammo": "sign_ammo_inc", "bullet_height": 24.0, "padding_cch": 0,
+            "shell_in": "shell_in_small", "shell_out": "shell_out_small2",
+            "shell_enemy": "shell_out_enemy_med", "shell_far": "shell_out_small_far",
+            "caliber": 130, "index": {index}, "speed": 1200.0, "ap_drag": 0.0007,
+            "explosive_power": 10.0, "penetrative_power": 2.0, "incendiary_power": 1000.0,
+            "ttl": 6.0, "shop_price": 40, "shop_rarity": 0.0, "shop_ammount": 0.0,
+            "fire_delay": 0.5, "unknown_180h": 10, "padding_184h": 0
+        }}"#
+    )
+}
+
+fn bench(name: &str, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<32} {:>10.2?} / iter", elapsed / ITERATIONS);
+    elapsed
+}
+
+fn main() {
+    let json = format!(
+        "[{}]",
+        (0..AMMO_COUNT).map(ammo_json).collect::<Vec<_>>().join(",")
+    );
+    let strings: Vec<String> = serde_json::from_str::<Vec<serde_json::Value>>(&json)
+        .unwrap()
+        .iter()
+        .flat_map(|ammo| ammo.as_object().unwrap().values().cloned())
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect();
+    let strings_json = serde_json::to_string(&strings).unwrap();
+
+    let via_string = bench("strings via String", || {
+        black_box(serde_json::from_str::<Vec<ViaString>>(black_box(&strings_json)).unwrap());
+    });
+    let direct = bench("strings direct", || {
+        black_box(serde_json::from_str::<Vec<EscadraString>>(black_box(&strings_json)).unwrap());
+    });
+    println!(
+        "speedup: {:.2}x",
+        via_string.as_secs_f64() / direct.as_secs_f64()
+    );
+
+    bench("ammo table round-trip", || {
+        let table: Vec<Ammo> = serde_json::from_str(black_box(&json)).unwrap();
+        black_box(serde_json::to_string(&table).unwrap());
+    });
+}
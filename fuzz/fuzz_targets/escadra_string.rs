@@ -0,0 +1,11 @@
+//! Runs random set, push, truncate, clear, reserve, clone and drop sequences on `EscadraString`s.
+//!
+//! `cargo fuzz` builds with `--cfg fuzzing`, so the strings are allocated with `RustAllocator` and checked by ASan.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    highfleet::general::escadra_string::fuzz::check_operations(data);
+});
@@ -0,0 +1,446 @@
+//! Derive macros for the game structs of the `highfleet` crate.
+//!
+//! Use them through `highfleet::general::GameStruct`, which re-exports them.
+
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, ExprLit, Fields, Lit, LitInt,
+    LitStr, Meta, Type,
+};
+
+/// Implements the traits every struct shared with the game needs, from the layout given in attributes.
+///
+/// - `#[size(0x188)]` on the struct and `#[offset(0x08)]` on its fields assert the layout at compile time,
+///   like `assert_layout!`. Fields without an offset aren't checked.
+/// - `Annotated` lists every field, for hex dumps.
+/// - `Diff` compares every field by its `Display` form.
+/// - `Inspect` lists every field with its value, formatted with `Display`.
+/// - `RawLayout` validates, resolves, and writes the `EscadraString` fields,
+///   and any other field marked with `#[raw]`, which must implement `RawLayout` itself.
+/// - `Decode` reads every field at its `#[offset]`, whatever the host's layout.
+///   Only implemented when the struct has a `#[size]` and every field an `#[offset]`.
+/// - `FieldNotes` records the doc comment and serde aliases of every field, and how much is known about it,
+///   guessed from the docs unless given like `#[provenance(suspected)]`.
+///
+/// The struct must be `#[repr(C)]` and have named fields.
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(GameStruct)]
+/// #[size(0x28)]
+/// pub struct Example {
+///     #[offset(0x00)]
+///     pub value: i32,
+///     #[offset(0x08)]
+///     pub name: EscadraString,
+/// }
+/// ```
+#[proc_macro_derive(GameStruct, attributes(size, offset, raw, provenance))]
+pub fn derive_game_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    game_struct(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// A field of the struct and what its attributes say.
+struct GameField<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a Type,
+    offset: Option<LitInt>,
+    raw: bool,
+    docs: String,
+    aliases: Vec<LitStr>,
+    provenance: Option<syn::Ident>,
+}
+
+fn game_struct(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(input, "GameStruct needs a struct"));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(Error::new_spanned(input, "GameStruct needs named fields"));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "GameStruct doesn't support generics",
+        ));
+    }
+    if !is_repr_c(&input.attrs) {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "GameStruct needs #[repr(C)]",
+        ));
+    }
+
+    let size = attribute_int(&input.attrs, "size")?;
+    let fields = named
+        .named
+        .iter()
+        .map(|field| {
+            Ok(GameField {
+                ident: field.ident.as_ref().unwrap(),
+                ty: &field.ty,
+                offset: attribute_int(&field.attrs, "offset")?,
+                raw: is_escadra_string(&field.ty)
+                    || field.attrs.iter().any(|attr| attr.path().is_ident("raw")),
+                docs: docs(&field.attrs),
+                aliases: aliases(&field.attrs),
+                provenance: field
+                    .attrs
+                    .iter()
+                    .find(|attr| attr.path().is_ident("provenance"))
+                    .map(|attr| attr.parse_args::<syn::Ident>())
+                    .transpose()?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let ident = &input.ident;
+    let layout = layout(ident, size.as_ref(), &fields);
+    let annotated = annotated(ident, &fields);
+    let diff = diff(ident, &fields);
+    let inspect = inspect(ident, &fields);
+    let raw_layout = raw_layout(ident, &fields);
+    let field_notes = field_notes(ident, &fields)?;
+    let decode = decode(ident, size.as_ref(), &fields);
+
+    Ok(quote! {
+        #layout
+        #annotated
+        #diff
+        #inspect
+        #raw_layout
+        #field_notes
+        #decode
+    })
+}
+
+fn layout(ident: &syn::Ident, size: Option<&LitInt>, fields: &[GameField]) -> TokenStream2 {
+    let size = size.map(|size| {
+        let message = format!("wrong size of {ident}");
+        quote! { assert!(::core::mem::size_of::<#ident>() == #size, #message); }
+    });
+    let offsets = fields.iter().filter_map(|field| {
+        let name = field.ident;
+        let offset = field.offset.as_ref()?;
+        let message = format!("wrong offset of {ident}::{name}");
+        Some(quote! { assert!(::core::mem::offset_of!(#ident, #name) == #offset, #message); })
+    });
+
+    // Layouts only match the 64-bit game on 64-bit targets, like `assert_layout!`.
+    quote! {
+        #[cfg(target_pointer_width = "64")]
+        const _: () = {
+            #size
+            #(#offsets)*
+        };
+    }
+}
+
+fn annotated(ident: &syn::Ident, fields: &[GameField]) -> TokenStream2 {
+    let infos = fields.iter().map(|field| {
+        let name = field.ident;
+        quote! {
+            ::highfleet::general::hexdump::FieldInfo::of(
+                stringify!(#name),
+                ::core::mem::offset_of!(#ident, #name),
+                |value: &#ident| &value.#name,
+            )
+        }
+    });
+
+    quote! {
+        impl ::highfleet::general::hexdump::Annotated for #ident {
+            fn fields() -> ::highfleet::__private::Vec<::highfleet::general::hexdump::FieldInfo> {
+                ::highfleet::__private::vec![#(#infos),*]
+            }
+        }
+    }
+}
+
+fn diff(ident: &syn::Ident, fields: &[GameField]) -> TokenStream2 {
+    let comparisons = fields.iter().map(|field| {
+        let name = field.ident;
+        quote! {
+            let old = ::highfleet::__private::ToString::to_string(&self.#name);
+            let new = ::highfleet::__private::ToString::to_string(&other.#name);
+            if old != new {
+                diffs.push(::highfleet::general::diff::FieldDiff {
+                    field: stringify!(#name),
+                    old,
+                    new,
+                });
+            }
+        }
+    });
+
+    quote! {
+        impl ::highfleet::general::diff::Diff for #ident {
+            fn diff(&self, other: &Self) -> ::highfleet::__private::Vec<::highfleet::general::diff::FieldDiff> {
+                let mut diffs = ::highfleet::__private::Vec::new();
+                #(#comparisons)*
+                diffs
+            }
+        }
+    }
+}
+
+fn inspect(ident: &syn::Ident, fields: &[GameField]) -> TokenStream2 {
+    let values = fields.iter().map(|field| {
+        let name = field.ident;
+        quote! {
+            ::highfleet::general::inspect::FieldValue {
+                info: ::highfleet::general::hexdump::FieldInfo::of(
+                    stringify!(#name),
+                    ::core::mem::offset_of!(#ident, #name),
+                    |value: &#ident| &value.#name,
+                ),
+                value: ::highfleet::__private::ToString::to_string(&self.#name),
+            }
+        }
+    });
+
+    quote! {
+        impl ::highfleet::general::inspect::Inspect for #ident {
+            fn inspect(&self) -> ::highfleet::__private::Vec<::highfleet::general::inspect::FieldValue> {
+                ::highfleet::__private::vec![#(#values),*]
+            }
+        }
+    }
+}
+
+fn raw_layout(ident: &syn::Ident, fields: &[GameField]) -> TokenStream2 {
+    let raw: Vec<_> = fields.iter().filter(|field| field.raw).collect();
+    let names: Vec<_> = raw.iter().map(|field| field.ident).collect();
+    let types: Vec<_> = raw.iter().map(|field| field.ty).collect();
+    let validate = quote! {
+        #(
+            ::highfleet::general::raw::validate_field::<#types>(
+                bytes,
+                ::core::mem::offset_of!(#ident, #names),
+            )?;
+        )*
+    };
+    let resolve = quote! {
+        #(
+            ::highfleet::general::raw::resolve_field::<_, #types>(
+                self,
+                ::core::mem::offset_of!(#ident, #names),
+                policy,
+            )?;
+        )*
+    };
+    let write = quote! {
+        #(
+            unsafe {
+                ::highfleet::general::raw::write_field::<_, #types>(
+                    self,
+                    bytes,
+                    ::core::mem::offset_of!(#ident, #names),
+                    policy,
+                )
+            };
+        )*
+    };
+
+    quote! {
+        #[allow(unused_variables)]
+        unsafe impl ::highfleet::general::raw::RawLayout for #ident {
+            fn validate(bytes: &[u8]) -> ::core::result::Result<(), ::highfleet::general::raw::RawError> {
+                #validate
+                Ok(())
+            }
+
+            unsafe fn resolve_pointers(
+                &mut self,
+                policy: &::highfleet::general::raw::PointerPolicy,
+            ) -> ::core::result::Result<(), ::highfleet::general::raw::RawError> {
+                #resolve
+                Ok(())
+            }
+
+            fn write_pointers(
+                &self,
+                bytes: &mut [u8],
+                policy: &::highfleet::general::raw::PointerPolicy,
+            ) {
+                #write
+            }
+        }
+    }
+}
+
+fn decode(ident: &syn::Ident, size: Option<&LitInt>, fields: &[GameField]) -> TokenStream2 {
+    let Some(size) = size else {
+        return TokenStream2::new();
+    };
+    let Some(offsets) = fields
+        .iter()
+        .map(|field| field.offset.as_ref())
+        .collect::<Option<Vec<_>>>()
+    else {
+        return TokenStream2::new();
+    };
+    let names = fields.iter().map(|field| field.ident);
+    let types = fields.iter().map(|field| field.ty);
+
+    quote! {
+        impl ::highfleet::general::offline::Decode for #ident {
+            const GAME_SIZE: usize = #size;
+
+            fn decode(
+                bytes: &[u8],
+                policy: &::highfleet::general::raw::PointerPolicy,
+            ) -> ::core::result::Result<Self, ::highfleet::general::raw::RawError> {
+                Ok(Self {
+                    #(
+                        #names: ::highfleet::general::offline::decode_field::<#types>(bytes, #offsets, policy)?,
+                    )*
+                })
+            }
+        }
+    }
+}
+
+fn field_notes(ident: &syn::Ident, fields: &[GameField]) -> syn::Result<TokenStream2> {
+    let notes = fields
+        .iter()
+        .map(|field| {
+            let name = field.ident;
+            let provenance = provenance(field)?;
+            let aliases = &field.aliases;
+            let docs = &field.docs;
+            Ok(quote! {
+                ::highfleet::general::provenance::FieldNote {
+                    name: stringify!(#name),
+                    offset: ::core::mem::offset_of!(#ident, #name),
+                    provenance: ::highfleet::general::provenance::Provenance::#provenance,
+                    aliases: &[#(#aliases),*],
+                    notes: #docs,
+                }
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl ::highfleet::general::provenance::FieldNotes for #ident {
+            const FIELD_NOTES: &'static [::highfleet::general::provenance::FieldNote] = &[#(#notes),*];
+        }
+    })
+}
+
+/// Returns the `Provenance` variant of a field, from `#[provenance(...)]` or else from its name and docs.
+fn provenance(field: &GameField) -> syn::Result<syn::Ident> {
+    let variant = match &field.provenance {
+        Some(given) => match given.to_string().as_str() {
+            "confirmed" => "Confirmed",
+            "suspected" => "Suspected",
+            "unknown" => "Unknown",
+            _ => {
+                return Err(Error::new_spanned(
+                    given,
+                    "expected confirmed, suspected, or unknown",
+                ))
+            }
+        },
+        None if field.ident.to_string().starts_with("unknown_")
+            || field.docs.contains("unknown purpose") =>
+        {
+            "Unknown"
+        }
+        None if field
+            .docs
+            .lines()
+            .next()
+            .is_some_and(|summary| summary.trim_end().ends_with('?')) =>
+        {
+            "Suspected"
+        }
+        None => "Confirmed",
+    };
+    Ok(syn::Ident::new(variant, field.ident.span()))
+}
+
+/// Joins the lines of the doc comments, without the space following `///`.
+fn docs(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("doc") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(line),
+                    ..
+                }) => {
+                    let line = line.value();
+                    Some(line.strip_prefix(' ').unwrap_or(&line).to_string())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Returns the names given by `#[serde(alias = "...")]`.
+fn aliases(attrs: &[Attribute]) -> Vec<LitStr> {
+    let mut aliases = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let Ok(tokens) = attr.parse_args::<TokenStream2>() else {
+            continue;
+        };
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+        for window in tokens.windows(3) {
+            if let [TokenTree::Ident(ident), TokenTree::Punct(punct), TokenTree::Literal(literal)] =
+                window
+            {
+                if ident == "alias" && punct.as_char() == '=' {
+                    if let Ok(alias) =
+                        syn::parse2::<LitStr>(TokenTree::Literal(literal.clone()).into())
+                    {
+                        aliases.push(alias);
+                    }
+                }
+            }
+        }
+    }
+    aliases
+}
+
+/// Reads the integer of an attribute like `#[size(0x188)]`.
+fn attribute_int(attrs: &[Attribute], name: &str) -> syn::Result<Option<LitInt>> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident(name))
+        .map(|attr| attr.parse_args::<LitInt>())
+        .transpose()
+}
+
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("repr")
+            && attr.parse_args::<TokenStream2>().is_ok_and(|tokens| {
+                tokens
+                    .into_iter()
+                    .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "C"))
+            })
+    })
+}
+
+/// Returns true for a type named `EscadraString`, whatever its path.
+fn is_escadra_string(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "EscadraString"),
+        _ => false,
+    }
+}
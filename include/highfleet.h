@@ -0,0 +1,165 @@
+#ifndef HIGHFLEET_H
+#define HIGHFLEET_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Version 1.151.
+#define HF_VERSION_1_151 0
+
+// Version 1.163.
+#define HF_VERSION_1_163 1
+
+// The kind of an ammo field.
+typedef enum HfFieldKind {
+  // Not a field, returned for invalid ids.
+  HfFieldNone,
+  // A `bool`.
+  HfFieldBool,
+  // A `uint16_t`.
+  HfFieldU16,
+  // An `int32_t`.
+  HfFieldI32,
+  // A `uint32_t`.
+  HfFieldU32,
+  // A `uint64_t`.
+  HfFieldU64,
+  // A `float`.
+  HfFieldF32,
+  // A pointer.
+  HfFieldPointer,
+  // An `EscadraString`.
+  HfFieldString,
+  // Bytes without a known meaning.
+  HfFieldBytes,
+} HfFieldKind;
+
+// An escadra string is a variable length string.
+// If the max-length of the escadra string (without the null terminator) exceeds 15 the string is stored as a pointer to memory.
+// Otherwise, the string is stored within the struct itself.
+//
+// The string should always be null terminated.
+// The `max_length` is 15 by default.
+typedef struct EscadraString EscadraString;
+
+// Represents an element in a triply linked list.
+// The only current known use is to hold Airplane loadout information and for keyboard input information.
+typedef struct TLL TLL;
+
+// A TLL and its links, as written by `hf_tll_explore`.
+typedef struct HfTllNode {
+  // The TLL.
+  const TLL *tll;
+  // Its a pointer.
+  const TLL *a;
+  // Its b pointer.
+  const TLL *b;
+  // Its c pointer.
+  const TLL *c;
+  // The index it holds.
+  uint32_t index;
+} HfTllNode;
+
+// Creates an `EscadraString` holding a copy of a nul terminated UTF-8 string.
+//
+// Returns null if `text` is null or not UTF-8. Free the string with `hf_string_free`.
+//
+// # Safety
+//
+// `text` must be null or a nul terminated string.
+EscadraString *hf_string_new(const char *text);
+
+// Frees an `EscadraString` created by `hf_string_new`. Does nothing if `string` is null.
+//
+// # Safety
+//
+// `string` must be null or returned by `hf_string_new`, and not freed before.
+void hf_string_free(EscadraString *string);
+
+// Returns the nul terminated bytes of an `EscadraString`, valid until it is changed or freed.
+//
+// # Safety
+//
+// `string` must be null or point to a valid `EscadraString`.
+const char *hf_string_data(const EscadraString *string);
+
+// Returns the length of an `EscadraString` in bytes, without the nul terminator.
+//
+// # Safety
+//
+// `string` must be null or point to a valid `EscadraString`.
+size_t hf_string_len(const EscadraString *string);
+
+// Replaces the text of an `EscadraString` with a nul terminated UTF-8 string.
+//
+// Returns false if a pointer is null or `text` is not UTF-8.
+//
+// # Safety
+//
+// `string` must be null or point to a valid `EscadraString`, `text` must be null or a nul terminated string.
+bool hf_string_set(EscadraString *string, const char *text);
+
+// Returns the size of an ammo of a version, or 0 for an unknown version.
+size_t hf_ammo_size(uint32_t version_id);
+
+// Returns the number of fields of an ammo of a version, or 0 for an unknown version.
+size_t hf_ammo_field_count(uint32_t version);
+
+// Returns the id of the ammo field with a name, or -1 if there is none.
+//
+// # Safety
+//
+// `name` must be null or a nul terminated string.
+intptr_t hf_ammo_field_id(uint32_t version, const char *name);
+
+// Returns the name of an ammo field, or null for an invalid id. The name lives for the rest of the program.
+const char *hf_ammo_field_name(uint32_t version, size_t id);
+
+// Returns the offset of an ammo field, or -1 for an invalid id.
+intptr_t hf_ammo_field_offset(uint32_t version, size_t id);
+
+// Returns the kind of an ammo field, or `HfFieldNone` for an invalid id.
+HfFieldKind hf_ammo_field_kind(uint32_t version, size_t id);
+
+// Reads a number or bool field of an ammo into `value`.
+//
+// Returns false if a pointer is null, the id is invalid, or the field is a string.
+//
+// # Safety
+//
+// `ammo` must be null or point to a valid ammo of the version, `value` must be null or writable.
+bool hf_ammo_get_number(uint32_t version, const void *ammo, size_t id, double *value);
+
+// Writes a number or bool field of an ammo.
+//
+// Returns false if `ammo` is null, the id is invalid, the field is a string,
+// or the value doesn't fit into an integer field.
+//
+// # Safety
+//
+// `ammo` must be null or point to a valid ammo of the version.
+bool hf_ammo_set_number(uint32_t version, void *ammo, size_t id, double value);
+
+// Returns the `EscadraString` of a string field of an ammo, or null if the field isn't a string.
+//
+// The string can be read with `hf_string_data` and changed with `hf_string_set`.
+//
+// # Safety
+//
+// `ammo` must be null or point to a valid ammo of the version.
+EscadraString *hf_ammo_get_string(uint32_t version, void *ammo, size_t id);
+
+// Writes every TLL reachable from `root` into `nodes`, depth first like `TLL::iter`.
+//
+// Returns the number of TLLs reachable, of which at most `capacity` are written,
+// so calling it with a `capacity` of 0 tells how large `nodes` must be.
+// Returns -1 if `root` is null or more than `limit` TLLs are reachable.
+//
+// # Safety
+//
+// `root` must be null or point to a valid TLL structure,
+// `nodes` must be null or writable for `capacity` nodes.
+intptr_t hf_tll_explore(const TLL *root, size_t limit, HfTllNode *nodes, size_t capacity);
+
+#endif /* HIGHFLEET_H */
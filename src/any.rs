@@ -0,0 +1,116 @@
+//! Defines types that work with the structs of any game version, picked at runtime.
+//!
+//! Combined with `general::version::detect` this lets a single mod support every game version.
+
+use core::mem::size_of;
+use core::ops::{Deref, DerefMut};
+
+use crate::general::traits::AmmoFields;
+use crate::general::version::GameVersion;
+use crate::{v1_151, v1_163};
+
+/// An `Ammo` of whichever game version is running.
+///
+/// Derefs to `AmmoFields`, so the fields common to every version can be used directly.
+/// Match on the variants for the fields of a specific version.
+#[derive(Debug)]
+pub enum AnyAmmo<'a> {
+    /// An ammo of version 1.151.
+    V1_151(&'a mut v1_151::Ammo),
+    /// An ammo of version 1.163.
+    V1_163(&'a mut v1_163::Ammo),
+}
+
+impl<'a> AnyAmmo<'a> {
+    /// Views the ammo at `pointer` as the `Ammo` of the given version.
+    ///
+    /// Returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must point to a valid `Ammo` of the given version that outlives `'a`, and isn't accessed through other references.
+    pub unsafe fn from_ptr(version: GameVersion, pointer: *mut u8) -> Option<Self> {
+        if pointer.is_null() {
+            return None;
+        }
+
+        Some(match version {
+            GameVersion::V1_151 => Self::V1_151(&mut *(pointer as *mut v1_151::Ammo)),
+            GameVersion::V1_163 => Self::V1_163(&mut *(pointer as *mut v1_163::Ammo)),
+        })
+    }
+
+    /// Returns the size of an `Ammo` of the given version, which is the stride of an array of them.
+    pub fn size(version: GameVersion) -> usize {
+        match version {
+            GameVersion::V1_151 => size_of::<v1_151::Ammo>(),
+            GameVersion::V1_163 => size_of::<v1_163::Ammo>(),
+        }
+    }
+
+    /// Returns the game version of the ammo.
+    pub fn version(&self) -> GameVersion {
+        match self {
+            Self::V1_151(_) => GameVersion::V1_151,
+            Self::V1_163(_) => GameVersion::V1_163,
+        }
+    }
+}
+
+impl Deref for AnyAmmo<'_> {
+    type Target = dyn AmmoFields;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::V1_151(ammo) => *ammo,
+            Self::V1_163(ammo) => *ammo,
+        }
+    }
+}
+
+impl DerefMut for AnyAmmo<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::V1_151(ammo) => *ammo,
+            Self::V1_163(ammo) => *ammo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_by_version() {
+        for version in GameVersion::ALL {
+            // An aligned, zeroed buffer holding one ammo.
+            let mut buffer = vec![0u64; AnyAmmo::size(version) / 8];
+            let pointer = buffer.as_mut_ptr() as *mut u8;
+
+            let mut ammo = unsafe { AnyAmmo::from_ptr(version, pointer).unwrap() };
+            assert_eq!(ammo.version(), version);
+
+            ammo.set_speed(1200.0);
+            ammo.item_name_mut().push_str("AMMO_57");
+            assert_eq!(ammo.speed(), 1200.0);
+            assert_eq!(ammo.item_name(), "AMMO_57");
+
+            let speed_offset = match version {
+                GameVersion::V1_151 => core::mem::offset_of!(v1_151::Ammo, speed),
+                GameVersion::V1_163 => core::mem::offset_of!(v1_163::Ammo, speed),
+            };
+            let bytes = unsafe { core::slice::from_raw_parts(pointer, AnyAmmo::size(version)) };
+            assert_eq!(
+                bytes[speed_offset..speed_offset + 4],
+                1200.0f32.to_le_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn null_is_none() {
+        let ammo = unsafe { AnyAmmo::from_ptr(GameVersion::V1_163, core::ptr::null_mut()) };
+        assert!(ammo.is_none());
+    }
+}
@@ -0,0 +1,208 @@
+//! Estimates how a shell flies from its ammo stats, to see the effect of a balance change without launching the game.
+//!
+//! The game's flight code hasn't been reverse engineered, so the model is an assumption built on the documented fields:
+//! - The shell leaves at `speed` units per second. Guns fire as the trigger is pulled,
+//!   `fire_delay` only delays plane payloads, as its field documents.
+//! - `ap_drag` slows it down proportionally to its speed, every second: `v(t) = speed * e^(-ap_drag * t)`.
+//! - It disappears after `ttl` seconds of flight.
+//! - Its penetrative power scales with its remaining speed, the other powers don't change in flight.
+//!
+//! Absolute numbers may be off, but comparing two versions of an ammo shows which one flies further or faster.
+//!
+//! ```
+//! use highfleet::calc::Shell;
+//! use highfleet::v1_163::AmmoBuilder;
+//!
+//! let mut ammo = AmmoBuilder::new()
+//!     .item_name("AMMO_57")
+//!     .index(5)
+//!     .magazine_image("shell_57")
+//!     .speed(1000.0)
+//!     .ttl(2.0)
+//!     .build()
+//!     .unwrap();
+//! let before = Shell::from(&ammo).max_range();
+//! ammo.ap_drag = 0.5;
+//! assert!(Shell::from(&ammo).max_range() < before);
+//! ```
+//!
+//! Only available with the `std` feature, which provides the exponential functions.
+
+use crate::v1_163::Ammo;
+
+/// The stats of an ammo that affect its flight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shell {
+    /// The speed the shell leaves the gun at, in units per second.
+    pub speed: f64,
+    /// How much of its speed the shell loses every second, see the module docs.
+    pub drag: f64,
+    /// How long the shell lasts in the air, in seconds.
+    pub ttl: f64,
+    /// How long a plane waits before releasing its payload, in seconds. Guns are unaffected.
+    pub fire_delay: f64,
+    /// The explosive power of the shell.
+    pub explosive_power: f64,
+    /// The penetrative power of the shell when it leaves the gun.
+    pub penetrative_power: f64,
+    /// The incendiary power of the shell.
+    pub incendiary_power: f64,
+}
+
+/// What a shell is estimated to be like when it hits a target, returned by `Shell::impact`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impact {
+    /// The time from the shell leaving to the hit, in seconds.
+    pub time: f64,
+    /// The speed of the shell at the hit.
+    pub speed: f64,
+    /// The explosive power of the shell.
+    pub explosive_power: f64,
+    /// The penetrative power left at the hit.
+    pub penetrative_power: f64,
+    /// The incendiary power of the shell.
+    pub incendiary_power: f64,
+}
+
+impl Shell {
+    /// Returns the speed of the shell after flying for `time` seconds.
+    pub fn speed_at(&self, time: f64) -> f64 {
+        self.speed * (-self.drag * time).exp()
+    }
+
+    /// Returns how far the shell has flown after `time` seconds.
+    pub fn distance_at(&self, time: f64) -> f64 {
+        if self.drag == 0.0 {
+            self.speed * time
+        } else {
+            self.speed / self.drag * (1.0 - (-self.drag * time).exp())
+        }
+    }
+
+    /// Returns how far the shell flies before it disappears.
+    pub fn max_range(&self) -> f64 {
+        self.distance_at(self.ttl)
+    }
+
+    /// Returns how long the shell flies to reach `distance`, or `None` if it disappears or stops before.
+    pub fn flight_time(&self, distance: f64) -> Option<f64> {
+        let time = if self.drag == 0.0 {
+            distance / self.speed
+        } else {
+            // Inverts `distance_at`, the shell never gets further than `speed / drag`.
+            let left = 1.0 - distance * self.drag / self.speed;
+            if left <= 0.0 {
+                return None;
+            }
+            -left.ln() / self.drag
+        };
+        (time.is_finite() && time >= 0.0 && time <= self.ttl).then_some(time)
+    }
+
+    /// Returns the time from firing a gun to hitting a target at `distance`, or `None` if out of range.
+    ///
+    /// Guns fire as the trigger is pulled, so this is the flight time. See `payload_time_to_target` for planes.
+    pub fn time_to_target(&self, distance: f64) -> Option<f64> {
+        self.flight_time(distance)
+    }
+
+    /// Returns the time from a plane's order to drop its payload to the payload hitting a target at `distance`,
+    /// or `None` if out of range. The payload is released after `fire_delay`.
+    pub fn payload_time_to_target(&self, distance: f64) -> Option<f64> {
+        Some(self.fire_delay + self.flight_time(distance)?)
+    }
+
+    /// Estimates the shell when it hits a target at `distance`, or `None` if out of range.
+    pub fn impact(&self, distance: f64) -> Option<Impact> {
+        let flight_time = self.flight_time(distance)?;
+        let speed = self.speed_at(flight_time);
+        let penetrative_power = if self.speed == 0.0 {
+            0.0
+        } else {
+            self.penetrative_power * speed / self.speed
+        };
+
+        Some(Impact {
+            time: flight_time,
+            speed,
+            explosive_power: self.explosive_power,
+            penetrative_power,
+            incendiary_power: self.incendiary_power,
+        })
+    }
+}
+
+impl From<&Ammo> for Shell {
+    fn from(ammo: &Ammo) -> Self {
+        Self {
+            speed: ammo.speed.into(),
+            drag: ammo.ap_drag.into(),
+            ttl: ammo.ttl.into(),
+            fire_delay: ammo.fire_delay.into(),
+            explosive_power: ammo.explosive_power.into(),
+            penetrative_power: ammo.penetrative_power.into(),
+            incendiary_power: ammo.incendiary_power.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(drag: f64) -> Shell {
+        Shell {
+            speed: 1000.0,
+            drag,
+            ttl: 2.0,
+            fire_delay: 0.5,
+            explosive_power: 10.0,
+            penetrative_power: 100.0,
+            incendiary_power: 100.0,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn flies_straight_without_drag() {
+        let shell = shell(0.0);
+        assert_eq!(shell.max_range(), 2000.0);
+        assert_eq!(shell.time_to_target(500.0), Some(0.5));
+        assert_eq!(shell.payload_time_to_target(500.0), Some(1.0));
+        assert_eq!(shell.time_to_target(2500.0), None);
+
+        let impact = shell.impact(1000.0).unwrap();
+        assert_eq!(impact.time, 1.0);
+        assert_eq!(impact.speed, 1000.0);
+        assert_eq!(impact.penetrative_power, 100.0);
+    }
+
+    #[test]
+    fn slows_down_with_drag() {
+        let shell = shell(0.5);
+        let range = shell.max_range();
+        assert!(close(range, 2000.0 * (1.0 - (-1.0f64).exp())));
+
+        let time = shell.flight_time(range / 2.0).unwrap();
+        assert!(close(shell.distance_at(time), range / 2.0));
+        assert!(shell.flight_time(range + 1.0).is_none());
+
+        let impact = shell.impact(range).unwrap();
+        assert!(close(impact.speed, 1000.0 * (-1.0f64).exp()));
+        assert!(close(impact.penetrative_power, 100.0 * (-1.0f64).exp()));
+        assert_eq!(impact.explosive_power, 10.0);
+    }
+
+    #[test]
+    fn never_reaches_past_the_drag_limit() {
+        let shell = Shell {
+            ttl: f64::INFINITY,
+            ..shell(1.0)
+        };
+        assert!(shell.flight_time(999.0).is_some());
+        assert!(shell.flight_time(1000.0).is_none());
+    }
+}
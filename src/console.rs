@@ -0,0 +1,660 @@
+//! A command console for live modding, such as `ammo set AMMO_57 explosive_power 500`.
+//!
+//! A `Console` maps command names to handlers, which get the mod's state and the parsed arguments.
+//! `ammo_get`, `ammo_set`, and `ammo_list` are ready made handlers for an `AmmoTable`.
+//!
+//! The game has no console, so a `CommandServer` takes commands over a local TCP connection instead,
+//! one per line, e.g. with `nc 127.0.0.1 7878`. The first line must be a token chosen by the mod.
+//! Commands are queued and run by `CommandServer::poll`, which takes a `GameThread` since handlers mutate
+//! live game structs.
+//!
+//! ```ignore
+//! let mut console = Console::new();
+//! console.register("ammo set", "<ammo> <field> <value>", |ammos: &mut AmmoTable<Ammo>, args| {
+//!     highfleet::console::ammo_set(ammos, args)
+//! });
+//! let token = std::fs::read_to_string("console_token.txt")?;
+//! let server = CommandServer::bind("127.0.0.1:7878", &token)?;
+//!
+//! // Every frame, from a hook on the game's main loop:
+//! server.poll(&console, &mut ammos, &thread);
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::general::{AmmoFields, AmmoTable, GameThread, Patch};
+
+/// Returned when running a command fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// No command matches the line.
+    UnknownCommand(String),
+    /// The line has unbalanced quotes.
+    UnclosedQuote,
+    /// The command needs more arguments.
+    MissingArgument {
+        /// The position of the missing argument.
+        index: usize,
+        /// The arguments the command takes.
+        usage: &'static str,
+    },
+    /// An argument couldn't be parsed.
+    InvalidArgument {
+        /// The position of the argument.
+        index: usize,
+        /// The argument as given.
+        value: String,
+        /// Why it couldn't be parsed.
+        message: String,
+    },
+    /// The command ran but failed.
+    Failed(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(command) => write!(f, "unknown command {command}, try help"),
+            Self::UnclosedQuote => write!(f, "unclosed quote"),
+            Self::MissingArgument { index, usage } => {
+                write!(f, "missing argument {}, expected {usage}", index + 1)
+            }
+            Self::InvalidArgument {
+                index,
+                value,
+                message,
+            } => write!(f, "invalid argument {} {value:?}: {message}", index + 1),
+            Self::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+/// The arguments of a command, after its name.
+///
+/// Arguments are separated by whitespace. Double quotes group words into one argument, like `"Laser Guided"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    words: Vec<String>,
+    usage: &'static str,
+}
+
+impl Args {
+    /// Returns the number of arguments.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns true if there are no arguments.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns the argument at `index`, or `None` if there are fewer arguments.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.words.get(index).map(String::as_str)
+    }
+
+    /// Returns the argument at `index`, or an error naming the usage of the command.
+    pub fn required(&self, index: usize) -> Result<&str, CommandError> {
+        self.get(index).ok_or(CommandError::MissingArgument {
+            index,
+            usage: self.usage,
+        })
+    }
+
+    /// Parses the argument at `index`.
+    pub fn parse<T: FromStr>(&self, index: usize) -> Result<T, CommandError>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = self.required(index)?;
+        value
+            .parse()
+            .map_err(|error: T::Err| CommandError::InvalidArgument {
+                index,
+                value: value.to_string(),
+                message: error.to_string(),
+            })
+    }
+}
+
+/// Splits a line into words, keeping quoted words together.
+fn split(line: &str) -> Result<Vec<String>, CommandError> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err(CommandError::UnclosedQuote);
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+type Handler<S> = Box<dyn Fn(&mut S, &Args) -> Result<String, CommandError> + Send + Sync>;
+
+struct Command<S> {
+    usage: &'static str,
+    handler: Handler<S>,
+}
+
+/// Maps command names to handlers working on the mod's state `S`.
+pub struct Console<S> {
+    commands: BTreeMap<String, Command<S>>,
+}
+
+impl<S> Default for Console<S> {
+    fn default() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S> Console<S> {
+    /// Creates a console with only the built in `help` command.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command, replacing any other command with the same name.
+    ///
+    /// The name may be several words, like `"ammo set"`. `usage` describes the arguments, for `help` and errors.
+    pub fn register(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        handler: impl Fn(&mut S, &Args) -> Result<String, CommandError> + Send + Sync + 'static,
+    ) {
+        let name = split(name).unwrap_or_default().join(" ");
+        self.commands.insert(
+            name,
+            Command {
+                usage,
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    /// Returns the registered commands with their usage, ordered by name.
+    pub fn commands(&self) -> impl Iterator<Item = (&str, &'static str)> {
+        self.commands
+            .iter()
+            .map(|(name, command)| (name.as_str(), command.usage))
+    }
+
+    /// Runs a line, returning the output of the command.
+    ///
+    /// The command with the longest name the line starts with is run. `help` lists every command,
+    /// unless a command named `help` was registered.
+    pub fn execute(&self, state: &mut S, line: &str) -> Result<String, CommandError> {
+        let words = split(line)?;
+
+        for length in (1..=words.len()).rev() {
+            let name = words[..length].join(" ");
+            if let Some(command) = self.commands.get(&name) {
+                let args = Args {
+                    words: words[length..].to_vec(),
+                    usage: command.usage,
+                };
+                return (command.handler)(state, &args);
+            }
+        }
+
+        match words.first().map(String::as_str) {
+            Some("help") => Ok(self.help()),
+            _ => Err(CommandError::UnknownCommand(words.join(" "))),
+        }
+    }
+
+    fn help(&self) -> String {
+        self.commands()
+            .map(|(name, usage)| format!("{name} {usage}").trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A line received by a `LineServer`, with where to send the reply.
+struct Request {
+    line: String,
+    reply: Sender<Option<String>>,
+}
+
+/// Queues lines received over local TCP connections until they are polled, and writes back the replies.
+///
+/// The first line of a connection must be the token given to `bind`, otherwise the connection is closed
+/// without a reply. Connections starting like an HTTP request are closed as well, so a web page can't send
+/// lines through the browser. The server stops listening when dropped.
+///
+/// Shared by the `CommandServer` and the `ipc` server.
+pub(crate) struct LineServer {
+    requests: Receiver<Request>,
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LineServer {
+    /// Listens on the given address on a background thread, with a thread per connection.
+    ///
+    /// Returns an `InvalidInput` error if the token is empty.
+    pub(crate) fn bind(address: impl ToSocketAddrs, token: &str) -> io::Result<Self> {
+        if token.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the token must not be empty",
+            ));
+        }
+
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        let token: Arc<str> = token.trim().into();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        let thread = std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let sender = sender.clone();
+                let token = token.clone();
+                std::thread::spawn(move || serve(stream, &token, sender));
+            }
+        });
+
+        Ok(Self {
+            requests,
+            address,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Answers every queued line with `answer`, returning how many were answered.
+    /// Lines answered with `None` get no reply.
+    pub(crate) fn poll(&self, mut answer: impl FnMut(&str) -> Option<String>) -> usize {
+        let mut count = 0;
+        while let Ok(request) = self.requests.try_recv() {
+            let _ = request.reply.send(answer(&request.line));
+            count += 1;
+        }
+        count
+    }
+}
+
+impl Drop for LineServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // The listener thread is blocked accepting, so wake it up with a connection of our own.
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect(address);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns true if the line looks like the request line of HTTP/1, such as `POST / HTTP/1.1`.
+fn is_http_request(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    matches!(
+        words.as_slice(),
+        [method, _, version]
+            if method.bytes().all(|byte| byte.is_ascii_uppercase()) && version.starts_with("HTTP/1.")
+    )
+}
+
+/// Compares the token without returning early, so the time taken doesn't tell how much of it matched.
+fn token_matches(line: &str, token: &str) -> bool {
+    let (line, token) = (line.as_bytes(), token.as_bytes());
+    line.len() == token.len()
+        && line
+            .iter()
+            .zip(token)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Checks the token of a connection, then queues its lines and writes back their replies,
+/// each followed by a newline.
+fn serve(stream: TcpStream, token: &str, requests: Sender<Request>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+
+    let first = lines.next().transpose()?.unwrap_or_default();
+    if is_http_request(&first) || !token_matches(first.trim(), token) {
+        return writer.shutdown(Shutdown::Both);
+    }
+
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (reply, receiver) = mpsc::channel();
+        if requests.send(Request { line, reply }).is_err() {
+            break;
+        }
+        match receiver.recv() {
+            Ok(Some(output)) => writeln!(writer, "{output}")?,
+            Ok(None) => {}
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// Takes commands over local TCP connections, one per line, and replies with their output.
+///
+/// Every reply ends with an empty line, since output may span several lines. Errors start with `error: `.
+///
+/// The first line of a connection must be the token given to `bind`, otherwise the connection is closed.
+/// The server stops listening when dropped.
+pub struct CommandServer {
+    lines: LineServer,
+}
+
+impl CommandServer {
+    /// Listens on the given address, such as `"127.0.0.1:7878"`, on a background thread.
+    ///
+    /// Clients must send `token` as their first line. Only bind to a loopback address and keep the token secret:
+    /// anyone who can connect and knows it can change the game.
+    /// Returns an `InvalidInput` error if the token is empty.
+    pub fn bind(address: impl ToSocketAddrs, token: &str) -> io::Result<Self> {
+        Ok(Self {
+            lines: LineServer::bind(address, token)?,
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.lines.local_addr()
+    }
+
+    /// Runs every queued command on `console`, returning how many ran.
+    ///
+    /// Call it regularly from the game's main thread, such as from a hook on a function called every frame.
+    pub fn poll<S>(&self, console: &Console<S>, state: &mut S, _thread: &GameThread) -> usize {
+        self.lines.poll(|line| {
+            Some(match console.execute(state, line) {
+                Ok(output) => format!("{output}\n"),
+                Err(error) => format!("error: {error}\n"),
+            })
+        })
+    }
+}
+
+/// Handler for `<ammo> [field]`: prints a field of an ammo, or every field, as JSON.
+pub fn ammo_get<A: AmmoFields + Serialize>(
+    table: &mut AmmoTable<'_, A>,
+    args: &Args,
+) -> Result<String, CommandError> {
+    let name = args.required(0)?;
+    let ammo = table
+        .get_by_name_mut(name)
+        .ok_or_else(|| CommandError::Failed(format!("no ammo is named {name}")))?;
+    let value =
+        serde_json::to_value(&*ammo).map_err(|error| CommandError::Failed(error.to_string()))?;
+
+    match args.get(1) {
+        None => serde_json::to_string_pretty(&value)
+            .map_err(|error| CommandError::Failed(error.to_string())),
+        Some(field) => value
+            .get(field)
+            .map(Value::to_string)
+            .ok_or_else(|| CommandError::Failed(format!("unknown field {field}"))),
+    }
+}
+
+/// Handler for `<ammo> <field> <value>`: sets a field of an ammo.
+///
+/// The value is read as JSON, or as a string if it isn't valid JSON, so both `500` and `Incendiary` work.
+/// Changed strings are reallocated, so set the `GameCrt` allocator before changing ammos of the game.
+pub fn ammo_set<A: AmmoFields + Serialize + DeserializeOwned>(
+    table: &mut AmmoTable<'_, A>,
+    args: &Args,
+) -> Result<String, CommandError> {
+    let name = args.required(0)?;
+    let field = args.required(1)?;
+    let text = args.required(2)?;
+    let value = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()));
+
+    let ammo = table
+        .get_by_name_mut(name)
+        .ok_or_else(|| CommandError::Failed(format!("no ammo is named {name}")))?;
+    let mut patch = Patch::new();
+    patch
+        .set(field, &value)
+        .and_then(|()| patch.apply(ammo))
+        .map_err(|error| CommandError::Failed(error.to_string()))?;
+
+    Ok(format!("{} {field} = {value}", ammo.item_name()))
+}
+
+/// Handler without arguments: lists the index and name of every ammo.
+pub fn ammo_list<A: AmmoFields>(
+    table: &mut AmmoTable<'_, A>,
+    _args: &Args,
+) -> Result<String, CommandError> {
+    Ok(table
+        .iter()
+        .map(|ammo| format!("{:>4} {}", ammo.index(), ammo.item_name()))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v1_163::{Ammo, AmmoBuilder};
+
+    fn console<'a>() -> Console<AmmoTable<'a, Ammo>> {
+        let mut console = Console::new();
+        console.register("ammo get", "<ammo> [field]", ammo_get);
+        console.register("ammo set", "<ammo> <field> <value>", ammo_set);
+        console.register("ammo list", "", ammo_list);
+        console
+    }
+
+    #[test]
+    fn splits_quoted_words() {
+        assert_eq!(
+            split(r#"ammo set  AMMO_57 shell_kind "Laser Guided" """#).unwrap(),
+            ["ammo", "set", "AMMO_57", "shell_kind", "Laser Guided", ""]
+        );
+        assert_eq!(split(r#"ammo "set"#), Err(CommandError::UnclosedQuote));
+    }
+
+    #[test]
+    fn runs_ammo_commands() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let console = console();
+
+        assert_eq!(
+            console.execute(&mut table, "ammo set AMMO_57 explosive_power 500"),
+            Ok("AMMO_57 explosive_power = 500".to_string())
+        );
+        console
+            .execute(&mut table, r#"ammo set AMMO_57 shell_kind "Laser Guided""#)
+            .unwrap();
+        assert_eq!(table.get_by_name("AMMO_57").unwrap().explosive_power, 500.0);
+        assert_eq!(
+            table.get_by_name("AMMO_57").unwrap().shell_kind,
+            "Laser Guided"
+        );
+
+        assert_eq!(
+            console.execute(&mut table, "ammo get AMMO_57 shell_kind"),
+            Ok(r#""Laser Guided""#.to_string())
+        );
+        assert_eq!(
+            console.execute(&mut table, "ammo list"),
+            Ok("   1 AMMO_57\n   2 AMMO_85".to_string())
+        );
+        assert_eq!(
+            console.execute(&mut table, "help").unwrap(),
+            "ammo get <ammo> [field]\nammo list\nammo set <ammo> <field> <value>"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let console = console();
+
+        let error = console.execute(&mut table, "ammo fire").unwrap_err();
+        assert_eq!(error, CommandError::UnknownCommand("ammo fire".to_string()));
+
+        let error = console.execute(&mut table, "ammo set AMMO_57").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing argument 2, expected <ammo> <field> <value>"
+        );
+
+        let error = console
+            .execute(&mut table, "ammo set AMMO_57 warp_speed 9")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "unknown field warp_speed");
+
+        let error = console.execute(&mut table, "ammo get AMMO_12").unwrap_err();
+        assert_eq!(error.to_string(), "no ammo is named AMMO_12");
+    }
+
+    #[test]
+    fn parses_typed_arguments() {
+        let args = Args {
+            words: vec!["500".to_string(), "fast".to_string()],
+            usage: "<power> <speed>",
+        };
+        assert_eq!(args.parse::<i32>(0), Ok(500));
+        assert!(matches!(
+            args.parse::<f32>(1),
+            Err(CommandError::InvalidArgument { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn serves_commands_over_tcp() {
+        let mut console = Console::new();
+        console.register("add", "<a> <b>", |total: &mut i32, args| {
+            *total += args.parse::<i32>(0)? + args.parse::<i32>(1)?;
+            Ok(total.to_string())
+        });
+
+        let server = CommandServer::bind("127.0.0.1:0", "secret").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"secret\nadd 2 3\nadd 2 x\n").unwrap();
+
+        let thread = unsafe { GameThread::new() };
+        let mut total = 0;
+        let mut ran = 0;
+        while ran < 2 {
+            ran += server.poll(&console, &mut total, &thread);
+            std::thread::yield_now();
+        }
+
+        let mut reader = BufReader::new(stream);
+        let mut lines = Vec::new();
+        for _ in 0..4 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            lines.push(line);
+        }
+        assert_eq!(total, 5);
+        assert_eq!(
+            lines,
+            [
+                "5\n",
+                "\n",
+                "error: invalid argument 2 \"x\": invalid digit found in string\n",
+                "\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn closes_connections_without_the_token() {
+        let mut console = Console::new();
+        console.register("add", "<a> <b>", |total: &mut i32, args| {
+            *total += args.parse::<i32>(0)? + args.parse::<i32>(1)?;
+            Ok(total.to_string())
+        });
+
+        let server = CommandServer::bind("127.0.0.1:0", "secret").unwrap();
+        for request in [
+            "add 2 3\nadd 2 3\n",
+            "wrong\nadd 2 3\n",
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\nsecret\nadd 2 3\n",
+        ] {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+
+            let mut reply = String::new();
+            let _ = BufReader::new(stream).read_line(&mut reply);
+            assert_eq!(reply, "");
+        }
+
+        let thread = unsafe { GameThread::new() };
+        let mut total = 0;
+        assert_eq!(server.poll(&console, &mut total, &thread), 0);
+        assert_eq!(total, 0);
+    }
+
+    #[test]
+    fn rejects_an_empty_token() {
+        let error = CommandServer::bind("127.0.0.1:0", " ").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn stops_listening_when_dropped() {
+        let server = CommandServer::bind("127.0.0.1:0", "secret").unwrap();
+        let address = server.local_addr();
+        drop(server);
+
+        TcpListener::bind(address).unwrap();
+    }
+}
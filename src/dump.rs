@@ -0,0 +1,486 @@
+//! Snapshots of game memory, so structs can be inspected offline.
+//!
+//! A `Snapshot` copies regions of an address space, such as the ammo table or every node of a TLL tree,
+//! along with the module base, the game version, and when it was taken.
+//! It is itself a `MemorySource`, so the structs it holds are read back with `read_struct` like from the game.
+//! Snapshots can be saved to a file, for example to attach repro data to a crash report.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem::size_of;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::general::raw::RawLayout;
+use crate::general::version::GameVersion;
+use crate::general::TLL;
+use crate::memory::{MemoryError, MemorySource};
+
+/// Bytes a saved snapshot starts with, the last one being the format version.
+const MAGIC: [u8; 8] = *b"HFSNAP\0\x01";
+
+/// A copied region of memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The address the region was copied from.
+    pub address: u64,
+    /// The copied bytes.
+    pub bytes: Vec<u8>,
+}
+
+impl Region {
+    /// Returns the address one past the end of the region.
+    pub fn end(&self) -> u64 {
+        self.address + self.bytes.len() as u64
+    }
+}
+
+/// Error returned when saving or loading a snapshot fails.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The data is not a snapshot, or is corrupted.
+    InvalidFormat(&'static str),
+    /// The snapshot was taken of a version this library doesn't know.
+    UnknownVersion(String),
+    /// Reading or writing failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFormat(reason) => write!(f, "invalid snapshot: {reason}"),
+            Self::UnknownVersion(version) => write!(f, "unknown game version {version:?}"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Copied regions of an address space, with metadata about where they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The base address of the game module.
+    pub base: u64,
+    /// The version of the game, if known.
+    pub version: Option<GameVersion>,
+    /// When the snapshot was taken, with a precision of a second.
+    pub timestamp: SystemTime,
+    /// The regions, keyed by address. Overlapping and adjacent regions are merged.
+    regions: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Creates an empty snapshot taken now.
+    pub fn new(base: u64, version: Option<GameVersion>) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Self {
+            base,
+            version,
+            timestamp: UNIX_EPOCH + Duration::from_secs(seconds),
+            regions: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the copied regions, ordered by address.
+    pub fn regions(&self) -> impl Iterator<Item = Region> + '_ {
+        self.regions.iter().map(|(&address, bytes)| Region {
+            address,
+            bytes: bytes.clone(),
+        })
+    }
+
+    /// Returns the total number of bytes copied.
+    pub fn len(&self) -> usize {
+        self.regions.values().map(Vec::len).sum()
+    }
+
+    /// Returns true if nothing has been copied.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Adds bytes to the snapshot, replacing the bytes already copied from the same addresses.
+    pub fn insert(&mut self, address: u64, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let end = address + bytes.len() as u64;
+
+        // Every region that overlaps or touches the new one is merged into it.
+        let touching: Vec<u64> = self
+            .regions
+            .range(..=end)
+            .filter(|(&start, existing)| start + existing.len() as u64 >= address)
+            .map(|(&start, _)| start)
+            .collect();
+
+        let start = touching
+            .first()
+            .map_or(address, |&first| first.min(address));
+        let mut merged_end = end;
+        let mut merged = Vec::new();
+        for region in &touching {
+            let existing = self.regions.remove(region).unwrap();
+            merged_end = merged_end.max(region + existing.len() as u64);
+            merged.push((*region, existing));
+        }
+
+        let mut buffer = vec![0u8; (merged_end - start) as usize];
+        for (region, existing) in merged {
+            let offset = (region - start) as usize;
+            buffer[offset..offset + existing.len()].copy_from_slice(&existing);
+        }
+        let offset = (address - start) as usize;
+        buffer[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        self.regions.insert(start, buffer);
+    }
+
+    /// Copies `size` bytes starting at `address`.
+    pub fn capture<M: MemorySource>(
+        &mut self,
+        memory: &M,
+        address: u64,
+        size: usize,
+    ) -> Result<(), MemoryError> {
+        let mut bytes = vec![0u8; size];
+        memory.read(address, &mut bytes)?;
+        self.insert(address, &bytes);
+        Ok(())
+    }
+
+    /// Copies the struct at `address`, along with the heap buffers it owns, such as those of an `EscadraString`.
+    ///
+    /// Fails without copying anything if the struct can't be read.
+    pub fn capture_struct<T: RawLayout, M: MemorySource>(
+        &mut self,
+        memory: &M,
+        address: u64,
+    ) -> Result<T, MemoryError> {
+        let recorder = Recorder {
+            memory,
+            reads: RefCell::new(Vec::new()),
+        };
+        let value = recorder.read_struct::<T>(address)?;
+
+        for (address, bytes) in recorder.reads.into_inner() {
+            self.insert(address, &bytes);
+        }
+        Ok(value)
+    }
+
+    /// Copies `count` structs laid out one after the other starting at `address`, such as the ammo table.
+    pub fn capture_slice<T: RawLayout, M: MemorySource>(
+        &mut self,
+        memory: &M,
+        address: u64,
+        count: usize,
+    ) -> Result<(), MemoryError> {
+        for i in 0..count {
+            self.capture_struct::<T, M>(memory, address + (i * size_of::<T>()) as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the TLL at `address` and every TLL reachable from it, up to `limit` TLLs.
+    ///
+    /// Returns the number of TLLs copied. Links that can't be read are skipped.
+    pub fn capture_tll_tree<M: MemorySource>(
+        &mut self,
+        memory: &M,
+        address: u64,
+        limit: usize,
+    ) -> Result<usize, MemoryError> {
+        self.capture_struct::<TLL, M>(memory, address)?;
+
+        let mut visited = HashSet::from([address]);
+        let mut copied = 1;
+        let mut stack = vec![address];
+        while let Some(address) = stack.pop() {
+            for link in self.tll_links(address) {
+                if copied >= limit {
+                    return Ok(copied);
+                }
+                if link == 0 || !visited.insert(link) {
+                    continue;
+                }
+
+                if self.capture_struct::<TLL, M>(memory, link).is_ok() {
+                    copied += 1;
+                    stack.push(link);
+                }
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Returns the addresses held by the a, b, and c links of a captured TLL.
+    ///
+    /// Reading the TLL as a struct sets its links to null, so they are taken from the copied bytes instead.
+    fn tll_links(&self, address: u64) -> [u64; 3] {
+        let mut bytes = [0u8; 0x18];
+        if self.read(address, &mut bytes).is_err() {
+            return [0; 3];
+        }
+
+        let link =
+            |index: usize| u64::from_le_bytes(bytes[index * 8..index * 8 + 8].try_into().unwrap());
+        [link(0), link(1), link(2)]
+    }
+
+    /// Saves the snapshot.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&self.base.to_le_bytes())?;
+
+        let version = self.version.map_or("", |version| version.name());
+        writer.write_all(&[version.len() as u8])?;
+        writer.write_all(version.as_bytes())?;
+
+        let seconds = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        writer.write_all(&seconds.to_le_bytes())?;
+
+        writer.write_all(&(self.regions.len() as u64).to_le_bytes())?;
+        for (address, bytes) in &self.regions {
+            writer.write_all(&address.to_le_bytes())?;
+            writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Loads a snapshot saved by `write_to`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut magic = [0u8; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::InvalidFormat("bad magic"));
+        }
+
+        let base = read_u64(&mut reader)?;
+
+        let mut version_length = [0u8; 1];
+        read_exact(&mut reader, &mut version_length)?;
+        let mut version = vec![0u8; version_length[0] as usize];
+        read_exact(&mut reader, &mut version)?;
+        let version = String::from_utf8(version)
+            .map_err(|_| SnapshotError::InvalidFormat("version is not UTF-8"))?;
+        let version = match version.as_str() {
+            "" => None,
+            name => Some(
+                GameVersion::ALL
+                    .into_iter()
+                    .find(|version| version.name() == name)
+                    .ok_or(SnapshotError::UnknownVersion(version))?,
+            ),
+        };
+
+        let timestamp = UNIX_EPOCH + Duration::from_secs(read_u64(&mut reader)?);
+
+        let mut snapshot = Self {
+            base,
+            version,
+            timestamp,
+            regions: BTreeMap::new(),
+        };
+        for _ in 0..read_u64(&mut reader)? {
+            let address = read_u64(&mut reader)?;
+            let size = read_u64(&mut reader)?;
+            let mut bytes = Vec::new();
+            reader.by_ref().take(size).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != size {
+                return Err(SnapshotError::InvalidFormat("truncated region"));
+            }
+            snapshot.insert(address, &bytes);
+        }
+        Ok(snapshot)
+    }
+}
+
+impl MemorySource for Snapshot {
+    /// Reads copied bytes. Fails if any of them weren't copied.
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+        let size = buffer.len();
+        let unreadable = MemoryError::Unreadable { address, size };
+
+        let (&start, bytes) = self
+            .regions
+            .range(..=address)
+            .next_back()
+            .ok_or(unreadable)?;
+        let offset = (address - start) as usize;
+        let bytes = offset
+            .checked_add(size)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(MemoryError::Unreadable { address, size })?;
+
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Always fails, as snapshots are read only.
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+        Err(MemoryError::Unwritable {
+            address,
+            size: bytes.len(),
+        })
+    }
+}
+
+/// Forwards reads to another source, keeping a copy of everything that was read.
+struct Recorder<'a, M> {
+    memory: &'a M,
+    reads: RefCell<Vec<(u64, Vec<u8>)>>,
+}
+
+impl<M: MemorySource> MemorySource for Recorder<'_, M> {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+        self.memory.read(address, buffer)?;
+        self.reads.borrow_mut().push((address, buffer.to_vec()));
+        Ok(())
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+        Err(MemoryError::Unwritable {
+            address,
+            size: bytes.len(),
+        })
+    }
+}
+
+/// Like `Read::read_exact`, but reports running out of data as a truncated snapshot.
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<(), SnapshotError> {
+    reader
+        .read_exact(buffer)
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::InvalidFormat("truncated header"),
+            _ => SnapshotError::Io(error),
+        })
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, SnapshotError> {
+    let mut bytes = [0u8; 8];
+    read_exact(reader, &mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_merges_regions() {
+        let mut snapshot = Snapshot::new(0, None);
+        snapshot.insert(0x10, &[1, 2, 3, 4]);
+        snapshot.insert(0x20, &[9]);
+        snapshot.insert(0x14, &[5, 6]);
+        snapshot.insert(0x12, &[7]);
+
+        let regions: Vec<_> = snapshot.regions().collect();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].address, 0x10);
+        assert_eq!(regions[0].bytes, [1, 2, 7, 4, 5, 6]);
+        assert_eq!(regions[1].end(), 0x21);
+        assert_eq!(snapshot.len(), 7);
+
+        let mut buffer = [0u8; 3];
+        snapshot.read(0x13, &mut buffer).unwrap();
+        assert_eq!(buffer, [4, 5, 6]);
+        assert!(snapshot.read(0x15, &mut buffer).is_err());
+        assert!(snapshot.read(0x08, &mut buffer).is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut snapshot = Snapshot::new(0x1_4000_0000, Some(GameVersion::V1_163));
+        snapshot.insert(0x1000, &[0xaa; 0x20]);
+        snapshot.insert(0x3000, b"Banana");
+
+        let mut saved = Vec::new();
+        snapshot.write_to(&mut saved).unwrap();
+        let loaded = Snapshot::read_from(saved.as_slice()).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        assert!(matches!(
+            Snapshot::read_from(&saved[..saved.len() - 1]),
+            Err(SnapshotError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Snapshot::read_from(&b"not a snapshot"[..]),
+            Err(SnapshotError::InvalidFormat(_))
+        ));
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn capture_owned_buffers() {
+        use crate::general::EscadraString;
+        use crate::memory::InProcess;
+
+        let strings: Vec<EscadraString> = ["Short", "Long enough to live on the heap"]
+            .into_iter()
+            .map(EscadraString::from)
+            .collect();
+        let address = strings.as_ptr() as u64;
+
+        let mut snapshot = Snapshot::new(0, None);
+        snapshot
+            .capture_slice::<EscadraString, _>(&InProcess, address, strings.len())
+            .unwrap();
+        drop(strings);
+
+        let stride = size_of::<EscadraString>() as u64;
+        let short: EscadraString = snapshot.read_struct(address).unwrap();
+        let long: EscadraString = snapshot.read_struct(address + stride).unwrap();
+        assert_eq!(short, "Short");
+        assert_eq!(long, "Long enough to live on the heap");
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn capture_tll_tree_follows_links() {
+        use crate::general::tll::TllData;
+        use crate::general::OwnedTll;
+        use crate::memory::InProcess;
+
+        let mut tll = OwnedTll::new();
+        for key in ["Apple", "Banana", "Cherry"] {
+            tll.insert(key, TllData::default());
+        }
+
+        let mut snapshot = Snapshot::new(0, None);
+        let copied = snapshot
+            .capture_tll_tree(&InProcess, tll.as_ptr() as u64, 100)
+            .unwrap();
+        assert_eq!(copied, 4);
+
+        let address = tll.find("Cherry").unwrap() as *const TLL as u64;
+        let cherry: TLL = snapshot.read_struct(address).unwrap();
+        assert_eq!(cherry.string, "Cherry");
+    }
+}
@@ -0,0 +1,239 @@
+//! A typed event bus, so several parts of a mod can react to what a hook sees without each hooking the function.
+//!
+//! Events are plain types. Callbacks are registered per type with `EventBus::on`,
+//! and a hook delivers an event to them with `EventBus::emit`, on the game's main thread.
+//! A panicking callback is skipped instead of unwinding into the hooked game function, which would abort the game.
+//!
+//! The game functions behind events like a shell being fired or a ship being destroyed haven't been identified yet,
+//! so no events are defined here. Mods define their own and emit them from their hooks:
+//!
+//! ```ignore
+//! pub struct ShipDestroyed {
+//!     pub address: u64,
+//! }
+//!
+//! events::bus().on(|event: &ShipDestroyed| log::info!("ship at {:#x} destroyed", event.address));
+//!
+//! // In the detour of the function destroying ships:
+//! events::bus().emit(&ShipDestroyed { address }, &thread);
+//! ```
+//!
+//! Events borrowing game structs, like a `ShellFired<'a>` holding an `&'a Ammo`, aren't `'static`.
+//! Register and emit those with `on_borrowed` and `emit_borrowed`, see `Borrowed`.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::general::GameThread;
+
+type Callback = Arc<dyn Fn(&dyn Any) + Send + Sync>;
+
+/// Identifies a registered callback, to remove it with `EventBus::off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+/// Callbacks registered per event type.
+#[derive(Default)]
+pub struct EventBus {
+    callbacks: RwLock<HashMap<TypeId, Vec<(Subscription, Callback)>>>,
+    next: AtomicU64,
+}
+
+impl EventBus {
+    /// Creates a bus without callbacks. Most mods use the shared `bus` instead.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a callback for events of type `E`.
+    pub fn on<E: 'static>(&self, callback: impl Fn(&E) + Send + Sync + 'static) -> Subscription {
+        self.insert(
+            TypeId::of::<E>(),
+            Arc::new(move |event: &dyn Any| {
+                if let Some(event) = event.downcast_ref::<E>() {
+                    callback(event);
+                }
+            }),
+        )
+    }
+
+    /// Registers a callback for events that borrow data, such as `ShellFired<'a>` holding an `&'a Ammo`.
+    ///
+    /// `K` is the `'static` version of the event type, e.g. `ShellFired<'static>`,
+    /// and the callback receives the event with whatever lifetime it was emitted with.
+    pub fn on_borrowed<K: Borrowed>(
+        &self,
+        callback: impl for<'a> Fn(&K::At<'a>) + Send + Sync + 'static,
+    ) -> Subscription {
+        self.insert(
+            TypeId::of::<K>(),
+            Arc::new(move |event: &dyn Any| {
+                if let Some(event) = event.downcast_ref::<Erased<K>>() {
+                    // `Erased` only exists while `emit_borrowed` holds the event, so the pointer is valid here.
+                    callback(unsafe { &*(event.0 as *const K::At<'_>) });
+                }
+            }),
+        )
+    }
+
+    fn insert(&self, type_id: TypeId, callback: Callback) -> Subscription {
+        let subscription = Subscription(self.next.fetch_add(1, Ordering::Relaxed));
+        self.callbacks
+            .write()
+            .unwrap_or_else(|error| error.into_inner())
+            .entry(type_id)
+            .or_default()
+            .push((subscription, callback));
+        subscription
+    }
+
+    /// Removes a callback. Returns false if it was already removed.
+    pub fn off(&self, subscription: Subscription) -> bool {
+        let mut callbacks = self
+            .callbacks
+            .write()
+            .unwrap_or_else(|error| error.into_inner());
+        for list in callbacks.values_mut() {
+            if let Some(position) = list.iter().position(|(id, _)| *id == subscription) {
+                list.remove(position);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Calls every callback registered for `E`, in the order they were registered.
+    ///
+    /// Returns how many callbacks completed. A panicking callback is skipped, after the panic hook reported it.
+    /// Callbacks may register or remove callbacks, which takes effect from the next event.
+    pub fn emit<E: 'static>(&self, event: &E, _thread: &GameThread) -> usize {
+        self.dispatch(TypeId::of::<E>(), event)
+    }
+
+    /// Calls every callback registered with `on_borrowed` for `K`. See `emit`.
+    pub fn emit_borrowed<K: Borrowed>(&self, event: &K::At<'_>, _thread: &GameThread) -> usize {
+        let erased = Erased::<K>(
+            event as *const K::At<'_> as *const (),
+            std::marker::PhantomData,
+        );
+        self.dispatch(TypeId::of::<K>(), &erased)
+    }
+
+    fn dispatch(&self, type_id: TypeId, event: &dyn Any) -> usize {
+        // The lock is released before calling anything, so callbacks can change the callbacks.
+        let callbacks: Vec<Callback> = match self
+            .callbacks
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+            .get(&type_id)
+        {
+            Some(list) => list.iter().map(|(_, callback)| callback.clone()).collect(),
+            None => return 0,
+        };
+
+        callbacks
+            .iter()
+            .filter(|callback| panic::catch_unwind(AssertUnwindSafe(|| callback(event))).is_ok())
+            .count()
+    }
+}
+
+/// An event type that borrows data, named by its `'static` version.
+///
+/// ```ignore
+/// struct ShellFired<'a> { ammo: &'a Ammo }
+///
+/// impl Borrowed for ShellFired<'static> {
+///     type At<'a> = ShellFired<'a>;
+/// }
+/// ```
+pub trait Borrowed: 'static {
+    /// The event type with its data borrowed for `'a`.
+    type At<'a>;
+}
+
+/// An event of `emit_borrowed`, with its lifetime erased so it can be passed as `Any`.
+struct Erased<K>(*const (), std::marker::PhantomData<K>);
+
+/// Returns the event bus shared by the whole process.
+pub fn bus() -> &'static EventBus {
+    static BUS: OnceLock<EventBus> = OnceLock::new();
+    BUS.get_or_init(EventBus::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    use crate::v1_163::{Ammo, AmmoBuilder};
+
+    struct CityEntered {
+        name: &'static str,
+    }
+
+    struct ShellFired<'a> {
+        ammo: &'a Ammo,
+    }
+
+    impl Borrowed for ShellFired<'static> {
+        type At<'a> = ShellFired<'a>;
+    }
+
+    #[test]
+    fn delivers_events_by_type() {
+        let bus = EventBus::new();
+        let thread = unsafe { GameThread::new() };
+        let cities = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = cities.clone();
+        let subscription = bus.on(move |event: &CityEntered| seen.lock().unwrap().push(event.name));
+        bus.on(|_: &u32| panic!("wrong event type"));
+
+        assert_eq!(bus.emit(&CityEntered { name: "first" }, &thread), 1);
+        assert!(bus.off(subscription));
+        assert!(!bus.off(subscription));
+        assert_eq!(bus.emit(&CityEntered { name: "second" }, &thread), 0);
+
+        assert_eq!(*cities.lock().unwrap(), ["first"]);
+    }
+
+    #[test]
+    fn delivers_borrowed_events() {
+        let bus = EventBus::new();
+        let thread = unsafe { GameThread::new() };
+        let speed = Arc::new(Mutex::new(0.0));
+
+        let seen = speed.clone();
+        bus.on_borrowed::<ShellFired<'static>>(move |event| {
+            *seen.lock().unwrap() = event.ammo.speed
+        });
+
+        let ammo = AmmoBuilder::test_ammo("AMMO_57", 5);
+        assert_eq!(
+            bus.emit_borrowed::<ShellFired<'static>>(&ShellFired { ammo: &ammo }, &thread),
+            1
+        );
+        assert_eq!(*speed.lock().unwrap(), 1000.0);
+    }
+
+    #[test]
+    fn skips_panicking_callbacks() {
+        let bus = EventBus::new();
+        let thread = unsafe { GameThread::new() };
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        bus.on(|_: &CityEntered| panic!("callback failed"));
+        let seen = calls.clone();
+        bus.on(move |_: &CityEntered| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert_eq!(bus.emit(&CityEntered { name: "first" }, &thread), 1);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,521 @@
+//! Exposes the layouts of this crate through `extern "C"` functions, for modding tools not written in Rust.
+//!
+//! Build the library to load with `cargo rustc --release --features ffi --crate-type cdylib`.
+//! The C header is `include/highfleet.h`, which `cbindgen --config cbindgen.toml` regenerates.
+//! Versions are passed as `HF_VERSION_*` numbers and ammo fields by their id,
+//! which is their position in the struct, see `hf_ammo_field_id`.
+//!
+//! Functions taking pointers don't check them beyond null checks, so they're all unsafe.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr::{self, null};
+use std::sync::OnceLock;
+
+use crate::general::hexdump::{Annotated, FieldInfo, FieldKind};
+use crate::general::version::GameVersion;
+use crate::general::{EscadraString, TLL};
+use crate::{v1_151, v1_163};
+
+/// Version 1.151.
+pub const HF_VERSION_1_151: u32 = 0;
+/// Version 1.163.
+pub const HF_VERSION_1_163: u32 = 1;
+
+/// The kind of an ammo field.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HfFieldKind {
+    /// Not a field, returned for invalid ids.
+    HfFieldNone,
+    /// A `bool`.
+    HfFieldBool,
+    /// A `uint16_t`.
+    HfFieldU16,
+    /// An `int32_t`.
+    HfFieldI32,
+    /// A `uint32_t`.
+    HfFieldU32,
+    /// A `uint64_t`.
+    HfFieldU64,
+    /// A `float`.
+    HfFieldF32,
+    /// A pointer.
+    HfFieldPointer,
+    /// An `EscadraString`.
+    HfFieldString,
+    /// Bytes without a known meaning.
+    HfFieldBytes,
+}
+
+impl From<FieldKind> for HfFieldKind {
+    fn from(kind: FieldKind) -> Self {
+        match kind {
+            FieldKind::Bool => Self::HfFieldBool,
+            FieldKind::U16 => Self::HfFieldU16,
+            FieldKind::I32 => Self::HfFieldI32,
+            FieldKind::U32 => Self::HfFieldU32,
+            FieldKind::U64 => Self::HfFieldU64,
+            FieldKind::F32 => Self::HfFieldF32,
+            FieldKind::Pointer => Self::HfFieldPointer,
+            FieldKind::String => Self::HfFieldString,
+            FieldKind::Bytes(_) => Self::HfFieldBytes,
+        }
+    }
+}
+
+/// A TLL and its links, as written by `hf_tll_explore`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HfTllNode {
+    /// The TLL.
+    pub tll: *const TLL,
+    /// Its a pointer.
+    pub a: *const TLL,
+    /// Its b pointer.
+    pub b: *const TLL,
+    /// Its c pointer.
+    pub c: *const TLL,
+    /// The index it holds.
+    pub index: u32,
+}
+
+/// The fields of the ammo of a version, with their names as C strings.
+struct AmmoFieldTable {
+    fields: Vec<FieldInfo>,
+    names: Vec<CString>,
+}
+
+impl AmmoFieldTable {
+    fn new<A: Annotated>() -> Self {
+        let fields = A::fields();
+        let names = fields
+            .iter()
+            .map(|field| CString::new(field.name).unwrap())
+            .collect();
+        Self { fields, names }
+    }
+}
+
+fn version(version: u32) -> Option<GameVersion> {
+    match version {
+        HF_VERSION_1_151 => Some(GameVersion::V1_151),
+        HF_VERSION_1_163 => Some(GameVersion::V1_163),
+        _ => None,
+    }
+}
+
+fn ammo_fields(version_id: u32) -> Option<&'static AmmoFieldTable> {
+    static V1_151: OnceLock<AmmoFieldTable> = OnceLock::new();
+    static V1_163: OnceLock<AmmoFieldTable> = OnceLock::new();
+
+    Some(match version(version_id)? {
+        GameVersion::V1_151 => V1_151.get_or_init(AmmoFieldTable::new::<v1_151::Ammo>),
+        GameVersion::V1_163 => V1_163.get_or_init(AmmoFieldTable::new::<v1_163::Ammo>),
+    })
+}
+
+fn ammo_field(version: u32, id: usize) -> Option<FieldInfo> {
+    ammo_fields(version)?.fields.get(id).copied()
+}
+
+/// Creates an `EscadraString` holding a copy of a nul terminated UTF-8 string.
+///
+/// Returns null if `text` is null or not UTF-8. Free the string with `hf_string_free`.
+///
+/// # Safety
+///
+/// `text` must be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hf_string_new(text: *const c_char) -> *mut EscadraString {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    match CStr::from_ptr(text).to_str() {
+        Ok(text) => Box::into_raw(Box::new(EscadraString::from(text))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees an `EscadraString` created by `hf_string_new`. Does nothing if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null or returned by `hf_string_new`, and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn hf_string_free(string: *mut EscadraString) {
+    if !string.is_null() {
+        drop(Box::from_raw(string));
+    }
+}
+
+/// Returns the nul terminated bytes of an `EscadraString`, valid until it is changed or freed.
+///
+/// # Safety
+///
+/// `string` must be null or point to a valid `EscadraString`.
+#[no_mangle]
+pub unsafe extern "C" fn hf_string_data(string: *const EscadraString) -> *const c_char {
+    match string.as_ref() {
+        Some(string) => string.get_bytes().as_ptr() as *const c_char,
+        None => null(),
+    }
+}
+
+/// Returns the length of an `EscadraString` in bytes, without the nul terminator.
+///
+/// # Safety
+///
+/// `string` must be null or point to a valid `EscadraString`.
+#[no_mangle]
+pub unsafe extern "C" fn hf_string_len(string: *const EscadraString) -> usize {
+    string.as_ref().map_or(0, |string| string.get_bytes().len())
+}
+
+/// Replaces the text of an `EscadraString` with a nul terminated UTF-8 string.
+///
+/// Returns false if a pointer is null or `text` is not UTF-8.
+///
+/// # Safety
+///
+/// `string` must be null or point to a valid `EscadraString`, `text` must be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hf_string_set(string: *mut EscadraString, text: *const c_char) -> bool {
+    let Some(string) = string.as_mut() else {
+        return false;
+    };
+    if text.is_null() {
+        return false;
+    }
+    match CStr::from_ptr(text).to_str() {
+        Ok(text) => {
+            string.set_string(&text.to_string());
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Returns the size of an ammo of a version, or 0 for an unknown version.
+#[no_mangle]
+pub extern "C" fn hf_ammo_size(version_id: u32) -> usize {
+    version(version_id).map_or(0, crate::any::AnyAmmo::size)
+}
+
+/// Returns the number of fields of an ammo of a version, or 0 for an unknown version.
+#[no_mangle]
+pub extern "C" fn hf_ammo_field_count(version: u32) -> usize {
+    ammo_fields(version).map_or(0, |table| table.fields.len())
+}
+
+/// Returns the id of the ammo field with a name, or -1 if there is none.
+///
+/// # Safety
+///
+/// `name` must be null or a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn hf_ammo_field_id(version: u32, name: *const c_char) -> isize {
+    let Some(table) = ammo_fields(version) else {
+        return -1;
+    };
+    if name.is_null() {
+        return -1;
+    }
+    let name = CStr::from_ptr(name);
+    table
+        .names
+        .iter()
+        .position(|field| field.as_c_str() == name)
+        .map_or(-1, |id| id as isize)
+}
+
+/// Returns the name of an ammo field, or null for an invalid id. The name lives for the rest of the program.
+#[no_mangle]
+pub extern "C" fn hf_ammo_field_name(version: u32, id: usize) -> *const c_char {
+    ammo_fields(version)
+        .and_then(|table| table.names.get(id))
+        .map_or(null(), |name| name.as_ptr())
+}
+
+/// Returns the offset of an ammo field, or -1 for an invalid id.
+#[no_mangle]
+pub extern "C" fn hf_ammo_field_offset(version: u32, id: usize) -> isize {
+    ammo_field(version, id).map_or(-1, |field| field.offset as isize)
+}
+
+/// Returns the kind of an ammo field, or `HfFieldNone` for an invalid id.
+#[no_mangle]
+pub extern "C" fn hf_ammo_field_kind(version: u32, id: usize) -> HfFieldKind {
+    ammo_field(version, id).map_or(HfFieldKind::HfFieldNone, |field| field.kind.into())
+}
+
+/// Reads a number or bool field of an ammo into `value`.
+///
+/// Returns false if a pointer is null, the id is invalid, or the field is a string.
+///
+/// # Safety
+///
+/// `ammo` must be null or point to a valid ammo of the version, `value` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn hf_ammo_get_number(
+    version: u32,
+    ammo: *const c_void,
+    id: usize,
+    value: *mut f64,
+) -> bool {
+    let Some(field) = ammo_field(version, id) else {
+        return false;
+    };
+    if ammo.is_null() || value.is_null() {
+        return false;
+    }
+
+    let pointer = (ammo as *const u8).add(field.offset);
+    *value = match field.kind {
+        FieldKind::Bool => (pointer.read() != 0) as u8 as f64,
+        FieldKind::U16 => (pointer as *const u16).read_unaligned() as f64,
+        FieldKind::I32 => (pointer as *const i32).read_unaligned() as f64,
+        FieldKind::U32 => (pointer as *const u32).read_unaligned() as f64,
+        FieldKind::U64 => (pointer as *const u64).read_unaligned() as f64,
+        FieldKind::F32 => (pointer as *const f32).read_unaligned() as f64,
+        FieldKind::Pointer | FieldKind::String | FieldKind::Bytes(_) => return false,
+    };
+    true
+}
+
+/// Writes a number or bool field of an ammo.
+///
+/// Returns false if `ammo` is null, the id is invalid, the field is a string,
+/// or the value doesn't fit into an integer field.
+///
+/// # Safety
+///
+/// `ammo` must be null or point to a valid ammo of the version.
+#[no_mangle]
+pub unsafe extern "C" fn hf_ammo_set_number(
+    version: u32,
+    ammo: *mut c_void,
+    id: usize,
+    value: f64,
+) -> bool {
+    let Some(field) = ammo_field(version, id) else {
+        return false;
+    };
+    if ammo.is_null() {
+        return false;
+    }
+
+    fn integer(value: f64, min: f64, max: f64) -> Option<f64> {
+        (value.fract() == 0.0 && (min..=max).contains(&value)).then_some(value)
+    }
+
+    let pointer = (ammo as *mut u8).add(field.offset);
+    let written = match field.kind {
+        FieldKind::Bool => integer(value, 0.0, 1.0).map(|value| pointer.write(value as u8)),
+        FieldKind::U16 => integer(value, 0.0, u16::MAX as f64)
+            .map(|value| (pointer as *mut u16).write_unaligned(value as u16)),
+        FieldKind::I32 => integer(value, i32::MIN as f64, i32::MAX as f64)
+            .map(|value| (pointer as *mut i32).write_unaligned(value as i32)),
+        FieldKind::U32 => integer(value, 0.0, u32::MAX as f64)
+            .map(|value| (pointer as *mut u32).write_unaligned(value as u32)),
+        FieldKind::U64 => integer(value, 0.0, u64::MAX as f64)
+            .map(|value| (pointer as *mut u64).write_unaligned(value as u64)),
+        FieldKind::F32 => {
+            (pointer as *mut f32).write_unaligned(value as f32);
+            Some(())
+        }
+        FieldKind::Pointer | FieldKind::String | FieldKind::Bytes(_) => None,
+    };
+    written.is_some()
+}
+
+/// Returns the `EscadraString` of a string field of an ammo, or null if the field isn't a string.
+///
+/// The string can be read with `hf_string_data` and changed with `hf_string_set`.
+///
+/// # Safety
+///
+/// `ammo` must be null or point to a valid ammo of the version.
+#[no_mangle]
+pub unsafe extern "C" fn hf_ammo_get_string(
+    version: u32,
+    ammo: *mut c_void,
+    id: usize,
+) -> *mut EscadraString {
+    match ammo_field(version, id) {
+        Some(field) if field.kind == FieldKind::String && !ammo.is_null() => {
+            (ammo as *mut u8).add(field.offset) as *mut EscadraString
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Writes every TLL reachable from `root` into `nodes`, depth first like `TLL::iter`.
+///
+/// Returns the number of TLLs reachable, of which at most `capacity` are written,
+/// so calling it with a `capacity` of 0 tells how large `nodes` must be.
+/// Returns -1 if `root` is null or more than `limit` TLLs are reachable.
+///
+/// # Safety
+///
+/// `root` must be null or point to a valid TLL structure,
+/// `nodes` must be null or writable for `capacity` nodes.
+#[no_mangle]
+pub unsafe extern "C" fn hf_tll_explore(
+    root: *const TLL,
+    limit: usize,
+    nodes: *mut HfTllNode,
+    capacity: usize,
+) -> isize {
+    let Some(root) = root.as_ref() else {
+        return -1;
+    };
+
+    let mut count = 0;
+    for tll in root.iter() {
+        if count == limit {
+            return -1;
+        }
+        if count < capacity && !nodes.is_null() {
+            let links = tll.links();
+            nodes.add(count).write(HfTllNode {
+                tll,
+                a: links.a.as_ptr(),
+                b: links.b.as_ptr(),
+                c: links.c.as_ptr(),
+                index: tll.index(),
+            });
+        }
+        count += 1;
+    }
+    count as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::general::TllData;
+    use crate::v1_163::AmmoBuilder;
+
+    #[test]
+    fn strings() {
+        unsafe {
+            let string = hf_string_new(c"AMMO_57".as_ptr());
+            assert_eq!(hf_string_len(string), 7);
+            assert_eq!(CStr::from_ptr(hf_string_data(string)), c"AMMO_57");
+
+            assert!(hf_string_set(
+                string,
+                c"a string longer than 15 bytes".as_ptr()
+            ));
+            assert_eq!(
+                CStr::from_ptr(hf_string_data(string)),
+                c"a string longer than 15 bytes"
+            );
+            assert!(!hf_string_set(string, null()));
+            hf_string_free(string);
+
+            assert!(hf_string_new(c"\xff".as_ptr()).is_null());
+        }
+    }
+
+    #[test]
+    fn ammo_fields_by_id() {
+        let mut ammo = AmmoBuilder::test_ammo("AMMO_57", 5);
+        let pointer = &mut ammo as *mut v1_163::Ammo as *mut c_void;
+
+        assert_eq!(
+            hf_ammo_size(HF_VERSION_1_163),
+            std::mem::size_of::<v1_163::Ammo>()
+        );
+        assert_eq!(hf_ammo_field_count(7), 0);
+
+        unsafe {
+            let speed = hf_ammo_field_id(HF_VERSION_1_163, c"speed".as_ptr()) as usize;
+            assert_eq!(
+                CStr::from_ptr(hf_ammo_field_name(HF_VERSION_1_163, speed)),
+                c"speed"
+            );
+            assert_eq!(
+                hf_ammo_field_kind(HF_VERSION_1_163, speed),
+                HfFieldKind::HfFieldF32
+            );
+
+            let mut value = 0.0;
+            assert!(hf_ammo_get_number(
+                HF_VERSION_1_163,
+                pointer,
+                speed,
+                &mut value
+            ));
+            assert_eq!(value, 1000.0);
+            assert!(hf_ammo_set_number(HF_VERSION_1_163, pointer, speed, 1200.0));
+
+            let index = hf_ammo_field_id(HF_VERSION_1_163, c"index".as_ptr()) as usize;
+            assert!(!hf_ammo_set_number(HF_VERSION_1_163, pointer, index, 1.5));
+            assert!(hf_ammo_set_number(HF_VERSION_1_163, pointer, index, -2.0));
+
+            let name = hf_ammo_field_id(HF_VERSION_1_163, c"item_name".as_ptr()) as usize;
+            assert!(!hf_ammo_get_number(
+                HF_VERSION_1_163,
+                pointer,
+                name,
+                &mut value
+            ));
+            let string = hf_ammo_get_string(HF_VERSION_1_163, pointer, name);
+            assert!(hf_string_set(string, c"AMMO_85".as_ptr()));
+            assert!(hf_ammo_get_string(HF_VERSION_1_163, pointer, speed).is_null());
+
+            assert_eq!(hf_ammo_field_id(HF_VERSION_1_163, c"nope".as_ptr()), -1);
+        }
+
+        assert_eq!(ammo.speed, 1200.0);
+        assert_eq!(ammo.index, -2);
+        assert_eq!(ammo.item_name, "AMMO_85");
+    }
+
+    #[test]
+    fn explore_tll() {
+        let map: BTreeMap<String, TllData> = ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let data = TllData {
+                    index: i as u32,
+                    ..Default::default()
+                };
+                (key.to_string(), data)
+            })
+            .collect();
+        let tll = TLL::from_map(&map);
+
+        unsafe {
+            let count = hf_tll_explore(tll.as_ptr(), usize::MAX, ptr::null_mut(), 0);
+            assert_eq!(count, 4);
+
+            let mut nodes = vec![HfTllNode::default_for_test(); count as usize];
+            assert_eq!(
+                hf_tll_explore(tll.as_ptr(), usize::MAX, nodes.as_mut_ptr(), nodes.len()),
+                4
+            );
+            assert_eq!(nodes[0].tll, tll.as_ptr() as *const TLL);
+            assert!(nodes.iter().all(|node| !node.tll.is_null()));
+
+            assert_eq!(hf_tll_explore(tll.as_ptr(), 3, ptr::null_mut(), 0), -1);
+            assert_eq!(hf_tll_explore(null(), 3, ptr::null_mut(), 0), -1);
+        }
+    }
+
+    impl HfTllNode {
+        fn default_for_test() -> Self {
+            Self {
+                tll: null(),
+                a: null(),
+                b: null(),
+                c: null(),
+                index: 0,
+            }
+        }
+    }
+}
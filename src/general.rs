@@ -1,7 +1,79 @@
 //! Defines types that are very unlikely to change between game versions.
 
+pub mod allocator;
+pub use allocator::*;
+
+pub mod ammo_kinds;
+pub use ammo_kinds::*;
+
+pub mod ammo_table;
+pub use ammo_table::{AmmoRef, AmmoTable, DanglingReference};
+
+pub mod arena;
+pub use arena::GameArena;
+
+pub mod build;
+pub use build::*;
+
+pub mod convert;
+pub use convert::*;
+
+pub mod csv;
+pub use csv::CsvError;
+
+pub mod diff;
+pub use diff::{diff, Diff, FieldDiff};
+
+pub mod game_ptr;
+pub use game_ptr::GamePtr;
+
+pub mod hexdump;
+pub use hexdump::{Annotated, HexDump};
+
+pub use highfleet_derive::GameStruct;
+
+pub mod inspect;
+pub use inspect::{FieldValue, Inspect};
+
+pub mod layout;
+
+pub mod offline;
+pub use offline::Decode;
+
+pub mod patch;
+pub use patch::{Patch, PatchError};
+
+pub mod provenance;
+pub use provenance::{FieldNote, FieldNotes, Provenance};
+
+pub mod raw;
+
+pub mod schema;
+
+#[cfg(feature = "std")]
+pub mod string_pool;
+#[cfg(feature = "std")]
+pub use string_pool::StringPool;
+
+pub mod thread;
+pub use thread::GameThread;
+
+pub mod traits;
+pub use traits::*;
+
+pub mod version;
+
+pub mod warning;
+pub use warning::AmmoWarning;
+
 pub mod escadra_string;
 pub use escadra_string::*;
 
+pub mod escadra_vector;
+pub use escadra_vector::*;
+
+pub mod escadra_map;
+pub use escadra_map::{EscadraMap, EscadraMapNode};
+
 pub mod tll;
 pub use tll::*;
@@ -0,0 +1,253 @@
+//! Defines the allocator used by types that own heap memory, such as the `EscadraString`.
+//!
+//! Memory allocated by the game lives on the game's CRT heap.
+//! When injected into Highfleet, freeing that memory with the CRT this library was linked against corrupts the heap.
+//! Setting the allocator to `GameCrt` makes allocations and frees go through the game's CRT instead.
+//!
+//! ```no_run
+//! use highfleet::general::{set_allocator, GameCrt};
+//!
+//! // Before anything allocates, such as at the start of the mod's entry point.
+//! let crt: &'static GameCrt = Box::leak(Box::new(GameCrt::new().unwrap()));
+//! unsafe { set_allocator(crt) };
+//! ```
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// An allocator compatible with the C `malloc`/`free` interface.
+///
+/// # Safety
+///
+/// Implementations must return memory that is valid for reads and writes of `size` bytes,
+/// or a null pointer if the allocation failed.
+/// `free` must accept any pointer previously returned by `malloc` of the same allocator.
+pub unsafe trait EscadraAllocator: Send + Sync {
+    /// Allocates `size` bytes of memory.
+    ///
+    /// # Safety
+    ///
+    /// The returned memory is uninitialized.
+    unsafe fn malloc(&self, size: usize) -> *mut u8;
+
+    /// Frees memory previously allocated with `malloc`.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must have been returned by `malloc` of this allocator and must not have been freed already.
+    unsafe fn free(&self, pointer: *mut u8);
+}
+
+/// The default allocator. Uses the `malloc` and `free` of the CRT this library was linked against.
+///
+/// Targets without a CRT, such as `wasm32-unknown-unknown`, use the Rust global allocator instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LibcAllocator;
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+unsafe impl EscadraAllocator for LibcAllocator {
+    unsafe fn malloc(&self, size: usize) -> *mut u8 {
+        libc::malloc(size) as *mut u8
+    }
+
+    unsafe fn free(&self, pointer: *mut u8) {
+        libc::free(pointer as _)
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+unsafe impl EscadraAllocator for LibcAllocator {
+    unsafe fn malloc(&self, size: usize) -> *mut u8 {
+        RustAllocator.malloc(size)
+    }
+
+    unsafe fn free(&self, pointer: *mut u8) {
+        RustAllocator.free(pointer)
+    }
+}
+
+/// An allocator that uses the Rust global allocator.
+///
+/// Unlike the CRT, every allocation is visible to Miri and the sanitizers, which makes it the default
+/// under Miri, when fuzzing, and with the `rust-allocator` feature.
+/// Never use it for memory shared with the game.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustAllocator;
+
+/// Every allocation starts with a header holding its size, since `free` isn't given the size.
+const HEADER_SIZE: usize = 16;
+
+unsafe impl EscadraAllocator for RustAllocator {
+    unsafe fn malloc(&self, size: usize) -> *mut u8 {
+        let Some(layout) = size
+            .checked_add(HEADER_SIZE)
+            .and_then(|size| alloc::alloc::Layout::from_size_align(size, HEADER_SIZE).ok())
+        else {
+            return core::ptr::null_mut();
+        };
+        let pointer = alloc::alloc::alloc(layout);
+        if pointer.is_null() {
+            return pointer;
+        }
+        (pointer as *mut usize).write(layout.size());
+        pointer.add(HEADER_SIZE)
+    }
+
+    unsafe fn free(&self, pointer: *mut u8) {
+        if pointer.is_null() {
+            return;
+        }
+        let pointer = pointer.sub(HEADER_SIZE);
+        let size = (pointer as *mut usize).read();
+        alloc::alloc::dealloc(
+            pointer,
+            alloc::alloc::Layout::from_size_align_unchecked(size, HEADER_SIZE),
+        );
+    }
+}
+
+type MallocFn = unsafe extern "C" fn(usize) -> *mut u8;
+type FreeFn = unsafe extern "C" fn(*mut u8);
+
+/// An allocator that uses the `malloc` and `free` exported by the CRT loaded by the game.
+///
+/// Use this when injected into Highfleet so strings can be freed by the game and vice versa.
+#[derive(Debug, Clone, Copy)]
+pub struct GameCrt {
+    malloc: MallocFn,
+    free: FreeFn,
+}
+
+impl GameCrt {
+    /// The CRT modules searched by `GameCrt::new`, in order.
+    pub const MODULES: [&'static str; 2] = ["ucrtbase.dll", "msvcrt.dll"];
+
+    /// Resolves `malloc` and `free` from the first CRT in `GameCrt::MODULES` loaded by the current process.
+    ///
+    /// Returns `None` if no CRT module is loaded, or when not running on Windows.
+    pub fn new() -> Option<Self> {
+        Self::MODULES
+            .iter()
+            .find_map(|module| Self::from_module(module))
+    }
+
+    /// Resolves `malloc` and `free` from the given module loaded by the current process.
+    ///
+    /// Returns `None` if the module is not loaded, doesn't export both functions, or when not running on Windows.
+    pub fn from_module(module: &str) -> Option<Self> {
+        let malloc = unsafe { resolve(module, "malloc")? };
+        let free = unsafe { resolve(module, "free")? };
+
+        unsafe {
+            Some(Self {
+                malloc: core::mem::transmute::<*const u8, MallocFn>(malloc),
+                free: core::mem::transmute::<*const u8, FreeFn>(free),
+            })
+        }
+    }
+}
+
+unsafe impl EscadraAllocator for GameCrt {
+    unsafe fn malloc(&self, size: usize) -> *mut u8 {
+        (self.malloc)(size)
+    }
+
+    unsafe fn free(&self, pointer: *mut u8) {
+        (self.free)(pointer)
+    }
+}
+
+#[cfg(windows)]
+unsafe fn resolve(module: &str, function: &str) -> Option<*const u8> {
+    use alloc::ffi::CString;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(module_name: *const i8) -> *mut u8;
+        fn GetProcAddress(module: *mut u8, proc_name: *const i8) -> *const u8;
+    }
+
+    let module = CString::new(module).ok()?;
+    let function = CString::new(function).ok()?;
+
+    let handle = GetModuleHandleA(module.as_ptr());
+    if handle.is_null() {
+        return None;
+    }
+
+    let address = GetProcAddress(handle, function.as_ptr());
+    if address.is_null() {
+        return None;
+    }
+
+    Some(address)
+}
+
+#[cfg(not(windows))]
+unsafe fn resolve(_module: &str, _function: &str) -> Option<*const u8> {
+    None
+}
+
+#[cfg(not(any(miri, fuzzing, feature = "rust-allocator")))]
+static DEFAULT_ALLOCATOR: &dyn EscadraAllocator = &LibcAllocator;
+
+#[cfg(any(miri, fuzzing, feature = "rust-allocator"))]
+static DEFAULT_ALLOCATOR: &dyn EscadraAllocator = &RustAllocator;
+
+/// Points to the current allocator. An atomic pointer instead of a lock, so it works without `std`.
+static ALLOCATOR: AtomicPtr<&'static dyn EscadraAllocator> =
+    AtomicPtr::new(&DEFAULT_ALLOCATOR as *const _ as *mut _);
+
+/// Sets the allocator used by all types that own heap memory.
+///
+/// This should be done once, before any heap backed value is created.
+/// Every call leaks a few bytes.
+///
+/// # Safety
+///
+/// Values allocated with the previous allocator are freed with the new one.
+/// No heap backed value allocated with the previous allocator, such as an `EscadraString` longer than 15 bytes,
+/// may be alive, unless both allocators can free each other's memory.
+/// Nothing may be allocated or freed on another thread while the allocator changes.
+pub unsafe fn set_allocator(allocator: &'static dyn EscadraAllocator) {
+    ALLOCATOR.store(Box::leak(Box::new(allocator)), Ordering::Release);
+}
+
+/// Returns the allocator used by all types that own heap memory.
+pub fn allocator() -> &'static dyn EscadraAllocator {
+    unsafe { *ALLOCATOR.load(Ordering::Acquire) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libc_allocator_round_trip() {
+        unsafe {
+            let pointer = LibcAllocator.malloc(32);
+            assert!(!pointer.is_null());
+            *pointer = b'a';
+            LibcAllocator.free(pointer);
+        }
+    }
+
+    #[test]
+    fn rust_allocator_round_trip() {
+        unsafe {
+            let pointer = RustAllocator.malloc(32);
+            assert!(!pointer.is_null());
+            core::ptr::write_bytes(pointer, b'a', 32);
+            RustAllocator.free(pointer);
+
+            assert!(RustAllocator.malloc(usize::MAX).is_null());
+            RustAllocator.free(core::ptr::null_mut());
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn game_crt_is_unavailable_outside_windows() {
+        assert!(GameCrt::new().is_none());
+    }
+}
@@ -0,0 +1,256 @@
+//! Defines typed versions of the magic values used by `Ammo` fields.
+//!
+//! The `Ammo` structs keep the raw values, as the game reads them as is.
+//! Every enum has an `Other` variant, so values set by mods convert without loss.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serialized form of the enums: the name of a known value, or the raw value otherwise.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr<'a, T> {
+    Name(Cow<'a, str>),
+    Raw(T),
+}
+
+/// Defines an enum over the known values of an integer `Ammo` field.
+macro_rules! int_kind {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident = $value:literal => $serde_name:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value not used by the vanilla game.
+            Other(i32),
+        }
+
+        impl $name {
+            /// Every known value.
+            pub const KNOWN: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// Returns the name the value is serialized as, or `None` for `Other`.
+            pub fn name(self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => Some($serde_name),)*
+                    Self::Other(_) => None,
+                }
+            }
+
+            /// Returns the known value with the given name.
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($serde_name => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(value: i32) -> Self {
+                match value {
+                    $($value => Self::$variant,)*
+                    other => Self::Other(other),
+                }
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Other(other) => other,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.name() {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "{}", i32::from(*self)),
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self.name() {
+                    Some(name) => Repr::<i32>::Name(name.into()).serialize(serializer),
+                    None => Repr::Raw(i32::from(*self)).serialize(serializer),
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                match Repr::<i32>::deserialize(deserializer)? {
+                    Repr::Name(name) => Self::from_name(&name).ok_or_else(|| {
+                        serde::de::Error::custom(format!(
+                            concat!("unknown ", stringify!($name), " {}"),
+                            name
+                        ))
+                    }),
+                    Repr::Raw(value) => Ok(Self::from(value)),
+                }
+            }
+        }
+    };
+}
+
+int_kind! {
+    /// What reticle to use when firing the ammo, stored in `Ammo::reticle`.
+    Reticle {
+        /// Standard reticle used by most ammos.
+        Standard = 1 => "standard",
+        /// Used by aircraft bombs.
+        Bomb = 2 => "bomb",
+        /// Used mostly by rockets.
+        Rocket = 3 => "rocket",
+        /// Used by aircraft ammos.
+        Aircraft = 4 => "aircraft",
+    }
+}
+
+int_kind! {
+    /// How the shell behaves, stored in `Ammo::caliber`.
+    ShellBehavior {
+        /// The default behaviour.
+        Default = 100 => "default",
+        /// Used by rockets and incendiary rounds.
+        Incendiary = 130 => "incendiary",
+        /// Laser guided shells.
+        LaserGuided = 140 => "laser_guided",
+        /// Proximity fused shells.
+        Proxy = 160 => "proxy",
+    }
+}
+
+/// What sign to use for the reticle, stored in `Ammo::sign_ammo`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AmmoSign {
+    /// "sign_ammo_unset", used by the standard rounds.
+    Unset,
+    /// "sign_ammo_inc_small", used by small incendiary rounds.
+    IncendiarySmall,
+    /// "sign_ammo_ap", used by armour piercing rounds.
+    ArmourPiercing,
+    /// "sign_ammo_proxy", used by proxy rounds.
+    Proxy,
+    /// "sign_ammo_inc", used by standard incendiary rounds.
+    Incendiary,
+    /// "sign_ammo_guided", used by laser guided rounds.
+    Guided,
+    /// "sign_ammo_craft", used by rounds (bombs, or rockets) used by aircraft.
+    Craft,
+    /// A sign not used by the vanilla game.
+    Other(String),
+}
+
+impl AmmoSign {
+    /// Every known sign.
+    pub const KNOWN: &'static [Self] = &[
+        Self::Unset,
+        Self::IncendiarySmall,
+        Self::ArmourPiercing,
+        Self::Proxy,
+        Self::Incendiary,
+        Self::Guided,
+        Self::Craft,
+    ];
+
+    /// Returns the sign as stored in `Ammo::sign_ammo`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Unset => "sign_ammo_unset",
+            Self::IncendiarySmall => "sign_ammo_inc_small",
+            Self::ArmourPiercing => "sign_ammo_ap",
+            Self::Proxy => "sign_ammo_proxy",
+            Self::Incendiary => "sign_ammo_inc",
+            Self::Guided => "sign_ammo_guided",
+            Self::Craft => "sign_ammo_craft",
+            Self::Other(sign) => sign,
+        }
+    }
+}
+
+impl From<&str> for AmmoSign {
+    fn from(value: &str) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|sign| sign.as_str() == value)
+            .cloned()
+            .unwrap_or_else(|| Self::Other(value.to_string()))
+    }
+}
+
+impl fmt::Display for AmmoSign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for AmmoSign {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for AmmoSign {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sign = Cow::<str>::deserialize(deserializer)?;
+        Ok(Self::from(sign.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip() {
+        for value in [1, 2, 3, 4, 0, 7] {
+            assert_eq!(i32::from(Reticle::from(value)), value);
+        }
+        assert_eq!(Reticle::from(3), Reticle::Rocket);
+        assert_eq!(ShellBehavior::from(140), ShellBehavior::LaserGuided);
+        assert_eq!(ShellBehavior::from(150), ShellBehavior::Other(150));
+    }
+
+    #[test]
+    fn sign_round_trip() {
+        for sign in AmmoSign::KNOWN {
+            assert_eq!(&AmmoSign::from(sign.as_str()), sign);
+        }
+        assert_eq!(
+            AmmoSign::from("sign_ammo_custom"),
+            AmmoSign::Other("sign_ammo_custom".to_string())
+        );
+    }
+
+    #[test]
+    fn serde_as_names() {
+        let json =
+            serde_json::to_string(&[ShellBehavior::Proxy, ShellBehavior::Other(150)]).unwrap();
+        assert_eq!(json, r#"["proxy",150]"#);
+
+        let result: Vec<ShellBehavior> = serde_json::from_str(r#"["proxy",150,100]"#).unwrap();
+        assert_eq!(
+            result,
+            [
+                ShellBehavior::Proxy,
+                ShellBehavior::Other(150),
+                ShellBehavior::Default
+            ]
+        );
+
+        assert!(serde_json::from_str::<Reticle>(r#""sniper""#).is_err());
+
+        let json = serde_json::to_string(&AmmoSign::Guided).unwrap();
+        assert_eq!(json, r#""sign_ammo_guided""#);
+    }
+}
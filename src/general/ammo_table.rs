@@ -0,0 +1,381 @@
+//! Defines a view of the array holding every ammo of the game.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Serializer};
+
+use super::csv::{self, CsvError};
+use super::hexdump::Annotated;
+use super::{AmmoFields, EscadraVector, GameThread};
+use crate::seria::Container;
+
+mod live;
+pub use live::{AmmoPatch, LiveError};
+
+/// How the value of a `.seria` entry refers to an ammo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmmoRef {
+    /// By the ammo's `item_name`.
+    Name,
+    /// By the ammo's `index` field, written as a decimal number.
+    Index,
+}
+
+/// A `.seria` entry referring to an ammo the table doesn't have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingReference {
+    /// The key of the entry.
+    pub key: String,
+    /// The value of the entry, which no ammo matches.
+    pub value: String,
+}
+
+/// A view of the game's contiguous array of ammos, for any version's `Ammo`.
+///
+/// Ammos can be looked up by their position in the array, by their `index` field, or by their `item_name`.
+/// Weapons refer to ammos by the `index` field, which doesn't have to match the position.
+///
+/// Serializes as a sequence of every ammo, for exporting the full table.
+pub struct AmmoTable<'a, A> {
+    ammos: &'a mut [A],
+}
+
+impl<'a, A: AmmoFields> AmmoTable<'a, A> {
+    /// Creates a table out of a slice of ammos.
+    pub fn from_slice(ammos: &'a mut [A]) -> Self {
+        Self { ammos }
+    }
+
+    /// Creates a table out of the ammos stored in a vector, such as one owned by the game.
+    pub fn from_vector(ammos: &'a mut EscadraVector<A>) -> Self {
+        Self::from_slice(ammos.as_mut_slice())
+    }
+
+    /// Creates a table out of a base pointer and the number of ammos in the array, such as the game's ammo table.
+    ///
+    /// A null pointer gives an empty table.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to `count` valid ammos that outlive `'a`, and aren't accessed through other references.
+    pub unsafe fn from_raw_parts(base: *mut A, count: usize, _thread: &'a GameThread) -> Self {
+        if base.is_null() {
+            return Self::from_slice(&mut []);
+        }
+        Self::from_slice(core::slice::from_raw_parts_mut(base, count))
+    }
+
+    /// Returns the number of ammos in the table.
+    pub fn len(&self) -> usize {
+        self.ammos.len()
+    }
+
+    /// Returns true if the table holds no ammos.
+    pub fn is_empty(&self) -> bool {
+        self.ammos.is_empty()
+    }
+
+    /// Returns the ammos as a slice.
+    pub fn as_slice(&self) -> &[A] {
+        self.ammos
+    }
+
+    /// Returns the ammos as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [A] {
+        self.ammos
+    }
+
+    /// Returns an iterator over the ammos, in array order.
+    pub fn iter(&self) -> core::slice::Iter<'_, A> {
+        self.ammos.iter()
+    }
+
+    /// Returns an iterator over the ammos that allows modifying them, in array order.
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, A> {
+        self.ammos.iter_mut()
+    }
+
+    /// Returns the ammo at the given position in the array, or `None` if out of bounds.
+    pub fn get(&self, position: usize) -> Option<&A> {
+        self.ammos.get(position)
+    }
+
+    /// Returns the ammo at the given position in the array mutably, or `None` if out of bounds.
+    pub fn get_mut(&mut self, position: usize) -> Option<&mut A> {
+        self.ammos.get_mut(position)
+    }
+
+    /// Returns the first ammo with the given `index` field.
+    pub fn get_by_index(&self, index: i32) -> Option<&A> {
+        self.ammos.iter().find(|ammo| ammo.index() == index)
+    }
+
+    /// Returns the first ammo with the given `index` field mutably.
+    pub fn get_by_index_mut(&mut self, index: i32) -> Option<&mut A> {
+        self.ammos.iter_mut().find(|ammo| ammo.index() == index)
+    }
+
+    /// Returns the first ammo with the given `item_name`.
+    pub fn get_by_name(&self, name: &str) -> Option<&A> {
+        self.ammos.iter().find(|ammo| ammo.item_name() == name)
+    }
+
+    /// Returns the first ammo with the given `item_name` mutably.
+    pub fn get_by_name_mut(&mut self, name: &str) -> Option<&mut A> {
+        self.ammos.iter_mut().find(|ammo| ammo.item_name() == name)
+    }
+
+    /// Returns the ammo a `.seria` value refers to.
+    pub fn resolve(&self, value: &str, by: AmmoRef) -> Option<&A> {
+        let value = value.trim();
+        match by {
+            AmmoRef::Name => self.get_by_name(value),
+            AmmoRef::Index => self.get_by_index(value.parse().ok()?),
+        }
+    }
+
+    /// Returns every entry of a `.seria` document, such as the parts library, that refers to an ammo missing from the table.
+    ///
+    /// `keys` lists the keys of the entries that refer to ammos, and how they do.
+    /// Entries in nested blocks are checked as well. Empty values are not references.
+    pub fn dangling_references<C: Container>(
+        &self,
+        document: &C,
+        keys: &[(&str, AmmoRef)],
+    ) -> Vec<DanglingReference> {
+        document
+            .all_entries()
+            .into_iter()
+            .filter(|entry| !entry.value.trim().is_empty())
+            .filter(|entry| {
+                keys.iter().any(|(key, by)| {
+                    entry.key.trim() == *key && self.resolve(&entry.value, *by).is_none()
+                })
+            })
+            .map(|entry| DanglingReference {
+                key: entry.key.trim().to_string(),
+                value: entry.value.clone(),
+            })
+            .collect()
+    }
+}
+
+impl<A: AmmoFields + Annotated + Serialize + DeserializeOwned> AmmoTable<'_, A> {
+    /// Writes the table as CSV, one row per ammo and one column per field.
+    ///
+    /// Fails if an ammo has a string that isn't valid UTF-8.
+    pub fn to_csv(&self) -> Result<String, CsvError> {
+        csv::to_csv(self.ammos.iter())
+    }
+
+    /// Reads ammos written by `to_csv`, for example after editing them in a spreadsheet.
+    ///
+    /// Errors point to the row and column of the bad cell.
+    pub fn from_csv(text: &str) -> Result<Vec<A>, CsvError> {
+        csv::from_csv(text)
+    }
+
+    /// Replaces the ammos of the table with the ammos of a CSV table with the same `item_name`.
+    ///
+    /// Fails without changing the table if a row has no matching ammo.
+    pub fn import_csv(&mut self, text: &str) -> Result<(), CsvError> {
+        let ammos = csv::from_csv_lines::<A>(text)?;
+        let positions = ammos
+            .iter()
+            .map(|(row, ammo)| {
+                self.ammos
+                    .iter()
+                    .position(|existing| existing.item_name() == ammo.item_name())
+                    .ok_or_else(|| CsvError {
+                        row: *row,
+                        column: Some("item_name".to_string()),
+                        message: format!("no ammo is named {}", ammo.item_name()),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (position, (_, ammo)) in positions.into_iter().zip(ammos) {
+            self.ammos[position] = ammo;
+        }
+        Ok(())
+    }
+}
+
+impl<'t, A: AmmoFields> IntoIterator for &'t AmmoTable<'_, A> {
+    type Item = &'t A;
+    type IntoIter = core::slice::Iter<'t, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<A: Serialize> Serialize for AmmoTable<'_, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.ammos.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::raw::{from_bytes, to_bytes, PointerPolicy};
+    use crate::v1_163::{Ammo, AmmoBuilder};
+
+    #[test]
+    fn lookups() {
+        let mut ammos: EscadraVector<Ammo> = vec![
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57", 5),
+            AmmoBuilder::test_ammo("AMMO_85", 4),
+        ]
+        .into();
+        let mut table = AmmoTable::from_vector(&mut ammos);
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get(1).unwrap().item_name, "AMMO_57");
+        assert!(table.get(3).is_none());
+        assert_eq!(table.get_by_index(4).unwrap().item_name, "AMMO_85");
+        assert!(table.get_by_index(0).is_none());
+        assert_eq!(table.get_by_name("AMMO_37").unwrap().index, 3);
+
+        table.get_by_name_mut("AMMO_57").unwrap().speed = 1500.0;
+        assert_eq!(ammos[1].speed, 1500.0);
+    }
+
+    #[test]
+    fn raw_parts() {
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57", 5),
+        ];
+        let thread = unsafe { GameThread::new() };
+        let table = unsafe { AmmoTable::from_raw_parts(ammos.as_mut_ptr(), ammos.len(), &thread) };
+        assert_eq!(table.iter().map(|ammo| ammo.index).sum::<i32>(), 8);
+
+        let table = unsafe { AmmoTable::<Ammo>::from_raw_parts(core::ptr::null_mut(), 4, &thread) };
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn find_dangling_references() {
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57", 5),
+        ];
+        let table = AmmoTable::from_slice(&mut ammos);
+        let document = crate::seria::Document::parse(
+            "{\nammo=AMMO_57\nammo_index=3\n}\n{\nammo=AMMO_100\nammo_index=7\nammo=\nother=AMMO_100\n}\n",
+        )
+        .unwrap();
+
+        let dangling = table.dangling_references(
+            &document,
+            &[("ammo", AmmoRef::Name), ("ammo_index", AmmoRef::Index)],
+        );
+        let values: Vec<_> = dangling
+            .iter()
+            .map(|reference| reference.value.as_str())
+            .collect();
+        assert_eq!(values, ["AMMO_100", "7"]);
+        assert_eq!(
+            table.resolve(" 5", AmmoRef::Index).unwrap().item_name,
+            "AMMO_57"
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57, \"AP\"", 5),
+        ];
+        ammos[1].speed = 0.1;
+        ammos[0]
+            .magazine_image
+            .set_string(&" shell_37 ".to_string());
+        let table = AmmoTable::from_slice(&mut ammos);
+
+        let csv = table.to_csv().unwrap();
+        let mut lines = csv.lines();
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("reticle,padding_4h,item_name,"));
+        assert!(lines.nth(1).unwrap().contains("\"AMMO_57, \"\"AP\"\"\""));
+        assert!(csv.contains(",0.1,"));
+
+        let imported = AmmoTable::<Ammo>::from_csv(&csv).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(imported[1].item_name, "AMMO_57, \"AP\"");
+        assert_eq!(imported[1].speed, 0.1);
+        assert_eq!(imported[0].magazine_image, " shell_37 ");
+        assert_eq!(crate::general::diff(&imported[0], &ammos[0]), []);
+
+        // Replaces the first byte of the inline name.
+        let mut bytes = to_bytes(&ammos[0], &PointerPolicy::Zero);
+        bytes[0x08] = 0xff;
+        ammos[0] = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        let error = AmmoTable::from_slice(&mut ammos).to_csv().unwrap_err();
+        assert_eq!(error.row, 2);
+    }
+
+    #[test]
+    fn csv_import_errors() {
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57", 5),
+        ];
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let csv = table.to_csv().unwrap().replace(",5,", ",five,");
+
+        let error = table.import_csv(&csv).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "row 3, column index: invalid i32 \"five\""
+        );
+
+        let csv = table
+            .to_csv()
+            .unwrap()
+            .replace("AMMO_57", "AMMO_100")
+            .replace(",5,", ",6,");
+        let error = table.import_csv(&csv).unwrap_err();
+        assert_eq!(error.row, 3);
+        assert_eq!(table.get(1).unwrap().index, 5);
+
+        let csv = table.to_csv().unwrap().replace(",5,", ",6,");
+        table.import_csv(&csv).unwrap();
+        assert_eq!(table.get(1).unwrap().index, 6);
+
+        // Rows are the lines records start on, past blank lines and cells spanning lines.
+        table
+            .get_by_name_mut("AMMO_37")
+            .unwrap()
+            .shell_kind
+            .push_str("Line\nbreak");
+        let csv = table.to_csv().unwrap().replace(",6,", ",six,");
+        let error = table.import_csv(&csv).unwrap_err();
+        assert_eq!(error.row, 4);
+        let error = table
+            .import_csv(&csv.replace("\r\n", "\r\n\r\n"))
+            .unwrap_err();
+        assert_eq!(error.row, 6);
+    }
+
+    #[test]
+    fn serialize_table() {
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 3),
+            AmmoBuilder::test_ammo("AMMO_57", 5),
+        ];
+        let table = AmmoTable::from_slice(&mut ammos);
+
+        let json: Vec<serde_json::Value> =
+            serde_json::from_str(&serde_json::to_string(&table).unwrap()).unwrap();
+        assert_eq!(json.len(), 2);
+        assert_eq!(json[1]["item_name"], "AMMO_57");
+    }
+}
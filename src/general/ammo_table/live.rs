@@ -0,0 +1,440 @@
+//! Exports and patches the ammo table of a running game through a `MemorySource`.
+//!
+//! Strings are written into the buffers the game already allocated for them, as the game's allocator can't be called
+//! from another process. A string longer than its buffer fails the whole write with `LiveError::StringTooLong`;
+//! mods injected into the game can set such strings through `AmmoTable::from_raw_parts` instead.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::mem::size_of;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::AmmoTable;
+use crate::general::hexdump::{Annotated, FieldKind};
+use crate::general::raw::{from_bytes, to_bytes, PointerPolicy, RawError, RawLayout};
+use crate::general::{AmmoFields, EscadraString, Patch, PatchError};
+use crate::memory::{MemoryError, MemorySource};
+
+/// A patch for the ammo with the given `item_name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmmoPatch {
+    /// The `item_name` of the ammo to patch.
+    pub item_name: String,
+    /// The fields to change.
+    pub patch: Patch,
+}
+
+/// Error returned when `AmmoTable::write_to` fails.
+///
+/// Nothing is written when a patch fails. A `Memory` error may also come from a write failing partway,
+/// after which the writes before it are left in place.
+#[derive(Debug)]
+pub enum LiveError {
+    /// The memory of the game couldn't be read or written.
+    Memory(MemoryError),
+    /// No ammo of the table has the `item_name` of a patch.
+    UnknownAmmo(String),
+    /// A patch couldn't be applied to its ammo.
+    Patch {
+        /// The `item_name` of the ammo.
+        item_name: String,
+        /// Why the patch failed.
+        error: PatchError,
+    },
+    /// A string is longer than the buffer the game allocated for it.
+    StringTooLong {
+        /// The `item_name` of the ammo.
+        item_name: String,
+        /// The name of the field.
+        field: &'static str,
+        /// The length of the new string.
+        length: usize,
+        /// The longest string the buffer holds.
+        capacity: usize,
+    },
+}
+
+impl fmt::Display for LiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(error) => write!(f, "{error}"),
+            Self::UnknownAmmo(item_name) => write!(f, "no ammo is named {item_name}"),
+            Self::Patch { item_name, error } => write!(f, "couldn't patch {item_name}: {error}"),
+            Self::StringTooLong {
+                item_name,
+                field,
+                length,
+                capacity,
+            } => write!(
+                f,
+                "{field} of {item_name} is {length} bytes long, the game's buffer holds {capacity}"
+            ),
+        }
+    }
+}
+
+impl Error for LiveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Memory(error) => Some(error),
+            Self::Patch { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<MemoryError> for LiveError {
+    fn from(error: MemoryError) -> Self {
+        Self::Memory(error)
+    }
+}
+
+impl<A> AmmoTable<'_, A>
+where
+    A: AmmoFields + RawLayout + Annotated + Serialize + DeserializeOwned,
+{
+    /// Reads the `count` ammos of the table at `base`, copying their strings into memory owned by this process.
+    ///
+    /// `A` is the `Ammo` of the running game version, see `general::version::detect`.
+    pub fn dump_from<M: MemorySource>(
+        source: &M,
+        base: u64,
+        count: usize,
+    ) -> Result<Vec<A>, MemoryError> {
+        let bytes = read_table::<A, M>(source, base, count)?;
+        bytes
+            .chunks_exact(size_of::<A>())
+            .map(|chunk| Ok(from_bytes(chunk, &PointerPolicy::Resolve(source))?))
+            .collect()
+    }
+
+    /// Applies the patches to the `count` ammos of the table at `base`, returning how many ammos changed.
+    ///
+    /// Patches for the same ammo are stacked with `Patch::stack`, failing if they set a field to different values.
+    /// Every patch is checked before anything is written, so a failing patch leaves the game untouched.
+    /// A failing write can't be undone though, and leaves the writes before it in place.
+    pub fn write_to<M: MemorySource>(
+        source: &M,
+        base: u64,
+        count: usize,
+        patches: &[AmmoPatch],
+    ) -> Result<usize, LiveError> {
+        let table = read_table::<A, M>(source, base, count)?;
+        let chunks: Vec<&[u8]> = table.chunks_exact(size_of::<A>()).collect();
+        let policy = PointerPolicy::Resolve(source);
+
+        let names = chunks
+            .iter()
+            .map(|chunk| {
+                let ammo: A = from_bytes(chunk, &policy)?;
+                Ok(ammo.item_name().to_string())
+            })
+            .collect::<Result<Vec<_>, RawError>>()
+            .map_err(MemoryError::from)?;
+
+        // Stack the patches of every ammo, as each one starts from the ammo in the game.
+        let mut stacked: Vec<(usize, Patch)> = Vec::new();
+        for ammo_patch in patches {
+            let item_name = &ammo_patch.item_name;
+            let position = names
+                .iter()
+                .position(|name| name == item_name)
+                .ok_or_else(|| LiveError::UnknownAmmo(item_name.clone()))?;
+            match stacked.iter_mut().find(|(other, _)| *other == position) {
+                Some((_, patch)) => {
+                    patch
+                        .stack(&ammo_patch.patch)
+                        .map_err(|error| LiveError::Patch {
+                            item_name: item_name.clone(),
+                            error,
+                        })?
+                }
+                None => stacked.push((position, ammo_patch.patch.clone())),
+            }
+        }
+
+        let mut strings = Vec::new();
+        let mut ammos = Vec::new();
+        for (position, patch) in stacked {
+            let item_name = &names[position];
+            let old_bytes = chunks[position];
+
+            let old: A = from_bytes(old_bytes, &policy).map_err(MemoryError::from)?;
+            let mut new: A = from_bytes(old_bytes, &policy).map_err(MemoryError::from)?;
+            patch.apply(&mut new).map_err(|error| LiveError::Patch {
+                item_name: item_name.clone(),
+                error,
+            })?;
+            let new_bytes = to_bytes(&new, &PointerPolicy::Preserve);
+
+            let mut bytes = old_bytes.to_vec();
+            for field in A::fields() {
+                let range = field.offset..field.offset + field.kind.size();
+                if field.kind != FieldKind::String {
+                    bytes[range.clone()].copy_from_slice(&new_bytes[range]);
+                    continue;
+                }
+
+                let (old_string, new_string) =
+                    unsafe { (string_at(&old, field.offset), string_at(&new, field.offset)) };
+                let text = new_string.get_bytes();
+                if old_string.get_bytes() == text {
+                    continue;
+                }
+
+                let max_length = read_u64(old_bytes, field.offset + 0x18) as usize;
+                let capacity = max_length.max(15);
+                if text.len() > capacity {
+                    return Err(LiveError::StringTooLong {
+                        item_name: item_name.clone(),
+                        field: field.name,
+                        length: text.len(),
+                        capacity,
+                    });
+                }
+
+                let mut buffer = text.to_vec();
+                buffer.push(0);
+                if max_length <= 15 {
+                    buffer.resize(16, 0);
+                    bytes[field.offset..field.offset + 16].copy_from_slice(&buffer);
+                } else {
+                    strings.push((read_u64(old_bytes, field.offset), buffer));
+                }
+                bytes[field.offset + 0x10..field.offset + 0x18]
+                    .copy_from_slice(&(text.len() as u64).to_le_bytes());
+            }
+
+            if bytes != old_bytes {
+                ammos.push((base + (position * size_of::<A>()) as u64, bytes));
+            }
+        }
+
+        // Heap strings first, so the lengths written with the ammos never cover bytes that aren't there yet.
+        for (address, bytes) in strings.iter().chain(&ammos) {
+            source.write(*address, bytes)?;
+        }
+        Ok(ammos.len())
+    }
+}
+
+fn read_table<A, M: MemorySource>(
+    source: &M,
+    base: u64,
+    count: usize,
+) -> Result<Vec<u8>, MemoryError> {
+    let mut bytes = vec![0u8; count * size_of::<A>()];
+    source.read(base, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Returns the `EscadraString` at `offset` inside of `value`.
+///
+/// # Safety
+///
+/// There must be an `EscadraString` at `offset` inside of `value`.
+unsafe fn string_at<A>(value: &A, offset: usize) -> &EscadraString {
+    &*((value as *const A as *const u8).add(offset) as *const EscadraString)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v1_163::Ammo;
+    use core::cell::RefCell;
+
+    const BASE: u64 = 0x1400_0000;
+    const LONG_NAME: &str = "AMMO_57_INCENDIARY_CUSTOM";
+
+    /// A fake game holding two ammos, the second with a heap backed name stored after them.
+    struct FakeGame {
+        memory: RefCell<Vec<u8>>,
+    }
+
+    impl FakeGame {
+        fn new() -> Self {
+            let ammo = |name: &str, index| {
+                let mut ammo: Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+                ammo.item_name.push_str(name);
+                ammo.magazine_image.push_str("shell_57");
+                ammo.index = index;
+                ammo.speed = 1000.0;
+                // Vanilla values, so `Patch::apply` accepts the ammo with the `strict` feature.
+                ammo.sign_ammo.push_str("sign_ammo_unset");
+                ammo.reticle = 1;
+                ammo.caliber = 100;
+                ammo.bullet_height = 20.0;
+                ammo.ttl = 1.0;
+                ammo
+            };
+
+            let mut memory = to_bytes(&ammo("AMMO_37", 3), &PointerPolicy::Zero);
+            let mut second = to_bytes(&ammo("AMMO_57", 5), &PointerPolicy::Zero);
+            let name_address = BASE + 2 * size_of::<Ammo>() as u64;
+            second[0x08..0x10].copy_from_slice(&name_address.to_le_bytes());
+            second[0x18..0x20].copy_from_slice(&(LONG_NAME.len() as u64).to_le_bytes());
+            second[0x20..0x28].copy_from_slice(&(LONG_NAME.len() as u64).to_le_bytes());
+            memory.extend(second);
+            memory.extend(LONG_NAME.as_bytes());
+            memory.push(0);
+
+            Self {
+                memory: RefCell::new(memory),
+            }
+        }
+
+        fn range(&self, address: u64, size: usize) -> Option<core::ops::Range<usize>> {
+            let start = address.checked_sub(BASE)? as usize;
+            (start + size <= self.memory.borrow().len()).then_some(start..start + size)
+        }
+    }
+
+    impl MemorySource for FakeGame {
+        fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+            let size = buffer.len();
+            let range = self
+                .range(address, size)
+                .ok_or(MemoryError::Unreadable { address, size })?;
+            buffer.copy_from_slice(&self.memory.borrow()[range]);
+            Ok(())
+        }
+
+        fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+            let size = bytes.len();
+            let range = self
+                .range(address, size)
+                .ok_or(MemoryError::Unwritable { address, size })?;
+            self.memory.borrow_mut()[range].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    fn patch(item_name: &str, fields: &[(&str, serde_json::Value)]) -> AmmoPatch {
+        let mut patch = Patch::new();
+        for (field, value) in fields {
+            patch.set(field, value).unwrap();
+        }
+        AmmoPatch {
+            item_name: item_name.into(),
+            patch,
+        }
+    }
+
+    #[test]
+    fn dumps_the_table() {
+        let game = FakeGame::new();
+        let ammos = AmmoTable::<Ammo>::dump_from(&game, BASE, 2).unwrap();
+
+        assert_eq!(ammos[0].item_name, "AMMO_37");
+        assert_eq!(ammos[1].item_name, LONG_NAME);
+        assert_eq!(ammos[1].index, 5);
+        assert!(AmmoTable::<Ammo>::dump_from(&game, BASE, 3).is_err());
+    }
+
+    #[test]
+    fn writes_patches_in_place() {
+        let game = FakeGame::new();
+        let patches = [
+            patch(
+                "AMMO_37",
+                &[
+                    ("speed", 1500.0.into()),
+                    ("magazine_image", "shell_37".into()),
+                ],
+            ),
+            patch(LONG_NAME, &[("item_name", "AMMO_57_CUSTOM".into())]),
+        ];
+        assert_eq!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &patches).unwrap(),
+            2
+        );
+
+        let ammos = AmmoTable::<Ammo>::dump_from(&game, BASE, 2).unwrap();
+        assert_eq!(ammos[0].speed, 1500.0);
+        assert_eq!(ammos[0].magazine_image, "shell_37");
+        assert_eq!(ammos[1].item_name, "AMMO_57_CUSTOM");
+
+        // The shorter name reuses the game's buffer.
+        let name_address = BASE + 2 * size_of::<Ammo>() as u64;
+        let second = &game.memory.borrow()[size_of::<Ammo>()..];
+        assert_eq!(read_u64(second, 0x08), name_address);
+        assert_eq!(&second[size_of::<Ammo>()..][..15], b"AMMO_57_CUSTOM\0");
+    }
+
+    #[test]
+    fn stacks_patches_of_the_same_ammo() {
+        let game = FakeGame::new();
+        let patches = [
+            patch("AMMO_37", &[("speed", 1500.0.into())]),
+            patch("AMMO_37", &[("magazine_image", "shell_37".into())]),
+        ];
+        assert_eq!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &patches).unwrap(),
+            1
+        );
+
+        let ammos = AmmoTable::<Ammo>::dump_from(&game, BASE, 2).unwrap();
+        assert_eq!(ammos[0].speed, 1500.0);
+        assert_eq!(ammos[0].magazine_image, "shell_37");
+
+        let before = game.memory.borrow().clone();
+        let conflicting = [
+            patch("AMMO_37", &[("speed", 1000.0.into())]),
+            patch("AMMO_37", &[("speed", 1250.0.into())]),
+        ];
+        assert!(matches!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &conflicting),
+            Err(LiveError::Patch {
+                error: PatchError::Conflict { .. },
+                ..
+            })
+        ));
+        assert_eq!(*game.memory.borrow(), before);
+    }
+
+    #[test]
+    fn fails_without_writing() {
+        let game = FakeGame::new();
+        let before = game.memory.borrow().clone();
+
+        let too_long = [
+            patch("AMMO_37", &[("speed", 1500.0.into())]),
+            patch(
+                "AMMO_37",
+                &[("magazine_image", "shell_37_with_a_long_name".into())],
+            ),
+        ];
+        assert!(matches!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &too_long),
+            Err(LiveError::StringTooLong {
+                field: "magazine_image",
+                length: 25,
+                capacity: 15,
+                ..
+            })
+        ));
+
+        let unknown = [patch("AMMO_85", &[("speed", 1500.0.into())])];
+        assert!(matches!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &unknown),
+            Err(LiveError::UnknownAmmo(name)) if name == "AMMO_85"
+        ));
+
+        let bad_field = [patch("AMMO_37", &[("range", 1500.0.into())])];
+        assert!(matches!(
+            AmmoTable::<Ammo>::write_to(&game, BASE, 2, &bad_field),
+            Err(LiveError::Patch { .. })
+        ));
+
+        assert_eq!(*game.memory.borrow(), before);
+    }
+}
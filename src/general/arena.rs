@@ -0,0 +1,197 @@
+//! Defines an arena allocating game structures in blocks, instead of one `malloc` per node.
+//!
+//! Building a TLL tree or another container node by node fragments the game heap.
+//! A `GameArena` takes blocks from the game allocator, places values one after the other inside of them,
+//! and frees every block at once when dropped. Values never move, so pointers to them can be linked together.
+//!
+//! The game must never free a value of the arena on its own, as it's not the start of an allocation.
+//! Structures handed over to the game for good are kept alive with `GameArena::leak`.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::mem::{align_of, needs_drop, size_of};
+
+use super::allocator::{allocator, EscadraAllocator};
+
+/// The size of the blocks allocated by `GameArena::new`.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// The largest alignment the arena supports, the alignment `malloc` guarantees on 64-bit Windows.
+pub const MAX_ALIGN: usize = 16;
+
+/// Allocates values in blocks taken from the game allocator, all freed when the arena is dropped.
+pub struct GameArena {
+    allocator: &'static dyn EscadraAllocator,
+    block_size: usize,
+    state: RefCell<State>,
+}
+
+/// Drops the value of a given type at a pointer.
+type DropFn = unsafe fn(*mut u8);
+
+struct State {
+    blocks: Vec<*mut u8>,
+    /// The next free byte of the current block.
+    next: *mut u8,
+    /// The number of free bytes left in the current block.
+    left: usize,
+    /// The values to drop with the arena, in allocation order.
+    drops: Vec<(*mut u8, DropFn)>,
+}
+
+impl GameArena {
+    /// Creates an empty arena using the allocator set by `set_allocator`, with blocks of `DEFAULT_BLOCK_SIZE` bytes.
+    pub fn new() -> Self {
+        Self::with_allocator(allocator(), DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Creates an empty arena taking blocks of `block_size` bytes from `allocator`.
+    ///
+    /// Values larger than `block_size` get a block of their own.
+    pub fn with_allocator(allocator: &'static dyn EscadraAllocator, block_size: usize) -> Self {
+        Self {
+            allocator,
+            block_size,
+            state: RefCell::new(State {
+                blocks: Vec::new(),
+                next: core::ptr::null_mut(),
+                left: 0,
+                drops: Vec::new(),
+            }),
+        }
+    }
+
+    /// Moves `value` into the arena, returning a reference that stays valid and in place as long as the arena.
+    ///
+    /// The value is dropped with the arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` needs an alignment above `MAX_ALIGN`, or the allocator is out of memory.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        assert!(
+            align_of::<T>() <= MAX_ALIGN,
+            "GameArena can't align to {}",
+            align_of::<T>()
+        );
+
+        let pointer = self.alloc_bytes(size_of::<T>(), align_of::<T>()) as *mut T;
+        unsafe {
+            pointer.write(value);
+            if needs_drop::<T>() {
+                self.state
+                    .borrow_mut()
+                    .drops
+                    .push((pointer as *mut u8, drop_value::<T>));
+            }
+            &mut *pointer
+        }
+    }
+
+    fn alloc_bytes(&self, size: usize, align: usize) -> *mut u8 {
+        let mut state = self.state.borrow_mut();
+
+        let padding = if state.next.is_null() {
+            0
+        } else {
+            state.next.align_offset(align)
+        };
+        if state.next.is_null() || padding + size > state.left {
+            let block_size = self.block_size.max(size).max(1);
+            let block = unsafe { self.allocator.malloc(block_size) };
+            assert!(!block.is_null(), "the game allocator is out of memory");
+            debug_assert!(block.align_offset(MAX_ALIGN) == 0);
+
+            state.blocks.push(block);
+            state.next = block;
+            state.left = block_size;
+            return Self::take(&mut state, 0, size);
+        }
+
+        Self::take(&mut state, padding, size)
+    }
+
+    fn take(state: &mut State, padding: usize, size: usize) -> *mut u8 {
+        unsafe {
+            let pointer = state.next.add(padding);
+            state.next = pointer.add(size);
+            state.left -= padding + size;
+            pointer
+        }
+    }
+
+    /// Returns the number of blocks taken from the allocator.
+    pub fn block_count(&self) -> usize {
+        self.state.borrow().blocks.len()
+    }
+
+    /// Gives up the arena without freeing or dropping anything, for structures handed over to the game for good.
+    pub fn leak(self) {
+        core::mem::forget(self);
+    }
+}
+
+unsafe fn drop_value<T>(pointer: *mut u8) {
+    core::ptr::drop_in_place(pointer as *mut T);
+}
+
+impl Default for GameArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GameArena {
+    fn drop(&mut self) {
+        let state = self.state.get_mut();
+        for (pointer, drop) in state.drops.drain(..).rev() {
+            unsafe { drop(pointer) };
+        }
+        for block in state.blocks.drain(..) {
+            unsafe { self.allocator.free(block) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::allocator::RustAllocator;
+    use crate::general::EscadraString;
+    use alloc::rc::Rc;
+
+    static ALLOCATOR: RustAllocator = RustAllocator;
+
+    #[test]
+    fn allocates_in_blocks() {
+        let arena = GameArena::with_allocator(&ALLOCATOR, 64);
+        let first = arena.alloc(1u8) as *mut u8;
+        let second = arena.alloc(2u64);
+        assert_eq!(*second, 2);
+        assert_eq!((second as *mut u64).align_offset(8), 0);
+        assert_eq!(unsafe { *first }, 1);
+        assert_eq!(arena.block_count(), 1);
+
+        for i in 0..8u64 {
+            assert_eq!(*arena.alloc(i), i);
+        }
+        assert_eq!(arena.block_count(), 2);
+
+        let large = arena.alloc([7u8; 100]);
+        assert_eq!(large[99], 7);
+        assert_eq!(arena.block_count(), 3);
+    }
+
+    #[test]
+    fn drops_values_with_the_arena() {
+        let counter = Rc::new(());
+        let arena = GameArena::with_allocator(&ALLOCATOR, 256);
+        arena.alloc(counter.clone());
+        arena.alloc(EscadraString::from("a string too long to be stored inline"));
+        assert_eq!(Rc::strong_count(&counter), 2);
+
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
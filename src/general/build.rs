@@ -0,0 +1,67 @@
+//! Defines the error returned when building a struct out of a builder fails.
+
+use core::error::Error;
+use core::fmt;
+
+/// Returned by `AmmoBuilder::build` when the ammo would be invalid.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmmoBuildError {
+    /// A field without a vanilla default was never set.
+    MissingField(&'static str),
+    /// A field was set to a value outside of its valid range.
+    OutOfRange {
+        /// The name of the field.
+        field: &'static str,
+        /// The value it was set to.
+        value: f32,
+    },
+}
+
+impl fmt::Display for AmmoBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "required field {field} was not set"),
+            Self::OutOfRange { field, value } => {
+                write!(f, "field {field} is out of range: {value}")
+            }
+        }
+    }
+}
+
+impl Error for AmmoBuildError {}
+
+/// Checks that a field set in a builder lies within `min..=max`.
+pub(crate) fn check_range(
+    field: &'static str,
+    value: f32,
+    min: f32,
+    max: f32,
+) -> Result<(), AmmoBuildError> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(AmmoBuildError::OutOfRange { field, value })
+    }
+}
+
+/// Implements `test_ammo` for a version's `AmmoBuilder`, building ammos for the tests of the crate.
+#[cfg(test)]
+macro_rules! impl_test_ammo {
+    ($ammo:ty) => {
+        impl AmmoBuilder {
+            /// Builds an ammo with vanilla defaults, named `item_name` and at `index`, for the tests of the crate.
+            pub(crate) fn test_ammo(item_name: &str, index: i32) -> $ammo {
+                Self::new()
+                    .item_name(item_name)
+                    .index(index)
+                    .magazine_image("shell_57")
+                    .speed(1000.0)
+                    .build()
+                    .unwrap()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use impl_test_ammo;
@@ -0,0 +1,40 @@
+//! Defines the error returned when converting a struct between game versions would lose data.
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+/// Returned by `TryFrom` conversions between the structs of different game versions when fields would be lost.
+///
+/// Fields that only exist in the source version are lost unless they hold the value the opposite `From` conversion fills in.
+/// Fields that only exist in the target version get a default value documented by the conversion, and aren't listed in `lost`.
+/// The converted value is still available through `value`, for when the loss is acceptable.
+pub struct LossyConversion<T> {
+    /// The converted value, missing the lost fields.
+    pub value: T,
+    /// The names of the fields whose values were lost.
+    pub lost: Vec<&'static str>,
+}
+
+impl<T> LossyConversion<T> {
+    /// Accepts the loss, returning the converted value.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> fmt::Debug for LossyConversion<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LossyConversion")
+            .field("lost", &self.lost)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for LossyConversion<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conversion loses fields: {}", self.lost.join(", "))
+    }
+}
+
+impl<T> Error for LossyConversion<T> {}
@@ -0,0 +1,264 @@
+//! Converts game structs to and from CSV, one row per struct and one column per field.
+//!
+//! Columns are named and ordered like the fields of the struct, as listed by `Annotated`.
+//! `EscadraString`s are written as plain text, and numbers in their shortest form.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+
+use super::hexdump::{Annotated, FieldKind};
+
+/// Returned when reading or writing a CSV table fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvError {
+    /// The line of the CSV text the record of the problem starts on, starting at 1.
+    ///
+    /// This is the row a spreadsheet shows, unless blank lines were skipped or a quoted cell spans several lines.
+    pub row: usize,
+    /// The column of the problem, if it's in a single cell.
+    pub column: Option<String>,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl CsvError {
+    fn new(row: usize, column: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            row,
+            column: column.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(f, "row {}, column {column}: {}", self.row, self.message),
+            None => write!(f, "row {}: {}", self.row, self.message),
+        }
+    }
+}
+
+impl Error for CsvError {}
+
+/// Writes structs as CSV, with a header row of field names.
+///
+/// Fields that can't be written as text, such as pointers, are left out.
+/// Fails if a struct can't be serialized, such as with a string that isn't valid UTF-8.
+pub fn to_csv<'a, T: Annotated + Serialize + 'a>(
+    rows: impl IntoIterator<Item = &'a T>,
+) -> Result<String, CsvError> {
+    let fields: Vec<_> = T::fields()
+        .into_iter()
+        .filter(|field| !matches!(field.kind, FieldKind::Pointer | FieldKind::Bytes(_)))
+        .collect();
+
+    let mut csv = String::new();
+    write_row(&mut csv, fields.iter().map(|field| field.name.to_string()));
+
+    for row in rows {
+        let value = serde_json::to_value(row).map_err(|error| {
+            // Quoted cells may span lines, so count the lines written so far.
+            CsvError::new(csv.matches('\n').count() + 1, None, error.to_string())
+        })?;
+        write_row(
+            &mut csv,
+            fields.iter().map(|field| match value.get(field.name) {
+                Some(Value::String(text)) => text.clone(),
+                // Shows the f32 in its shortest form, rather than the f64 it was widened to.
+                Some(Value::Number(number)) if field.kind == FieldKind::F32 => {
+                    (number.as_f64().unwrap_or_default() as f32).to_string()
+                }
+                Some(Value::Null) | None => String::new(),
+                Some(other) => other.to_string(),
+            }),
+        );
+    }
+    Ok(csv)
+}
+
+/// Reads structs written by `to_csv`.
+///
+/// Columns may be in any order, but must all be fields of `T`.
+pub fn from_csv<T: Annotated + DeserializeOwned>(csv: &str) -> Result<Vec<T>, CsvError> {
+    Ok(from_csv_lines(csv)?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect())
+}
+
+/// Reads structs like `from_csv`, along with the line each of their records starts on.
+pub(crate) fn from_csv_lines<T: Annotated + DeserializeOwned>(
+    csv: &str,
+) -> Result<Vec<(usize, T)>, CsvError> {
+    let mut records = parse(csv)?.into_iter();
+    let (header_line, header) = records
+        .next()
+        .ok_or_else(|| CsvError::new(1, None, "missing header"))?;
+
+    let fields = T::fields();
+    let columns = header
+        .iter()
+        .map(|name| {
+            fields
+                .iter()
+                .find(|field| field.name == name)
+                .ok_or_else(|| CsvError::new(header_line, Some(name), "unknown field"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut rows = Vec::new();
+    for (row, record) in records {
+        if record.len() != columns.len() {
+            let message = format!("expected {} cells, found {}", columns.len(), record.len());
+            return Err(CsvError::new(row, None, message));
+        }
+
+        let mut object = Map::new();
+        for (field, cell) in columns.iter().zip(record) {
+            let value = parse_cell(field.kind, &cell).ok_or_else(|| {
+                CsvError::new(
+                    row,
+                    Some(field.name),
+                    format!("invalid {} {cell:?}", field.kind.name()),
+                )
+            })?;
+            object.insert(field.name.to_string(), value);
+        }
+
+        let value = serde_json::from_value(Value::Object(object))
+            .map_err(|error| CsvError::new(row, None, error.to_string()))?;
+        rows.push((row, value));
+    }
+    Ok(rows)
+}
+
+/// Converts the text of a cell into the JSON value of a field.
+///
+/// Whitespace around numbers and booleans is ignored, strings are kept as they are.
+fn parse_cell(kind: FieldKind, cell: &str) -> Option<Value> {
+    let trimmed = cell.trim();
+    Some(match kind {
+        FieldKind::String => Value::String(cell.to_string()),
+        FieldKind::Bool => Value::Bool(trimmed.parse().ok()?),
+        FieldKind::I32 => trimmed.parse::<i32>().ok()?.into(),
+        FieldKind::U16 => trimmed.parse::<u16>().ok()?.into(),
+        FieldKind::U32 => trimmed.parse::<u32>().ok()?.into(),
+        FieldKind::U64 => trimmed.parse::<u64>().ok()?.into(),
+        FieldKind::F32 => Value::Number(Number::from_f64(trimmed.parse::<f32>().ok()? as f64)?),
+        FieldKind::Pointer | FieldKind::Bytes(_) => return None,
+    })
+}
+
+/// Appends a row, quoting the cells that need it.
+fn write_row(csv: &mut String, cells: impl Iterator<Item = String>) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        let needs_quotes = cell.contains([',', '"', '\n', '\r']) || cell.trim() != cell;
+        if needs_quotes {
+            csv.push('"');
+            csv.push_str(&cell.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(&cell);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+/// Splits CSV text into records of cells, each with the line it starts on.
+///
+/// Quoted cells may hold commas, quotes written as `""`, and line breaks. Blank lines are skipped.
+fn parse(csv: &str) -> Result<Vec<(usize, Vec<String>)>, CsvError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut row = 1;
+    let mut start = 1;
+    let mut chars = csv.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if cell.is_empty() => quoted = true,
+            (',', false) => record.push(core::mem::take(&mut cell)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                record.push(core::mem::take(&mut cell));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push((start, core::mem::take(&mut record)));
+                }
+                record.clear();
+                row += 1;
+                start = row;
+            }
+            ('\n', true) => {
+                cell.push(c);
+                row += 1;
+            }
+            _ => cell.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(CsvError::new(row, None, "unclosed quote"));
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push((start, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_quoted_cells() {
+        let records = parse("a,b\r\n\"x, \"\"y\"\"\",\"line\nbreak\"\n\n1,2").unwrap();
+        let lines: Vec<_> = records.iter().map(|(line, _)| *line).collect();
+        let cells: Vec<_> = records.into_iter().map(|(_, cells)| cells).collect();
+        assert_eq!(lines, [1, 2, 5]);
+        assert_eq!(
+            cells,
+            [
+                vec!["a", "b"],
+                vec!["x, \"y\"", "line\nbreak"],
+                vec!["1", "2"]
+            ]
+        );
+
+        assert_eq!(
+            parse("a\n\"open").unwrap_err(),
+            CsvError::new(2, None, "unclosed quote")
+        );
+    }
+
+    #[test]
+    fn write_quotes_when_needed() {
+        let mut csv = String::new();
+        write_row(
+            &mut csv,
+            ["plain", "a,b", " padded", "say \"hi\""]
+                .map(String::from)
+                .into_iter(),
+        );
+        assert_eq!(csv, "plain,\"a,b\",\" padded\",\"say \"\"hi\"\"\"\r\n");
+    }
+}
@@ -0,0 +1,39 @@
+//! Defines how to compare two values of a game struct field by field.
+//!
+//! A mod manager can use this to show exactly what a patch changes compared to vanilla.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A field whose value differs between two structs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// The name of the field.
+    pub field: &'static str,
+    /// The old value, formatted with `Display`.
+    pub old: String,
+    /// The new value, formatted with `Display`.
+    pub new: String,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// A struct that can be compared field by field.
+///
+/// Implemented by `#[derive(GameStruct)]`, comparing every field by its `Display` form.
+pub trait Diff {
+    /// Returns every field whose value differs between `self` and `other`, in declaration order.
+    fn diff(&self, other: &Self) -> Vec<FieldDiff>;
+}
+
+/// Returns every field whose value differs between `old` and `new`, in declaration order.
+///
+/// Values are compared by their formatted form, so a `NaN` equals itself.
+pub fn diff<T: Diff>(old: &T, new: &T) -> Vec<FieldDiff> {
+    old.diff(new)
+}
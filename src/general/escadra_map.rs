@@ -0,0 +1,369 @@
+//! Defines an ordered map used within Highfleet, laid out like an MSVC `std::map`.
+//!
+//! An MSVC `std::map` is a red-black tree with a sentinel head node.
+//! The head's parent is the root of the tree, its left is the smallest node, and its right is the largest node.
+//! Every leaf points back to the head, which is marked by `is_nil`.
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+
+use super::layout::assert_layout;
+
+/// A node in the red-black tree of an `EscadraMap`.
+#[repr(C)]
+pub struct EscadraMapNode<K, V> {
+    left: *mut EscadraMapNode<K, V>,
+    parent: *mut EscadraMapNode<K, V>,
+    right: *mut EscadraMapNode<K, V>,
+    /// 0 for red, 1 for black.
+    color: u8,
+    /// True for the head of the tree.
+    is_nil: bool,
+    pub(crate) key: K,
+    value: V,
+}
+
+assert_layout!(
+    EscadraMapNode<super::EscadraString, [u8; 0x20]>,
+    size = 0x60,
+    left = 0x00,
+    parent = 0x08,
+    right = 0x10,
+    color = 0x18,
+    is_nil = 0x19,
+    key = 0x20,
+    value = 0x40,
+);
+
+impl<K, V> EscadraMapNode<K, V> {
+    /// Returns the key of the node.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the value of the node.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// Returns the value of the node mutably.
+    pub fn value_mut(&mut self) -> &mut V {
+        &mut self.value
+    }
+
+    /// Returns true if the node is the sentinel head of the tree, which holds no key or value.
+    pub fn is_nil(&self) -> bool {
+        self.is_nil
+    }
+
+    /// Returns true if the node is black.
+    pub fn is_black(&self) -> bool {
+        self.color == 1
+    }
+
+    /// Returns the node with the smallest key in the subtree of this node.
+    fn leftmost(&self) -> &Self {
+        let mut node = self;
+        unsafe {
+            while !(*node.left).is_nil {
+                node = &*node.left;
+            }
+        }
+        node
+    }
+
+    /// Returns the in-order successor of this node, which is the head once the end is reached.
+    fn next(&self) -> &Self {
+        unsafe {
+            if !(*self.right).is_nil {
+                return (*self.right).leftmost();
+            }
+
+            let mut node = self;
+            let mut parent = &*self.parent;
+            while !parent.is_nil && core::ptr::eq(node, parent.right) {
+                node = parent;
+                parent = &*parent.parent;
+            }
+            parent
+        }
+    }
+}
+
+/// An ordered map owned by the game, laid out like an MSVC `std::map`.
+///
+/// This is a read-only view of the tree: it never allocates or frees nodes.
+#[repr(C)]
+pub struct EscadraMap<K, V> {
+    /// The sentinel head of the tree.
+    head: *mut EscadraMapNode<K, V>,
+    /// The number of nodes in the tree, excluding the head.
+    size: u64,
+}
+
+assert_layout!(EscadraMap<u8, u8>, size = 0x10, head = 0x00, size = 0x08);
+
+impl<K, V> EscadraMap<K, V> {
+    /// Creates a view of the tree with the given head, counting its nodes.
+    ///
+    /// Use this when only the head node is known, for example when starting from a `TLL`.
+    ///
+    /// # Safety
+    ///
+    /// `head` must point to the sentinel head of a valid tree that outlives the returned map.
+    pub unsafe fn from_head(head: *mut EscadraMapNode<K, V>) -> Self {
+        let mut map = Self { head, size: 0 };
+        map.size = map.iter().count() as u64;
+        map
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.size as _
+    }
+
+    /// Returns true if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the root node of the tree, or `None` if the map is empty.
+    pub fn root(&self) -> Option<&EscadraMapNode<K, V>> {
+        if self.head.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let root = &*(*self.head).parent;
+            (!root.is_nil).then_some(root)
+        }
+    }
+
+    /// Returns an iterator over the entries, ordered by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let node = if self.head.is_null() {
+            self.head
+        } else {
+            unsafe { (*self.head).left }
+        };
+
+        Iter {
+            node,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the keys, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over the values, ordered by key.
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Returns the node with the given key by walking down the tree.
+    ///
+    /// The game orders keys the same way Rust orders `Q`, which holds for strings and integers.
+    pub fn get_node<Q>(&self, key: &Q) -> Option<&EscadraMapNode<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self.root()?;
+        loop {
+            let next = match key.cmp(node.key.borrow()) {
+                Ordering::Less => node.left,
+                Ordering::Greater => node.right,
+                Ordering::Equal => return Some(node),
+            };
+
+            node = unsafe { &*next };
+            if node.is_nil {
+                return None;
+            }
+        }
+    }
+
+    /// Returns the value with the given key.
+    ///
+    /// For maps keyed by `EscadraString` this takes the bytes of the key, such as `"key".as_bytes()`,
+    /// which are compared the way `TLL::find` compares them.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_node(key).map(|node| &node.value)
+    }
+
+    /// Returns true if the map has an entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_node(key).is_some()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for EscadraMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a EscadraMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An in-order iterator over the entries of an `EscadraMap`.
+pub struct Iter<'a, K, V> {
+    /// The next node to yield, or the head once done.
+    node: *mut EscadraMapNode<K, V>,
+    _marker: PhantomData<&'a EscadraMapNode<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.node.is_null() {
+            return None;
+        }
+
+        let node: &'a EscadraMapNode<K, V> = unsafe { &*self.node };
+        if node.is_nil {
+            return None;
+        }
+
+        self.node = node.next() as *const _ as *mut _;
+        Some((&node.key, &node.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+    use core::ptr::null_mut;
+
+    /// Allocates a node. The nodes of a test tree are leaked.
+    fn node<K, V>(key: K, value: V, is_nil: bool) -> *mut EscadraMapNode<K, V> {
+        Box::into_raw(Box::new(EscadraMapNode {
+            left: null_mut(),
+            parent: null_mut(),
+            right: null_mut(),
+            color: 1,
+            is_nil,
+            key,
+            value,
+        }))
+    }
+
+    /// Builds a balanced tree out of three sorted entries.
+    fn three_node_map<K: Default, V: Default>(entries: [(K, V); 3]) -> EscadraMap<K, V> {
+        let head = node(K::default(), V::default(), true);
+        let [a, b, c] = entries.map(|(key, value)| node(key, value, false));
+
+        unsafe {
+            (*head).parent = b;
+            (*head).left = a;
+            (*head).right = c;
+
+            (*b).parent = head;
+            (*b).left = a;
+            (*b).right = c;
+
+            for leaf in [a, c] {
+                (*leaf).parent = b;
+                (*leaf).left = head;
+                (*leaf).right = head;
+                (*leaf).color = 0;
+            }
+
+            EscadraMap::from_head(head)
+        }
+    }
+
+    fn fruit_map() -> EscadraMap<EscadraString, u32> {
+        three_node_map([
+            ("Apple".to_string().into(), 1),
+            ("Banana".to_string().into(), 2),
+            ("Cherry Cherry Cherry".to_string().into(), 3),
+        ])
+    }
+
+    #[test]
+    fn map_size() {
+        assert_eq!(core::mem::size_of::<EscadraMap<u32, u32>>(), 0x10);
+    }
+
+    #[test]
+    fn iter_is_ordered() {
+        let map = fruit_map();
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(
+            map.keys().map(|key| key.to_string()).collect::<Vec<_>>(),
+            ["Apple", "Banana", "Cherry Cherry Cherry"]
+        );
+    }
+
+    #[test]
+    fn get_by_bytes() {
+        let map = fruit_map();
+
+        assert_eq!(map.get("Apple".as_bytes()), Some(&1));
+        assert_eq!(map.get("Banana".as_bytes()), Some(&2));
+        assert_eq!(map.get("Cherry Cherry Cherry".as_bytes()), Some(&3));
+        assert_eq!(map.get("Durian".as_bytes()), None);
+        assert!(!map.contains_key("Aardvark".as_bytes()));
+    }
+
+    #[test]
+    fn get_with_non_utf8_keys() {
+        use crate::general::raw::{from_bytes, PointerPolicy};
+
+        // An inline CP1251 string, as the game writes it.
+        let mut bytes = [0u8; 32];
+        bytes[..2].copy_from_slice(b"\xCF\xF0");
+        bytes[16] = 2;
+        bytes[24] = 15;
+        let glyphs: EscadraString = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+
+        let map = three_node_map([
+            ("Apple".to_string().into(), 1),
+            (glyphs, 2),
+            ("\u{442}".to_string().into(), 3),
+        ]);
+
+        assert_eq!(map.get("Apple".as_bytes()), Some(&1));
+        assert_eq!(map.get(&b"\xCF\xF0"[..]), Some(&2));
+        assert_eq!(map.get("\u{442}".as_bytes()), Some(&3));
+    }
+
+    #[test]
+    fn empty_map() {
+        let head = node(0u32, 0u32, true);
+        unsafe {
+            (*head).parent = head;
+            (*head).left = head;
+            (*head).right = head;
+        }
+        let map = unsafe { EscadraMap::from_head(head) };
+
+        assert!(map.is_empty());
+        assert!(map.root().is_none());
+        assert_eq!(map.get(&1), None);
+    }
+}
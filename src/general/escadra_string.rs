@@ -1,8 +1,21 @@
 //! Defines a variable length string frequently used within Highfleet called an EscadraString.
 
-use libc;
-use serde::{Deserialize, Serialize};
-use std::fmt;
+use crate::general::allocator::{allocator, EscadraAllocator};
+use crate::general::hexdump::{impl_annotated, DumpField, FieldKind};
+use crate::general::layout::assert_layout;
+use crate::general::offline::Decode;
+use crate::general::raw::{MemoryReader, PointerPolicy, RawError, RawLayout};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::str::Utf8Error;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// An union that stores either a raw 16 char string or a pointer to a raw char string.
 #[derive(Clone, Copy)]
@@ -22,9 +35,6 @@ union CharPointer {
 /// The string should always be null terminated.
 /// The `max_length` is 15 by default.
 #[repr(C)]
-#[derive(Deserialize, Serialize, Clone)]
-#[serde(from = "String")]
-#[serde(into = "String")]
 pub struct EscadraString {
     /// The \[u8;16\] char or the pointer to the char, depending on if max_length is either 15 or more.
     string: CharPointer,
@@ -35,9 +45,27 @@ pub struct EscadraString {
     max_length: u64,
 }
 
+assert_layout!(
+    EscadraString,
+    size = 0x20,
+    string = 0x00,
+    length = 0x10,
+    max_length = 0x18,
+);
+
+impl DumpField for CharPointer {
+    const KIND: FieldKind = FieldKind::Bytes(16);
+}
+
+impl_annotated!(EscadraString {
+    string,
+    length,
+    max_length
+});
+
 impl fmt::Debug for EscadraString {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let string = self.get_string();
+        let string = self.get_string_lossy();
 
         f.debug_struct("EscadraString")
             .field("string", &string)
@@ -58,47 +86,483 @@ impl EscadraString {
     }
 
     /// Writes the given string into the `EscadraString`.
+    ///
+    /// Heap memory is allocated and freed with the allocator set by `set_allocator`.
     pub fn set_string(&mut self, string: &String) {
-        if self.max_length > 15 || string.len() > 15 {
+        self.set_bytes(string.as_bytes());
+    }
+
+    /// Writes the given bytes into the `EscadraString`, adding the null terminator.
+    ///
+    /// The existing buffer is reused if it is large enough.
+    fn set_bytes(&mut self, string: &[u8]) {
+        self.length = 0;
+        self.push_bytes(string);
+    }
+
+    /// Creates an `EscadraString` holding `string`, allocating a buffer of exactly its length if it doesn't fit inline.
+    ///
+    /// Unlike `From<String>` there's no intermediate `String`, and unlike `push_str` the capacity isn't rounded up.
+    /// The string is still copied, since the game expects a null terminated buffer it may free.
+    pub fn from_static(string: &'static str) -> Self {
+        let mut es = Self::new();
+        es.set_exact(string.as_bytes(), allocator());
+        es
+    }
+
+    /// Writes every string into its `EscadraString`, such as when populating all the string fields of a struct.
+    ///
+    /// Strings that fit their current buffer reuse it and the others get a buffer of exactly their length,
+    /// so nothing is reallocated twice. The allocator is looked up once for the whole batch.
+    /// Every string still gets its own buffer, since the game frees them one by one.
+    pub fn set_many(strings: &mut [(&mut EscadraString, &str)]) {
+        let allocator = allocator();
+        for (es, string) in strings.iter_mut() {
+            es.set_exact(string.as_bytes(), allocator);
+        }
+    }
+
+    /// Writes the given bytes, replacing a heap buffer that is too small with one of exactly their length.
+    fn set_exact(&mut self, bytes: &[u8], allocator: &dyn EscadraAllocator) {
+        if bytes.len() > self.capacity().max(15) {
             unsafe {
+                let pointer = allocator.malloc(bytes.len() + 1);
+                assert!(!pointer.is_null(), "the game allocator is out of memory");
                 if self.max_length > 15 {
-                    libc::free(self.string.pointer as _);
+                    free_heap(self.string.pointer);
                 }
+                self.string.pointer = pointer;
+            }
+            self.max_length = bytes.len() as _;
+        }
 
-                let mut size: usize = (self.max_length + 1).try_into().unwrap();
-                while size <= string.len() {
-                    size *= 2;
-                }
-                let size = size;
+        self.set_bytes(bytes);
+    }
+
+    /// Creates an empty `EscadraString` that can hold at least `capacity` bytes without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut es = Self::new();
+        es.reserve(capacity);
+        es
+    }
+
+    /// Returns the number of bytes the `EscadraString` can hold without reallocating, excluding the null terminator.
+    pub fn capacity(&self) -> usize {
+        self.max_length as _
+    }
+
+    /// Reserves capacity for at least `additional` more bytes.
+    ///
+    /// Like `set_string`, the buffer size is doubled until it fits.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.length as usize + additional;
+        if required <= self.capacity() {
+            return;
+        }
 
-                self.string.pointer = libc::malloc(size) as *mut u8;
-                libc::memcpy(self.string.pointer as _, string.as_ptr() as _, string.len());
+        // The inline buffer always holds 15 chars, even if the game set a lower max_length.
+        if required <= 15 {
+            self.max_length = 15;
+            return;
+        }
+
+        let mut size = self.capacity().max(15) + 1;
+        while size <= required {
+            size *= 2;
+        }
 
-                *self.string.pointer.add(string.len()) = b'\0';
+        unsafe {
+            let pointer = allocator().malloc(size);
+            assert!(!pointer.is_null(), "the game allocator is out of memory");
+            core::ptr::copy_nonoverlapping(self.as_ptr(), pointer, self.length as usize + 1);
 
-                self.max_length = (size - 1) as u64;
+            if self.max_length > 15 {
+                free_heap(self.string.pointer);
             }
+
+            self.string.pointer = pointer;
+        }
+
+        self.max_length = (size - 1) as u64;
+    }
+
+    /// Appends the given string to the end of the `EscadraString`.
+    pub fn push_str(&mut self, string: &str) {
+        self.push_bytes(string.as_bytes());
+    }
+
+    /// Appends the given bytes to the end of the `EscadraString`, keeping the null terminator.
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+
+        unsafe {
+            let end = self.as_mut_ptr().add(self.length as _);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), end, bytes.len());
+            *end.add(bytes.len()) = b'\0';
+        }
+
+        self.length += bytes.len() as u64;
+    }
+
+    /// Shortens the `EscadraString` to `new_len` bytes. Does nothing if `new_len` is greater than the current length.
+    ///
+    /// The capacity is left unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the string is valid UTF-8 and `new_len` does not lie on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.length as usize {
+            return;
+        }
+
+        if let Ok(string) = self.try_get_string() {
+            assert!(string.is_char_boundary(new_len));
+        }
+
+        unsafe {
+            *self.as_mut_ptr().add(new_len) = b'\0';
+        }
+        self.length = new_len as _;
+    }
+
+    /// Empties the `EscadraString`, keeping its capacity.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Replaces the string with formatted text, like `set_fmt(format_args!("{} km", range))`.
+    ///
+    /// The text is written straight into the buffer, without an intermediate `String`,
+    /// so updating a label every frame reuses the buffer once it's large enough.
+    pub fn set_fmt(&mut self, args: fmt::Arguments) {
+        self.clear();
+        // Writing to an `EscadraString` never fails, only a `Display` impl could.
+        let _ = fmt::Write::write_fmt(self, args);
+    }
+
+    /// Returns a pointer to the start of the buffer, whether it's stored inline or on the heap.
+    fn as_ptr(&self) -> *const u8 {
+        if self.max_length > 15 {
+            unsafe { self.string.pointer }
         } else {
-            let mut buffer = [0u8; 16];
-            buffer[..string.len()].copy_from_slice(string.as_bytes());
-            self.string.chars = buffer;
+            unsafe { self.string.chars.as_ptr() }
         }
+    }
+
+    /// Returns a mutable pointer to the start of the buffer, whether it's stored inline or on the heap.
+    ///
+    /// A buffer shared by a `StringPool` is copied first, so the other strings never see the write.
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        if self.max_length > 15 {
+            #[cfg(feature = "std")]
+            self.unshare();
+            unsafe { self.string.pointer }
+        } else {
+            unsafe { self.string.chars.as_mut_ptr() }
+        }
+    }
+
+    /// Creates an `EscadraString` using a heap buffer shared by a `StringPool`.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` must be a shared buffer holding `length` bytes and a null terminator, with a reference taken for the new string.
+    #[cfg(feature = "std")]
+    pub(crate) unsafe fn from_shared(pointer: *mut u8, length: usize) -> Self {
+        Self {
+            string: CharPointer { pointer },
+            length: length as _,
+            max_length: length as _,
+        }
+    }
+
+    /// Moves the string out of a buffer shared by a `StringPool` into its own buffer of the same capacity.
+    #[cfg(feature = "std")]
+    fn unshare(&mut self) {
+        unsafe {
+            let shared = self.string.pointer;
+            if !crate::general::string_pool::is_shared(shared) {
+                return;
+            }
+
+            let pointer = allocator().malloc(self.max_length as usize + 1);
+            assert!(!pointer.is_null(), "the game allocator is out of memory");
+            core::ptr::copy_nonoverlapping(shared, pointer, self.length as usize + 1);
+            crate::general::string_pool::release(shared);
+            self.string.pointer = pointer;
+        }
+    }
+
+    /// Returns the length of the string in bytes, without the null terminator.
+    pub fn len(&self) -> usize {
+        self.length as _
+    }
+
+    /// Returns true if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
 
-        self.length = string.len() as _;
+    /// Returns the raw bytes of the string inside of the `EscadraString`, without the null terminator.
+    pub fn get_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.length as _) }
     }
 
     /// Returns the string inside of the `EscadraString`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the string is not valid UTF-8.
+    /// Use `try_get_string` or `get_string_lossy` when reading strings written by the game.
     pub fn get_string(&self) -> &str {
+        self.try_get_string().unwrap()
+    }
+
+    /// Returns the string inside of the `EscadraString`, or an error if it is not valid UTF-8.
+    pub fn try_get_string(&self) -> Result<&str, Utf8Error> {
+        core::str::from_utf8(self.get_bytes())
+    }
+
+    /// Returns the string inside of the `EscadraString`.
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn get_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.get_bytes())
+    }
+
+    /// Leaks the `EscadraString`, returning a reference that lives for the rest of the program.
+    ///
+    /// The string's memory is never freed by this library.
+    /// Use this to hand a string over to the game.
+    /// Don't leak strings from a `StringPool` if the game may free them, since their buffer is shared.
+    pub fn leak(self) -> &'static mut EscadraString {
+        Box::leak(Box::new(self))
+    }
+}
+
+// An owned `EscadraString` is an ordinary heap string: the allocator is `Send + Sync`,
+// and shared buffers of a `StringPool` are copied before any write.
+// Strings living in the game's memory are only reached through a `GameThread`.
+unsafe impl Send for EscadraString {}
+unsafe impl Sync for EscadraString {}
+
+impl Clone for EscadraString {
+    /// Deep copies the string, so both copies own their own memory.
+    fn clone(&self) -> Self {
+        let mut es = EscadraString::new();
+        es.set_bytes(self.get_bytes());
+        es
+    }
+}
+
+/// A view of an `EscadraString` owned by someone else, usually the game.
+///
+/// Unlike an `EscadraString` read out of game memory, dropping an `EscadraStringRef` never frees the string.
+#[derive(Clone, Copy)]
+pub struct EscadraStringRef<'a> {
+    string: &'a EscadraString,
+}
+
+impl<'a> EscadraStringRef<'a> {
+    /// Creates a view of the `EscadraString` at the given pointer.
+    ///
+    /// Returns `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to a valid `EscadraString` that outlives `'a`.
+    pub unsafe fn from_ptr(pointer: *const EscadraString) -> Option<Self> {
+        pointer.as_ref().map(|string| Self { string })
+    }
+
+    /// Returns a pointer to the viewed `EscadraString`.
+    pub fn as_ptr(&self) -> *const EscadraString {
+        self.string
+    }
+
+    /// Deep copies the viewed string into an `EscadraString` owned by the caller.
+    pub fn into_owned(self) -> EscadraString {
+        self.string.clone()
+    }
+}
+
+impl<'a> From<&'a EscadraString> for EscadraStringRef<'a> {
+    fn from(string: &'a EscadraString) -> Self {
+        Self { string }
+    }
+}
+
+impl Deref for EscadraStringRef<'_> {
+    type Target = EscadraString;
+
+    fn deref(&self) -> &Self::Target {
+        self.string
+    }
+}
+
+impl fmt::Debug for EscadraStringRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EscadraStringRef")
+            .field(self.string)
+            .finish()
+    }
+}
+
+unsafe impl RawLayout for EscadraString {
+    fn validate(bytes: &[u8]) -> Result<(), RawError> {
+        let length = u64::from_le_bytes(bytes[16..24].try_into().unwrap());
+        let max_length = u64::from_le_bytes(bytes[24..32].try_into().unwrap());
+
+        if length > max_length.max(15) {
+            return Err(RawError::InvalidValue { offset: 16 });
+        }
+
+        Ok(())
+    }
+
+    unsafe fn resolve_pointers(&mut self, policy: &PointerPolicy) -> Result<(), RawError> {
+        if self.max_length <= 15 {
+            return Ok(());
+        }
+
+        let resolved = match policy {
+            PointerPolicy::Preserve => return Err(RawError::OwnedPointer { offset: 0 }),
+            PointerPolicy::Zero => EscadraString::new(),
+            PointerPolicy::Resolve(reader) => {
+                let buffer = read_heap_buffer(*reader, self.string.pointer as u64, self.length)?;
+                let mut es = EscadraString::new();
+                es.set_bytes(&buffer);
+                es
+            }
+        };
+
+        // The game's pointer must not be freed, so the old value is overwritten without dropping it.
+        core::ptr::write(self, resolved);
+        Ok(())
+    }
+
+    fn write_pointers(&self, bytes: &mut [u8], policy: &PointerPolicy) {
         if self.max_length > 15 {
-            unsafe {
-                let buf: &[u8] = core::slice::from_raw_parts(self.string.pointer, self.length as _);
-                return std::str::from_utf8(buf).unwrap();
+            if let PointerPolicy::Zero = policy {
+                bytes[..8].fill(0);
             }
         }
+    }
+}
 
-        unsafe {
-            return std::str::from_utf8(&self.string.chars[0..self.length as _]).unwrap();
+/// Reads the `length` bytes of a heap buffer of the game.
+///
+/// The length comes from the game, or from bytes that may not be a string at all,
+/// so the buffer is read a page at a time and only grows as far as the memory can be read.
+fn read_heap_buffer(
+    reader: &dyn MemoryReader,
+    address: u64,
+    length: u64,
+) -> Result<Vec<u8>, RawError> {
+    const CHUNK_SIZE: u64 = 0x1000;
+
+    let mut buffer = Vec::new();
+    let mut read = 0;
+    while read < length {
+        let size = (length - read).min(CHUNK_SIZE) as usize;
+        let start = buffer.len();
+        buffer.resize(start + size, 0);
+        let readable = address
+            .checked_add(read)
+            .is_some_and(|chunk| reader.read_bytes(chunk, &mut buffer[start..]));
+        if !readable {
+            return Err(RawError::UnreadablePointer { address });
         }
+        read += size as u64;
+    }
+    Ok(buffer)
+}
+
+impl Decode for EscadraString {
+    const GAME_SIZE: usize = 0x20;
+
+    fn decode(bytes: &[u8], policy: &PointerPolicy) -> Result<Self, RawError> {
+        Self::validate(bytes)?;
+        let length = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+        let max_length = u64::from_le_bytes(bytes[24..32].try_into().unwrap());
+
+        let mut es = EscadraString::new();
+        if max_length <= 15 {
+            es.set_bytes(&bytes[..length]);
+            return Ok(es);
+        }
+
+        match policy {
+            PointerPolicy::Preserve => return Err(RawError::OwnedPointer { offset: 0 }),
+            PointerPolicy::Zero => {}
+            PointerPolicy::Resolve(reader) => {
+                let address = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+                es.set_bytes(&read_heap_buffer(*reader, address, length as u64)?);
+            }
+        }
+        Ok(es)
+    }
+}
+
+impl fmt::Display for EscadraString {
+    /// Writes the string, replacing invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.get_string_lossy(), f)
+    }
+}
+
+impl fmt::Write for EscadraString {
+    /// Appends the string, growing the buffer with the allocator set by `set_allocator` when needed.
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.push_str(string);
+        Ok(())
+    }
+}
+
+impl Borrow<[u8]> for EscadraString {
+    /// Borrows the bytes, so maps keyed by `EscadraString` can be searched with `"key".as_bytes()`,
+    /// whether or not the keys are valid UTF-8.
+    fn borrow(&self) -> &[u8] {
+        self.get_bytes()
+    }
+}
+
+impl PartialEq for EscadraString {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_bytes() == other.get_bytes()
+    }
+}
+
+impl Eq for EscadraString {}
+
+impl Hash for EscadraString {
+    /// Hashes the same way as the bytes, so `&[u8]` keys can be used for lookups.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_bytes().hash(state)
+    }
+}
+
+impl PartialEq<str> for EscadraString {
+    fn eq(&self, other: &str) -> bool {
+        self.get_bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for EscadraString {
+    fn eq(&self, other: &&str) -> bool {
+        self.get_bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<EscadraString> for str {
+    fn eq(&self, other: &EscadraString) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<EscadraString> for &str {
+    fn eq(&self, other: &EscadraString) -> bool {
+        other == self
     }
 }
 
@@ -110,9 +574,61 @@ impl From<String> for EscadraString {
     }
 }
 
+impl From<&str> for EscadraString {
+    fn from(value: &str) -> Self {
+        let mut es = EscadraString::new();
+        es.push_str(value);
+        es
+    }
+}
+
 impl From<EscadraString> for String {
+    /// Copies the string, replacing invalid UTF-8 like `get_string_lossy`.
     fn from(val: EscadraString) -> Self {
-        val.get_string().to_string()
+        val.get_string_lossy().into_owned()
+    }
+}
+
+impl Serialize for EscadraString {
+    /// Serializes the string, failing if it is not valid UTF-8. Use `bytes` for such strings.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let string = self
+            .try_get_string()
+            .map_err(|error| serde::ser::Error::custom(format_args!("EscadraString {error}")))?;
+        serializer.serialize_str(string)
+    }
+}
+
+/// Visitor that writes the deserialized string straight into an `EscadraString`.
+///
+/// Strings of 15 bytes or less never touch the heap.
+struct EscadraStringVisitor;
+
+impl<'de> Visitor<'de> for EscadraStringVisitor {
+    type Value = EscadraString;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        let mut es = EscadraString::new();
+        es.push_str(v);
+        Ok(es)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        self.visit_str(v)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.visit_str(&v)
+    }
+}
+
+impl<'de> Deserialize<'de> for EscadraString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(EscadraStringVisitor)
     }
 }
 
@@ -126,12 +642,273 @@ impl Drop for EscadraString {
     fn drop(&mut self) {
         if self.max_length > 15 {
             unsafe {
-                libc::free(self.string.pointer as _);
+                free_heap(self.string.pointer);
             }
         }
     }
 }
 
+/// Frees a heap buffer, or drops a reference to it if it's shared by a `StringPool`.
+unsafe fn free_heap(pointer: *mut u8) {
+    #[cfg(feature = "std")]
+    if crate::general::string_pool::release(pointer) {
+        return;
+    }
+    allocator().free(pointer);
+}
+
+/// Serializes an `EscadraString` as its raw bytes, for strings that aren't valid UTF-8.
+///
+/// The default `Serialize` impl fails on such strings, which the game sometimes writes for its display glyphs.
+/// Human-readable formats such as JSON get the bytes as a base64 string, others get them as bytes.
+///
+/// ```
+/// use highfleet::general::EscadraString;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Label {
+///     #[serde(with = "highfleet::general::escadra_string::bytes")]
+///     text: EscadraString,
+/// }
+/// ```
+pub mod bytes {
+    use super::EscadraString;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use core::fmt;
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    /// Serializes the bytes of `string`, see the module docs.
+    pub fn serialize<S: Serializer>(
+        string: &EscadraString,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&encode(string.get_bytes()))
+        } else {
+            serializer.serialize_bytes(string.get_bytes())
+        }
+    }
+
+    /// Deserializes the bytes written by `serialize`.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<EscadraString, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = EscadraString;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("base64 encoded bytes or a byte array")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            let bytes = decode(v).ok_or_else(|| E::custom("invalid base64"))?;
+            self.visit_bytes(&bytes)
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            let mut es = EscadraString::new();
+            es.set_bytes(v);
+            Ok(es)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            self.visit_bytes(&bytes)
+        }
+    }
+
+    /// Encodes bytes as padded standard base64.
+    fn encode(bytes: &[u8]) -> String {
+        let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+                group | (byte as u32) << (16 - 8 * i)
+            });
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    /// Decodes padded standard base64, or returns `None` if it isn't valid.
+    fn decode(encoded: &str) -> Option<Vec<u8>> {
+        let encoded = encoded.as_bytes();
+        if !encoded.len().is_multiple_of(4) {
+            return None;
+        }
+
+        let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+        for (index, chunk) in encoded.chunks(4).enumerate() {
+            let last = index == encoded.len() / 4 - 1;
+            let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+            if padding > 2 || (padding > 0 && !last) {
+                return None;
+            }
+
+            let mut group = 0u32;
+            for &c in &chunk[..4 - padding] {
+                let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+                group = group << 6 | value;
+            }
+            group <<= 6 * padding as u32;
+
+            bytes.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+        }
+        Some(bytes)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn base64_matches_rfc_4648() {
+            for (bytes, encoded) in [
+                ("", ""),
+                ("f", "Zg=="),
+                ("fo", "Zm8="),
+                ("foo", "Zm9v"),
+                ("foob", "Zm9vYg=="),
+                ("fooba", "Zm9vYmE="),
+                ("foobar", "Zm9vYmFy"),
+            ] {
+                assert_eq!(encode(bytes.as_bytes()), encoded);
+                assert_eq!(decode(encoded).unwrap(), bytes.as_bytes());
+            }
+
+            assert_eq!(decode("Zg="), None);
+            assert_eq!(decode("Z==="), None);
+            assert_eq!(decode("Zg==Zg=="), None);
+            assert_eq!(decode("Zm9*"), None);
+        }
+
+        #[test]
+        fn deserializes_raw_bytes() {
+            use serde::de::value::{BytesDeserializer, Error};
+
+            let text = deserialize(BytesDeserializer::<Error>::new(b"\xffa\0")).unwrap();
+            assert_eq!(text.get_bytes(), b"\xffa\0");
+        }
+    }
+}
+
+/// Runs a sequence of operations decoded from arbitrary bytes against `EscadraString`s and a `Vec` model of each.
+///
+/// Used by the fuzz target in `fuzz/` and the tests, so the union and allocator code can be run under Miri or ASan.
+/// Build with `--cfg fuzzing` or the `rust-allocator` feature so allocations go through `RustAllocator`.
+#[cfg(any(test, fuzzing))]
+#[doc(hidden)]
+pub mod fuzz {
+    use super::EscadraString;
+    use alloc::vec::Vec;
+
+    /// The most strings alive at once.
+    const MAX_STRINGS: usize = 4;
+
+    /// Lengths around the inline buffer and the first heap sizes, picked more often than others.
+    const EDGE_LENGTHS: [usize; 6] = [0, 15, 16, 31, 32, 63];
+
+    /// Decodes and runs operations until `data` runs out, panicking on the first mismatch with the model.
+    pub fn check_operations(data: &[u8]) {
+        let mut bytes = data.iter().copied();
+        let mut strings: Vec<(EscadraString, Vec<u8>)> = Vec::new();
+
+        while let Some(op) = bytes.next() {
+            let arg = bytes.next().unwrap_or(0);
+            if strings.is_empty() {
+                strings.push((EscadraString::new(), Vec::new()));
+            }
+            let full = strings.len() >= MAX_STRINGS;
+            let target = (op >> 4) as usize % strings.len();
+            let (string, model) = &mut strings[target];
+
+            match op % 8 {
+                0 => {
+                    let text = text(length(arg), op);
+                    string.set_bytes(&text);
+                    *model = text;
+                }
+                1 => {
+                    let text = text(length(arg) % 20, op);
+                    string.push_bytes(&text);
+                    model.extend_from_slice(&text);
+                }
+                2 => {
+                    string.truncate(length(arg));
+                    model.truncate(length(arg));
+                }
+                3 => {
+                    string.clear();
+                    model.clear();
+                }
+                4 => string.reserve(length(arg)),
+                5 if !full => {
+                    let copy = (string.clone(), model.clone());
+                    strings.push(copy);
+                }
+                6 => {
+                    strings.swap_remove(target);
+                }
+                7 => {
+                    // Shrink through the inline buffer boundary, reusing the heap buffer each time.
+                    for len in [31, 16, 15, 16, 0] {
+                        string.set_bytes(&text(len, op));
+                        check(string, &text(len, op));
+                    }
+                    *model = Vec::new();
+                }
+                _ => {}
+            }
+
+            for (string, model) in &strings {
+                check(string, model);
+            }
+        }
+    }
+
+    /// Picks a length from `arg`, preferring the edges of the inline buffer.
+    fn length(arg: u8) -> usize {
+        match arg {
+            0..=191 => EDGE_LENGTHS[arg as usize % EDGE_LENGTHS.len()],
+            _ => (arg - 192) as usize,
+        }
+    }
+
+    /// Builds ASCII text, so truncating never splits a char.
+    fn text(len: usize, seed: u8) -> Vec<u8> {
+        (0..len).map(|i| b'a' + (i as u8 ^ seed) % 26).collect()
+    }
+
+    fn check(string: &EscadraString, model: &[u8]) {
+        assert_eq!(string.get_bytes(), model);
+        assert!(string.capacity() >= model.len());
+        assert!(string.capacity() >= 15);
+        assert_eq!(unsafe { *string.as_ptr().add(model.len()) }, b'\0');
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,4 +1042,286 @@ mod tests {
             assert!(es.string.chars[string.len()] == b'\0');
         }
     }
+
+    #[test]
+    fn get_bytes_matches_string() {
+        let mut es = EscadraString::new();
+
+        let string = "Banana".to_string();
+        es.set_string(&string);
+        assert_eq!(es.get_bytes(), string.as_bytes());
+
+        let string = "Banana Banana Banana Banana".to_string();
+        es.set_string(&string);
+        assert_eq!(es.get_bytes(), string.as_bytes());
+    }
+
+    #[test]
+    fn non_utf8_string_is_read_fallibly() {
+        let mut es = EscadraString::new();
+
+        // "Привет" encoded as CP1251.
+        let bytes = [0xCF, 0xF0, 0xE8, 0xE2, 0xE5, 0xF2];
+        unsafe {
+            es.string.chars[..bytes.len()].copy_from_slice(&bytes);
+        }
+        es.length = bytes.len() as _;
+
+        assert_eq!(es.get_bytes(), bytes);
+        assert!(es.try_get_string().is_err());
+        assert_eq!(es.get_string_lossy(), "\u{FFFD}".repeat(bytes.len()));
+    }
+
+    #[test]
+    fn clone_is_deep() {
+        let mut es = EscadraString::new();
+
+        let string = "Banana Banana Banana Banana".to_string();
+        es.set_string(&string);
+
+        let clone = es.clone();
+        unsafe {
+            assert_ne!(es.string.pointer, clone.string.pointer);
+        }
+        assert_eq!(clone.get_string(), string);
+    }
+
+    #[test]
+    fn ref_does_not_free_string() {
+        let mut es = EscadraString::new();
+
+        let string = "Banana Banana Banana Banana".to_string();
+        es.set_string(&string);
+
+        let owned = {
+            let es_ref = unsafe { EscadraStringRef::from_ptr(&es).unwrap() };
+            assert_eq!(es_ref.get_string(), string);
+            es_ref.into_owned()
+        };
+
+        assert_eq!(es.get_string(), string);
+        assert_eq!(owned.get_string(), string);
+    }
+
+    #[test]
+    fn ref_from_null_is_none() {
+        assert!(unsafe { EscadraStringRef::from_ptr(core::ptr::null()) }.is_none());
+    }
+
+    #[test]
+    fn with_capacity_above_15_uses_pointer() {
+        let es = EscadraString::with_capacity(40);
+
+        assert!(es.capacity() >= 40);
+        assert_eq!(es.get_string(), "");
+        unsafe {
+            assert!(*es.string.pointer == b'\0');
+        }
+    }
+
+    #[test]
+    fn push_str_grows_from_chars_to_pointer() {
+        let mut es = EscadraString::new();
+
+        es.push_str("Banana ");
+        assert_eq!(es.get_string(), "Banana ");
+        assert_eq!(es.capacity(), 15);
+
+        es.push_str("Banana Banana");
+        assert_eq!(es.get_string(), "Banana Banana Banana");
+        assert_eq!(es.capacity(), 31);
+        unsafe {
+            assert!(*es.string.pointer.add(es.length as _) == b'\0');
+        }
+    }
+
+    #[test]
+    fn reserve_keeps_contents() {
+        let mut es = EscadraString::from("Banana Banana Banana".to_string());
+        let capacity = es.capacity();
+
+        es.reserve(capacity);
+
+        assert!(es.capacity() >= 20 + capacity);
+        assert_eq!(es.get_string(), "Banana Banana Banana");
+    }
+
+    #[test]
+    fn truncate_and_clear_keep_capacity() {
+        let mut es = EscadraString::from("Banana Banana Banana".to_string());
+        let capacity = es.capacity();
+
+        es.truncate(6);
+        assert_eq!(es.get_string(), "Banana");
+        assert_eq!(es.capacity(), capacity);
+        unsafe {
+            assert!(*es.string.pointer.add(6) == b'\0');
+        }
+
+        es.truncate(100);
+        assert_eq!(es.get_string(), "Banana");
+
+        es.clear();
+        assert_eq!(es.get_string(), "");
+        assert_eq!(es.capacity(), capacity);
+    }
+
+    #[test]
+    fn write_formats_in_place() {
+        use core::fmt::Write;
+
+        let mut es = EscadraString::new();
+        write!(es, "{} km", 1250).unwrap();
+        assert_eq!(es.get_string(), "1250 km");
+        assert_eq!(es.capacity(), 15);
+
+        write!(es, ", {:.1} km/h", 512.25).unwrap();
+        assert_eq!(es.get_string(), "1250 km, 512.2 km/h");
+        let capacity = es.capacity();
+        assert!(capacity > 15);
+
+        es.set_fmt(format_args!("{} km", 30));
+        assert_eq!(es.get_string(), "30 km");
+        assert_eq!(es.capacity(), capacity);
+    }
+
+    #[test]
+    #[should_panic]
+    fn truncate_inside_char_panics() {
+        let mut es = EscadraString::from("Привет".to_string());
+        es.truncate(1);
+    }
+
+    #[test]
+    fn display_and_len() {
+        let es = EscadraString::from("Banana".to_string());
+
+        assert_eq!(format!("{es}"), "Banana");
+        assert_eq!(es.len(), 6);
+        assert!(!es.is_empty());
+        assert!(EscadraString::new().is_empty());
+    }
+
+    #[test]
+    fn compare_with_str() {
+        let es = EscadraString::from("Banana Banana Banana Banana".to_string());
+
+        assert_eq!(es, "Banana Banana Banana Banana");
+        assert_eq!("Banana Banana Banana Banana", es);
+        assert_ne!(es, "Banana");
+        assert_eq!(es, es.clone());
+    }
+
+    #[test]
+    fn hashmap_lookup_by_bytes() {
+        let mut map = std::collections::HashMap::new();
+        map.insert(EscadraString::from("Banana".to_string()), 1);
+        map.insert(
+            EscadraString::from("Banana Banana Banana Banana".to_string()),
+            2,
+        );
+        let mut glyphs = EscadraString::new();
+        glyphs.set_bytes(b"\xCF\xF0");
+        map.insert(glyphs, 3);
+
+        assert_eq!(map.get("Banana".as_bytes()), Some(&1));
+        assert_eq!(map.get("Banana Banana Banana Banana".as_bytes()), Some(&2));
+        assert_eq!(map.get(&b"\xCF\xF0"[..]), Some(&3));
+        assert_eq!(map.get("Apple".as_bytes()), None);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let short = EscadraString::from("Banana".to_string());
+        let long = EscadraString::from("Banana Banana Banana Banana".to_string());
+
+        let json = serde_json::to_string(&[&short, &long]).unwrap();
+        assert_eq!(json, r#"["Banana","Banana Banana Banana Banana"]"#);
+
+        let result: Vec<EscadraString> = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, [short, long]);
+        assert_eq!(result[0].capacity(), 15);
+    }
+
+    #[test]
+    fn deserialize_escaped_string() {
+        let result: EscadraString = serde_json::from_str(r#""Ban\"ana""#).unwrap();
+        assert_eq!(result, "Ban\"ana");
+    }
+
+    #[test]
+    fn operations_match_model() {
+        // Grow past 31, shrink to exactly 16 and 15, clone, push across the boundary, then drop.
+        fuzz::check_operations(&[0, 3, 0, 2, 0, 1, 5, 0, 17, 200, 2, 1, 7, 0, 4, 5, 6, 0]);
+
+        // Every op with every target and argument class.
+        let data: Vec<u8> = (0..=255u8)
+            .flat_map(|op| [op, op.wrapping_mul(37)])
+            .collect();
+        fuzz::check_operations(&data);
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Label {
+        #[serde(with = "super::bytes")]
+        text: EscadraString,
+    }
+
+    #[test]
+    fn serde_bytes_round_trips_non_utf8() {
+        let mut text = EscadraString::new();
+        text.set_bytes(b"\xff\xfeGlyph \x80 of the fleet");
+
+        let json = serde_json::to_string(&Label { text }).unwrap();
+        assert_eq!(json, r#"{"text":"//5HbHlwaCCAIG9mIHRoZSBmbGVldA=="}"#);
+
+        let label: Label = serde_json::from_str(&json).unwrap();
+        assert_eq!(label.text.get_bytes(), b"\xff\xfeGlyph \x80 of the fleet");
+        assert!(label.text.capacity() > 15);
+
+        let error = serde_json::from_str::<Label>(r#"{"text":"not base64"}"#).err();
+        assert!(error.unwrap().to_string().contains("invalid base64"));
+    }
+
+    #[test]
+    fn serialize_non_utf8_is_an_error() {
+        let mut text = EscadraString::new();
+        text.set_bytes(b"Glyph \x80");
+
+        let error = serde_json::to_string(&text).unwrap_err();
+        assert!(error.to_string().contains("invalid utf-8"));
+    }
+
+    #[test]
+    fn from_static_allocates_exactly() {
+        let short = EscadraString::from_static("shell_in_small");
+        assert_eq!(short, "shell_in_small");
+        assert_eq!(short.capacity(), 15);
+
+        let long = EscadraString::from_static("shell_out_small_far");
+        assert_eq!(long, "shell_out_small_far");
+        assert_eq!(long.capacity(), 19);
+    }
+
+    #[test]
+    fn set_many_reuses_buffers() {
+        let mut a = EscadraString::with_capacity(40);
+        let mut b = EscadraString::new();
+        let mut c = EscadraString::from("shell_out_big_far_and_then_some");
+        let a_buffer = a.get_bytes().as_ptr();
+
+        EscadraString::set_many(&mut [
+            (&mut a, "shell_out_small_far"),
+            (&mut b, "shell_out_tiny_far"),
+            (&mut c, "AMMO_57"),
+        ]);
+
+        assert_eq!(a, "shell_out_small_far");
+        assert_eq!(a.get_bytes().as_ptr(), a_buffer);
+        assert_eq!(a.capacity(), 63);
+        assert_eq!(b, "shell_out_tiny_far");
+        assert_eq!(b.capacity(), 18);
+        assert_eq!(c, "AMMO_57");
+        assert_eq!(c.capacity(), 31);
+    }
 }
@@ -0,0 +1,348 @@
+//! Defines a growable array used within Highfleet, laid out like an MSVC `std::vector`.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::general::allocator::allocator;
+use crate::general::layout::assert_layout;
+
+/// A growable array, laid out like an MSVC `std::vector`.
+///
+/// The vector is made of three pointers: the start of the buffer, the end of the stored elements, and the end of the buffer.
+/// An empty vector has all three pointers set to null.
+///
+/// Like the `EscadraString`, heap memory is allocated and freed with the allocator set by `set_allocator`.
+/// Elements must not need an alignment above 16, as that's the most `malloc` guarantees.
+#[repr(C)]
+pub struct EscadraVector<T> {
+    /// Pointer to the first element.
+    first: *mut T,
+    /// Pointer one past the last element.
+    last: *mut T,
+    /// Pointer one past the end of the buffer.
+    end: *mut T,
+}
+
+assert_layout!(
+    EscadraVector<u8>,
+    size = 0x18,
+    first = 0x00,
+    last = 0x08,
+    end = 0x10,
+);
+
+impl<T> EscadraVector<T> {
+    /// Creates an empty `EscadraVector`.
+    pub fn new() -> Self {
+        assert!(core::mem::align_of::<T>() <= 16);
+        assert!(core::mem::size_of::<T>() > 0);
+
+        Self {
+            first: core::ptr::null_mut(),
+            last: core::ptr::null_mut(),
+            end: core::ptr::null_mut(),
+        }
+    }
+
+    /// Creates an empty `EscadraVector` that can hold at least `capacity` elements without reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut vector = Self::new();
+        vector.reserve(capacity);
+        vector
+    }
+
+    /// Returns the number of elements in the vector.
+    pub fn len(&self) -> usize {
+        if self.first.is_null() {
+            return 0;
+        }
+        unsafe { self.last.offset_from(self.first) as usize }
+    }
+
+    /// Returns true if the vector holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements the vector can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        if self.first.is_null() {
+            return 0;
+        }
+        unsafe { self.end.offset_from(self.first) as usize }
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        if self.first.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.first, self.len()) }
+    }
+
+    /// Returns the elements as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.first.is_null() {
+            return &mut [];
+        }
+        unsafe { core::slice::from_raw_parts_mut(self.first, self.len()) }
+    }
+
+    /// Reserves capacity for at least `additional` more elements.
+    ///
+    /// Like MSVC, the capacity grows by half of itself until it fits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity in bytes overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+        let required = len.checked_add(additional).expect("capacity overflow");
+        if required <= self.capacity() {
+            return;
+        }
+
+        let mut capacity = self.capacity().max(1);
+        while capacity < required {
+            capacity = capacity
+                .checked_add((capacity / 2).max(1))
+                .unwrap_or(required);
+        }
+        let size = capacity
+            .checked_mul(core::mem::size_of::<T>())
+            .expect("capacity overflow");
+
+        unsafe {
+            let first = allocator().malloc(size) as *mut T;
+            assert!(!first.is_null());
+
+            if !self.first.is_null() {
+                core::ptr::copy_nonoverlapping(self.first, first, len);
+                allocator().free(self.first as *mut u8);
+            }
+
+            self.first = first;
+            self.last = first.add(len);
+            self.end = first.add(capacity);
+        }
+    }
+
+    /// Appends an element to the end of the vector.
+    pub fn push(&mut self, value: T) {
+        self.reserve(1);
+
+        unsafe {
+            self.last.write(value);
+            self.last = self.last.add(1);
+        }
+    }
+
+    /// Removes the last element and returns it, or `None` if the vector is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        unsafe {
+            self.last = self.last.sub(1);
+            Some(self.last.read())
+        }
+    }
+
+    /// Removes all elements, keeping the capacity.
+    pub fn clear(&mut self) {
+        let elements: *mut [T] = self.as_mut_slice();
+        self.last = self.first;
+
+        unsafe {
+            core::ptr::drop_in_place(elements);
+        }
+    }
+}
+
+impl<T> Deref for EscadraVector<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for EscadraVector<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T> Default for EscadraVector<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for EscadraVector<T> {
+    fn drop(&mut self) {
+        self.clear();
+
+        if !self.first.is_null() {
+            unsafe {
+                allocator().free(self.first as *mut u8);
+            }
+        }
+    }
+}
+
+impl<T: Clone> Clone for EscadraVector<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for EscadraVector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for EscadraVector<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T> FromIterator<T> for EscadraVector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut vector = Self::with_capacity(iter.size_hint().0);
+        for value in iter {
+            vector.push(value);
+        }
+        vector
+    }
+}
+
+impl<T> From<Vec<T>> for EscadraVector<T> {
+    fn from(value: Vec<T>) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a EscadraVector<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut EscadraVector<T> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: Serialize> Serialize for EscadraVector<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for EscadraVector<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+
+    #[test]
+    fn vector_size() {
+        assert_eq!(core::mem::size_of::<EscadraVector<u32>>(), 0x18);
+    }
+
+    #[test]
+    fn empty_vector_is_null() {
+        let vector = EscadraVector::<u32>::new();
+
+        assert!(vector.first.is_null());
+        assert_eq!(vector.len(), 0);
+        assert_eq!(vector.capacity(), 0);
+        assert_eq!(vector.first(), None);
+        assert_eq!(vector.iter().count(), 0);
+    }
+
+    #[test]
+    fn push_then_read() {
+        let mut vector = EscadraVector::new();
+        for i in 0..10u32 {
+            vector.push(i);
+        }
+
+        assert_eq!(vector.len(), 10);
+        assert!(vector.capacity() >= 10);
+        assert_eq!(vector.get(3), Some(&3));
+        assert_eq!(vector.iter().sum::<u32>(), 45);
+        assert_eq!(vector.pop(), Some(9));
+        assert_eq!(vector.len(), 9);
+    }
+
+    #[test]
+    fn clear_keeps_capacity() {
+        let mut vector: EscadraVector<_> = (0..10u32).collect();
+        let capacity = vector.capacity();
+
+        vector.clear();
+
+        assert!(vector.is_empty());
+        assert_eq!(vector.capacity(), capacity);
+    }
+
+    #[test]
+    fn holds_strings() {
+        let mut vector = EscadraVector::new();
+        vector.push(EscadraString::from("Banana".to_string()));
+        vector.push(EscadraString::from(
+            "Banana Banana Banana Banana".to_string(),
+        ));
+
+        let clone = vector.clone();
+        drop(vector);
+
+        assert_eq!(clone[1], "Banana Banana Banana Banana");
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserve_overflow_panics() {
+        let mut vector: EscadraVector<u32> = vec![1].into();
+        vector.reserve(usize::MAX / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserve_length_overflow_panics() {
+        let mut vector: EscadraVector<u32> = vec![1].into();
+        vector.reserve(usize::MAX);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let vector: EscadraVector<u32> = vec![1, 2, 3].into();
+
+        let json = serde_json::to_string(&vector).unwrap();
+        assert_eq!(json, "[1,2,3]");
+
+        let result: EscadraVector<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, vector);
+    }
+}
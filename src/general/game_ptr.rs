@@ -0,0 +1,189 @@
+//! Defines a pointer into the memory of the game.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem::size_of;
+
+use crate::general::raw::RawLayout;
+use crate::general::thread::GameThread;
+use crate::memory::{MemoryError, MemorySource};
+
+/// A pointer into the memory of the game, laid out like a raw pointer.
+///
+/// Unlike a raw pointer it can be read safely through a `MemorySource`, which checks the memory instead of crashing on a bad address.
+/// It works the same whether the game runs in this process or another one.
+#[repr(transparent)]
+pub struct GamePtr<T> {
+    pointer: *mut T,
+}
+
+impl<T> GamePtr<T> {
+    /// Creates a null pointer.
+    pub const fn null() -> Self {
+        Self {
+            pointer: core::ptr::null_mut(),
+        }
+    }
+
+    /// Creates a pointer out of a raw pointer.
+    pub const fn new(pointer: *mut T) -> Self {
+        Self { pointer }
+    }
+
+    /// Creates a pointer to the given address.
+    pub fn from_address(address: u64) -> Self {
+        Self::new(address as usize as *mut T)
+    }
+
+    /// Returns the raw pointer.
+    pub fn as_ptr(self) -> *mut T {
+        self.pointer
+    }
+
+    /// Returns the address pointed to.
+    pub fn address(self) -> u64 {
+        self.pointer as usize as u64
+    }
+
+    /// Returns true if the pointer is null.
+    pub fn is_null(self) -> bool {
+        self.pointer.is_null()
+    }
+
+    /// Returns the pointer `count` elements of `T` further.
+    pub fn wrapping_add(self, count: u64) -> Self {
+        self.wrapping_byte_add(count.wrapping_mul(size_of::<T>() as u64))
+    }
+
+    /// Returns the pointer `bytes` bytes further.
+    pub fn wrapping_byte_add(self, bytes: u64) -> Self {
+        Self::from_address(self.address().wrapping_add(bytes))
+    }
+
+    /// Returns the same address as a pointer to a `U`.
+    pub fn cast<U>(self) -> GamePtr<U> {
+        GamePtr::new(self.pointer as *mut U)
+    }
+
+    /// Returns a reference to the value, or `None` if the pointer is null.
+    ///
+    /// # Safety
+    ///
+    /// A non-null pointer must point to a valid `T` in the memory of this process, that outlives `'a`.
+    pub unsafe fn as_ref<'a>(self) -> Option<&'a T> {
+        self.pointer.as_ref()
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the pointer is null.
+    ///
+    /// Takes a `GameThread`, since the game mutates its structs from its main thread without locking.
+    ///
+    /// # Safety
+    ///
+    /// See `as_ref`. The value must not be accessed through other references while the returned one is alive.
+    pub unsafe fn as_mut<'a>(self, _thread: &GameThread) -> Option<&'a mut T> {
+        self.pointer.as_mut()
+    }
+
+    /// Reads a copy of the value through the given memory.
+    ///
+    /// Fails for a null pointer, or if the memory can't be read.
+    pub fn try_deref<M: MemorySource>(self, memory: &M) -> Result<T, MemoryError>
+    where
+        T: RawLayout,
+    {
+        if self.is_null() {
+            return Err(MemoryError::Unreadable {
+                address: 0,
+                size: size_of::<T>(),
+            });
+        }
+        memory.read_struct(self.address())
+    }
+}
+
+// A `GamePtr` is only an address. Reading through it is unsafe or checked by a `MemorySource`,
+// and mutating through it takes a `GameThread`, so it can be passed between threads freely.
+unsafe impl<T> Send for GamePtr<T> {}
+unsafe impl<T> Sync for GamePtr<T> {}
+
+impl<T> Clone for GamePtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GamePtr<T> {}
+
+impl<T> PartialEq for GamePtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pointer == other.pointer
+    }
+}
+
+impl<T> Eq for GamePtr<T> {}
+
+impl<T> Hash for GamePtr<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pointer.hash(state)
+    }
+}
+
+impl<T> Default for GamePtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> From<*mut T> for GamePtr<T> {
+    fn from(pointer: *mut T) -> Self {
+        Self::new(pointer)
+    }
+}
+
+impl<T> fmt::Debug for GamePtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GamePtr({:p})", self.pointer)
+    }
+}
+
+impl<T> fmt::Pointer for GamePtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Pointer::fmt(&self.pointer, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+
+    #[test]
+    fn address_arithmetic() {
+        let pointer = GamePtr::<u32>::from_address(0x1000);
+
+        assert_eq!(pointer.wrapping_add(3).address(), 0x100c);
+        assert_eq!(pointer.wrapping_byte_add(3).address(), 0x1003);
+        assert_eq!(pointer.cast::<u64>().wrapping_add(1).address(), 0x1008);
+        assert!(!pointer.is_null());
+        assert!(GamePtr::<u32>::default().is_null());
+        assert_eq!(format!("{pointer:?}"), "GamePtr(0x1000)");
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn try_deref_through_memory() {
+        use crate::memory::InProcess;
+
+        let mut string = EscadraString::from("Banana");
+        let pointer = GamePtr::new(&mut string as *mut EscadraString);
+
+        assert_eq!(pointer.try_deref(&InProcess).unwrap(), "Banana");
+        assert!(GamePtr::<EscadraString>::null()
+            .try_deref(&InProcess)
+            .is_err());
+        assert!(GamePtr::<EscadraString>::from_address(0x10)
+            .try_deref(&InProcess)
+            .is_err());
+    }
+}
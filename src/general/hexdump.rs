@@ -0,0 +1,404 @@
+//! Prints game structs as annotated hex dumps, to help find out what the `unknown_*` fields hold.
+//!
+//! Every row shows the offset, the raw bytes, the field name, and the decoded value of one field.
+//! Fields named `unknown_*` or `padding_*`, and bytes no field covers, are marked with a `?`.
+//! Formatting with `{:#}` highlights them in yellow as well, for terminals.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::escadra_string::EscadraString;
+use super::raw::{to_bytes, PointerPolicy, RawLayout};
+use super::GamePtr;
+
+/// How many bytes are shown per row.
+const BYTES_PER_ROW: usize = 16;
+
+/// How the bytes of a field are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// A `bool`.
+    Bool,
+    /// A `u16`.
+    U16,
+    /// An `i32`.
+    I32,
+    /// A `u32`.
+    U32,
+    /// A `u64`.
+    U64,
+    /// An `f32`.
+    F32,
+    /// A pointer.
+    Pointer,
+    /// An `EscadraString`.
+    String,
+    /// Bytes without a known meaning.
+    Bytes(usize),
+}
+
+impl FieldKind {
+    /// Returns the number of bytes a field of this kind takes.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Bool => 1,
+            Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::U64 | Self::Pointer => 8,
+            Self::String => core::mem::size_of::<EscadraString>(),
+            Self::Bytes(size) => *size,
+        }
+    }
+
+    /// Returns a short name for the kind, like "f32".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::U16 => "u16",
+            Self::I32 => "i32",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::F32 => "f32",
+            Self::Pointer => "ptr",
+            Self::String => "string",
+            Self::Bytes(_) => "bytes",
+        }
+    }
+
+    /// Decodes the bytes of a field of this kind.
+    ///
+    /// `bytes` must hold at least `size` bytes.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> String {
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let u32_bytes = || bytes[..4].try_into().unwrap();
+
+        match self {
+            Self::Bool => match bytes[0] {
+                0 => "false".to_string(),
+                1 => "true".to_string(),
+                other => format!("invalid ({other})"),
+            },
+            Self::U16 => u16::from_le_bytes(bytes[..2].try_into().unwrap()).to_string(),
+            Self::I32 => i32::from_le_bytes(u32_bytes()).to_string(),
+            Self::U32 => u32::from_le_bytes(u32_bytes()).to_string(),
+            Self::U64 => u64_at(0).to_string(),
+            Self::F32 => f32::from_le_bytes(u32_bytes()).to_string(),
+            Self::Pointer => match u64_at(0) {
+                0 => "null".to_string(),
+                address => format!("{address:#x}"),
+            },
+            Self::String => {
+                let length = u64_at(0x10);
+                let max_length = u64_at(0x18);
+                if max_length < 16 && length <= max_length {
+                    format!("{:?}", String::from_utf8_lossy(&bytes[..length as usize]))
+                } else {
+                    format!("heap {:#x}, length {length}", u64_at(0))
+                }
+            }
+            Self::Bytes(_) => String::new(),
+        }
+    }
+}
+
+/// Implemented by the types of struct fields, to know how to decode them.
+pub trait DumpField {
+    /// How fields of this type are decoded.
+    const KIND: FieldKind;
+}
+
+macro_rules! dump_field {
+    ($($type:ty => $kind:ident),* $(,)?) => {
+        $(
+            impl DumpField for $type {
+                const KIND: FieldKind = FieldKind::$kind;
+            }
+        )*
+    };
+}
+
+dump_field!(
+    bool => Bool,
+    u16 => U16,
+    i32 => I32,
+    u32 => U32,
+    u64 => U64,
+    f32 => F32,
+    EscadraString => String,
+);
+
+impl<T> DumpField for *mut T {
+    const KIND: FieldKind = FieldKind::Pointer;
+}
+
+impl<T> DumpField for GamePtr<T> {
+    const KIND: FieldKind = FieldKind::Pointer;
+}
+
+impl<const N: usize> DumpField for [u8; N] {
+    const KIND: FieldKind = FieldKind::Bytes(N);
+}
+
+/// A field of a struct, as shown in a hex dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The offset of the field inside of the struct.
+    pub offset: usize,
+    /// How the field is decoded.
+    pub kind: FieldKind,
+}
+
+impl FieldInfo {
+    /// Describes a field, taking its kind from the type `accessor` returns.
+    pub fn of<S, T: DumpField>(name: &'static str, offset: usize, _accessor: fn(&S) -> &T) -> Self {
+        Self {
+            name,
+            offset,
+            kind: T::KIND,
+        }
+    }
+
+    /// Returns true if the purpose of the field is not known.
+    pub fn is_unknown(&self) -> bool {
+        self.name.starts_with("unknown_") || self.name.starts_with("padding_")
+    }
+}
+
+/// A struct whose fields can be shown in a hex dump.
+pub trait Annotated {
+    /// Returns the fields of the struct, ordered by offset.
+    fn fields() -> Vec<FieldInfo>;
+}
+
+/// Implements `Annotated` for a struct by listing its fields in order.
+///
+/// ```ignore
+/// impl_annotated!(Ammo { reticle, padding_4h, item_name });
+/// ```
+macro_rules! impl_annotated {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::general::hexdump::Annotated for $type {
+            fn fields() -> ::alloc::vec::Vec<$crate::general::hexdump::FieldInfo> {
+                ::alloc::vec![$(
+                    $crate::general::hexdump::FieldInfo::of(
+                        stringify!($field),
+                        ::core::mem::offset_of!($type, $field),
+                        |value: &$type| &value.$field,
+                    ),
+                )*]
+            }
+        }
+    };
+}
+
+pub(crate) use impl_annotated;
+
+/// An annotated hex dump of a struct, created by `HexDump::of` or `HexDump::from_bytes`.
+///
+/// Shown by formatting it with `{}`, or `{:#}` for colors.
+pub struct HexDump<'a> {
+    bytes: Cow<'a, [u8]>,
+    fields: Vec<FieldInfo>,
+}
+
+impl<'a> HexDump<'a> {
+    /// Dumps a struct held by this process. Pointers are shown as they are.
+    pub fn of<T: Annotated + RawLayout>(value: &T) -> HexDump<'static> {
+        HexDump {
+            bytes: Cow::Owned(to_bytes(value, &PointerPolicy::Preserve)),
+            fields: T::fields(),
+        }
+    }
+
+    /// Dumps bytes read from the game as a `T`, for example from a `Snapshot`.
+    ///
+    /// The bytes don't have to be a valid `T`. Missing bytes are shown as truncated.
+    pub fn from_bytes<T: Annotated>(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes: Cow::Borrowed(bytes),
+            fields: T::fields(),
+        }
+    }
+
+    /// Returns the rows of the dump: every field, plus the gaps between them.
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut position = 0;
+        for field in &self.fields {
+            if field.offset > position {
+                rows.push(Row::Gap(position..field.offset));
+            }
+            rows.push(Row::Field(*field));
+            position = position.max(field.offset + field.kind.size());
+        }
+        if self.bytes.len() > position {
+            rows.push(Row::Gap(position..self.bytes.len()));
+        }
+        rows
+    }
+}
+
+/// A row of a hex dump.
+enum Row {
+    Field(FieldInfo),
+    /// Bytes no field covers.
+    Gap(core::ops::Range<usize>),
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self.rows();
+        let name_width = self
+            .fields
+            .iter()
+            .map(|field| field.name.len())
+            .max()
+            .unwrap_or(0);
+
+        for row in rows {
+            let (range, name, kind, value, unknown) = match row {
+                Row::Field(field) => {
+                    let range = field.offset..field.offset + field.kind.size();
+                    let value = match self.bytes.get(range.clone()) {
+                        Some(bytes) => field.kind.decode(bytes),
+                        None => "truncated".to_string(),
+                    };
+                    (
+                        range,
+                        field.name,
+                        field.kind.name(),
+                        value,
+                        field.is_unknown(),
+                    )
+                }
+                Row::Gap(range) => (range, "", "", String::new(), true),
+            };
+
+            let (start, end) = if f.alternate() && unknown {
+                ("\x1b[33m", "\x1b[0m")
+            } else {
+                ("", "")
+            };
+            let marker = if unknown { '?' } else { ' ' };
+
+            for (i, chunk_start) in range.clone().step_by(BYTES_PER_ROW).enumerate() {
+                let chunk_end = (chunk_start + BYTES_PER_ROW).min(range.end);
+                let hex = (chunk_start..chunk_end)
+                    .map(|offset| match self.bytes.get(offset) {
+                        Some(byte) => format!("{byte:02x}"),
+                        None => "..".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                write!(f, "{start}{chunk_start:#06x}  {hex:<47}")?;
+                if i == 0 {
+                    write!(f, " {marker} {name:<name_width$}  {kind:<6} {value}")?;
+                }
+                writeln!(f, "{end}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    struct Example {
+        count: i32,
+        unknown_4h: f32,
+        name: EscadraString,
+        next: *mut Example,
+        flag: bool,
+    }
+
+    impl_annotated!(Example {
+        count,
+        unknown_4h,
+        name,
+        next,
+        flag
+    });
+
+    fn example_bytes() -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x38];
+        bytes[0..4].copy_from_slice(&7i32.to_le_bytes());
+        bytes[4..8].copy_from_slice(&1.5f32.to_le_bytes());
+        bytes[8..13].copy_from_slice(b"Apple");
+        bytes[0x18..0x20].copy_from_slice(&5u64.to_le_bytes());
+        bytes[0x20..0x28].copy_from_slice(&15u64.to_le_bytes());
+        bytes[0x28..0x30].copy_from_slice(&0x1234u64.to_le_bytes());
+        bytes[0x30] = 1;
+        bytes[0x31] = 0xee;
+        bytes
+    }
+
+    #[test]
+    fn fields_from_macro() {
+        let fields = Example::fields();
+
+        assert_eq!(fields.len(), 5);
+        assert_eq!(fields[2].offset, 8);
+        assert_eq!(fields[2].kind, FieldKind::String);
+        assert_eq!(fields[3].kind, FieldKind::Pointer);
+        assert!(fields[1].is_unknown());
+        assert!(!fields[0].is_unknown());
+    }
+
+    #[test]
+    fn dump_rows() {
+        let bytes = example_bytes();
+        let dump = HexDump::from_bytes::<Example>(&bytes).to_string();
+        let lines: Vec<_> = dump.lines().collect();
+
+        assert!(lines[0].starts_with("0x0000  07 00 00 00"));
+        assert!(lines[0].ends_with("   count       i32    7"));
+        assert!(lines[1].contains(" ? unknown_4h  f32    1.5"));
+        assert!(lines[2].contains("name        string \"Apple\""));
+        // The string continues on a second row.
+        assert!(lines[3].starts_with("0x0018  05 00"));
+        assert!(lines[4].contains("next        ptr    0x1234"));
+        assert!(lines[5].contains("flag        bool   true"));
+        // The trailing padding isn't covered by a field.
+        assert!(lines[6].starts_with("0x0031  ee 00 00 00 00 00 00"));
+        assert!(lines[6].contains(" ? "));
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn dump_truncated_bytes() {
+        let bytes = example_bytes();
+        let dump = HexDump::from_bytes::<Example>(&bytes[..6]).to_string();
+
+        assert!(dump.lines().nth(1).unwrap().contains("00 00 .. .."));
+        assert!(dump.lines().nth(1).unwrap().ends_with("truncated"));
+    }
+
+    #[test]
+    fn dump_ammo() {
+        let ammo = crate::v1_163::AmmoBuilder::new()
+            .item_name("AP")
+            .magazine_image("ammo")
+            .index(3)
+            .speed(100.0)
+            .build()
+            .unwrap();
+
+        let dump = format!("{:#}", HexDump::of(&ammo));
+        let item_name = dump
+            .lines()
+            .find(|line| line.contains("item_name"))
+            .unwrap();
+        assert!(item_name.contains("string \"AP\""));
+        assert!(dump.contains("\x1b[33m0x0180"));
+    }
+}
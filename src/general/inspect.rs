@@ -0,0 +1,87 @@
+//! Lists the fields of a game struct along with their values, for generic property-grid UIs.
+//!
+//! Where `Annotated` describes the fields of a type, `Inspect` describes those of a value,
+//! so a UI can show any struct implementing it without knowing its type.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::hexdump::FieldInfo;
+
+/// A field of a struct along with its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldValue {
+    /// The name, offset, and kind of the field.
+    pub info: FieldInfo,
+    /// The value, formatted with `Display`.
+    pub value: String,
+}
+
+impl FieldValue {
+    /// Returns the name of the field.
+    pub fn name(&self) -> &'static str {
+        self.info.name
+    }
+
+    /// Returns the offset of the field inside of the struct.
+    pub fn offset(&self) -> usize {
+        self.info.offset
+    }
+
+    /// Returns a short name for the type of the field, like "f32".
+    pub fn type_name(&self) -> &'static str {
+        self.info.kind.name()
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.info.name, self.value)
+    }
+}
+
+/// A struct whose fields can be listed with their values.
+///
+/// Implemented by `#[derive(GameStruct)]`, formatting every field with `Display`.
+pub trait Inspect {
+    /// Returns every field with its value, in declaration order.
+    fn inspect(&self) -> Vec<FieldValue>;
+}
+
+/// Returns every field of `value` with its value, in declaration order.
+pub fn inspect<T: Inspect>(value: &T) -> impl Iterator<Item = FieldValue> {
+    value.inspect().into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::hexdump::{Annotated, FieldKind};
+    use crate::general::raw::{from_bytes, PointerPolicy};
+    use crate::v1_163::Ammo;
+    use alloc::string::ToString;
+
+    #[test]
+    fn lists_every_field_with_its_value() {
+        let mut ammo: Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+        ammo.magazine_image.push_str("shell_57");
+        ammo.speed = 1250.5;
+
+        let fields: Vec<_> = inspect(&ammo).collect();
+        assert_eq!(fields.len(), Ammo::fields().len());
+        for (field, info) in fields.iter().zip(Ammo::fields()) {
+            assert_eq!(field.info, info);
+        }
+
+        let speed = fields.iter().find(|field| field.name() == "speed").unwrap();
+        assert_eq!(speed.value, "1250.5");
+        assert_eq!(speed.type_name(), "f32");
+        let image = fields
+            .iter()
+            .find(|field| field.name() == "magazine_image")
+            .unwrap();
+        assert_eq!(image.info.kind, FieldKind::String);
+        assert_eq!(image.to_string(), "magazine_image: shell_57");
+    }
+}
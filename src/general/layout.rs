@@ -0,0 +1,34 @@
+//! Defines compile-time checks of struct layouts.
+//!
+//! Every struct shared with the game must match the game's layout exactly.
+//! A mistake there shows up as an in-game crash, so layouts are asserted at compile time instead.
+
+/// Asserts the size of a struct and the offsets of its fields at compile time.
+///
+/// ```ignore
+/// assert_layout!(Ammo, size = 0x188, item_name = 0x08, caliber = 0x150);
+/// ```
+///
+/// Fields that aren't listed are not checked, so list every field the game reads.
+///
+/// The game is 64-bit, so layouts are only checked on 64-bit targets.
+/// Structs holding pointers are smaller elsewhere, such as on `wasm32`, and only useful for their serialized form there.
+macro_rules! assert_layout {
+    ($type:ty, size = $size:expr $(, $field:ident = $offset:expr)* $(,)?) => {
+        #[cfg(target_pointer_width = "64")]
+        const _: () = {
+            assert!(
+                ::core::mem::size_of::<$type>() == $size,
+                concat!("wrong size of ", stringify!($type))
+            );
+            $(
+                assert!(
+                    ::core::mem::offset_of!($type, $field) == $offset,
+                    concat!("wrong offset of ", stringify!($type), "::", stringify!($field))
+                );
+            )*
+        };
+    };
+}
+
+pub(crate) use assert_layout;
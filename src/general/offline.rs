@@ -0,0 +1,179 @@
+//! Decoding game structs field by field, for parsing dumps and saves on any machine.
+//!
+//! `raw::from_bytes` copies bytes straight into a struct, which only works where `#[repr(C)]` gives the game's layout:
+//! a little-endian 64-bit target. `Decode` instead reads every field at its offset in the game,
+//! as little-endian with 64-bit pointers, so the result is the same on a 32-bit or big-endian machine.
+//!
+//! `#[derive(GameStruct)]` implements `Decode` for structs that give their `#[size]` and the `#[offset]` of every field.
+
+use core::mem::size_of;
+
+use super::raw::{PointerPolicy, RawError};
+
+/// A type that can be decoded from its bytes in the game, whatever the host's layout.
+pub trait Decode: Sized {
+    /// The size of the type in the game, which may differ from its size on this machine.
+    const GAME_SIZE: usize;
+
+    /// Decodes the value from the first `GAME_SIZE` bytes of `bytes`.
+    ///
+    /// Owned heap buffers, such as the one of an `EscadraString`, are handled according to `policy`.
+    /// `PointerPolicy::Preserve` can't keep them, as their pointers are only meaningful in the game.
+    fn decode(bytes: &[u8], policy: &PointerPolicy) -> Result<Self, RawError>;
+}
+
+/// Decodes a value of type `T`, checking that `bytes` is long enough.
+pub fn decode<T: Decode>(bytes: &[u8], policy: &PointerPolicy) -> Result<T, RawError> {
+    let bytes = bytes.get(..T::GAME_SIZE).ok_or(RawError::TooShort {
+        expected: T::GAME_SIZE,
+        actual: bytes.len(),
+    })?;
+    T::decode(bytes, policy)
+}
+
+/// Decodes a field of type `F` at `offset`, adjusting the offset of any error.
+pub fn decode_field<F: Decode>(
+    bytes: &[u8],
+    offset: usize,
+    policy: &PointerPolicy,
+) -> Result<F, RawError> {
+    let field = bytes.get(offset..).unwrap_or_default();
+    decode(field, policy).map_err(|error| match error {
+        RawError::TooShort { expected, .. } => RawError::TooShort {
+            expected: offset + expected,
+            actual: bytes.len(),
+        },
+        error => error.offset_by(offset),
+    })
+}
+
+macro_rules! impl_decode_numbers {
+    ($($type:ty),*) => {
+        $(
+            impl Decode for $type {
+                const GAME_SIZE: usize = size_of::<$type>();
+
+                fn decode(bytes: &[u8], _policy: &PointerPolicy) -> Result<Self, RawError> {
+                    Ok(<$type>::from_le_bytes(bytes[..size_of::<$type>()].try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_decode_numbers!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl Decode for bool {
+    const GAME_SIZE: usize = 1;
+
+    fn decode(bytes: &[u8], _policy: &PointerPolicy) -> Result<Self, RawError> {
+        match bytes[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(RawError::InvalidValue { offset: 0 }),
+        }
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    const GAME_SIZE: usize = T::GAME_SIZE * N;
+
+    fn decode(bytes: &[u8], policy: &PointerPolicy) -> Result<Self, RawError> {
+        let mut values = alloc::vec::Vec::with_capacity(N);
+        for i in 0..N {
+            values.push(decode_field(bytes, i * T::GAME_SIZE, policy)?);
+        }
+        Ok(values
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("decoded {N} values")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::raw::{from_bytes, to_bytes, MemoryReader};
+    use crate::general::EscadraString;
+    use crate::v1_163::Ammo;
+
+    /// A fake game memory holding a single string at `ADDRESS`.
+    struct Memory;
+
+    const ADDRESS: u64 = 0x1400_0000;
+    const NAME: &[u8] = b"AMMO_57_INCENDIARY_CUSTOM";
+
+    impl MemoryReader for Memory {
+        fn read_bytes(&self, address: u64, buffer: &mut [u8]) -> bool {
+            let start = (address - ADDRESS) as usize;
+            match NAME.get(start..start + buffer.len()) {
+                Some(bytes) => {
+                    buffer.copy_from_slice(bytes);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    fn ammo_bytes() -> Vec<u8> {
+        let mut ammo: Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+        ammo.magazine_image.push_str("shell_57");
+        ammo.index = 7;
+        ammo.speed = 1250.5;
+
+        let mut bytes = to_bytes(&ammo, &PointerPolicy::Zero);
+        bytes[0x08..0x10].copy_from_slice(&ADDRESS.to_le_bytes());
+        bytes[0x18..0x20].copy_from_slice(&(NAME.len() as u64).to_le_bytes());
+        bytes[0x20..0x28].copy_from_slice(&(NAME.len() as u64).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_numbers_as_little_endian() {
+        let policy = PointerPolicy::Zero;
+        assert_eq!(decode::<u32>(&[1, 2, 3, 4, 5], &policy), Ok(0x0403_0201));
+        assert_eq!(decode::<f32>(&1.5f32.to_le_bytes(), &policy), Ok(1.5));
+        assert_eq!(decode::<[u16; 2]>(&[1, 0, 2, 0], &policy), Ok([1, 2]));
+        assert_eq!(
+            decode::<bool>(&[2], &policy),
+            Err(RawError::InvalidValue { offset: 0 })
+        );
+        assert_eq!(
+            decode_field::<u64>(&[0; 12], 8, &policy),
+            Err(RawError::TooShort {
+                expected: 16,
+                actual: 12
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_ammo_field_by_field() {
+        let bytes = ammo_bytes();
+        let ammo: Ammo = decode(&bytes, &PointerPolicy::Resolve(&Memory)).unwrap();
+        assert_eq!(ammo.item_name.get_bytes(), NAME);
+        assert_eq!(ammo.magazine_image, "shell_57");
+        assert_eq!(ammo.index, 7);
+        assert_eq!(ammo.speed, 1250.5);
+
+        // On this 64-bit little-endian host, copying the bytes gives the same struct.
+        let copied: Ammo = from_bytes(&bytes, &PointerPolicy::Resolve(&Memory)).unwrap();
+        assert_eq!(
+            serde_json::to_value(&ammo).unwrap(),
+            serde_json::to_value(&copied).unwrap()
+        );
+
+        let zeroed: Ammo = decode(&bytes, &PointerPolicy::Zero).unwrap();
+        assert_eq!(zeroed.item_name, "");
+        assert_eq!(
+            decode::<Ammo>(&bytes, &PointerPolicy::Preserve).err(),
+            Some(RawError::OwnedPointer { offset: 0x08 })
+        );
+        assert_eq!(
+            decode::<EscadraString>(&bytes[0x08..], &PointerPolicy::Resolve(&Memory))
+                .unwrap()
+                .get_bytes(),
+            NAME
+        );
+    }
+}
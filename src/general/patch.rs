@@ -0,0 +1,240 @@
+//! Defines patches: partial edits of a game struct that only record the changed fields.
+//!
+//! Patches are the building block for merging mods.
+//! Each mod ships the fields it changes, and the patches are stacked, failing when two mods change the same field differently.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use core::error::Error;
+use core::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The changed fields of a struct, keyed by field name.
+///
+/// Serializes as a map of the field names to their new values, e.g. `{"speed": 1200.0}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct Patch {
+    fields: BTreeMap<String, Value>,
+}
+
+/// Returned when creating, stacking, or applying a `Patch` fails.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The patch sets a field the struct doesn't have.
+    UnknownField(String),
+    /// Two stacked patches set the same field to different values.
+    Conflict {
+        /// The name of the field.
+        field: String,
+        /// The value set by the patch stacked first.
+        ours: Value,
+        /// The value set by the patch stacked second.
+        theirs: Value,
+    },
+    /// The struct couldn't be converted to or from its serialized form.
+    Serde(serde_json::Error),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownField(field) => write!(f, "unknown field {field}"),
+            Self::Conflict {
+                field,
+                ours,
+                theirs,
+            } => write!(f, "conflicting values for {field}: {ours} and {theirs}"),
+            Self::Serde(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for PatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Serde(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for PatchError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Serde(error)
+    }
+}
+
+/// Serializes a struct into its map of fields.
+fn to_fields<T: Serialize>(value: &T) -> Result<Map<String, Value>, PatchError> {
+    match serde_json::to_value(value)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(PatchError::Serde(serde::ser::Error::custom(
+            "only structs can be patched",
+        ))),
+    }
+}
+
+impl Patch {
+    /// Creates a patch that changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a patch holding every field that differs between `old` and `new`.
+    ///
+    /// Applying it to `old` gives `new`.
+    pub fn from_diff<T: Serialize>(old: &T, new: &T) -> Result<Self, PatchError> {
+        let old = to_fields(old)?;
+        let fields = to_fields(new)?
+            .into_iter()
+            .filter(|(field, value)| old.get(field) != Some(value))
+            .collect();
+
+        Ok(Self { fields })
+    }
+
+    /// Sets a field to the given value.
+    pub fn set(&mut self, field: &str, value: impl Serialize) -> Result<(), PatchError> {
+        self.fields
+            .insert(field.to_string(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Sets a field to a value that is already serialized.
+    #[cfg(feature = "std")]
+    pub(crate) fn insert(&mut self, field: &str, value: Value) {
+        self.fields.insert(field.to_string(), value);
+    }
+
+    /// Returns the value the patch sets a field to, if any.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.fields.get(field)
+    }
+
+    /// Returns the changed fields and their values, ordered by name.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value))
+    }
+
+    /// Returns the number of changed fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns true if the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Adds the fields of `other` to this patch.
+    ///
+    /// Fails without changing this patch if both set a field to different values.
+    pub fn stack(&mut self, other: &Patch) -> Result<(), PatchError> {
+        for (field, theirs) in &other.fields {
+            match self.fields.get(field) {
+                Some(ours) if ours != theirs => {
+                    return Err(PatchError::Conflict {
+                        field: field.clone(),
+                        ours: ours.clone(),
+                        theirs: theirs.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        self.fields.extend(
+            other
+                .fields
+                .iter()
+                .map(|(field, value)| (field.clone(), value.clone())),
+        );
+        Ok(())
+    }
+
+    /// Applies the patch to `target`.
+    ///
+    /// Fails without changing `target` if the patch sets a field `target` doesn't have.
+    pub fn apply<T: Serialize + DeserializeOwned>(&self, target: &mut T) -> Result<(), PatchError> {
+        let mut fields = to_fields(target)?;
+        for (field, value) in &self.fields {
+            match fields.get_mut(field) {
+                Some(old) => *old = value.clone(),
+                None => return Err(PatchError::UnknownField(field.clone())),
+            }
+        }
+
+        *target = serde_json::from_value(Value::Object(fields))?;
+        Ok(())
+    }
+}
+
+/// Applies `patch` to `target`. See `Patch::apply`.
+pub fn apply<T: Serialize + DeserializeOwned>(
+    target: &mut T,
+    patch: &Patch,
+) -> Result<(), PatchError> {
+    patch.apply(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v1_163::AmmoBuilder;
+
+    #[test]
+    fn diff_then_apply() {
+        let old = AmmoBuilder::test_ammo("AMMO_57", 7);
+        let mut new = AmmoBuilder::test_ammo("AMMO_57", 7);
+        new.speed = 1200.0;
+        new.shell_kind.push_str("Incendiary");
+
+        let patch = Patch::from_diff(&old, &new).unwrap();
+        assert_eq!(patch.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&patch).unwrap(),
+            r#"{"shell_kind":"Incendiary","speed":1200.0}"#
+        );
+
+        let mut patched = AmmoBuilder::test_ammo("AMMO_57", 7);
+        apply(&mut patched, &patch).unwrap();
+        assert_eq!(crate::general::diff(&patched, &new), []);
+    }
+
+    #[test]
+    fn stack_conflicts() {
+        let mut first = Patch::new();
+        first.set("speed", 1200.0).unwrap();
+        first.set("index", 8).unwrap();
+
+        let mut same = Patch::new();
+        same.set("speed", 1200.0).unwrap();
+        first.stack(&same).unwrap();
+
+        let mut second = Patch::new();
+        second.set("speed", 900.0).unwrap();
+        second.set("ttl", 10.0).unwrap();
+
+        let error = first.stack(&second).unwrap_err();
+        assert!(matches!(error, PatchError::Conflict { field, .. } if field == "speed"));
+        assert!(first.get("ttl").is_none());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let mut patch = Patch::new();
+        patch.set("speed", 1200.0).unwrap();
+        patch.set("warp_speed", 9).unwrap();
+
+        let mut target = AmmoBuilder::test_ammo("AMMO_57", 7);
+        let error = patch.apply(&mut target).unwrap_err();
+        assert!(matches!(error, PatchError::UnknownField(field) if field == "warp_speed"));
+        assert_eq!(target.speed, 1000.0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Records how much is known about every field of the game structs, for editors and reverse-engineering tools.
+//!
+//! The notes are generated by `#[derive(GameStruct)]` from the doc comments of the fields, so they never drift apart:
+//! - Fields named `unknown_*`, or documented as having an unknown purpose, are `Unknown`.
+//! - Fields whose doc comment starts with a line ending in `?` are `Suspected`.
+//! - Every other field is `Confirmed`.
+//!
+//! A field can override the guess with `#[provenance(confirmed)]`, `#[provenance(suspected)]` or `#[provenance(unknown)]`.
+//!
+//! ```
+//! use highfleet::general::provenance::{notes, Provenance};
+//! use highfleet::general::version::GameVersion;
+//!
+//! let ammo = notes("Ammo", GameVersion::V1_163).unwrap();
+//! let unknown = ammo.iter().find(|note| note.name == "unknown_180h").unwrap();
+//! assert_eq!(unknown.provenance, Provenance::Unknown);
+//! assert_eq!(unknown.offset, 0x180);
+//! ```
+
+use core::fmt;
+
+use serde::Serialize;
+
+use super::version::GameVersion;
+
+/// How much is known about a field.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// What the field does is confirmed.
+    Confirmed,
+    /// What the field does is a guess.
+    Suspected,
+    /// What the field does isn't known.
+    Unknown,
+}
+
+impl Provenance {
+    /// Returns the name of the provenance, e.g. "suspected".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::Suspected => "suspected",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What is known about a field of a struct.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldNote {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The offset of the field from the start of the struct.
+    pub offset: usize,
+    /// How much is known about the field.
+    pub provenance: Provenance,
+    /// The names the field had before it was understood, like `unknown_16ch` for `ttl`.
+    pub aliases: &'static [&'static str],
+    /// The doc comment of the field.
+    pub notes: &'static str,
+}
+
+impl fmt::Display for FieldNote {
+    /// Writes a line like `0x180 unknown_180h (unknown): Value with unknown purpose.`, using the first line of the notes.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x} {} ({})", self.offset, self.name, self.provenance)?;
+        match self.notes.lines().next() {
+            Some(summary) => write!(f, ": {summary}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A struct with a `FieldNote` for every field, implemented by `#[derive(GameStruct)]`.
+pub trait FieldNotes {
+    /// The notes of every field, in declaration order.
+    const FIELD_NOTES: &'static [FieldNote];
+}
+
+/// Every struct with notes: its name, the version it belongs to, and its notes.
+pub const REGISTRY: &[(&str, GameVersion, &[FieldNote])] = &[
+    (
+        "Ammo",
+        GameVersion::V1_151,
+        crate::v1_151::Ammo::FIELD_NOTES,
+    ),
+    (
+        "Ammo",
+        GameVersion::V1_163,
+        crate::v1_163::Ammo::FIELD_NOTES,
+    ),
+];
+
+/// Returns the notes of the struct with the given name in the given version.
+pub fn notes(name: &str, version: GameVersion) -> Option<&'static [FieldNote]> {
+    REGISTRY
+        .iter()
+        .find(|(struct_name, struct_version, _)| *struct_name == name && *struct_version == version)
+        .map(|(_, _, notes)| *notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::Annotated;
+
+    fn note(version: GameVersion, name: &str) -> FieldNote {
+        *notes("Ammo", version)
+            .unwrap()
+            .iter()
+            .find(|note| note.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn guesses_provenance_from_docs() {
+        let ttl = note(GameVersion::V1_163, "ttl");
+        assert_eq!(ttl.provenance, Provenance::Confirmed);
+        assert_eq!(ttl.offset, 0x16c);
+        assert_eq!(ttl.aliases, ["unknown_16ch"]);
+        assert!(ttl
+            .notes
+            .starts_with("Determines how long a shell will last in the air.\n\nIn vanilla"));
+
+        assert_eq!(
+            note(GameVersion::V1_163, "ap_drag").provenance,
+            Provenance::Suspected
+        );
+        assert_eq!(
+            note(GameVersion::V1_151, "unknown_158h").provenance,
+            Provenance::Unknown
+        );
+        assert_eq!(
+            note(GameVersion::V1_163, "padding_184h").provenance,
+            Provenance::Confirmed
+        );
+        assert_eq!(
+            note(GameVersion::V1_163, "padding_4h").provenance,
+            Provenance::Suspected
+        );
+
+        assert!(notes("Ship", GameVersion::V1_163).is_none());
+    }
+
+    #[test]
+    fn formats_and_serializes() {
+        let note = note(GameVersion::V1_163, "unknown_180h");
+        assert_eq!(
+            note.to_string(),
+            "0x180 unknown_180h (unknown): Value with unknown purpose."
+        );
+
+        let json = serde_json::to_value(note).unwrap();
+        assert_eq!(json["provenance"], "unknown");
+        assert_eq!(json["offset"], 0x180);
+    }
+
+    #[test]
+    fn covers_every_field() {
+        for (_, _, notes) in REGISTRY {
+            assert!(notes.windows(2).all(|pair| pair[0].offset < pair[1].offset));
+        }
+        assert_eq!(
+            crate::v1_151::Ammo::FIELD_NOTES.len(),
+            crate::v1_151::Ammo::fields().len()
+        );
+        assert_eq!(
+            crate::v1_163::Ammo::FIELD_NOTES.len(),
+            crate::v1_163::Ammo::fields().len()
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Reading and writing game structs as raw bytes, for example from a memory dump.
+//!
+//! Structs are copied byte for byte, after which their pointer fields are handled according to a `PointerPolicy`.
+//! Pointers inside of a dump point into the memory of the game, not into the memory of this process.
+//! Owned data behind those pointers, such as the heap buffer of an `EscadraString`, therefore has to be read through a `MemoryReader`
+//! before it can be used.
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::mem::{align_of, size_of, ManuallyDrop, MaybeUninit};
+
+/// Reads memory from an address space, usually the memory of the game.
+pub trait MemoryReader {
+    /// Fills `buffer` with the bytes starting at `address`.
+    ///
+    /// Returns `false` if the memory could not be read.
+    fn read_bytes(&self, address: u64, buffer: &mut [u8]) -> bool;
+}
+
+/// Determines how pointer fields are handled when converting structs from and to bytes.
+#[derive(Clone, Copy)]
+pub enum PointerPolicy<'a> {
+    /// Pointers are kept as they are.
+    ///
+    /// Owned heap buffers can't be preserved when reading, as they would be freed by this process.
+    /// Neither can links that safe code follows, such as those between TLLs.
+    /// Reading a struct that contains one returns `RawError::OwnedPointer`.
+    Preserve,
+    /// Pointers are set to null.
+    ///
+    /// Heap backed strings are read as empty strings.
+    Zero,
+    /// Owned heap buffers are read through the given reader and copied into memory owned by this process.
+    /// Links that safe code follows, such as those between TLLs, are set to null.
+    /// Other non-owning pointers are kept as they are.
+    ///
+    /// Acts like `Preserve` when writing.
+    Resolve(&'a dyn MemoryReader),
+}
+
+impl fmt::Debug for PointerPolicy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Preserve => write!(f, "Preserve"),
+            Self::Zero => write!(f, "Zero"),
+            Self::Resolve(_) => write!(f, "Resolve(..)"),
+        }
+    }
+}
+
+/// Error returned when converting bytes into a struct fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawError {
+    /// The byte slice is shorter than the struct.
+    TooShort {
+        /// The size of the struct.
+        expected: usize,
+        /// The length of the byte slice.
+        actual: usize,
+    },
+    /// The byte slice is not aligned for the struct.
+    Misaligned {
+        /// The alignment of the struct.
+        expected: usize,
+    },
+    /// A field holds a value that is invalid for its type, like a `bool` that is neither 0 nor 1.
+    InvalidValue {
+        /// The offset of the field inside of the byte slice.
+        offset: usize,
+    },
+    /// A field owns a heap buffer, or links to another struct, that can't be kept under `PointerPolicy::Preserve`.
+    OwnedPointer {
+        /// The offset of the field inside of the byte slice.
+        offset: usize,
+    },
+    /// The memory behind a pointer could not be read.
+    UnreadablePointer {
+        /// The address that could not be read.
+        address: u64,
+    },
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort { expected, actual } => {
+                write!(f, "expected at least {expected} bytes, got {actual}")
+            }
+            Self::Misaligned { expected } => write!(f, "bytes are not aligned to {expected}"),
+            Self::InvalidValue { offset } => write!(f, "invalid value at offset {offset:#x}"),
+            Self::OwnedPointer { offset } => {
+                write!(f, "owned pointer at offset {offset:#x} can't be preserved")
+            }
+            Self::UnreadablePointer { address } => {
+                write!(f, "could not read memory at {address:#x}")
+            }
+        }
+    }
+}
+
+impl Error for RawError {}
+
+/// A `#[repr(C)]` struct that can be converted from and to its in-game byte representation.
+///
+/// # Safety
+///
+/// The struct must be `#[repr(C)]` without implicit padding bytes.
+/// `validate` must reject every byte pattern that isn't a valid value of the struct, ignoring pointer fields.
+pub unsafe trait RawLayout: Sized {
+    /// Checks that the bytes are a valid value of the struct.
+    ///
+    /// `bytes` is exactly `size_of::<Self>()` long.
+    fn validate(bytes: &[u8]) -> Result<(), RawError> {
+        let _ = bytes;
+        Ok(())
+    }
+
+    /// Fixes up the pointer fields of a value that was copied byte for byte from the game.
+    ///
+    /// On error the value must be left in a state that is safe to forget, but not to drop.
+    ///
+    /// # Safety
+    ///
+    /// `self` must be a bitwise copy that is not owned by this process, and must not be dropped unless this returns `Ok`.
+    unsafe fn resolve_pointers(&mut self, policy: &PointerPolicy) -> Result<(), RawError>;
+
+    /// Applies the policy to the pointer fields of `bytes`, which is the byte representation of `self`.
+    fn write_pointers(&self, bytes: &mut [u8], policy: &PointerPolicy);
+}
+
+/// Reads a struct from its in-game byte representation.
+///
+/// Only the first `size_of::<T>()` bytes are read.
+pub fn from_bytes<T: RawLayout>(bytes: &[u8], policy: &PointerPolicy) -> Result<T, RawError> {
+    let bytes = check_size::<T>(bytes)?;
+    T::validate(bytes)?;
+
+    let mut value = MaybeUninit::<T>::uninit();
+    unsafe {
+        core::ptr::copy_nonoverlapping(bytes.as_ptr(), value.as_mut_ptr() as *mut u8, bytes.len());
+
+        let mut value = ManuallyDrop::new(value.assume_init());
+        value.resolve_pointers(policy)?;
+        Ok(ManuallyDrop::into_inner(value))
+    }
+}
+
+/// Views the in-game byte representation of a struct as the struct itself, without copying.
+///
+/// Pointers are preserved.
+///
+/// # Safety
+///
+/// Heap buffers owned by the struct are owned by the game.
+/// Methods that reallocate them, such as `EscadraString::set_string`, must only be used with an allocator compatible with the game's,
+/// and the struct must never be dropped.
+pub unsafe fn from_bytes_mut<T: RawLayout>(bytes: &mut [u8]) -> Result<&mut T, RawError> {
+    T::validate(check_size::<T>(bytes)?)?;
+
+    let pointer = bytes.as_mut_ptr() as *mut T;
+    if !pointer.is_aligned() {
+        return Err(RawError::Misaligned {
+            expected: align_of::<T>(),
+        });
+    }
+
+    Ok(&mut *pointer)
+}
+
+/// Writes a struct into its in-game byte representation.
+pub fn to_bytes<T: RawLayout>(value: &T, policy: &PointerPolicy) -> Vec<u8> {
+    let mut bytes =
+        unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+            .to_vec();
+    value.write_pointers(&mut bytes, policy);
+    bytes
+}
+
+fn check_size<T>(bytes: &[u8]) -> Result<&[u8], RawError> {
+    bytes.get(..size_of::<T>()).ok_or(RawError::TooShort {
+        expected: size_of::<T>(),
+        actual: bytes.len(),
+    })
+}
+
+/// Validates a field of type `F` at `offset`, adjusting the offset of any error.
+pub fn validate_field<F: RawLayout>(bytes: &[u8], offset: usize) -> Result<(), RawError> {
+    F::validate(&bytes[offset..offset + size_of::<F>()]).map_err(|error| error.offset_by(offset))
+}
+
+/// Resolves the pointers of a field of type `F` at `offset` inside of `value`, adjusting the offset of any error.
+///
+/// # Safety
+///
+/// See `RawLayout::resolve_pointers`. There must be a valid `F` at `offset` inside of `value`.
+pub unsafe fn resolve_field<T, F: RawLayout>(
+    value: &mut T,
+    offset: usize,
+    policy: &PointerPolicy,
+) -> Result<(), RawError> {
+    let field = &mut *((value as *mut T as *mut u8).add(offset) as *mut F);
+    field
+        .resolve_pointers(policy)
+        .map_err(|error| error.offset_by(offset))
+}
+
+/// Writes the pointers of a field of type `F` at `offset` inside of `value`.
+///
+/// # Safety
+///
+/// There must be a valid `F` at `offset` inside of `value`.
+pub unsafe fn write_field<T, F: RawLayout>(
+    value: &T,
+    bytes: &mut [u8],
+    offset: usize,
+    policy: &PointerPolicy,
+) {
+    let field = &*((value as *const T as *const u8).add(offset) as *const F);
+    field.write_pointers(&mut bytes[offset..offset + size_of::<F>()], policy);
+}
+
+impl RawError {
+    pub(crate) fn offset_by(self, base: usize) -> Self {
+        match self {
+            Self::InvalidValue { offset } => Self::InvalidValue {
+                offset: base + offset,
+            },
+            Self::OwnedPointer { offset } => Self::OwnedPointer {
+                offset: base + offset,
+            },
+            error => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::{EscadraString, TllData, TLL};
+    use crate::v1_163::Ammo;
+    use std::collections::HashMap;
+
+    /// A fake address space for tests.
+    struct FakeMemory(HashMap<u64, Vec<u8>>);
+
+    impl MemoryReader for FakeMemory {
+        fn read_bytes(&self, address: u64, buffer: &mut [u8]) -> bool {
+            match self.0.get(&address) {
+                Some(bytes) if bytes.len() >= buffer.len() => {
+                    buffer.copy_from_slice(&bytes[..buffer.len()]);
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    /// Builds the bytes of a heap backed `EscadraString` pointing to `address`.
+    fn heap_string_bytes(address: u64, length: u64) -> Vec<u8> {
+        let mut bytes = vec![0u8; 16];
+        bytes[..8].copy_from_slice(&address.to_le_bytes());
+        bytes.extend(length.to_le_bytes());
+        bytes.extend(31u64.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn inline_string_round_trip() {
+        let es = EscadraString::from("Banana".to_string());
+
+        let bytes = to_bytes(&es, &PointerPolicy::Preserve);
+        assert_eq!(bytes.len(), 32);
+
+        let result: EscadraString = from_bytes(&bytes, &PointerPolicy::Preserve).unwrap();
+        assert_eq!(result, "Banana");
+    }
+
+    #[test]
+    fn heap_string_is_resolved() {
+        let memory = FakeMemory(HashMap::from([(
+            0x1000,
+            b"Banana Banana Banana\0".to_vec(),
+        )]));
+        let bytes = heap_string_bytes(0x1000, 20);
+
+        let result: EscadraString = from_bytes(&bytes, &PointerPolicy::Resolve(&memory)).unwrap();
+        assert_eq!(result, "Banana Banana Banana");
+
+        let result: EscadraString = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        assert_eq!(result, "");
+
+        let result = from_bytes::<EscadraString>(&bytes, &PointerPolicy::Preserve);
+        assert_eq!(result.unwrap_err(), RawError::OwnedPointer { offset: 0 });
+    }
+
+    #[test]
+    fn unreadable_pointer_is_an_error() {
+        let memory = FakeMemory(HashMap::new());
+        let bytes = heap_string_bytes(0x1000, 20);
+
+        let result = from_bytes::<EscadraString>(&bytes, &PointerPolicy::Resolve(&memory));
+        assert_eq!(
+            result.unwrap_err(),
+            RawError::UnreadablePointer { address: 0x1000 }
+        );
+    }
+
+    #[test]
+    fn too_short_is_an_error() {
+        let result = from_bytes::<Ammo>(&[0u8; 16], &PointerPolicy::Zero);
+        assert_eq!(
+            result.unwrap_err(),
+            RawError::TooShort {
+                expected: size_of::<Ammo>(),
+                actual: 16
+            }
+        );
+    }
+
+    #[test]
+    fn ammo_round_trip() {
+        let bytes = vec![0u8; size_of::<Ammo>()];
+
+        let mut ammo: Ammo = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        ammo.item_name
+            .set_string(&"Banana Banana Banana".to_string());
+        ammo.milimeterage.set_string(&"57mm".to_string());
+        ammo.index = 12;
+
+        let bytes = to_bytes(&ammo, &PointerPolicy::Preserve);
+        let result = from_bytes::<Ammo>(&bytes, &PointerPolicy::Preserve);
+        assert_eq!(
+            result.unwrap_err(),
+            RawError::OwnedPointer {
+                offset: core::mem::offset_of!(Ammo, item_name)
+            }
+        );
+
+        let bytes = to_bytes(&ammo, &PointerPolicy::Zero);
+        let result: Ammo = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+
+        assert_eq!(result.item_name, "");
+        assert_eq!(result.milimeterage, "57mm");
+        assert_eq!(result.index, 12);
+    }
+
+    #[test]
+    fn tll_rejects_invalid_bool() {
+        let mut bytes = vec![0u8; size_of::<TLL>()];
+        bytes[0x18] = 2;
+
+        let result = from_bytes::<TLL>(&bytes, &PointerPolicy::Zero);
+        assert_eq!(result.unwrap_err(), RawError::InvalidValue { offset: 0x18 });
+    }
+
+    #[test]
+    fn tll_links_are_not_followed_into_the_game() {
+        let mut bytes = vec![0u8; size_of::<TLL>()];
+        for offset in [0x00, 0x08, 0x10] {
+            bytes[offset..offset + 8].copy_from_slice(&0xdead_0000u64.to_le_bytes());
+        }
+
+        let result = from_bytes::<TLL>(&bytes, &PointerPolicy::Preserve);
+        assert_eq!(result.unwrap_err(), RawError::OwnedPointer { offset: 0 });
+
+        let memory = FakeMemory(HashMap::new());
+        for policy in [PointerPolicy::Resolve(&memory), PointerPolicy::Zero] {
+            let tll: TLL = from_bytes(&bytes, &policy).unwrap();
+            let links = tll.links();
+            assert!(links.a.is_null() && links.b.is_null() && links.c.is_null());
+            assert_eq!(tll.iter().count(), 1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "insert must be called on the head of an initialised tree")]
+    fn tll_head_with_null_links_rejects_insert() {
+        let mut bytes = vec![0u8; size_of::<TLL>()];
+        bytes[0x19] = 1;
+
+        let mut head: TLL = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        head.insert("Banana", TllData::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "remove must be called on the head of an initialised tree")]
+    fn tll_head_with_null_links_rejects_remove() {
+        let mut bytes = vec![0u8; size_of::<TLL>()];
+        bytes[0x19] = 1;
+
+        let mut head: TLL = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        head.remove("Banana");
+    }
+
+    #[test]
+    fn from_bytes_mut_views_in_place() {
+        let es = EscadraString::from("Banana".to_string());
+        let mut bytes = to_bytes(&es, &PointerPolicy::Preserve);
+        // Copy into an aligned buffer.
+        let mut buffer = [0u64; 4];
+        let buffer_bytes =
+            unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, 32) };
+        buffer_bytes.copy_from_slice(&bytes);
+
+        let view = unsafe { from_bytes_mut::<EscadraString>(buffer_bytes).unwrap() };
+        assert_eq!(*view, "Banana");
+        view.truncate(3);
+        assert_eq!(buffer_bytes[3], 0);
+
+        bytes.insert(0, 0);
+        let result = unsafe { from_bytes_mut::<EscadraString>(&mut bytes[1..]) };
+        assert_eq!(result.unwrap_err(), RawError::Misaligned { expected: 8 });
+    }
+}
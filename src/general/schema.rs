@@ -0,0 +1,154 @@
+//! Describes the serialized form of game structs as JSON Schema and TypeScript definitions.
+//!
+//! Editors can use these to validate configs, such as ammo patches, without knowing the layouts.
+//! The properties come from the fields of the struct as listed by `Annotated`,
+//! leaving out pointers and raw bytes, which aren't serialized.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde_json::{json, Map, Value};
+
+use super::hexdump::{Annotated, FieldInfo, FieldKind};
+use super::version::GameVersion;
+use crate::{v1_151, v1_163};
+
+/// The JSON Schema dialect of the generated schemas.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Returns a JSON Schema of a struct, serialized as an object with one property per field.
+pub fn json_schema<T: Annotated>(title: &str) -> Value {
+    let fields = serialized_fields::<T>();
+
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|field| (field.name.to_string(), property(field.kind)))
+        .collect();
+    let required: Vec<&str> = fields.iter().map(|field| field.name).collect();
+
+    json!({
+        "$schema": DIALECT,
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// Returns a TypeScript interface of a struct, serialized as an object with one property per field.
+pub fn typescript<T: Annotated>(name: &str) -> String {
+    let mut definition = format!("export interface {name} {{\n");
+    for field in serialized_fields::<T>() {
+        let kind = match field.kind {
+            FieldKind::Bool => "boolean",
+            FieldKind::String => "string",
+            _ => "number",
+        };
+        definition += &format!("  {}: {kind}; // {}\n", field.name, field.kind.name());
+    }
+    definition.push_str("}\n");
+    definition
+}
+
+/// Returns the JSON Schema of the `Ammo` of a version.
+pub fn ammo_schema(version: GameVersion) -> Value {
+    let title = format!("Ammo {version}");
+    match version {
+        GameVersion::V1_151 => json_schema::<v1_151::Ammo>(&title),
+        GameVersion::V1_163 => json_schema::<v1_163::Ammo>(&title),
+    }
+}
+
+/// Returns the TypeScript interfaces of the `Ammo` of every version, named like `Ammo_1_163`.
+pub fn ammo_typescript() -> String {
+    GameVersion::ALL
+        .iter()
+        .map(|version| {
+            let name = format!("Ammo_{}", version.name().replace('.', "_"));
+            match version {
+                GameVersion::V1_151 => typescript::<v1_151::Ammo>(&name),
+                GameVersion::V1_163 => typescript::<v1_163::Ammo>(&name),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the fields that are serialized.
+fn serialized_fields<T: Annotated>() -> Vec<FieldInfo> {
+    T::fields()
+        .into_iter()
+        .filter(|field| !matches!(field.kind, FieldKind::Pointer | FieldKind::Bytes(_)))
+        .collect()
+}
+
+/// Returns the schema of a property, with the range of integers.
+fn property(kind: FieldKind) -> Value {
+    match kind {
+        FieldKind::Bool => json!({ "type": "boolean" }),
+        FieldKind::String => json!({ "type": "string" }),
+        FieldKind::F32 => json!({ "type": "number" }),
+        FieldKind::U16 => integer(0, u16::MAX),
+        FieldKind::I32 => integer(i32::MIN, i32::MAX),
+        FieldKind::U32 => integer(0, u32::MAX),
+        FieldKind::U64 => integer(0, u64::MAX),
+        FieldKind::Pointer | FieldKind::Bytes(_) => unreachable!("not serialized"),
+    }
+}
+
+fn integer(minimum: impl Into<Value>, maximum: impl Into<Value>) -> Value {
+    json!({ "type": "integer", "minimum": minimum.into(), "maximum": maximum.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ammo_schema_matches_serialized_form() {
+        for version in GameVersion::ALL {
+            let schema = ammo_schema(version);
+            let properties = schema["properties"].as_object().unwrap();
+
+            let ammo = match version {
+                GameVersion::V1_151 => {
+                    serde_json::to_value(v1_151::AmmoBuilder::test_ammo("AMMO_57", 5))
+                }
+                GameVersion::V1_163 => {
+                    serde_json::to_value(v1_163::AmmoBuilder::test_ammo("AMMO_57", 5))
+                }
+            }
+            .unwrap();
+            let serialized = ammo.as_object().unwrap();
+
+            assert_eq!(properties.len(), serialized.len());
+            for (name, value) in serialized {
+                let kind = properties[name]["type"].as_str().unwrap();
+                match value {
+                    Value::String(_) => assert_eq!(kind, "string"),
+                    Value::Number(number) if number.is_f64() => assert_eq!(kind, "number"),
+                    Value::Number(_) => assert_eq!(kind, "integer"),
+                    other => panic!("unexpected value {other}"),
+                }
+            }
+        }
+
+        let schema = ammo_schema(GameVersion::V1_163);
+        assert_eq!(schema["title"], "Ammo 1.163");
+        assert_eq!(schema["properties"]["reticle"]["minimum"], i32::MIN);
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("fire_delay")));
+    }
+
+    #[test]
+    fn ammo_typescript_interfaces() {
+        let typescript = ammo_typescript();
+        assert!(
+            typescript.starts_with("export interface Ammo_1_151 {\n  reticle: number; // i32\n")
+        );
+        assert!(typescript.contains("export interface Ammo_1_163 {"));
+        assert!(typescript.contains("  item_name: string; // string\n"));
+    }
+}
@@ -0,0 +1,232 @@
+//! Defines a pool that makes equal `EscadraString`s share one heap buffer.
+//!
+//! Mods that register hundreds of ammos repeat the same few strings, such as "shell_in_small".
+//! Interned strings point at one buffer per distinct string, instead of allocating their own.
+//! Every buffer starts with a header holding the number of strings and pools using it.
+//!
+//! A shared buffer is only freed once the pool and every string using it are dropped.
+//! Writing to an interned string first copies it into its own buffer, so the others never see the change.
+//!
+//! The game doesn't know about the sharing. Only hand interned strings to the game if it never frees them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::string::String;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+use crate::general::allocator::allocator;
+use crate::general::escadra_string::EscadraString;
+
+/// The address of every shared buffer. Their reference counts live in a header before the string.
+///
+/// Only creating and freeing a buffer takes the write lock, so strings can be dropped on several threads at once.
+static SHARED: RwLock<BTreeSet<usize>> = RwLock::new(BTreeSet::new());
+
+/// The number of entries in `SHARED`, so strings can skip the lock while nothing is interned.
+static SHARED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The size of the reference count before a shared buffer, keeping the string aligned like `malloc` would.
+const HEADER_SIZE: usize = 16;
+
+/// Hands out `EscadraString`s that share their heap buffer with every equal string from the same pool.
+///
+/// Strings of 15 bytes or less are stored inline and never shared.
+///
+/// ```
+/// use highfleet::general::StringPool;
+///
+/// let mut pool = StringPool::new();
+/// let a = pool.intern("shell_out_small_far");
+/// let b = pool.intern("shell_out_small_far");
+///
+/// assert_eq!(a, b);
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct StringPool {
+    buffers: HashMap<String, *mut u8>,
+}
+
+impl StringPool {
+    /// Creates an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns an `EscadraString` holding `string`, sharing its buffer with the other strings interned as `string`.
+    ///
+    /// The buffer is allocated with the allocator set by `set_allocator` the first time `string` is interned.
+    pub fn intern(&mut self, string: &str) -> EscadraString {
+        if string.len() <= 15 {
+            return EscadraString::from(string);
+        }
+
+        let pointer = *self
+            .buffers
+            .entry(string.into())
+            .or_insert_with(|| unsafe { share(string.as_bytes()) });
+        retain(pointer);
+
+        unsafe { EscadraString::from_shared(pointer, string.len()) }
+    }
+
+    /// Returns the number of distinct heap buffers in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Returns true if nothing was interned on the heap yet.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+}
+
+// The buffers are only written once, before being shared, and their reference counts are atomic.
+unsafe impl Send for StringPool {}
+unsafe impl Sync for StringPool {}
+
+impl Drop for StringPool {
+    /// Gives up the pool's reference to every buffer. Buffers still used by strings stay alive.
+    fn drop(&mut self) {
+        for pointer in self.buffers.values() {
+            release(*pointer);
+        }
+    }
+}
+
+/// Allocates a null terminated copy of `bytes`, with a reference count of one held by the pool.
+unsafe fn share(bytes: &[u8]) -> *mut u8 {
+    let header = allocator().malloc(HEADER_SIZE + bytes.len() + 1);
+    assert!(!header.is_null(), "the game allocator is out of memory");
+    (header as *mut AtomicUsize).write(AtomicUsize::new(1));
+
+    let pointer = header.add(HEADER_SIZE);
+    core::ptr::copy_nonoverlapping(bytes.as_ptr(), pointer, bytes.len());
+    *pointer.add(bytes.len()) = b'\0';
+
+    SHARED
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(pointer as usize);
+    SHARED_COUNT.fetch_add(1, Ordering::Release);
+    pointer
+}
+
+/// Returns the reference count of a shared buffer.
+///
+/// # Safety
+///
+/// `pointer` must be a shared buffer that is kept alive by a reference.
+unsafe fn references<'a>(pointer: *const u8) -> &'a AtomicUsize {
+    &*(pointer.sub(HEADER_SIZE) as *const AtomicUsize)
+}
+
+fn retain(pointer: *mut u8) {
+    // The pool holds a reference until it is dropped, so the buffer is alive.
+    unsafe { references(pointer) }.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns true if `pointer` is a buffer shared by the pool.
+pub(crate) fn is_shared(pointer: *const u8) -> bool {
+    SHARED_COUNT.load(Ordering::Acquire) > 0
+        && SHARED
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&(pointer as usize))
+}
+
+/// Drops one reference to `pointer`, freeing it once nothing uses it anymore.
+///
+/// Returns false without doing anything if `pointer` isn't a shared buffer, so the caller frees it instead.
+pub(crate) fn release(pointer: *mut u8) -> bool {
+    if !is_shared(pointer) {
+        return false;
+    }
+
+    // The caller's reference keeps the buffer alive until here, and nothing takes a new one once the count reaches zero.
+    if unsafe { references(pointer) }.fetch_sub(1, Ordering::Release) == 1 {
+        fence(Ordering::Acquire);
+        SHARED
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(pointer as usize));
+        SHARED_COUNT.fetch_sub(1, Ordering::Release);
+        unsafe { allocator().free(pointer.sub(HEADER_SIZE)) };
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(string: &EscadraString) -> Option<usize> {
+        let pointer = string.get_bytes().as_ptr();
+        is_shared(pointer).then(|| unsafe { super::references(pointer) }.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn interned_strings_share_buffers() {
+        let mut pool = StringPool::new();
+        let a = pool.intern("shell_in_small_pooled");
+        let b = pool.intern("shell_in_small_pooled");
+        let c = pool.intern("shell_out_med_pooled");
+        let short = pool.intern("shell_in_small");
+
+        assert_eq!(a, "shell_in_small_pooled");
+        assert_eq!(a.get_bytes().as_ptr(), b.get_bytes().as_ptr());
+        assert_ne!(a.get_bytes().as_ptr(), c.get_bytes().as_ptr());
+        assert_eq!(short.capacity(), 15);
+        assert_eq!(pool.len(), 2);
+        assert_eq!(references(&a), Some(3));
+
+        drop(b);
+        assert_eq!(references(&a), Some(2));
+
+        // The buffer outlives the pool while a string still uses it.
+        drop(pool);
+        assert_eq!(references(&a), Some(1));
+        assert_eq!(a, "shell_in_small_pooled");
+        assert_eq!(c, "shell_out_med_pooled");
+    }
+
+    #[test]
+    fn strings_drop_on_other_threads() {
+        let mut pool = StringPool::new();
+        let strings: Vec<_> = (0..8)
+            .map(|_| pool.intern("shell_out_large_pooled"))
+            .collect();
+        let first = pool.intern("shell_out_large_pooled");
+        assert_eq!(references(&first), Some(10));
+
+        std::thread::scope(|scope| {
+            for string in strings {
+                scope.spawn(move || drop(string));
+            }
+        });
+        assert_eq!(references(&first), Some(2));
+    }
+
+    #[test]
+    fn writes_copy_shared_buffers() {
+        let mut pool = StringPool::new();
+        let mut a = pool.intern("sign_ammo_inc_small_pooled");
+        let b = pool.intern("sign_ammo_inc_small_pooled");
+
+        a.push_str("_2");
+        assert_eq!(a, "sign_ammo_inc_small_pooled_2");
+        assert_eq!(b, "sign_ammo_inc_small_pooled");
+        assert_eq!(references(&a), None);
+        assert_eq!(references(&b), Some(2));
+
+        let mut c = pool.intern("sign_ammo_inc_small_pooled");
+        c.truncate(3);
+        assert_eq!(c, "sig");
+        assert_eq!(b, "sign_ammo_inc_small_pooled");
+        assert_eq!(references(&b), Some(2));
+
+        let mut d = b.clone();
+        d.clear();
+        assert_eq!(b, "sign_ammo_inc_small_pooled");
+    }
+}
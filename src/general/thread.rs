@@ -0,0 +1,98 @@
+//! Defines `GameThread`, the proof needed to mutate structs that live in the game's memory.
+//!
+//! The game touches its structs from its main thread without any locking,
+//! so mods must only mutate them from the same thread, such as from a hook on a function of the main loop.
+//! Functions that hand out mutable access to live structs, like `GamePtr::as_mut` and `AmmoTable::from_raw_parts`,
+//! take a `&GameThread`, which can't be sent to or shared with other threads.
+//!
+//! Values owned by the mod are ordinary Rust values: `EscadraString` and the structs built from it are `Send` and `Sync`.
+//! `TLL` is neither, since its links always point into a structure the game owns.
+
+use core::marker::PhantomData;
+
+/// Proof that the current thread is the game's main thread.
+///
+/// It's neither `Send` nor `Sync`, so it can't leave the thread it was created on.
+///
+/// ```compile_fail
+/// fn send<T: Send>(_: T) {}
+/// send(unsafe { highfleet::general::GameThread::new() });
+/// ```
+#[derive(Debug)]
+pub struct GameThread {
+    _not_send: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "std")]
+static REGISTERED: std::sync::OnceLock<std::thread::ThreadId> = std::sync::OnceLock::new();
+
+impl GameThread {
+    /// Returns the proof without checking anything.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the game's main thread.
+    pub unsafe fn new() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Remembers the current thread as the game's main thread, so `current` can return the proof later.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the game's main thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread was already registered.
+    #[cfg(feature = "std")]
+    pub unsafe fn register() -> Self {
+        let current = std::thread::current().id();
+        let registered = *REGISTERED.get_or_init(|| current);
+        assert_eq!(
+            registered, current,
+            "another thread was registered as the game thread"
+        );
+
+        Self::new()
+    }
+
+    /// Returns the proof if the current thread was registered with `register`.
+    #[cfg(feature = "std")]
+    pub fn current() -> Option<Self> {
+        let registered = *REGISTERED.get()?;
+        (registered == std::thread::current().id()).then(|| unsafe { Self::new() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owned_values_are_send_and_sync() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<crate::general::EscadraString>();
+        send_sync::<crate::general::GamePtr<crate::general::TLL>>();
+        send_sync::<crate::v1_151::Ammo>();
+        send_sync::<crate::v1_163::Ammo>();
+    }
+
+    #[test]
+    fn only_the_registered_thread_gets_the_proof() {
+        assert!(GameThread::current().is_none());
+
+        unsafe { GameThread::register() };
+        assert!(GameThread::current().is_some());
+        unsafe { GameThread::register() };
+
+        std::thread::spawn(|| {
+            assert!(GameThread::current().is_none());
+            assert!(std::panic::catch_unwind(|| unsafe { GameThread::register() }).is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -1,28 +1,118 @@
 //! Defines the data type for the TLL (triply linked list) type
 
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 use core::fmt;
-use std::collections::{HashMap, HashSet};
+use core::marker::PhantomData;
+use core::mem::offset_of;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
-use super::EscadraString;
+use super::escadra_map::{EscadraMap, EscadraMapNode};
+use super::hexdump::impl_annotated;
+use super::layout::assert_layout;
+use super::raw::{resolve_field, validate_field, write_field, PointerPolicy, RawError, RawLayout};
+use super::{EscadraString, GamePtr};
+
+mod graph;
+mod owned;
+#[cfg(feature = "std")]
+mod print;
+mod repr;
+mod tree;
+mod validate;
+
+pub use owned::{ArenaTll, OwnedTll};
+#[cfg(feature = "std")]
+pub use print::PrintOptions;
+pub use repr::TllNodeRepr;
+pub use validate::TllError;
 
 /// Struct used when exploring the TLL.
-/// It holds the *mut TLL pointers for the a, b, and c fields for a given TLL.
+/// It holds the a, b, and c links of a given TLL.
+#[derive(Debug)]
 pub struct TLLRef {
     /// The a pointer for the TLL.
-    pub a: *mut TLL,
+    pub a: GamePtr<TLL>,
     /// The b pointer for the TLL.
-    pub b: *mut TLL,
+    pub b: GamePtr<TLL>,
     /// The c pointer for the TLL.
-    pub c: *mut TLL,
+    pub c: GamePtr<TLL>,
+}
+
+/// A TLL tree viewed as an `EscadraMap`, keyed by the TLL strings.
+/// The value holds the raw bytes stored after the string.
+pub type TLLMap = EscadraMap<EscadraString, [u8; 0x20]>;
+
+/// The data held by a TLL besides its string and links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TllData {
+    /// The index held by the TLL.
+    pub index: u32,
+    /// Value with unknown purpose.
+    pub unknown_40h: u32,
+    /// Pointer with unknown purpose.
+    pub data1: GamePtr<u8>,
+    /// Pointer with unknown purpose.
+    pub data2: GamePtr<u8>,
+    /// Pointer with unknown purpose.
+    pub data3: GamePtr<u8>,
+}
+
+/// Iterator over every TLL reachable from a starting TLL.
+///
+/// TLLs are visited depth first: a TLL is yielded, then everything reachable through `a`, then through `b`, then through `c`.
+/// Every TLL is yielded once, even if the structure contains cycles.
+pub struct TLLIter<'a> {
+    stack: Vec<&'a TLL>,
+    visited: BTreeSet<*const TLL>,
+}
+
+impl<'a> Iterator for TLLIter<'a> {
+    type Item = &'a TLL;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(tll) = self.stack.pop() {
+            if !self.visited.insert(tll) {
+                continue;
+            }
+
+            for next in [tll.c, tll.b, tll.a] {
+                if let Some(next) = tll.follow(next) {
+                    if !self.visited.contains(&(next as *const TLL)) {
+                        self.stack.push(next);
+                    }
+                }
+            }
+
+            return Some(tll);
+        }
+
+        None
+    }
 }
 
 /// Represents an element in a triply linked list.
 /// The only current known use is to hold Airplane loadout information and for keyboard input information.
+///
+/// A `TLL` is neither `Send` nor `Sync`, since its links point into a structure the game mutates from its main thread.
+/// Even an `OwnedTll` built by a mod is only meant to be handed over to the game.
+///
+/// Safe methods such as `iter` and `find` follow the links, so a non-null link must always point to a live TLL.
+/// Only unsafe code, such as `GamePtr::as_ref` or `raw::from_bytes_mut`, can produce a TLL with links into the game;
+/// `raw::from_bytes` refuses to keep them.
+///
+/// ```compile_fail
+/// fn send<T: Send>() {}
+/// send::<highfleet::general::TLL>();
+/// ```
 #[repr(C)]
 pub struct TLL {
-    a: *mut TLL,
-    b: *mut TLL,
-    c: *mut TLL,
+    a: GamePtr<TLL>,
+    b: GamePtr<TLL>,
+    c: GamePtr<TLL>,
     end: bool,
     flag: bool,
     padding_1ah: u16,
@@ -31,11 +121,46 @@ pub struct TLL {
     pub string: EscadraString,
     unknown_40h: u32,
     padding_44h: u32,
-    data1: *mut u8,
-    data2: *mut u8,
-    data3: *mut u8,
+    data1: GamePtr<u8>,
+    data2: GamePtr<u8>,
+    data3: GamePtr<u8>,
+    _not_send: PhantomData<*const ()>,
 }
 
+assert_layout!(
+    TLL,
+    size = 0x60,
+    a = 0x00,
+    b = 0x08,
+    c = 0x10,
+    end = 0x18,
+    flag = 0x19,
+    padding_1ah = 0x1a,
+    index = 0x1c,
+    string = 0x20,
+    unknown_40h = 0x40,
+    padding_44h = 0x44,
+    data1 = 0x48,
+    data2 = 0x50,
+    data3 = 0x58,
+);
+
+impl_annotated!(TLL {
+    a,
+    b,
+    c,
+    end,
+    flag,
+    padding_1ah,
+    index,
+    string,
+    unknown_40h,
+    padding_44h,
+    data1,
+    data2,
+    data3,
+});
+
 impl fmt::Debug for TLL {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TLL")
@@ -56,86 +181,203 @@ impl fmt::Debug for TLL {
     }
 }
 
-impl TLL {
-    /// Recursively explores a TLL. Returns a Hashmap of TLL pointers and their a, b, c pointers in a TLLRef.
-    pub fn explore(&self) -> HashMap<*const TLL, TLLRef> {
-        let mut visited = HashSet::new();
-        let mut result = HashMap::new();
+unsafe impl RawLayout for TLL {
+    fn validate(bytes: &[u8]) -> Result<(), RawError> {
+        for offset in [offset_of!(TLL, end), offset_of!(TLL, flag)] {
+            if bytes[offset] > 1 {
+                return Err(RawError::InvalidValue { offset });
+            }
+        }
 
-        self.explore_internal(&mut visited, &mut result);
-        result
+        validate_field::<EscadraString>(bytes, offset_of!(TLL, string))
     }
 
-    fn explore_internal(
-        &self,
-        visited: &mut HashSet<*mut TLL>,
-        result: &mut HashMap<*const TLL, TLLRef>,
-    ) {
-        visited.insert(self as *const TLL as *mut TLL);
+    unsafe fn resolve_pointers(&mut self, policy: &PointerPolicy) -> Result<(), RawError> {
+        // The links are followed by safe methods such as `iter`, so they can't be kept pointing into another address space.
+        if let PointerPolicy::Preserve = policy {
+            for (offset, link) in [
+                (offset_of!(TLL, a), self.a),
+                (offset_of!(TLL, b), self.b),
+                (offset_of!(TLL, c), self.c),
+            ] {
+                if !link.is_null() {
+                    return Err(RawError::OwnedPointer { offset });
+                }
+            }
+        }
 
-        result.insert(
-            self as *const TLL,
-            TLLRef {
-                a: self.a,
-                b: self.b,
-                c: self.c,
-            },
-        );
+        resolve_field::<_, EscadraString>(self, offset_of!(TLL, string), policy)?;
 
-        unsafe {
-            if !self.a.is_null() && !visited.contains(&self.a) {
-                (*self.a).explore_internal(visited, result);
-            }
-            if !self.b.is_null() && !visited.contains(&self.b) {
-                (*self.b).explore_internal(visited, result);
-            }
-            if !self.c.is_null() && !visited.contains(&self.c) {
-                (*self.c).explore_internal(visited, result);
+        self.a = GamePtr::null();
+        self.b = GamePtr::null();
+        self.c = GamePtr::null();
+
+        if let PointerPolicy::Zero = policy {
+            self.data1 = GamePtr::null();
+            self.data2 = GamePtr::null();
+            self.data3 = GamePtr::null();
+        }
+
+        Ok(())
+    }
+
+    fn write_pointers(&self, bytes: &mut [u8], policy: &PointerPolicy) {
+        unsafe { write_field::<_, EscadraString>(self, bytes, offset_of!(TLL, string), policy) };
+
+        if let PointerPolicy::Zero = policy {
+            for offset in [
+                offset_of!(TLL, a),
+                offset_of!(TLL, b),
+                offset_of!(TLL, c),
+                offset_of!(TLL, data1),
+                offset_of!(TLL, data2),
+                offset_of!(TLL, data3),
+            ] {
+                bytes[offset..offset + 8].fill(0);
             }
         }
     }
+}
 
-    /// Recursively prints the TLL and all of its children.
-    pub fn print(&self) {
-        let mut visited = HashSet::new();
-        visited.insert(self as *const TLL as *mut TLL);
-        self.print_internal(0, &mut visited);
+impl TLL {
+    /// Views the tree this TLL is the head of as an `EscadraMap`.
+    ///
+    /// The TLL is likely a node of an MSVC `std::map`, with `a`, `b`, and `c` being the left, parent, and right pointers,
+    /// and `flag` marking the sentinel head of the tree.
+    ///
+    /// Returns `None` if this TLL is not the head.
+    pub fn as_map(&self) -> Option<TLLMap> {
+        if !self.flag {
+            return None;
+        }
+
+        let head = self as *const TLL as *mut EscadraMapNode<EscadraString, [u8; 0x20]>;
+        unsafe { Some(EscadraMap::from_head(head)) }
     }
 
-    /// Internal function to print the TLL, avoiding already visited pointers.
-    fn print_internal(&self, depth: usize, visited: &mut HashSet<*mut TLL>) {
-        let indent = "  ".repeat(depth);
+    /// Returns the index held by the TLL.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
 
-        println!("{}TLL {:p} {{", indent, self as *const TLL as *mut TLL);
-        println!("{}  a: {:p}", indent, self.a);
-        println!("{}  b: {:p}", indent, self.b);
-        println!("{}  c: {:p}", indent, self.c);
-        println!("{}  end: {}", indent, self.end);
-        println!("{}  flag: {}", indent, self.flag);
-        println!("{}  padding_1ah: {}", indent, self.padding_1ah);
-        println!("{}  index: {}", indent, self.index);
-        println!("{}  string: {:?}", indent, self.string);
-        println!("{}  unknown_40h: {}", indent, self.unknown_40h);
-        println!("{}  padding_44h: {}", indent, self.padding_44h);
-        println!("{}  data1: {:p}", indent, self.data1);
-        println!("{}  data2: {:p}", indent, self.data2);
-        println!("{}  data3: {:p}", indent, self.data3);
-        println!("{}}}", indent);
+    /// Returns the a, b, and c pointers of the TLL.
+    pub fn links(&self) -> TLLRef {
+        TLLRef {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+        }
+    }
 
-        unsafe {
-            if !self.a.is_null() && !visited.contains(&self.a) {
-                visited.insert(self.a);
-                (*self.a).print_internal(depth + 1, visited);
+    /// Returns the TLL a link of this TLL points to, or `None` if the link is null.
+    ///
+    /// The link itself is not checked, this relies on the invariant documented on `TLL`.
+    fn follow(&self, link: GamePtr<TLL>) -> Option<&TLL> {
+        // SAFETY: Safe code can only get a TLL whose links are null or point to live TLLs of the same structure:
+        // `raw::from_bytes` rejects or nulls foreign links, and TLLs with links into the game are only reachable
+        // through unsafe functions, whose callers vouch for the structure outliving the reference to this TLL.
+        unsafe { link.as_ref() }
+    }
+
+    /// Returns an iterator over this TLL and every TLL reachable from it.
+    ///
+    /// See `TLLIter` for the order in which they are visited.
+    pub fn iter(&self) -> TLLIter<'_> {
+        TLLIter {
+            stack: vec![self],
+            visited: BTreeSet::new(),
+        }
+    }
+
+    /// Returns an iterator over the string and index of this TLL and every TLL reachable from it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a string is not valid UTF-8, see `EscadraString::get_string`.
+    /// Use `iter` together with `EscadraString::get_string_lossy` for strings written by the game.
+    pub fn iter_strings(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.iter().map(|tll| (tll.string.get_string(), tll.index))
+    }
+
+    /// Finds the TLL with the given string in the tree below this TLL.
+    ///
+    /// The TLL is walked like a sorted tree: smaller strings are found through `a` and larger ones through `c`.
+    /// If this TLL is the head of the tree (`flag` is set) the search starts at its `b`, the root.
+    pub fn find(&self, key: &str) -> Option<&TLL> {
+        if self.holds(key) {
+            return Some(self);
+        }
+        self.follow(self.find_pointer(key)?)
+    }
+
+    /// Finds the TLL with the given string in the tree below this TLL, see `find`.
+    pub fn find_mut(&mut self, key: &str) -> Option<&mut TLL> {
+        if self.holds(key) {
+            return Some(self);
+        }
+        let pointer = self.find_pointer(key)?;
+        // SAFETY: The pointer was read from a link of the tree below this TLL, never derived from `self`,
+        // and `&mut self` stands for the whole structure, see `follow`.
+        unsafe { Some(&mut *pointer.as_ptr()) }
+    }
+
+    /// Returns true if this TLL is a node, not the head, holding the given string.
+    fn holds(&self, key: &str) -> bool {
+        !self.flag && self.string.get_bytes() == key.as_bytes()
+    }
+
+    /// Returns the link to the TLL with the given string in the tree below this TLL, excluding this TLL.
+    fn find_pointer(&self, key: &str) -> Option<GamePtr<TLL>> {
+        let mut pointer = match (self.flag, key.as_bytes().cmp(self.string.get_bytes())) {
+            (true, _) => self.b,
+            (false, Ordering::Less) => self.a,
+            (false, Ordering::Greater) => self.c,
+            (false, Ordering::Equal) => return None,
+        };
+
+        loop {
+            // The leaves of a tree link back to the head, which may be `self`: compare the address instead of
+            // reading it through the link.
+            if core::ptr::eq(pointer.as_ptr(), self) {
+                return None;
             }
-            if !self.b.is_null() && !visited.contains(&self.b) {
-                visited.insert(self.b);
-                (*self.b).print_internal(depth + 1, visited);
+            let tll = self.follow(pointer)?;
+            if tll.flag {
+                return None;
             }
-            if !self.c.is_null() && !visited.contains(&self.c) {
-                visited.insert(self.c);
-                (*self.c).print_internal(depth + 1, visited);
+
+            pointer = match key.as_bytes().cmp(tll.string.get_bytes()) {
+                Ordering::Less => tll.a,
+                Ordering::Greater => tll.c,
+                Ordering::Equal => return Some(pointer),
+            };
+        }
+    }
+
+    /// Explores a TLL. Returns a Hashmap of TLL pointers and their a, b, c pointers in a TLLRef.
+    ///
+    /// The traversal uses an explicit stack, so very deep structures don't overflow the stack.
+    #[cfg(feature = "std")]
+    pub fn explore(&self) -> HashMap<*const TLL, TLLRef> {
+        self.explore_limited(usize::MAX).unwrap()
+    }
+
+    /// Explores a TLL like `explore`, but stops with an error once more than `limit` TLLs were visited.
+    ///
+    /// Use this on structures read from the game, where a corrupted pointer can lead into huge graphs of garbage.
+    #[cfg(feature = "std")]
+    pub fn explore_limited(&self, limit: usize) -> Result<HashMap<*const TLL, TLLRef>, TllError> {
+        let mut result = HashMap::new();
+
+        for tll in self.iter() {
+            if result.len() == limit {
+                return Err(TllError::TooManyNodes { limit });
             }
+
+            result.insert(tll as *const TLL, tll.links());
         }
+
+        Ok(result)
     }
 }
 
@@ -143,8 +385,150 @@ impl TLL {
 mod tests {
     use super::*;
 
+    /// Allocates a TLL with the given string and index. The TLLs of a test structure are leaked.
+    fn tll(string: &str, index: u32) -> *mut TLL {
+        Box::into_raw(Box::new(TLL {
+            a: GamePtr::null(),
+            b: GamePtr::null(),
+            c: GamePtr::null(),
+            end: false,
+            flag: false,
+            padding_1ah: 0,
+            index,
+            string: EscadraString::from(string.to_string()),
+            unknown_40h: 0,
+            padding_44h: 0,
+            data1: GamePtr::null(),
+            data2: GamePtr::null(),
+            data3: GamePtr::null(),
+            _not_send: PhantomData,
+        }))
+    }
+
+    /// Builds a structure where `root` links to `left` and `right` through a and c, which link back through b.
+    fn three_tlls() -> *mut TLL {
+        let root = tll("Banana", 1);
+        let left = tll("Apple", 0);
+        let right = tll("Cherry Cherry Cherry", 2);
+
+        unsafe {
+            (*root).a = left.into();
+            (*root).c = right.into();
+            (*left).b = root.into();
+            (*right).b = root.into();
+        }
+
+        root
+    }
+
+    #[test]
+    fn iter_visits_every_tll_once() {
+        let root = unsafe { &*three_tlls() };
+
+        let strings: Vec<_> = root.iter_strings().collect();
+        assert_eq!(
+            strings,
+            [("Banana", 1), ("Apple", 0), ("Cherry Cherry Cherry", 2)]
+        );
+
+        let right = unsafe { root.c.as_ref().unwrap() };
+        assert_eq!(right.iter().count(), 3);
+        assert_eq!(right.iter().next().unwrap().index(), 2);
+    }
+
+    #[test]
+    fn find_walks_sorted_tree() {
+        let root = unsafe { &mut *three_tlls() };
+
+        assert_eq!(root.find("Apple").unwrap().index(), 0);
+        assert_eq!(root.find("Banana").unwrap().index(), 1);
+        assert_eq!(root.find("Cherry Cherry Cherry").unwrap().index(), 2);
+        assert!(root.find("Durian").is_none());
+
+        root.find_mut("Apple").unwrap().string.push_str(" pie");
+        assert!(root.find("Apple").is_none());
+        assert_eq!(root.find("Apple pie").unwrap().index(), 0);
+    }
+
+    #[test]
+    fn find_mut_own_key_returns_self() {
+        // Run under Miri to check the references: `cargo +nightly miri test find_mut_own_key`.
+        let root = three_tlls();
+        let head = tll("", 0);
+
+        unsafe {
+            (*head).flag = true;
+            (*head).b = root.into();
+            (*root).b = head.into();
+            for leaf in [(*root).a, (*root).c] {
+                (*leaf.as_ptr()).a = head.into();
+                (*leaf.as_ptr()).c = head.into();
+            }
+
+            let tree = &mut *head;
+            let banana = tree.find_mut("Banana").unwrap();
+            let pointer: *const TLL = banana;
+            let found = banana.find_mut("Banana").unwrap();
+            assert!(core::ptr::eq(found, pointer));
+            found.index = 7;
+
+            assert!(tree.find_mut("Durian").is_none());
+            assert_eq!(tree.find("Banana").unwrap().index(), 7);
+
+            for tll in [(*root).a.as_ptr(), (*root).c.as_ptr(), root, head] {
+                drop(Box::from_raw(tll));
+            }
+        }
+    }
+
+    #[test]
+    fn find_from_head_starts_at_root() {
+        let root = three_tlls();
+        let head = tll("", 0);
+
+        unsafe {
+            (*head).flag = true;
+            (*head).b = root.into();
+            (*root).b = head.into();
+
+            assert_eq!((*head).find("Cherry Cherry Cherry").unwrap().index(), 2);
+            assert!((*head).find("").is_none());
+        }
+    }
+
+    #[test]
+    fn explore_deep_structure() {
+        // A chain this long overflows the stack when explored recursively.
+        let count = 200_000;
+        let first = tll("", 0);
+        let mut last = first;
+        for index in 1..count {
+            let next = tll("", index);
+            unsafe {
+                (*last).c = next.into();
+                (*next).b = last.into();
+            }
+            last = next;
+        }
+
+        let first = unsafe { &*first };
+        assert_eq!(first.explore().len(), count as usize);
+        assert_eq!(
+            first.explore_limited(1000).unwrap_err(),
+            TllError::TooManyNodes { limit: 1000 }
+        );
+    }
+
     #[test]
     fn tll_size() {
-        assert_eq!(std::mem::size_of::<TLL>(), 0x60);
+        assert_eq!(core::mem::size_of::<TLL>(), 0x60);
+    }
+
+    #[test]
+    fn tll_matches_map_node_layout() {
+        type Node = EscadraMapNode<EscadraString, [u8; 0x20]>;
+
+        assert_eq!(core::mem::size_of::<Node>(), core::mem::size_of::<TLL>());
+        assert_eq!(offset_of!(Node, key), offset_of!(TLL, string));
     }
 }
@@ -0,0 +1,130 @@
+//! Exports TLL structures as graphs, for visualizing them with Graphviz or Mermaid.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::TLL;
+use crate::general::GamePtr;
+
+/// The links drawn for every TLL, with their Graphviz color.
+const EDGES: [(&str, &str); 3] = [("a", "blue"), ("b", "gray"), ("c", "red")];
+
+impl TLL {
+    /// Returns the links of the TLL paired with their name.
+    fn edges(&self) -> [(&'static str, GamePtr<TLL>); 3] {
+        [("a", self.a), ("b", self.b), ("c", self.c)]
+    }
+
+    /// Returns the label of the TLL: its address, string, and index.
+    fn label(&self) -> String {
+        let mut label = format!(
+            "{:p}\n{:?}\nindex: {}",
+            self as *const TLL,
+            self.string.get_string_lossy(),
+            self.index
+        );
+        if self.flag {
+            label.push_str("\nflag");
+        }
+        if self.end {
+            label.push_str("\nend");
+        }
+        label
+    }
+
+    /// Exports this TLL and every TLL reachable from it as a Graphviz DOT graph.
+    ///
+    /// Every TLL is a node labeled with its address, string, and index.
+    /// The a, b, and c links are drawn as blue, gray, and red edges.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph TLL {\n    node [shape=box, fontname=monospace];\n");
+
+        for tll in self.iter() {
+            let label = tll.label().replace('\\', "\\\\").replace('"', "\\\"");
+            let label = label.replace('\n', "\\n");
+            writeln!(dot, "    \"{:p}\" [label=\"{}\"];", tll, label).unwrap();
+        }
+
+        for tll in self.iter() {
+            for ((name, target), (_, color)) in tll.edges().into_iter().zip(EDGES) {
+                if !target.is_null() {
+                    writeln!(
+                        dot,
+                        "    \"{:p}\" -> \"{:p}\" [label=\"{}\", color={}, fontcolor={}];",
+                        tll, target, name, color, color
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Exports this TLL and every TLL reachable from it as a Mermaid flowchart.
+    ///
+    /// Uses the same labels and edge colors as `to_dot`.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+        let mut styles = Vec::new();
+
+        for tll in self.iter() {
+            let label = tll.label().replace('"', "#quot;").replace('\n', "<br>");
+            writeln!(mermaid, "    n{:p}[\"{}\"]", tll, label).unwrap();
+        }
+
+        for tll in self.iter() {
+            for ((name, target), (_, color)) in tll.edges().into_iter().zip(EDGES) {
+                if !target.is_null() {
+                    writeln!(mermaid, "    n{:p} -->|{}| n{:p}", tll, name, target).unwrap();
+                    styles.push(color);
+                }
+            }
+        }
+
+        for (index, color) in styles.into_iter().enumerate() {
+            writeln!(mermaid, "    linkStyle {} stroke:{}", index, color).unwrap();
+        }
+
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::TllData;
+    use alloc::collections::BTreeMap;
+
+    #[test]
+    fn dot_has_every_tll_and_link() {
+        let map = BTreeMap::from([
+            ("Apple".to_string(), TllData::default()),
+            ("Ban\"ana".to_string(), TllData::default()),
+        ]);
+        let tll = TLL::from_map(&map);
+
+        let dot = tll.to_dot();
+        assert!(dot.starts_with("digraph TLL {"));
+        assert!(dot.contains(r#"\"Apple\""#));
+        assert!(dot.contains(r#"\"Ban\\\"ana\""#));
+        // Three TLLs, each with three links.
+        assert_eq!(dot.matches(" -> ").count(), 9);
+        assert_eq!(dot.matches(r#"[label="c", color=red"#).count(), 3);
+    }
+
+    #[test]
+    fn mermaid_has_every_tll_and_link() {
+        let map = BTreeMap::from([("Apple".to_string(), TllData::default())]);
+        let tll = TLL::from_map(&map);
+
+        let mermaid = tll.to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD"));
+        assert!(mermaid.contains("#quot;Apple#quot;"));
+        assert_eq!(mermaid.matches(" -->|").count(), 6);
+        assert_eq!(mermaid.matches("linkStyle").count(), 6);
+    }
+}
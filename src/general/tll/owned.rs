@@ -0,0 +1,241 @@
+//! A TLL tree built and owned by Rust.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use super::{TllData, TLL};
+use crate::general::arena::GameArena;
+
+/// The head of a TLL tree that was allocated by this library.
+///
+/// Every TLL in the tree is freed when the `OwnedTll` is dropped.
+/// Use `into_raw` to hand the tree over to the game instead.
+///
+/// Derefs to the head TLL, so `insert`, `remove`, and `find` can be used directly.
+pub struct OwnedTll {
+    head: NonNull<TLL>,
+}
+
+impl OwnedTll {
+    /// Creates an empty tree, only made of its head.
+    pub fn new() -> Self {
+        Self {
+            head: NonNull::new(TLL::allocate_head()).unwrap(),
+        }
+    }
+
+    /// Takes ownership of a tree allocated by the game, or returned by `into_raw`.
+    ///
+    /// # Safety
+    ///
+    /// `head` must be the head of a valid tree that is no longer used by anyone else,
+    /// and every TLL must have been allocated with the allocator set by `set_allocator`.
+    pub unsafe fn from_raw(head: *mut TLL) -> Option<Self> {
+        let head = NonNull::new(head)?;
+        if !head.as_ref().flag {
+            return None;
+        }
+
+        Some(Self { head })
+    }
+
+    /// Returns the head of the tree without giving up ownership.
+    pub fn as_ptr(&self) -> *mut TLL {
+        self.head.as_ptr()
+    }
+
+    /// Gives up ownership of the tree, returning its head.
+    ///
+    /// The tree is no longer freed by this library.
+    pub fn into_raw(self) -> *mut TLL {
+        let head = self.head.as_ptr();
+        core::mem::forget(self);
+        head
+    }
+}
+
+impl TLL {
+    /// Builds a balanced tree holding every entry of the map.
+    pub fn from_map(map: &BTreeMap<String, TllData>) -> OwnedTll {
+        let mut tll = OwnedTll::new();
+        for (key, data) in map {
+            tll.insert(key, *data);
+        }
+        tll
+    }
+
+    /// Builds a balanced tree holding every entry of the map, with every TLL allocated in `arena`.
+    pub fn from_map_in<'a>(map: &BTreeMap<String, TllData>, arena: &'a GameArena) -> ArenaTll<'a> {
+        let mut tll = TLL::head_in(arena);
+        for (key, data) in map {
+            tll.insert(key, *data);
+        }
+        tll
+    }
+}
+
+/// The head of a TLL tree allocated in a `GameArena`, returned by `TLL::head_in`.
+///
+/// The TLLs of an arena can't be freed one by one, so unlike an `OwnedTll` the tree can only grow:
+/// `insert` allocates in the same arena, and `remove` is unavailable, as the head is never handed out mutably.
+/// Derefs to the head TLL, so `find` and `iter` can be used directly.
+///
+/// ```
+/// use highfleet::general::{GameArena, TllData, TLL};
+///
+/// let arena = GameArena::new();
+/// let mut tll = TLL::head_in(&arena);
+/// tll.insert("Apple", TllData::default());
+/// assert!(tll.find("Apple").is_some());
+/// ```
+///
+/// ```compile_fail
+/// use highfleet::general::{GameArena, TllData, TLL};
+///
+/// let arena = GameArena::new();
+/// let mut tll = TLL::head_in(&arena);
+/// tll.insert("Apple", TllData::default());
+/// tll.remove("Apple");
+/// ```
+pub struct ArenaTll<'a> {
+    head: NonNull<TLL>,
+    arena: &'a GameArena,
+}
+
+impl<'a> ArenaTll<'a> {
+    /// # Safety
+    ///
+    /// `head` must be the head of a tree allocated in `arena`, only reachable through the new `ArenaTll`.
+    pub(super) unsafe fn new(head: *mut TLL, arena: &'a GameArena) -> Self {
+        Self {
+            head: NonNull::new(head).unwrap(),
+            arena,
+        }
+    }
+
+    /// Inserts a new TLL allocated in the arena of the tree, see `TLL::insert`.
+    ///
+    /// Returns the new TLL, or `None` if a TLL with the same string already exists.
+    pub fn insert(&mut self, key: &str, data: TllData) -> Option<&mut TLL> {
+        unsafe { self.head.as_mut().insert_in(self.arena, key, data) }
+    }
+
+    /// Returns the head of the tree, for example to hand it over to the game.
+    ///
+    /// The game must never free a TLL of the tree, see `GameArena`.
+    pub fn as_ptr(&self) -> *mut TLL {
+        self.head.as_ptr()
+    }
+}
+
+impl Deref for ArenaTll<'_> {
+    type Target = TLL;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.head.as_ref() }
+    }
+}
+
+impl Default for OwnedTll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for OwnedTll {
+    type Target = TLL;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.head.as_ref() }
+    }
+}
+
+impl DerefMut for OwnedTll {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.head.as_mut() }
+    }
+}
+
+impl Drop for OwnedTll {
+    fn drop(&mut self) {
+        let head = self.head.as_ptr();
+        let mut pending = vec![unsafe { (*head).b.as_ptr() }];
+
+        while let Some(tll) = pending.pop() {
+            unsafe {
+                if (*tll).flag {
+                    continue;
+                }
+                pending.push((*tll).a.as_ptr());
+                pending.push((*tll).c.as_ptr());
+                TLL::free(tll);
+            }
+        }
+
+        unsafe { TLL::free(head) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fruit_map() -> BTreeMap<String, TllData> {
+        ["Apple", "Banana", "Cherry Cherry Cherry", "Durian"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let data = TllData {
+                    index: index as u32,
+                    ..Default::default()
+                };
+                (key.to_string(), data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_map_holds_every_entry() {
+        let map = fruit_map();
+        let tll = TLL::from_map(&map);
+
+        for (key, data) in &map {
+            assert_eq!(tll.find(key).unwrap().data(), *data);
+        }
+        assert_eq!(tll.as_map().unwrap().len(), map.len());
+    }
+
+    #[test]
+    fn from_map_in_arena() {
+        let map = fruit_map();
+        let arena = GameArena::new();
+        let mut tll = TLL::from_map_in(&map, &arena);
+
+        for (key, data) in &map {
+            assert_eq!(tll.find(key).unwrap().data(), *data);
+        }
+        assert_eq!(tll.as_map().unwrap().len(), map.len());
+        assert!(tll.insert("Apple", TllData::default()).is_none());
+        assert_eq!(arena.block_count(), 1);
+    }
+
+    #[test]
+    fn into_raw_then_from_raw() {
+        let tll = TLL::from_map(&fruit_map());
+        let head = tll.into_raw();
+
+        let tll = unsafe { OwnedTll::from_raw(head).unwrap() };
+        assert_eq!(tll.find("Durian").unwrap().index(), 3);
+    }
+
+    #[test]
+    fn from_raw_rejects_non_head() {
+        let tll = TLL::from_map(&fruit_map());
+        let banana = tll.find("Banana").unwrap() as *const TLL as *mut TLL;
+
+        assert!(unsafe { OwnedTll::from_raw(banana) }.is_none());
+    }
+}
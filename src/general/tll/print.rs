@@ -0,0 +1,200 @@
+//! Printing TLL structures for debugging.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use super::{TllError, TLL};
+
+/// Options controlling what `TLL::print_to` prints.
+#[derive(Debug, Clone)]
+pub struct PrintOptions {
+    /// TLLs deeper than this are neither printed nor explored. The starting TLL has a depth of 0.
+    pub max_depth: Option<usize>,
+    /// Only TLLs whose string contains this pattern are printed. The others are still explored.
+    pub pattern: Option<String>,
+    /// Leaves out the padding fields.
+    pub hide_padding: bool,
+    /// Stops with a `TllError::TooManyNodes` error once more than this many TLLs were explored.
+    pub max_nodes: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            pattern: None,
+            hide_padding: false,
+            max_nodes: usize::MAX,
+        }
+    }
+}
+
+impl TLL {
+    /// Prints the TLL and all of its children to stdout.
+    pub fn print(&self) {
+        self.print_to(&mut io::stdout(), &PrintOptions::default())
+            .unwrap()
+    }
+
+    /// Prints the TLL and all of its children to stdout like `print`, but stops with an error once more than `limit` TLLs were printed.
+    pub fn print_limited(&self, limit: usize) -> Result<(), TllError> {
+        let options = PrintOptions {
+            max_nodes: limit,
+            ..Default::default()
+        };
+
+        self.print_to(&mut io::stdout(), &options)
+            .map_err(|error| match error.into_inner() {
+                Some(error) => *error.downcast::<TllError>().unwrap(),
+                None => panic!("failed printing to stdout"),
+            })
+    }
+
+    /// Prints the TLL and all of its children to the given writer, for example a log file.
+    ///
+    /// Children are printed below their parent, indented by their depth.
+    /// Exceeding `PrintOptions::max_nodes` returns an `io::Error` wrapping a `TllError::TooManyNodes`.
+    pub fn print_to(&self, writer: &mut dyn Write, options: &PrintOptions) -> io::Result<()> {
+        let mut visited = HashSet::new();
+        visited.insert(self as *const TLL);
+
+        let mut pending = vec![(self, 0)];
+        let mut explored = 0;
+
+        while let Some((tll, depth)) = pending.pop() {
+            if explored == options.max_nodes {
+                return Err(io::Error::other(TllError::TooManyNodes {
+                    limit: options.max_nodes,
+                }));
+            }
+            explored += 1;
+
+            let matches = match &options.pattern {
+                Some(pattern) => tll.string.get_string_lossy().contains(pattern.as_str()),
+                None => true,
+            };
+            if matches {
+                tll.print_single(writer, depth, options)?;
+            }
+
+            if options
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth)
+            {
+                continue;
+            }
+
+            for next in [tll.c, tll.b, tll.a] {
+                if let Some(next) = tll.follow(next) {
+                    if visited.insert(next as *const TLL) {
+                        pending.push((next, depth + 1));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Internal function to print a single TLL, indented by its depth.
+    fn print_single(
+        &self,
+        writer: &mut dyn Write,
+        depth: usize,
+        options: &PrintOptions,
+    ) -> io::Result<()> {
+        let indent = "  ".repeat(depth);
+
+        writeln!(
+            writer,
+            "{}TLL {:p} {{",
+            indent, self as *const TLL as *mut TLL
+        )?;
+        writeln!(writer, "{}  a: {:p}", indent, self.a)?;
+        writeln!(writer, "{}  b: {:p}", indent, self.b)?;
+        writeln!(writer, "{}  c: {:p}", indent, self.c)?;
+        writeln!(writer, "{}  end: {}", indent, self.end)?;
+        writeln!(writer, "{}  flag: {}", indent, self.flag)?;
+        if !options.hide_padding {
+            writeln!(writer, "{}  padding_1ah: {}", indent, self.padding_1ah)?;
+        }
+        writeln!(writer, "{}  index: {}", indent, self.index)?;
+        writeln!(writer, "{}  string: {:?}", indent, self.string)?;
+        writeln!(writer, "{}  unknown_40h: {}", indent, self.unknown_40h)?;
+        if !options.hide_padding {
+            writeln!(writer, "{}  padding_44h: {}", indent, self.padding_44h)?;
+        }
+        writeln!(writer, "{}  data1: {:p}", indent, self.data1)?;
+        writeln!(writer, "{}  data2: {:p}", indent, self.data2)?;
+        writeln!(writer, "{}  data3: {:p}", indent, self.data3)?;
+        writeln!(writer, "{}}}", indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::{OwnedTll, TllData};
+
+    fn tree() -> OwnedTll {
+        let mut tll = OwnedTll::new();
+        for key in ["Apple", "Banana", "Cherry", "Durian", "Elderberry"] {
+            tll.insert(key, TllData::default());
+        }
+        tll
+    }
+
+    fn print(tll: &TLL, options: &PrintOptions) -> String {
+        let mut output = Vec::new();
+        tll.print_to(&mut output, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn prints_every_tll() {
+        let output = print(&tree(), &PrintOptions::default());
+
+        assert_eq!(output.matches("TLL 0x").count(), 6);
+        assert!(output.contains("padding_1ah"));
+    }
+
+    #[test]
+    fn filters_by_pattern_and_hides_padding() {
+        let options = PrintOptions {
+            pattern: Some("an".to_string()),
+            hide_padding: true,
+            ..Default::default()
+        };
+        let output = print(&tree(), &options);
+
+        assert_eq!(output.matches("TLL 0x").count(), 2);
+        assert!(output.contains("\"Banana\""));
+        assert!(output.contains("\"Durian\""));
+        assert!(!output.contains("padding"));
+    }
+
+    #[test]
+    fn stops_at_max_depth() {
+        let options = PrintOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        };
+        let output = print(&tree(), &options);
+
+        // The head, and the smallest TLL, root, and largest TLL it links to.
+        assert_eq!(output.matches("TLL 0x").count(), 4);
+        assert!(!output.contains("\n    TLL"));
+    }
+
+    #[test]
+    fn max_nodes_is_an_error() {
+        let options = PrintOptions {
+            max_nodes: 3,
+            ..Default::default()
+        };
+
+        let error = tree().print_to(&mut io::sink(), &options).unwrap_err();
+        let error = error.into_inner().unwrap().downcast::<TllError>().unwrap();
+        assert_eq!(*error, TllError::TooManyNodes { limit: 3 });
+    }
+}
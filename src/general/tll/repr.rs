@@ -0,0 +1,182 @@
+//! A portable representation of TLL trees, used to serialize them.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{OwnedTll, TllData, TLL};
+
+/// A TLL and the TLLs below it, without any pointers.
+///
+/// The children of a TLL are the TLLs reached through `a` and `c`.
+/// The children of the head of a tree (`flag` set) is the root, reached through `b`.
+///
+/// Pointers held in the data of a TLL can't be carried over, so they are null when rebuilding the tree.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TllNodeRepr {
+    /// String held by the TLL.
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub string: String,
+    /// The index held by the TLL.
+    pub index: u32,
+    /// Set for the head of a tree.
+    pub flag: bool,
+    /// Value with unknown purpose.
+    #[serde(default)]
+    pub unknown_40h: u32,
+    /// The TLLs below this TLL.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TllNodeRepr>,
+}
+
+impl TllNodeRepr {
+    /// Captures the given TLL and every TLL below it.
+    pub fn from_tll(tll: &TLL) -> Self {
+        let mut visited = BTreeSet::new();
+        Self::from_tll_internal(tll, &mut visited)
+    }
+
+    fn from_tll_internal(tll: &TLL, visited: &mut BTreeSet<*const TLL>) -> Self {
+        visited.insert(tll);
+
+        let links = if tll.flag {
+            vec![tll.b]
+        } else {
+            vec![tll.a, tll.c]
+        };
+
+        let mut children = Vec::new();
+        for link in links {
+            let Some(child) = tll.follow(link) else {
+                continue;
+            };
+            if !child.flag && !visited.contains(&(child as *const TLL)) {
+                children.push(Self::from_tll_internal(child, visited));
+            }
+        }
+
+        Self {
+            string: tll.string.get_string_lossy().into_owned(),
+            index: tll.index,
+            flag: tll.flag,
+            unknown_40h: tll.unknown_40h,
+            children,
+        }
+    }
+
+    /// Collects the string and data of every TLL in the representation, except for the head.
+    pub fn to_map(&self) -> BTreeMap<String, TllData> {
+        let mut map = BTreeMap::new();
+        let mut pending = vec![self];
+
+        while let Some(node) = pending.pop() {
+            if !node.flag {
+                let data = TllData {
+                    index: node.index,
+                    unknown_40h: node.unknown_40h,
+                    ..Default::default()
+                };
+                map.insert(node.string.clone(), data);
+            }
+            pending.extend(&node.children);
+        }
+
+        map
+    }
+
+    /// Rebuilds a balanced tree holding every TLL in the representation, see `TLL::from_map`.
+    pub fn build(&self) -> OwnedTll {
+        TLL::from_map(&self.to_map())
+    }
+}
+
+impl Serialize for TLL {
+    /// Serializes the TLL and every TLL below it as a `TllNodeRepr`.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TllNodeRepr::from_tll(self).serialize(serializer)
+    }
+}
+
+impl Serialize for OwnedTll {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OwnedTll {
+    /// Deserializes a `TllNodeRepr` and rebuilds the tree from it.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        TllNodeRepr::deserialize(deserializer).map(|repr| repr.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fruit_tll() -> OwnedTll {
+        let map = [
+            "Apple",
+            "Banana",
+            "Cherry Cherry Cherry",
+            "Durian",
+            "Elderberry",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let data = TllData {
+                index: index as u32,
+                unknown_40h: 10,
+                ..Default::default()
+            };
+            (key.to_string(), data)
+        })
+        .collect();
+        TLL::from_map(&map)
+    }
+
+    #[test]
+    fn repr_holds_every_tll() {
+        let tll = fruit_tll();
+        let repr = TllNodeRepr::from_tll(&tll);
+
+        assert!(repr.flag);
+        assert_eq!(repr.children.len(), 1);
+        assert_eq!(repr.to_map().len(), 5);
+        assert_eq!(repr.to_map()["Durian"].index, 3);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let tll = fruit_tll();
+
+        let json = serde_json::to_string(&tll).unwrap();
+        let result: OwnedTll = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            TllNodeRepr::from_tll(&result).to_map(),
+            TllNodeRepr::from_tll(&tll).to_map()
+        );
+        assert_eq!(result.find("Elderberry").unwrap().data().unknown_40h, 10);
+    }
+
+    #[test]
+    fn deserialize_edited_json() {
+        let json = r#"{
+            "string": "", "index": 0, "flag": true,
+            "children": [
+                { "string": "Banana", "index": 1, "flag": false, "children": [
+                    { "string": "Apple", "index": 0, "flag": false }
+                ] }
+            ]
+        }"#;
+
+        let tll: OwnedTll = serde_json::from_str(json).unwrap();
+        assert_eq!(tll.find("Apple").unwrap().index(), 0);
+        assert_eq!(tll.find("Banana").unwrap().index(), 1);
+    }
+}
@@ -0,0 +1,586 @@
+//! Inserting and removing TLLs while keeping the tree balanced.
+//!
+//! The TLL is treated as a node of an MSVC `std::map` red-black tree:
+//! `a`, `b`, and `c` are the left, parent, and right pointers, `end` is set for black nodes, and `flag` marks the sentinel head.
+//! The head's `a` is the smallest TLL, its `b` the root, and its `c` the largest TLL.
+//! Every missing child points back to the head.
+
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+use super::{ArenaTll, TllData, TLL};
+use crate::general::allocator::allocator;
+use crate::general::arena::GameArena;
+use crate::general::{EscadraString, GamePtr};
+
+impl TLL {
+    /// Creates a TLL with the game's layout, all of its pointers pointing to `link`.
+    fn node(key: &str, data: TllData, link: *mut TLL) -> TLL {
+        let mut string = EscadraString::new();
+        string.push_str(key);
+
+        TLL {
+            a: link.into(),
+            b: link.into(),
+            c: link.into(),
+            end: false,
+            flag: false,
+            padding_1ah: 0,
+            index: data.index,
+            string,
+            unknown_40h: data.unknown_40h,
+            padding_44h: 0,
+            data1: data.data1,
+            data2: data.data2,
+            data3: data.data3,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Allocates a TLL with the game's layout, using the allocator set by `set_allocator`.
+    ///
+    /// All of its pointers point to `link`.
+    pub(super) fn allocate(key: &str, data: TllData, link: *mut TLL) -> *mut TLL {
+        unsafe {
+            let pointer = allocator().malloc(size_of::<TLL>()) as *mut TLL;
+            assert!(!pointer.is_null());
+            pointer.write(TLL::node(key, data, link));
+            pointer
+        }
+    }
+
+    /// Allocates the head of an empty tree.
+    pub(super) fn allocate_head() -> *mut TLL {
+        TLL::make_head(TLL::allocate("", TllData::default(), core::ptr::null_mut()))
+    }
+
+    /// Allocates the head of an empty tree in `arena`.
+    ///
+    /// The tree lives as long as the arena, unless the arena is leaked to hand the tree over to the game.
+    pub fn head_in(arena: &GameArena) -> ArenaTll<'_> {
+        let head = arena.alloc(TLL::node("", TllData::default(), core::ptr::null_mut()));
+        unsafe { ArenaTll::new(TLL::make_head(head), arena) }
+    }
+
+    fn make_head(head: *mut TLL) -> *mut TLL {
+        unsafe {
+            (*head).a = head.into();
+            (*head).b = head.into();
+            (*head).c = head.into();
+            (*head).end = true;
+            (*head).flag = true;
+        }
+
+        head
+    }
+
+    /// Drops and frees a TLL allocated by `allocate`, or by the game.
+    ///
+    /// # Safety
+    ///
+    /// The TLL must not be linked to anymore.
+    pub(super) unsafe fn free(pointer: *mut TLL) {
+        core::ptr::drop_in_place(pointer);
+        allocator().free(pointer as *mut u8);
+    }
+
+    /// Returns true if this TLL is the head of a tree whose links can be followed.
+    ///
+    /// A head read with `raw::from_bytes` has `flag` set but null links, as they can't be kept.
+    fn is_initialised_head(&self) -> bool {
+        let this = GamePtr::new(self as *const TLL as *mut TLL);
+        self.flag
+            && !self.a.is_null()
+            && !self.b.is_null()
+            && !self.c.is_null()
+            && (self.b != this || (self.a == this && self.c == this))
+    }
+
+    /// Returns the data held by the TLL.
+    pub fn data(&self) -> TllData {
+        TllData {
+            index: self.index,
+            unknown_40h: self.unknown_40h,
+            data1: self.data1,
+            data2: self.data2,
+            data3: self.data3,
+        }
+    }
+
+    /// Inserts a new TLL with the given string and data into the tree this TLL is the head of,
+    /// rebalancing the tree like MSVC's `std::map` does.
+    ///
+    /// Returns the new TLL, or `None` if a TLL with the same string already exists, in which case the tree is left unchanged.
+    ///
+    /// The game may keep the number of TLLs in the tree next to the head, which has to be updated by the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this TLL is not the head of the tree (`flag` is not set), or if its links are null.
+    pub fn insert(&mut self, key: &str, data: TllData) -> Option<&mut TLL> {
+        self.insert_with(key, || TLL::allocate(key, data, core::ptr::null_mut()))
+    }
+
+    /// Inserts a new TLL like `insert`, allocating it in `arena` instead.
+    ///
+    /// # Safety
+    ///
+    /// The tree must have been created by `head_in` with the same arena, as a TLL of the arena can't be freed.
+    /// Only `ArenaTll` calls this, which never hands out the head mutably, so nothing can `remove` from the tree.
+    pub(super) unsafe fn insert_in(
+        &mut self,
+        arena: &GameArena,
+        key: &str,
+        data: TllData,
+    ) -> Option<&mut TLL> {
+        self.insert_with(key, || {
+            arena.alloc(TLL::node(key, data, core::ptr::null_mut())) as *mut TLL
+        })
+    }
+
+    /// Inserts the TLL returned by `allocate`, which is only called if `key` is not in the tree yet.
+    fn insert_with(&mut self, key: &str, allocate: impl FnOnce() -> *mut TLL) -> Option<&mut TLL> {
+        assert!(
+            self.is_initialised_head(),
+            "insert must be called on the head of an initialised tree"
+        );
+        let head = self as *mut TLL;
+
+        unsafe {
+            let mut parent = head;
+            let mut node = (*head).b.as_ptr();
+            let mut add_left = true;
+            while !(*node).flag {
+                parent = node;
+                match key.as_bytes().cmp((*node).string.get_bytes()) {
+                    Ordering::Less => {
+                        add_left = true;
+                        node = (*node).a.as_ptr();
+                    }
+                    Ordering::Greater => {
+                        add_left = false;
+                        node = (*node).c.as_ptr();
+                    }
+                    Ordering::Equal => return None,
+                }
+            }
+
+            let new = allocate();
+            (*new).a = head.into();
+            (*new).b = parent.into();
+            (*new).c = head.into();
+
+            if parent == head {
+                (*head).a = new.into();
+                (*head).b = new.into();
+                (*head).c = new.into();
+            } else if add_left {
+                (*parent).a = new.into();
+                if parent == (*head).a.as_ptr() {
+                    (*head).a = new.into();
+                }
+            } else {
+                (*parent).c = new.into();
+                if parent == (*head).c.as_ptr() {
+                    (*head).c = new.into();
+                }
+            }
+
+            let mut node = new;
+            while is_red((*node).b.as_ptr()) {
+                let parent = (*node).b.as_ptr();
+                let grandparent = (*parent).b.as_ptr();
+
+                if parent == (*grandparent).a.as_ptr() {
+                    let uncle = (*grandparent).c.as_ptr();
+                    if is_red(uncle) {
+                        (*parent).end = true;
+                        (*uncle).end = true;
+                        (*grandparent).end = false;
+                        node = grandparent;
+                    } else {
+                        if node == (*parent).c.as_ptr() {
+                            node = parent;
+                            rotate_left(head, node);
+                        }
+                        (*(*node).b.as_ptr()).end = true;
+                        (*(*(*node).b.as_ptr()).b.as_ptr()).end = false;
+                        rotate_right(head, (*(*node).b.as_ptr()).b.as_ptr());
+                    }
+                } else {
+                    let uncle = (*grandparent).a.as_ptr();
+                    if is_red(uncle) {
+                        (*parent).end = true;
+                        (*uncle).end = true;
+                        (*grandparent).end = false;
+                        node = grandparent;
+                    } else {
+                        if node == (*parent).a.as_ptr() {
+                            node = parent;
+                            rotate_right(head, node);
+                        }
+                        (*(*node).b.as_ptr()).end = true;
+                        (*(*(*node).b.as_ptr()).b.as_ptr()).end = false;
+                        rotate_left(head, (*(*node).b.as_ptr()).b.as_ptr());
+                    }
+                }
+            }
+            (*(*head).b.as_ptr()).end = true;
+
+            Some(&mut *new)
+        }
+    }
+
+    /// Removes the TLL with the given string from the tree this TLL is the head of,
+    /// rebalancing the tree like MSVC's `std::map` does.
+    ///
+    /// The removed TLL is freed with the allocator set by `set_allocator`, and its data is returned.
+    /// Returns `None` if no TLL has the given string.
+    ///
+    /// The game may keep the number of TLLs in the tree next to the head, which has to be updated by the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this TLL is not the head of the tree (`flag` is not set), or if its links are null.
+    pub fn remove(&mut self, key: &str) -> Option<TllData> {
+        assert!(
+            self.is_initialised_head(),
+            "remove must be called on the head of an initialised tree"
+        );
+        let head = self as *mut TLL;
+        let erased = self.find_pointer(key)?.as_ptr();
+
+        unsafe {
+            let mut fix;
+            let mut fix_parent;
+
+            if (*(*erased).a.as_ptr()).flag || (*(*erased).c.as_ptr()).flag {
+                // At most one child, which takes the place of the erased TLL.
+                fix = if (*(*erased).a.as_ptr()).flag {
+                    (*erased).c.as_ptr()
+                } else {
+                    (*erased).a.as_ptr()
+                };
+                fix_parent = (*erased).b.as_ptr();
+
+                if !(*fix).flag {
+                    (*fix).b = fix_parent.into();
+                }
+
+                if (*head).b.as_ptr() == erased {
+                    (*head).b = fix.into();
+                } else if (*fix_parent).a.as_ptr() == erased {
+                    (*fix_parent).a = fix.into();
+                } else {
+                    (*fix_parent).c = fix.into();
+                }
+
+                if (*head).a.as_ptr() == erased {
+                    (*head).a = if (*fix).flag {
+                        fix_parent.into()
+                    } else {
+                        leftmost(fix).into()
+                    };
+                }
+                if (*head).c.as_ptr() == erased {
+                    (*head).c = if (*fix).flag {
+                        fix_parent.into()
+                    } else {
+                        rightmost(fix).into()
+                    };
+                }
+            } else {
+                // Two children, the successor takes the place of the erased TLL.
+                let successor = leftmost((*erased).c.as_ptr());
+                fix = (*successor).c.as_ptr();
+
+                (*(*erased).a.as_ptr()).b = successor.into();
+                (*successor).a = (*erased).a;
+
+                if successor == (*erased).c.as_ptr() {
+                    fix_parent = successor;
+                } else {
+                    fix_parent = (*successor).b.as_ptr();
+                    if !(*fix).flag {
+                        (*fix).b = fix_parent.into();
+                    }
+                    (*fix_parent).a = fix.into();
+                    (*successor).c = (*erased).c;
+                    (*(*erased).c.as_ptr()).b = successor.into();
+                }
+
+                if (*head).b.as_ptr() == erased {
+                    (*head).b = successor.into();
+                } else if (*(*erased).b.as_ptr()).a.as_ptr() == erased {
+                    (*(*erased).b.as_ptr()).a = successor.into();
+                } else {
+                    (*(*erased).b.as_ptr()).c = successor.into();
+                }
+
+                (*successor).b = (*erased).b;
+                core::mem::swap(&mut (*successor).end, &mut (*erased).end);
+            }
+
+            if is_black(erased) {
+                while fix != (*head).b.as_ptr() && is_black(fix) {
+                    if fix == (*fix_parent).a.as_ptr() {
+                        let mut sibling = (*fix_parent).c.as_ptr();
+                        if is_red(sibling) {
+                            (*sibling).end = true;
+                            (*fix_parent).end = false;
+                            rotate_left(head, fix_parent);
+                            sibling = (*fix_parent).c.as_ptr();
+                        }
+
+                        if (*sibling).flag {
+                            fix = fix_parent;
+                        } else if is_black((*sibling).a.as_ptr()) && is_black((*sibling).c.as_ptr())
+                        {
+                            (*sibling).end = false;
+                            fix = fix_parent;
+                        } else {
+                            if is_black((*sibling).c.as_ptr()) {
+                                (*(*sibling).a.as_ptr()).end = true;
+                                (*sibling).end = false;
+                                rotate_right(head, sibling);
+                                sibling = (*fix_parent).c.as_ptr();
+                            }
+
+                            (*sibling).end = (*fix_parent).end;
+                            (*fix_parent).end = true;
+                            (*(*sibling).c.as_ptr()).end = true;
+                            rotate_left(head, fix_parent);
+                            break;
+                        }
+                    } else {
+                        let mut sibling = (*fix_parent).a.as_ptr();
+                        if is_red(sibling) {
+                            (*sibling).end = true;
+                            (*fix_parent).end = false;
+                            rotate_right(head, fix_parent);
+                            sibling = (*fix_parent).a.as_ptr();
+                        }
+
+                        if (*sibling).flag {
+                            fix = fix_parent;
+                        } else if is_black((*sibling).a.as_ptr()) && is_black((*sibling).c.as_ptr())
+                        {
+                            (*sibling).end = false;
+                            fix = fix_parent;
+                        } else {
+                            if is_black((*sibling).a.as_ptr()) {
+                                (*(*sibling).c.as_ptr()).end = true;
+                                (*sibling).end = false;
+                                rotate_left(head, sibling);
+                                sibling = (*fix_parent).a.as_ptr();
+                            }
+
+                            (*sibling).end = (*fix_parent).end;
+                            (*fix_parent).end = true;
+                            (*(*sibling).a.as_ptr()).end = true;
+                            rotate_right(head, fix_parent);
+                            break;
+                        }
+                    }
+
+                    fix_parent = (*fix).b.as_ptr();
+                }
+
+                (*fix).end = true;
+            }
+
+            let data = (*erased).data();
+            TLL::free(erased);
+            Some(data)
+        }
+    }
+}
+
+/// Returns true if the TLL is red. The head always counts as black.
+unsafe fn is_red(tll: *mut TLL) -> bool {
+    !(*tll).flag && !(*tll).end
+}
+
+/// Returns true if the TLL is black. The head always counts as black.
+unsafe fn is_black(tll: *mut TLL) -> bool {
+    !is_red(tll)
+}
+
+/// Returns the smallest TLL below the given TLL.
+unsafe fn leftmost(mut tll: *mut TLL) -> *mut TLL {
+    while !(*(*tll).a.as_ptr()).flag {
+        tll = (*tll).a.as_ptr();
+    }
+    tll
+}
+
+/// Returns the largest TLL below the given TLL.
+unsafe fn rightmost(mut tll: *mut TLL) -> *mut TLL {
+    while !(*(*tll).c.as_ptr()).flag {
+        tll = (*tll).c.as_ptr();
+    }
+    tll
+}
+
+/// Rotates the right child of `tll` into its place.
+unsafe fn rotate_left(head: *mut TLL, tll: *mut TLL) {
+    let child = (*tll).c.as_ptr();
+    (*tll).c = (*child).a;
+    if !(*(*child).a.as_ptr()).flag {
+        (*(*child).a.as_ptr()).b = tll.into();
+    }
+    (*child).b = (*tll).b;
+
+    if tll == (*head).b.as_ptr() {
+        (*head).b = child.into();
+    } else if tll == (*(*tll).b.as_ptr()).a.as_ptr() {
+        (*(*tll).b.as_ptr()).a = child.into();
+    } else {
+        (*(*tll).b.as_ptr()).c = child.into();
+    }
+
+    (*child).a = tll.into();
+    (*tll).b = child.into();
+}
+
+/// Rotates the left child of `tll` into its place.
+unsafe fn rotate_right(head: *mut TLL, tll: *mut TLL) {
+    let child = (*tll).a.as_ptr();
+    (*tll).a = (*child).c;
+    if !(*(*child).c.as_ptr()).flag {
+        (*(*child).c.as_ptr()).b = tll.into();
+    }
+    (*child).b = (*tll).b;
+
+    if tll == (*head).b.as_ptr() {
+        (*head).b = child.into();
+    } else if tll == (*(*tll).b.as_ptr()).c.as_ptr() {
+        (*(*tll).b.as_ptr()).c = child.into();
+    } else {
+        (*(*tll).b.as_ptr()).a = child.into();
+    }
+
+    (*child).c = tll.into();
+    (*tll).b = child.into();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Allocates an empty tree, only made of the head.
+    fn empty_tree() -> &'static mut TLL {
+        unsafe { &mut *TLL::allocate_head() }
+    }
+
+    /// Checks the red-black invariants of the subtree, returning its black height.
+    unsafe fn check_subtree(tll: *mut TLL, head: *mut TLL) -> usize {
+        if (*tll).flag {
+            assert_eq!(tll, head);
+            return 1;
+        }
+
+        for child in [(*tll).a.as_ptr(), (*tll).c.as_ptr()] {
+            if !(*child).flag {
+                assert_eq!(
+                    (*child).b.as_ptr(),
+                    tll,
+                    "child doesn't link back to its parent"
+                );
+                assert!(!(is_red(tll) && is_red(child)), "red TLL has a red child");
+            }
+        }
+
+        let left = check_subtree((*tll).a.as_ptr(), head);
+        let right = check_subtree((*tll).c.as_ptr(), head);
+        assert_eq!(left, right, "black heights differ");
+
+        left + is_black(tll) as usize
+    }
+
+    /// Checks the tree is a valid red-black tree and returns its strings in order.
+    fn check_tree(head: &TLL) -> Vec<String> {
+        let head_pointer = head as *const TLL as *mut TLL;
+
+        unsafe {
+            let root = head.b.as_ptr();
+            if !(*root).flag {
+                assert!(is_black(root));
+                assert_eq!((*root).b.as_ptr(), head_pointer);
+                assert_eq!(head.a.as_ptr(), leftmost(root));
+                assert_eq!(head.c.as_ptr(), rightmost(root));
+            }
+            check_subtree(root, head_pointer);
+        }
+
+        head.as_map()
+            .unwrap()
+            .keys()
+            .map(|key| key.to_string())
+            .collect()
+    }
+
+    fn key(i: u32) -> String {
+        format!("key {:03}", (i * 37) % 100)
+    }
+
+    #[test]
+    fn insert_keeps_tree_balanced() {
+        let head = empty_tree();
+
+        for i in 0..100 {
+            let data = TllData {
+                index: i,
+                ..Default::default()
+            };
+            assert_eq!(head.insert(&key(i), data).unwrap().index(), i);
+            check_tree(head);
+        }
+
+        let mut expected: Vec<_> = (0..100).map(key).collect();
+        expected.sort();
+        assert_eq!(check_tree(head), expected);
+        assert_eq!(head.find(&key(42)).unwrap().index(), 42);
+    }
+
+    #[test]
+    fn insert_existing_key_is_rejected() {
+        let head = empty_tree();
+
+        head.insert("Banana", TllData::default());
+        assert!(head.insert("Banana", TllData::default()).is_none());
+        assert_eq!(check_tree(head), ["Banana"]);
+    }
+
+    #[test]
+    fn remove_keeps_tree_balanced() {
+        let head = empty_tree();
+        for i in 0..100 {
+            let data = TllData {
+                index: i,
+                ..Default::default()
+            };
+            head.insert(&key(i), data);
+        }
+
+        for i in (0..100).step_by(3) {
+            assert_eq!(head.remove(&key(i)).unwrap().index, i);
+            check_tree(head);
+        }
+        assert!(head.remove(&key(0)).is_none());
+
+        let mut expected: Vec<_> = (0..100).filter(|i| i % 3 != 0).map(key).collect();
+        expected.sort();
+        assert_eq!(check_tree(head), expected);
+
+        for i in (0..100).filter(|i| i % 3 != 0) {
+            head.remove(&key(i)).unwrap();
+            check_tree(head);
+        }
+        let pointer = GamePtr::new(head as *mut TLL);
+        assert_eq!(head.a, pointer);
+        assert_eq!(head.b, pointer);
+        assert_eq!(head.c, pointer);
+    }
+}
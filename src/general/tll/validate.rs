@@ -0,0 +1,360 @@
+//! Checks the integrity of TLL trees.
+
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use super::TLL;
+
+/// A problem found in a TLL tree by `TLL::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TllError {
+    /// A link points to memory that isn't a valid TLL.
+    DanglingPointer {
+        /// The TLL holding the link.
+        tll: *const TLL,
+        /// The name of the link: "a", "b", or "c".
+        link: &'static str,
+        /// The address the link points to.
+        target: *const TLL,
+    },
+    /// A child's `b` doesn't point back to its parent.
+    BrokenParentLink {
+        /// The parent that links to the child through `a` or `c`.
+        parent: *const TLL,
+        /// The child.
+        child: *const TLL,
+    },
+    /// The head's `a` or `c` doesn't point to the smallest or largest TLL.
+    BrokenHeadLink {
+        /// The name of the link: "a" or "c".
+        link: &'static str,
+        /// The TLL the link should point to.
+        expected: *const TLL,
+        /// The TLL the link points to.
+        actual: *const TLL,
+    },
+    /// A TLL other than the head has `flag` set.
+    MisplacedFlag {
+        /// The TLL with `flag` set.
+        tll: *const TLL,
+    },
+    /// `end` is placed wrongly: the head or root doesn't have it set, or a TLL without it has a child without it.
+    MisplacedEnd {
+        /// The TLL with the wrong `end`.
+        tll: *const TLL,
+    },
+    /// Paths from the root to the head pass through a different number of TLLs with `end` set.
+    UnbalancedEnd {
+        /// The TLL where the path ends.
+        tll: *const TLL,
+    },
+    /// A TLL is reachable through more than one path.
+    Cycle {
+        /// The TLL reached again.
+        tll: *const TLL,
+    },
+    /// A traversal visited more TLLs than the allowed limit.
+    TooManyNodes {
+        /// The limit that was exceeded.
+        limit: usize,
+    },
+}
+
+impl fmt::Display for TllError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DanglingPointer { tll, link, target } => {
+                write!(f, "TLL {tll:p} has dangling {link} pointer {target:p}")
+            }
+            Self::BrokenParentLink { parent, child } => {
+                write!(
+                    f,
+                    "TLL {child:p} doesn't link back to its parent {parent:p}"
+                )
+            }
+            Self::BrokenHeadLink {
+                link,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "head {link} pointer is {actual:p}, expected {expected:p}"
+            ),
+            Self::MisplacedFlag { tll } => write!(f, "TLL {tll:p} has flag set but isn't the head"),
+            Self::MisplacedEnd { tll } => write!(f, "TLL {tll:p} has a misplaced end"),
+            Self::UnbalancedEnd { tll } => {
+                write!(
+                    f,
+                    "path ending at TLL {tll:p} has a different number of ends"
+                )
+            }
+            Self::Cycle { tll } => write!(f, "TLL {tll:p} is reachable through more than one path"),
+            Self::TooManyNodes { limit } => write!(f, "more than {limit} TLLs were visited"),
+        }
+    }
+}
+
+impl Error for TllError {}
+
+// The pointers are only reported, never dereferenced.
+unsafe impl Send for TllError {}
+unsafe impl Sync for TllError {}
+
+impl TLL {
+    /// Checks the integrity of the tree this TLL is the head or the root of.
+    ///
+    /// Links are only followed if they are not null.
+    /// Use `validate_with` to also check that they point to readable memory.
+    pub fn validate(&self) -> Result<(), Vec<TllError>> {
+        self.validate_with(|pointer| !pointer.is_null() && pointer.is_aligned())
+    }
+
+    /// Checks the integrity of the tree this TLL is the head or the root of, see `TLL::insert` for the layout.
+    ///
+    /// `is_valid` is called before following any link, and should return false if the memory it points to can't be read.
+    ///
+    /// Returns every problem found.
+    pub fn validate_with(
+        &self,
+        is_valid: impl Fn(*const TLL) -> bool,
+    ) -> Result<(), Vec<TllError>> {
+        let mut errors = Vec::new();
+
+        let head = self.flag.then_some(self as *const TLL);
+        let root = match head {
+            Some(head) => {
+                if !self.end {
+                    errors.push(TllError::MisplacedEnd { tll: head });
+                }
+
+                for (link, target) in [("a", self.a), ("b", self.b), ("c", self.c)]
+                    .map(|(link, target)| (link, target.as_ptr() as *const TLL))
+                {
+                    if !is_valid(target) {
+                        errors.push(TllError::DanglingPointer {
+                            tll: head,
+                            link,
+                            target,
+                        });
+                    }
+                }
+                if !errors.is_empty() {
+                    return Err(errors);
+                }
+
+                self.b.as_ptr() as *const TLL
+            }
+            None => self as *const TLL,
+        };
+
+        if Some(root) == head {
+            return finish(errors);
+        }
+
+        unsafe {
+            if head.is_some() && !(*root).end {
+                errors.push(TllError::MisplacedEnd { tll: root });
+            }
+        }
+
+        let mut visited = BTreeSet::new();
+        let mut black_height = None;
+        let mut leftmost = None;
+        let mut rightmost = None;
+        // The TLL to check, its expected parent, the number of TLLs with `end` set above it,
+        // and whether it is reached from the root through only `a` or only `c` links.
+        let mut pending = vec![(root, head, 0usize, true, true)];
+
+        while let Some((pointer, parent, ends, left_spine, right_spine)) = pending.pop() {
+            if !visited.insert(pointer) {
+                errors.push(TllError::Cycle { tll: pointer });
+                continue;
+            }
+
+            let tll = unsafe { &*pointer };
+            if tll.flag {
+                errors.push(TllError::MisplacedFlag { tll: pointer });
+                continue;
+            }
+
+            if let Some(parent) = parent {
+                if !core::ptr::eq(tll.b.as_ptr(), parent) {
+                    errors.push(TllError::BrokenParentLink {
+                        parent,
+                        child: pointer,
+                    });
+                }
+            }
+
+            let ends = ends + tll.end as usize;
+            for (link, target) in [("c", tll.c.as_ptr()), ("a", tll.a.as_ptr())] {
+                if !is_valid(target) {
+                    errors.push(TllError::DanglingPointer {
+                        tll: pointer,
+                        link,
+                        target,
+                    });
+                    continue;
+                }
+
+                if Some(target as *const TLL) == head || (head.is_none() && target.is_null()) {
+                    // Reached the end of a path.
+                    match black_height {
+                        None => black_height = Some(ends),
+                        Some(height) if height != ends => {
+                            errors.push(TllError::UnbalancedEnd { tll: pointer })
+                        }
+                        _ => {}
+                    }
+
+                    if link == "a" && left_spine {
+                        leftmost = Some(pointer);
+                    }
+                    if link == "c" && right_spine {
+                        rightmost = Some(pointer);
+                    }
+                    continue;
+                }
+
+                let child = unsafe { &*target };
+                if !tll.end && !child.flag && !child.end {
+                    errors.push(TllError::MisplacedEnd { tll: target });
+                }
+
+                pending.push((
+                    target,
+                    Some(pointer),
+                    ends,
+                    left_spine && link == "a",
+                    right_spine && link == "c",
+                ));
+            }
+        }
+
+        if head.is_some() {
+            for (link, expected, actual) in [
+                ("a", leftmost, self.a.as_ptr()),
+                ("c", rightmost, self.c.as_ptr()),
+            ] {
+                if let Some(expected) = expected {
+                    if !core::ptr::eq(expected, actual) {
+                        errors.push(TllError::BrokenHeadLink {
+                            link,
+                            expected,
+                            actual,
+                        });
+                    }
+                }
+            }
+        }
+
+        finish(errors)
+    }
+}
+
+fn finish(errors: Vec<TllError>) -> Result<(), Vec<TllError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::{OwnedTll, TllData};
+
+    fn tree(count: u32) -> OwnedTll {
+        let mut tll = OwnedTll::new();
+        for i in 0..count {
+            tll.insert(&format!("key {i:02}"), TllData::default());
+        }
+        tll
+    }
+
+    #[test]
+    fn built_tree_is_valid() {
+        assert_eq!(tree(0).validate(), Ok(()));
+        assert_eq!(tree(1).validate(), Ok(()));
+        assert_eq!(tree(50).validate(), Ok(()));
+    }
+
+    #[test]
+    fn broken_parent_link_is_found() {
+        let tll = tree(10);
+        let child = tll.find("key 00").unwrap() as *const TLL as *mut TLL;
+        let parent = unsafe { (*child).b.as_ptr() };
+
+        unsafe { (*child).b = child.into() };
+
+        let errors = tll.validate().unwrap_err();
+        assert!(errors.contains(&TllError::BrokenParentLink { parent, child }));
+
+        unsafe { (*child).b = parent.into() };
+    }
+
+    #[test]
+    fn broken_head_link_is_found() {
+        let mut tll = tree(10);
+        let rightmost = tll.c;
+        tll.c = tll.b;
+
+        let errors = tll.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            [TllError::BrokenHeadLink {
+                link: "c",
+                expected: rightmost.as_ptr(),
+                actual: tll.b.as_ptr()
+            }]
+        );
+
+        tll.c = rightmost;
+    }
+
+    #[test]
+    fn misplaced_end_is_found() {
+        let tll = tree(10);
+        let root = tll.b.as_ptr();
+
+        unsafe { (*root).end = false };
+
+        let errors = tll.validate().unwrap_err();
+        assert!(errors.contains(&TllError::MisplacedEnd { tll: root }));
+
+        unsafe { (*root).end = true };
+    }
+
+    #[test]
+    fn dangling_pointer_is_found() {
+        let tll = tree(3);
+        let leaf = tll.find("key 02").unwrap() as *const TLL as *mut TLL;
+        let head = unsafe { (*leaf).c.as_ptr() };
+
+        let errors = tll.validate_with(|pointer| pointer != head).unwrap_err();
+        assert!(errors.iter().all(|error| matches!(
+            error,
+            TllError::DanglingPointer { target, .. } if *target == head
+        )));
+    }
+
+    #[test]
+    fn cycle_is_found() {
+        let tll = tree(3);
+        let leaf = tll.find("key 02").unwrap() as *const TLL as *mut TLL;
+        let head = unsafe { (*leaf).c.as_ptr() };
+
+        unsafe { (*leaf).c = tll.b };
+
+        let errors = tll.validate().unwrap_err();
+        assert!(errors.contains(&TllError::Cycle {
+            tll: tll.b.as_ptr()
+        }));
+
+        unsafe { (*leaf).c = head.into() };
+    }
+}
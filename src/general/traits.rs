@@ -0,0 +1,214 @@
+//! Defines traits shared by the structs of every game version, so tooling can be written once for all of them.
+
+use super::{AmmoSign, EscadraString, Reticle, ShellBehavior};
+
+/// Access to the `Ammo` fields that exist in every game version.
+///
+/// See the `Ammo` struct of each version for the meaning of every field.
+pub trait AmmoFields {
+    /// What reticle to use when firing the ammo.
+    fn reticle(&self) -> i32;
+    /// Sets what reticle to use when firing the ammo.
+    fn set_reticle(&mut self, reticle: i32);
+
+    /// The internal name for the item within Highfleet.
+    fn item_name(&self) -> &EscadraString;
+    /// The internal name for the item within Highfleet.
+    fn item_name_mut(&mut self) -> &mut EscadraString;
+
+    /// The text that displays the shell's kind in the shop.
+    fn shell_kind(&self) -> &EscadraString;
+    /// The text that displays the shell's kind in the shop.
+    fn shell_kind_mut(&mut self) -> &mut EscadraString;
+
+    /// The internal text to determine the shell's kind.
+    fn shell_kind2(&self) -> &EscadraString;
+    /// The internal text to determine the shell's kind.
+    fn shell_kind2_mut(&mut self) -> &mut EscadraString;
+
+    /// The text to display for the ammo's milimeter in the shop.
+    fn milimeterage(&self) -> &EscadraString;
+    /// The text to display for the ammo's milimeter in the shop.
+    fn milimeterage_mut(&mut self) -> &mut EscadraString;
+
+    /// The image to use for the ammo in the magazine.
+    fn magazine_image(&self) -> &EscadraString;
+    /// The image to use for the ammo in the magazine.
+    fn magazine_image_mut(&mut self) -> &mut EscadraString;
+
+    /// What sign to use for the reticle.
+    fn sign_ammo(&self) -> &EscadraString;
+    /// What sign to use for the reticle.
+    fn sign_ammo_mut(&mut self) -> &mut EscadraString;
+
+    /// How tall the bullet is in the magazine.
+    fn bullet_height(&self) -> f32;
+    /// Sets how tall the bullet is in the magazine.
+    fn set_bullet_height(&mut self, bullet_height: f32);
+
+    /// The sound set to play when a shell is loaded into the magazine.
+    fn shell_in(&self) -> &EscadraString;
+    /// The sound set to play when a shell is loaded into the magazine.
+    fn shell_in_mut(&mut self) -> &mut EscadraString;
+
+    /// The sound set to play when firing the gun.
+    fn shell_out(&self) -> &EscadraString;
+    /// The sound set to play when firing the gun.
+    fn shell_out_mut(&mut self) -> &mut EscadraString;
+
+    /// The sound set to play when the gun is fired from far away.
+    fn shell_far(&self) -> &EscadraString;
+    /// The sound set to play when the gun is fired from far away.
+    fn shell_far_mut(&mut self) -> &mut EscadraString;
+
+    /// Determines if the shell behaves like HE, AP, INC, or LG.
+    fn caliber(&self) -> i32;
+    /// Sets if the shell behaves like HE, AP, INC, or LG.
+    fn set_caliber(&mut self, caliber: i32);
+
+    /// The index of the ammo.
+    fn index(&self) -> i32;
+    /// Sets the index of the ammo.
+    fn set_index(&mut self, index: i32);
+
+    /// The speed of the shell.
+    fn speed(&self) -> f32;
+    /// Sets the speed of the shell.
+    fn set_speed(&mut self, speed: f32);
+
+    /// The drag the shell experiences.
+    fn ap_drag(&self) -> f32;
+    /// Sets the drag the shell experiences.
+    fn set_ap_drag(&mut self, ap_drag: f32);
+
+    /// The shell's explosive power.
+    fn explosive_power(&self) -> f32;
+    /// Sets the shell's explosive power.
+    fn set_explosive_power(&mut self, explosive_power: f32);
+
+    /// The shell's penetrative power.
+    fn penetrative_power(&self) -> f32;
+    /// Sets the shell's penetrative power.
+    fn set_penetrative_power(&mut self, penetrative_power: f32);
+
+    /// The shell's incendiary power.
+    fn incendiary_power(&self) -> f32;
+    /// Sets the shell's incendiary power.
+    fn set_incendiary_power(&mut self, incendiary_power: f32);
+
+    /// The price of the ammo inside of city shops.
+    fn shop_price(&self) -> i32;
+    /// Sets the price of the ammo inside of city shops.
+    fn set_shop_price(&mut self, shop_price: i32);
+
+    /// The reticle as a `Reticle`.
+    fn reticle_kind(&self) -> Reticle {
+        self.reticle().into()
+    }
+    /// Sets the reticle from a `Reticle`.
+    fn set_reticle_kind(&mut self, reticle: Reticle) {
+        self.set_reticle(reticle.into());
+    }
+
+    /// The caliber as a `ShellBehavior`.
+    fn shell_behavior(&self) -> ShellBehavior {
+        self.caliber().into()
+    }
+    /// Sets the caliber from a `ShellBehavior`.
+    fn set_shell_behavior(&mut self, behavior: ShellBehavior) {
+        self.set_caliber(behavior.into());
+    }
+
+    /// The sign as an `AmmoSign`.
+    fn ammo_sign(&self) -> AmmoSign {
+        AmmoSign::from(self.sign_ammo().get_string_lossy().as_ref())
+    }
+    /// Sets the sign from an `AmmoSign`.
+    fn set_ammo_sign(&mut self, sign: &AmmoSign) {
+        let sign_ammo = self.sign_ammo_mut();
+        sign_ammo.clear();
+        sign_ammo.push_str(sign.as_str());
+    }
+}
+
+/// Implements `AmmoFields` for a version's `Ammo`, which must have every common field with the same name.
+macro_rules! impl_ammo_fields {
+    ($ammo:ty) => {
+        impl $crate::general::traits::AmmoFields for $ammo {
+            $crate::general::traits::impl_ammo_fields!(@values
+                reticle, set_reticle: i32;
+                bullet_height, set_bullet_height: f32;
+                caliber, set_caliber: i32;
+                index, set_index: i32;
+                speed, set_speed: f32;
+                ap_drag, set_ap_drag: f32;
+                explosive_power, set_explosive_power: f32;
+                penetrative_power, set_penetrative_power: f32;
+                incendiary_power, set_incendiary_power: f32;
+                shop_price, set_shop_price: i32;
+            );
+            $crate::general::traits::impl_ammo_fields!(@strings
+                item_name, item_name_mut;
+                shell_kind, shell_kind_mut;
+                shell_kind2, shell_kind2_mut;
+                milimeterage, milimeterage_mut;
+                magazine_image, magazine_image_mut;
+                sign_ammo, sign_ammo_mut;
+                shell_in, shell_in_mut;
+                shell_out, shell_out_mut;
+                shell_far, shell_far_mut;
+            );
+        }
+    };
+    (@values $($field:ident, $setter:ident: $type:ty;)*) => {
+        $(
+            fn $field(&self) -> $type {
+                self.$field
+            }
+
+            fn $setter(&mut self, $field: $type) {
+                self.$field = $field;
+            }
+        )*
+    };
+    (@strings $($field:ident, $getter_mut:ident;)*) => {
+        $(
+            fn $field(&self) -> &$crate::general::EscadraString {
+                &self.$field
+            }
+
+            fn $getter_mut(&mut self) -> &mut $crate::general::EscadraString {
+                &mut self.$field
+            }
+        )*
+    };
+}
+
+pub(crate) use impl_ammo_fields;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::raw::{from_bytes, PointerPolicy};
+    use crate::{v1_151, v1_163};
+
+    /// Version independent code, written once.
+    fn rename_and_reprice(ammo: &mut impl AmmoFields) {
+        ammo.item_name_mut().set_string(&"BANANA_57".to_string());
+        ammo.set_shop_price(ammo.shop_price() + 10);
+    }
+
+    #[test]
+    fn works_for_every_version() {
+        let mut old: v1_151::Ammo = from_bytes(&[0u8; 0x168], &PointerPolicy::Zero).unwrap();
+        let mut new: v1_163::Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+
+        rename_and_reprice(&mut old);
+        rename_and_reprice(&mut new);
+
+        assert_eq!(old.item_name, "BANANA_57");
+        assert_eq!(new.item_name(), "BANANA_57");
+        assert_eq!(old.shop_price, 10);
+        assert_eq!(new.shop_price(), 10);
+    }
+}
@@ -0,0 +1,230 @@
+//! Detects which version of Highfleet is running, so injected mods can pick the right struct definitions.
+//!
+//! Versions are recognized by fingerprinting the PE headers of the loaded executable.
+
+use core::fmt;
+
+use crate::memory::{MemoryError, MemorySource};
+
+/// A version of Highfleet with its own module in this library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GameVersion {
+    /// Version 1.151, see the `v1_151` module.
+    V1_151,
+    /// Version 1.163, see the `v1_163` module.
+    V1_163,
+}
+
+impl GameVersion {
+    /// Every version, oldest first.
+    pub const ALL: [GameVersion; 2] = [GameVersion::V1_151, GameVersion::V1_163];
+
+    /// Returns the version number, e.g. "1.163".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::V1_151 => "1.151",
+            Self::V1_163 => "1.163",
+        }
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Values read from the PE headers of an executable that identify its build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeInfo {
+    /// The `TimeDateStamp` of the COFF file header, set by the linker.
+    pub timestamp: u32,
+    /// The `SizeOfImage` of the optional header.
+    pub size_of_image: u32,
+}
+
+/// Identifies a build of the game executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The PE header values of the build.
+    pub pe: PeInfo,
+    /// The version of the build.
+    pub version: GameVersion,
+}
+
+/// The fingerprints of the builds known to this library.
+///
+/// No build has been fingerprinted yet. Use `read_pe_info` on the running game to record one,
+/// and `detect_with` to detect versions with your own fingerprints until then.
+pub const KNOWN_BUILDS: &[Fingerprint] = &[];
+
+/// Reads the identifying PE header values of a module mapped into memory.
+///
+/// Returns `None` if the module doesn't start with valid DOS and PE headers.
+///
+/// # Safety
+///
+/// `module_base` must point to readable memory holding at least the headers of the module,
+/// such as the base address of a loaded module.
+pub unsafe fn read_pe_info(module_base: *const u8) -> Option<PeInfo> {
+    if module_base.is_null() {
+        return None;
+    }
+    read_pe_info_from(&Unchecked, module_base as u64)
+}
+
+/// Reads the identifying PE header values of the module at `module_base` in the given memory.
+///
+/// Returns `None` if the headers can't be read, or aren't valid DOS and PE headers.
+pub fn read_pe_info_from<M: MemorySource>(memory: &M, module_base: u64) -> Option<PeInfo> {
+    let read_u32 = |offset: u64| {
+        let mut bytes = [0u8; 4];
+        memory.read(module_base + offset, &mut bytes).ok()?;
+        Some(u32::from_le_bytes(bytes))
+    };
+
+    // "MZ"
+    let mut magic = [0u8; 2];
+    memory.read(module_base, &mut magic).ok()?;
+    if magic != *b"MZ" {
+        return None;
+    }
+
+    let pe_offset = read_u32(0x3c)? as u64;
+    // "PE\0\0"
+    if read_u32(pe_offset)? != 0x4550 {
+        return None;
+    }
+
+    let coff_header = pe_offset + 4;
+    let optional_header = coff_header + 20;
+
+    Some(PeInfo {
+        timestamp: read_u32(coff_header + 4)?,
+        size_of_image: read_u32(optional_header + 56)?,
+    })
+}
+
+/// Reads memory of the current process through raw pointers, without any checks.
+struct Unchecked;
+
+impl MemorySource for Unchecked {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+        unsafe {
+            core::ptr::copy_nonoverlapping(address as *const u8, buffer.as_mut_ptr(), buffer.len())
+        };
+        Ok(())
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), address as *mut u8, bytes.len()) };
+        Ok(())
+    }
+}
+
+/// Detects the version of the game from the module mapped at `module_base`, using `KNOWN_BUILDS`.
+///
+/// # Safety
+///
+/// See `read_pe_info`.
+pub unsafe fn detect(module_base: *const u8) -> Option<GameVersion> {
+    detect_with(module_base, KNOWN_BUILDS)
+}
+
+/// Detects the version of the game from the module mapped at `module_base`, using the given fingerprints.
+///
+/// # Safety
+///
+/// See `read_pe_info`.
+pub unsafe fn detect_with(
+    module_base: *const u8,
+    fingerprints: &[Fingerprint],
+) -> Option<GameVersion> {
+    let pe = read_pe_info(module_base)?;
+
+    find_version(pe, fingerprints)
+}
+
+/// Detects the version of the game from the module at `module_base` in the given memory, using the given fingerprints.
+///
+/// Use `KNOWN_BUILDS` as the fingerprints to detect the versions known to this library.
+pub fn detect_from<M: MemorySource>(
+    memory: &M,
+    module_base: u64,
+    fingerprints: &[Fingerprint],
+) -> Option<GameVersion> {
+    find_version(read_pe_info_from(memory, module_base)?, fingerprints)
+}
+
+fn find_version(pe: PeInfo, fingerprints: &[Fingerprint]) -> Option<GameVersion> {
+    fingerprints
+        .iter()
+        .find(|fingerprint| fingerprint.pe == pe)
+        .map(|fingerprint| fingerprint.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the headers of a 64-bit PE image.
+    fn pe_image(timestamp: u32, size_of_image: u32) -> Vec<u8> {
+        let mut image = vec![0u8; 0x200];
+        image[0..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        image[0x88..0x8c].copy_from_slice(&timestamp.to_le_bytes());
+        image[0xd0..0xd4].copy_from_slice(&size_of_image.to_le_bytes());
+        image
+    }
+
+    #[test]
+    fn reads_pe_info() {
+        let image = pe_image(0x6000_0000, 0x0180_0000);
+
+        let info = unsafe { read_pe_info(image.as_ptr()) }.unwrap();
+        assert_eq!(info.timestamp, 0x6000_0000);
+        assert_eq!(info.size_of_image, 0x0180_0000);
+    }
+
+    #[test]
+    fn rejects_non_pe() {
+        let image = vec![0u8; 0x200];
+        assert!(unsafe { read_pe_info(image.as_ptr()) }.is_none());
+        assert!(unsafe { read_pe_info(core::ptr::null()) }.is_none());
+    }
+
+    #[test]
+    fn detects_with_fingerprints() {
+        let image = pe_image(0x6000_0000, 0x0180_0000);
+        let fingerprints = [Fingerprint {
+            pe: PeInfo {
+                timestamp: 0x6000_0000,
+                size_of_image: 0x0180_0000,
+            },
+            version: GameVersion::V1_163,
+        }];
+
+        let version = unsafe { detect_with(image.as_ptr(), &fingerprints) };
+        assert_eq!(version, Some(GameVersion::V1_163));
+
+        let version = unsafe { detect_with(pe_image(1, 1).as_ptr(), &fingerprints) };
+        assert_eq!(version, None);
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn detects_from_memory_source() {
+        use crate::memory::InProcess;
+
+        let image = pe_image(0x6000_0000, 0x0180_0000);
+        let fingerprints = [Fingerprint {
+            pe: read_pe_info_from(&InProcess, image.as_ptr() as u64).unwrap(),
+            version: GameVersion::V1_151,
+        }];
+
+        let version = detect_from(&InProcess, image.as_ptr() as u64, &fingerprints);
+        assert_eq!(version, Some(GameVersion::V1_151));
+        assert_eq!(detect_from(&InProcess, 0x10, &fingerprints), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! Defines the warnings returned when checking a struct against the values the vanilla game uses.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{AmmoFields, AmmoSign, EscadraString, Reticle, ShellBehavior};
+use crate::res::{ResourceIndex, SoundSet, SpriteError, SpriteRef};
+
+/// A value of an `Ammo` the game may not handle as expected.
+///
+/// Warnings aren't errors: mods may use values the vanilla game doesn't, on purpose.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmmoWarning {
+    /// A number is outside of the range used by the vanilla ammos.
+    OutOfRange {
+        /// The name of the field.
+        field: &'static str,
+        /// The value of the field.
+        value: f32,
+        /// The smallest vanilla value.
+        min: f32,
+        /// The largest vanilla value.
+        max: f32,
+    },
+    /// A string is empty, while the game expects a name.
+    Empty(&'static str),
+    /// A field holds a value with a meaning the game doesn't know about.
+    UnknownValue {
+        /// The name of the field.
+        field: &'static str,
+        /// The value of the field.
+        value: String,
+    },
+    /// A field names an image or sound set that isn't installed.
+    MissingResource {
+        /// The name of the field.
+        field: &'static str,
+        /// The name of the resource.
+        name: String,
+    },
+    /// A sound set field names one of the sounds of a set, like "crowd_01", instead of the set, so nothing plays.
+    NotASoundSet {
+        /// The name of the field.
+        field: &'static str,
+        /// The name in the field.
+        name: String,
+        /// The name of the set the sound belongs to.
+        set: String,
+    },
+    /// An image field names an animation without the number of its first frame, so nothing shows.
+    MissingFrame {
+        /// The name of the field.
+        field: &'static str,
+        /// The name in the field.
+        name: String,
+        /// The name of the first frame of the animation.
+        first_frame: String,
+    },
+}
+
+impl AmmoWarning {
+    /// Returns true for the warnings that fail deserialization with the `strict` feature:
+    /// values outside of the vanilla range, and values the game doesn't know about.
+    pub fn fails_strict(&self) -> bool {
+        matches!(self, Self::OutOfRange { .. } | Self::UnknownValue { .. })
+    }
+}
+
+impl fmt::Display for AmmoWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{field} is {value}, outside of the vanilla range {min} to {max}"
+            ),
+            Self::Empty(field) => write!(f, "{field} is empty"),
+            Self::UnknownValue { field, value } => write!(f, "{field} has unknown value {value}"),
+            Self::MissingResource { field, name } => {
+                write!(f, "{field} refers to {name:?}, which isn't installed")
+            }
+            Self::NotASoundSet { field, name, set } => write!(
+                f,
+                "{field} refers to the sound {name:?}, use the name of its sound set {set:?}"
+            ),
+            Self::MissingFrame {
+                field,
+                name,
+                first_frame,
+            } => write!(
+                f,
+                "{field} refers to the animation {name:?}, use the name of its first frame {first_frame:?}"
+            ),
+        }
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for an ammo marked `#[serde(remote = "Self")]`,
+/// failing deserialization on the first warning of `validate` that `AmmoWarning::fails_strict`.
+///
+/// ```ignore
+/// #[cfg_attr(feature = "strict", serde(remote = "Self"))]
+/// pub struct Ammo { ... }
+///
+/// #[cfg(feature = "strict")]
+/// impl_strict_serde!(Ammo);
+/// ```
+#[cfg(feature = "strict")]
+macro_rules! impl_strict_serde {
+    ($ammo:ty) => {
+        impl serde::Serialize for $ammo {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                <$ammo>::serialize(self, serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $ammo {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let ammo = <$ammo>::deserialize(deserializer)?;
+                match ammo
+                    .validate()
+                    .into_iter()
+                    .find($crate::general::AmmoWarning::fails_strict)
+                {
+                    Some(warning) => Err(serde::de::Error::custom(warning)),
+                    None => Ok(ammo),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "strict")]
+pub(crate) use impl_strict_serde;
+
+/// Collects the warnings of a single `Ammo`.
+pub(crate) struct AmmoChecker {
+    pub(crate) warnings: Vec<AmmoWarning>,
+}
+
+impl AmmoChecker {
+    /// Checks the fields every version has.
+    pub(crate) fn new(ammo: &dyn AmmoFields) -> Self {
+        let mut checker = Self {
+            warnings: Vec::new(),
+        };
+
+        for (field, value) in [
+            ("item_name", ammo.item_name()),
+            ("magazine_image", ammo.magazine_image()),
+            ("shell_in", ammo.shell_in()),
+            ("shell_out", ammo.shell_out()),
+            ("shell_far", ammo.shell_far()),
+        ] {
+            checker.non_empty(field, value);
+        }
+
+        checker.range("bullet_height", ammo.bullet_height(), 16.0, 38.0);
+        checker.range("ap_drag", ammo.ap_drag(), 0.0, 1.0);
+
+        if ammo.index() < 0 {
+            checker.unknown("index", ammo.index());
+        }
+        if let Reticle::Other(value) = ammo.reticle_kind() {
+            checker.unknown("reticle", value);
+        }
+        if let ShellBehavior::Other(value) = ammo.shell_behavior() {
+            checker.unknown("caliber", value);
+        }
+        if let AmmoSign::Other(value) = ammo.ammo_sign() {
+            checker.unknown("sign_ammo", value);
+        }
+
+        checker
+    }
+
+    /// Checks the resources the fields every version has refer to.
+    pub(crate) fn resources(ammo: &dyn AmmoFields, resources: &ResourceIndex) -> Self {
+        let mut checker = Self {
+            warnings: Vec::new(),
+        };
+
+        checker.image("magazine_image", ammo.magazine_image(), resources);
+        checker.sound_set("shell_in", ammo.shell_in(), resources);
+        checker.sound_set("shell_out", ammo.shell_out(), resources);
+        checker.sound_set("shell_far", ammo.shell_far(), resources);
+
+        checker
+    }
+
+    /// Warns if `name` isn't an installed image. Empty names are warned about by `non_empty`.
+    fn image(&mut self, field: &'static str, name: &EscadraString, resources: &ResourceIndex) {
+        let name = name.get_string_lossy();
+        match SpriteRef::resolve(&name, resources) {
+            Ok(_) | Err(SpriteError::Empty) => {}
+            Err(SpriteError::Missing(_)) => self.missing(field, &name),
+            Err(SpriteError::MissingFrame { first_frame, .. }) => {
+                self.warnings.push(AmmoWarning::MissingFrame {
+                    field,
+                    name: name.to_string(),
+                    first_frame,
+                })
+            }
+        }
+    }
+
+    /// Warns if `name` isn't an installed sound set. Empty names are warned about by `non_empty`.
+    /// Names of sounds, like "crowd_01", are warned about even if installed, as the game only looks up sets.
+    pub(crate) fn sound_set(
+        &mut self,
+        field: &'static str,
+        name: &EscadraString,
+        resources: &ResourceIndex,
+    ) {
+        let name = name.get_string_lossy();
+        if let Some(set) = SoundSet::of_sound(&name) {
+            self.warnings.push(AmmoWarning::NotASoundSet {
+                field,
+                name: name.to_string(),
+                set: set.to_string(),
+            });
+        } else if !name.is_empty() && !resources.has_sound_set(&name) {
+            self.missing(field, &name);
+        }
+    }
+
+    fn missing(&mut self, field: &'static str, name: &str) {
+        self.warnings.push(AmmoWarning::MissingResource {
+            field,
+            name: name.to_string(),
+        });
+    }
+
+    /// Warns if `value` is outside of `min..=max`.
+    pub(crate) fn range(&mut self, field: &'static str, value: f32, min: f32, max: f32) {
+        if !(min..=max).contains(&value) {
+            self.warnings.push(AmmoWarning::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            });
+        }
+    }
+
+    /// Warns if `value` is empty.
+    pub(crate) fn non_empty(&mut self, field: &'static str, value: &EscadraString) {
+        if value.get_bytes().is_empty() {
+            self.warnings.push(AmmoWarning::Empty(field));
+        }
+    }
+
+    /// Warns about a value with an unknown meaning.
+    fn unknown(&mut self, field: &'static str, value: impl ToString) {
+        self.warnings.push(AmmoWarning::UnknownValue {
+            field,
+            value: value.to_string(),
+        });
+    }
+}
@@ -0,0 +1,303 @@
+//! Defines inline hooks (detours) on game functions.
+//!
+//! A hook overwrites the start of a function with a jump to a replacement, the detour.
+//! The overwritten bytes are copied into a trampoline, followed by a jump back into the function,
+//! so the detour can still call the original function through `Hook::original`.
+//!
+//! Instructions are not decoded: the caller tells how many bytes to move into the trampoline.
+//! They must be whole instructions covering at least `JUMP_SIZE` bytes, without relative operands such as `call rel32`,
+//! `jmp rel8` or `[rip + x]`, as those would point elsewhere once moved.
+//! Only x86-64 is supported.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+
+mod sys;
+
+/// The size of the absolute jump written over the start of a hooked function: `jmp [rip + 0]` followed by the address.
+pub const JUMP_SIZE: usize = 14;
+
+/// A function pointer type that can be hooked, like `unsafe extern "C" fn(i32) -> f32`.
+///
+/// # Safety
+///
+/// The type must be a function pointer.
+pub unsafe trait FnPtr: Copy {
+    /// Returns the address of the function.
+    fn to_address(self) -> usize;
+
+    /// Creates a function pointer out of an address.
+    ///
+    /// # Safety
+    ///
+    /// `address` must point to a function with the signature of this type.
+    unsafe fn from_address(address: usize) -> Self;
+}
+
+/// Implements `FnPtr` for the function pointers of the given ABI with the given argument types.
+macro_rules! impl_fn_ptr {
+    ($abi:literal; $($arg:ident),*) => {
+            unsafe impl<R, $($arg),*> FnPtr for extern $abi fn($($arg),*) -> R {
+                fn to_address(self) -> usize {
+                    self as usize
+                }
+
+                unsafe fn from_address(address: usize) -> Self {
+                    std::mem::transmute::<usize, Self>(address)
+                }
+            }
+
+            unsafe impl<R, $($arg),*> FnPtr for unsafe extern $abi fn($($arg),*) -> R {
+                fn to_address(self) -> usize {
+                    self as usize
+                }
+
+                unsafe fn from_address(address: usize) -> Self {
+                    std::mem::transmute::<usize, Self>(address)
+                }
+            }
+    };
+    ($($arg:ident),*) => {
+        impl_fn_ptr!("C"; $($arg),*);
+        impl_fn_ptr!("system"; $($arg),*);
+    };
+}
+
+impl_fn_ptr!();
+impl_fn_ptr!(A);
+impl_fn_ptr!(A, B);
+impl_fn_ptr!(A, B, C);
+impl_fn_ptr!(A, B, C, D);
+impl_fn_ptr!(A, B, C, D, E);
+impl_fn_ptr!(A, B, C, D, E, F);
+impl_fn_ptr!(A, B, C, D, E, F, G);
+impl_fn_ptr!(A, B, C, D, E, F, G, H);
+
+/// Returned when creating, enabling, or disabling a hook fails.
+#[derive(Debug)]
+pub enum HookError {
+    /// Hooks are not supported on this platform.
+    Unsupported,
+    /// Fewer bytes were given to move into the trampoline than the jump overwrites.
+    TooFewStolenBytes {
+        /// The number of bytes given.
+        stolen: usize,
+    },
+    /// The operating system failed to allocate or protect memory.
+    Os(io::Error),
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "hooks are not supported on this platform"),
+            Self::TooFewStolenBytes { stolen } => {
+                write!(
+                    f,
+                    "{stolen} stolen bytes can't hold a {JUMP_SIZE} byte jump"
+                )
+            }
+            Self::Os(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for HookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Os(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HookError {
+    fn from(error: io::Error) -> Self {
+        Self::Os(error)
+    }
+}
+
+/// Encodes an absolute jump to `destination`.
+fn jump(destination: usize) -> [u8; JUMP_SIZE] {
+    let mut bytes = [0u8; JUMP_SIZE];
+    // jmp [rip + 0]
+    bytes[..6].copy_from_slice(&[0xff, 0x25, 0x00, 0x00, 0x00, 0x00]);
+    bytes[6..].copy_from_slice(&(destination as u64).to_le_bytes());
+    bytes
+}
+
+/// An inline hook redirecting a function of type `F` to a detour.
+///
+/// The hook is created disabled. Dropping it disables it and frees the trampoline.
+pub struct Hook<F: FnPtr> {
+    target: usize,
+    detour: usize,
+    /// The original bytes of the start of the target.
+    stolen: Vec<u8>,
+    /// Only freed once the target no longer jumps to the detour, see `Drop`.
+    trampoline: ManuallyDrop<sys::ExecutableMemory>,
+    enabled: bool,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FnPtr> Hook<F> {
+    /// Creates a disabled hook redirecting `target` to `detour`.
+    ///
+    /// The first `stolen` bytes of `target` are moved into the trampoline. See the module documentation for the requirements.
+    ///
+    /// # Safety
+    ///
+    /// `target` must be a function with at least `stolen` bytes of whole, position independent instructions at its start.
+    pub unsafe fn new(target: F, detour: F, stolen: usize) -> Result<Self, HookError> {
+        if !cfg!(target_arch = "x86_64") {
+            return Err(HookError::Unsupported);
+        }
+        if stolen < JUMP_SIZE {
+            return Err(HookError::TooFewStolenBytes { stolen });
+        }
+
+        let target = target.to_address();
+        let stolen = std::slice::from_raw_parts(target as *const u8, stolen).to_vec();
+
+        let mut code = stolen.clone();
+        code.extend(jump(target + stolen.len()));
+        let trampoline = sys::ExecutableMemory::new(&code)?;
+
+        Ok(Self {
+            target,
+            detour: detour.to_address(),
+            stolen,
+            trampoline: ManuallyDrop::new(trampoline),
+            enabled: false,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the function that was hooked.
+    pub fn target(&self) -> F {
+        unsafe { F::from_address(self.target) }
+    }
+
+    /// Returns a function that behaves like the target did before it was hooked.
+    ///
+    /// Call this from the detour to run the original function.
+    pub fn original(&self) -> F {
+        unsafe { F::from_address(self.trampoline.address()) }
+    }
+
+    /// Returns true if calls to the target are redirected to the detour.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Redirects calls to the target to the detour.
+    ///
+    /// # Safety
+    ///
+    /// No thread may be executing the start of the target while it is overwritten.
+    pub unsafe fn enable(&mut self) -> Result<(), HookError> {
+        if !self.enabled {
+            sys::patch(self.target, &jump(self.detour))?;
+            self.enabled = true;
+        }
+        Ok(())
+    }
+
+    /// Restores the target, so calls are no longer redirected.
+    ///
+    /// # Safety
+    ///
+    /// No thread may be executing the start of the target while it is overwritten.
+    pub unsafe fn disable(&mut self) -> Result<(), HookError> {
+        if self.enabled {
+            sys::patch(self.target, &self.stolen)?;
+            self.enabled = false;
+        }
+        Ok(())
+    }
+}
+
+impl<F: FnPtr> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hook")
+            .field("target", &(self.target as *const u8))
+            .field("detour", &(self.detour as *const u8))
+            .field("trampoline", &(self.trampoline.address() as *const u8))
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
+
+impl<F: FnPtr> Drop for Hook<F> {
+    /// Disables the hook and frees the trampoline.
+    ///
+    /// The target can't be left jumping to a detour that may be unloaded, nor to a freed trampoline.
+    /// If the hook can't be disabled, the trampoline is leaked and the error logged, instead of panicking inside the game.
+    fn drop(&mut self) {
+        match unsafe { self.disable() } {
+            Ok(()) => unsafe { ManuallyDrop::drop(&mut self.trampoline) },
+            Err(_error) => {
+                #[cfg(feature = "log")]
+                log::error!(
+                    "failed to disable the hook of {:#x}, leaking its trampoline: {_error}",
+                    self.target
+                );
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64", any(windows, target_os = "linux")))]
+mod tests {
+    use super::*;
+
+    type Answer = unsafe extern "C" fn() -> i32;
+
+    extern "C" fn detour() -> i32 {
+        7
+    }
+
+    /// Creates a function with 16 `nop`s before `mov eax, 42; ret`.
+    fn answer() -> sys::ExecutableMemory {
+        let mut code = vec![0x90; 16];
+        code.extend([0xb8, 42, 0, 0, 0, 0xc3]);
+        sys::ExecutableMemory::new(&code).unwrap()
+    }
+
+    #[test]
+    fn hook_and_call_original() {
+        let function = answer();
+        let target: Answer = unsafe { Answer::from_address(function.address()) };
+        let detour: Answer = detour;
+
+        let mut hook = unsafe { Hook::new(target, detour, 16) }.unwrap();
+        unsafe {
+            assert_eq!(target(), 42);
+
+            hook.enable().unwrap();
+            assert!(hook.is_enabled());
+            assert_eq!(target(), 7);
+            assert_eq!(hook.original()(), 42);
+
+            hook.disable().unwrap();
+            assert_eq!(target(), 42);
+
+            hook.enable().unwrap();
+        }
+
+        drop(hook);
+        assert_eq!(unsafe { target() }, 42);
+    }
+
+    #[test]
+    fn too_few_stolen_bytes() {
+        let function = answer();
+        let target: Answer = unsafe { Answer::from_address(function.address()) };
+
+        let error = unsafe { Hook::new(target, detour as Answer, 8) }.unwrap_err();
+        assert!(matches!(error, HookError::TooFewStolenBytes { stolen: 8 }));
+    }
+}
@@ -0,0 +1,198 @@
+//! Platform specific memory management for hooks.
+
+use std::io;
+
+/// A block of executable memory owned by this process, such as a trampoline.
+pub(super) struct ExecutableMemory {
+    address: usize,
+    size: usize,
+}
+
+impl ExecutableMemory {
+    /// Allocates executable memory holding `code`.
+    pub(super) fn new(code: &[u8]) -> io::Result<Self> {
+        let size = code.len();
+        let address = unsafe { allocate(size)? };
+        unsafe {
+            std::ptr::copy_nonoverlapping(code.as_ptr(), address as *mut u8, size);
+            flush(address, size);
+        }
+        Ok(Self { address, size })
+    }
+
+    /// Returns the address of the memory.
+    pub(super) fn address(&self) -> usize {
+        self.address
+    }
+}
+
+impl Drop for ExecutableMemory {
+    fn drop(&mut self) {
+        unsafe { free(self.address, self.size) }
+    }
+}
+
+/// Overwrites code at `address`, temporarily making it writable.
+///
+/// # Safety
+///
+/// `address` must point to `bytes.len()` bytes of code that no thread is executing.
+pub(super) unsafe fn patch(address: usize, bytes: &[u8]) -> io::Result<()> {
+    let old = make_writable(address, bytes.len())?;
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), address as *mut u8, bytes.len());
+    restore(address, bytes.len(), old)?;
+    flush(address, bytes.len());
+    Ok(())
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::io;
+
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn VirtualAlloc(
+            address: *mut u8,
+            size: usize,
+            allocation_type: u32,
+            protect: u32,
+        ) -> *mut u8;
+        fn VirtualFree(address: *mut u8, size: usize, free_type: u32) -> i32;
+        fn VirtualProtect(
+            address: *mut u8,
+            size: usize,
+            protect: u32,
+            old_protect: *mut u32,
+        ) -> i32;
+        fn FlushInstructionCache(process: *mut u8, address: *const u8, size: usize) -> i32;
+        fn GetCurrentProcess() -> *mut u8;
+    }
+
+    pub(in crate::hook) unsafe fn allocate(size: usize) -> io::Result<usize> {
+        let address = VirtualAlloc(
+            std::ptr::null_mut(),
+            size,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_EXECUTE_READWRITE,
+        );
+        if address.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(address as usize)
+    }
+
+    pub(in crate::hook) unsafe fn free(address: usize, _size: usize) {
+        VirtualFree(address as *mut u8, 0, MEM_RELEASE);
+    }
+
+    pub(in crate::hook) unsafe fn make_writable(address: usize, size: usize) -> io::Result<u32> {
+        let mut old = 0;
+        if VirtualProtect(address as *mut u8, size, PAGE_EXECUTE_READWRITE, &mut old) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(old)
+    }
+
+    pub(in crate::hook) unsafe fn restore(address: usize, size: usize, old: u32) -> io::Result<()> {
+        let mut previous = 0;
+        if VirtualProtect(address as *mut u8, size, old, &mut previous) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(in crate::hook) unsafe fn flush(address: usize, size: usize) {
+        FlushInstructionCache(GetCurrentProcess(), address as *const u8, size);
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::io;
+
+    /// Returns the page aligned range covering `size` bytes at `address`.
+    fn pages(address: usize, size: usize) -> (usize, usize) {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = address / page_size * page_size;
+        let end = (address + size).div_ceil(page_size) * page_size;
+        (start, end - start)
+    }
+
+    pub(in crate::hook) unsafe fn allocate(size: usize) -> io::Result<usize> {
+        let address = libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(address as usize)
+    }
+
+    pub(in crate::hook) unsafe fn free(address: usize, size: usize) {
+        libc::munmap(address as *mut libc::c_void, size);
+    }
+
+    /// Returns the protection to restore afterwards.
+    /// The previous protection can't be queried on unix, so code is assumed to be readable and executable.
+    pub(in crate::hook) unsafe fn make_writable(address: usize, size: usize) -> io::Result<i32> {
+        let (start, size) = pages(address, size);
+        let protection = libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC;
+        if libc::mprotect(start as *mut libc::c_void, size, protection) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(libc::PROT_READ | libc::PROT_EXEC)
+    }
+
+    pub(in crate::hook) unsafe fn restore(address: usize, size: usize, old: i32) -> io::Result<()> {
+        let (start, size) = pages(address, size);
+        if libc::mprotect(start as *mut libc::c_void, size, old) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(in crate::hook) unsafe fn flush(_address: usize, _size: usize) {
+        // x86-64 keeps the instruction cache coherent.
+    }
+}
+
+#[cfg(not(any(windows, unix)))]
+mod platform {
+    use std::io;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "hooks are not supported")
+    }
+
+    pub(in crate::hook) unsafe fn allocate(_size: usize) -> io::Result<usize> {
+        Err(unsupported())
+    }
+
+    pub(in crate::hook) unsafe fn free(_address: usize, _size: usize) {}
+
+    pub(in crate::hook) unsafe fn make_writable(_address: usize, _size: usize) -> io::Result<u32> {
+        Err(unsupported())
+    }
+
+    pub(in crate::hook) unsafe fn restore(
+        _address: usize,
+        _size: usize,
+        _old: u32,
+    ) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub(in crate::hook) unsafe fn flush(_address: usize, _size: usize) {}
+}
+
+use platform::{allocate, flush, free, make_writable, restore};
@@ -0,0 +1,355 @@
+//! Injects a mod DLL into a running Highfleet process.
+//!
+//! The DLL is loaded by starting a thread in the game that calls `LoadLibraryW` with the path of the DLL.
+//! Only available with the `windows` feature, and only works on Windows.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The name of the game executable, for `find_process`.
+pub const GAME_EXECUTABLE: &str = crate::install::EXECUTABLE;
+
+/// How long `inject` waits for `LoadLibraryW` to return.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returned when injecting a DLL fails.
+#[derive(Debug)]
+pub enum InjectError {
+    /// The DLL doesn't exist.
+    DllNotFound(PathBuf),
+    /// The process is 32-bit while this one is 64-bit, or the other way around.
+    ArchitectureMismatch,
+    /// The process could not be opened, usually because it runs as administrator and this process doesn't.
+    AccessDenied,
+    /// `LoadLibraryW` failed inside of the process, for example because the DLL or one of its dependencies couldn't be loaded.
+    LoadLibraryFailed,
+    /// `LoadLibraryW` didn't return in time. The DLL may still be loaded later.
+    Timeout,
+    /// Injection is not supported on this platform.
+    Unsupported,
+    /// Another operating system call failed.
+    Os(io::Error),
+}
+
+impl fmt::Display for InjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DllNotFound(path) => write!(f, "DLL not found: {}", path.display()),
+            Self::ArchitectureMismatch => {
+                write!(
+                    f,
+                    "the process and the injector have different architectures"
+                )
+            }
+            Self::AccessDenied => write!(
+                f,
+                "access denied, try running the injector with the same privileges as the game"
+            ),
+            Self::LoadLibraryFailed => write!(f, "LoadLibraryW failed inside of the process"),
+            Self::Timeout => write!(f, "timed out waiting for LoadLibraryW"),
+            Self::Unsupported => write!(f, "injection is only supported on Windows"),
+            Self::Os(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for InjectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Os(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for InjectError {
+    fn from(error: io::Error) -> Self {
+        Self::Os(error)
+    }
+}
+
+/// Returns the id of the first running process with the given executable name, compared case insensitively.
+pub fn find_process(executable: &str) -> Option<u32> {
+    sys::find_process(executable)
+}
+
+/// Loads the DLL at `dll` into the process with the given id, waiting up to `DEFAULT_TIMEOUT`.
+pub fn inject(pid: u32, dll: &Path) -> Result<(), InjectError> {
+    inject_with_timeout(pid, dll, DEFAULT_TIMEOUT)
+}
+
+/// Loads the DLL at `dll` into the process with the given id, waiting up to `timeout`.
+pub fn inject_with_timeout(pid: u32, dll: &Path, timeout: Duration) -> Result<(), InjectError> {
+    // The process resolves relative paths against its own working directory.
+    let dll = dll
+        .canonicalize()
+        .map_err(|_| InjectError::DllNotFound(dll.to_path_buf()))?;
+    if !dll.is_file() {
+        return Err(InjectError::DllNotFound(dll));
+    }
+
+    unsafe { sys::inject(pid, &dll, timeout) }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::InjectError;
+
+    const PROCESS_CREATE_THREAD: u32 = 0x0002;
+    const PROCESS_VM_OPERATION: u32 = 0x0008;
+    const PROCESS_VM_READ: u32 = 0x0010;
+    const PROCESS_VM_WRITE: u32 = 0x0020;
+    const PROCESS_QUERY_INFORMATION: u32 = 0x0400;
+    const MEM_COMMIT: u32 = 0x1000;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_READWRITE: u32 = 0x04;
+    const WAIT_OBJECT_0: u32 = 0;
+    const WAIT_TIMEOUT: u32 = 0x102;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const TH32CS_SNAPPROCESS: u32 = 0x2;
+    const INVALID_HANDLE_VALUE: *mut u8 = -1isize as *mut u8;
+
+    #[repr(C)]
+    struct ProcessEntry32W {
+        size: u32,
+        usage: u32,
+        process_id: u32,
+        default_heap_id: usize,
+        module_id: u32,
+        threads: u32,
+        parent_process_id: u32,
+        base_priority: i32,
+        flags: u32,
+        exe_file: [u16; 260],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut u8;
+        fn CloseHandle(handle: *mut u8) -> i32;
+        fn GetCurrentProcess() -> *mut u8;
+        fn IsWow64Process(process: *mut u8, wow64: *mut i32) -> i32;
+        fn VirtualAllocEx(
+            process: *mut u8,
+            address: *mut u8,
+            size: usize,
+            allocation_type: u32,
+            protect: u32,
+        ) -> *mut u8;
+        fn VirtualFreeEx(process: *mut u8, address: *mut u8, size: usize, free_type: u32) -> i32;
+        fn WriteProcessMemory(
+            process: *mut u8,
+            base_address: *mut u8,
+            buffer: *const u8,
+            size: usize,
+            bytes_written: *mut usize,
+        ) -> i32;
+        fn GetModuleHandleA(module_name: *const i8) -> *mut u8;
+        fn GetProcAddress(module: *mut u8, proc_name: *const i8) -> *const u8;
+        fn CreateRemoteThread(
+            process: *mut u8,
+            thread_attributes: *mut u8,
+            stack_size: usize,
+            start_address: *const u8,
+            parameter: *mut u8,
+            creation_flags: u32,
+            thread_id: *mut u32,
+        ) -> *mut u8;
+        fn WaitForSingleObject(handle: *mut u8, milliseconds: u32) -> u32;
+        fn GetExitCodeThread(thread: *mut u8, exit_code: *mut u32) -> i32;
+        fn CreateToolhelp32Snapshot(flags: u32, process_id: u32) -> *mut u8;
+        fn Process32FirstW(snapshot: *mut u8, entry: *mut ProcessEntry32W) -> i32;
+        fn Process32NextW(snapshot: *mut u8, entry: *mut ProcessEntry32W) -> i32;
+    }
+
+    /// Closes a handle when dropped.
+    struct Handle(*mut u8);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    pub(super) fn find_process(executable: &str) -> Option<u32> {
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let snapshot = Handle(snapshot);
+
+            let mut entry: ProcessEntry32W = std::mem::zeroed();
+            entry.size = std::mem::size_of::<ProcessEntry32W>() as u32;
+            let mut found = Process32FirstW(snapshot.0, &mut entry) != 0;
+            while found {
+                let length = entry.exe_file.iter().position(|&c| c == 0).unwrap_or(260);
+                let name = String::from_utf16_lossy(&entry.exe_file[..length]);
+                if name.eq_ignore_ascii_case(executable) {
+                    return Some(entry.process_id);
+                }
+                found = Process32NextW(snapshot.0, &mut entry) != 0;
+            }
+            None
+        }
+    }
+
+    unsafe fn is_wow64(process: *mut u8) -> io::Result<bool> {
+        let mut wow64 = 0;
+        if IsWow64Process(process, &mut wow64) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wow64 != 0)
+    }
+
+    pub(super) unsafe fn inject(
+        pid: u32,
+        dll: &Path,
+        timeout: Duration,
+    ) -> Result<(), InjectError> {
+        let access = PROCESS_CREATE_THREAD
+            | PROCESS_VM_OPERATION
+            | PROCESS_VM_READ
+            | PROCESS_VM_WRITE
+            | PROCESS_QUERY_INFORMATION;
+        let process = OpenProcess(access, 0, pid);
+        if process.is_null() {
+            let error = io::Error::last_os_error();
+            return Err(match error.raw_os_error() {
+                Some(ERROR_ACCESS_DENIED) => InjectError::AccessDenied,
+                _ => InjectError::Os(error),
+            });
+        }
+        let process = Handle(process);
+
+        if is_wow64(process.0)? != is_wow64(GetCurrentProcess())? {
+            return Err(InjectError::ArchitectureMismatch);
+        }
+
+        let path: Vec<u16> = dll.as_os_str().encode_wide().chain([0]).collect();
+        let size = path.len() * 2;
+        let remote_path = VirtualAllocEx(
+            process.0,
+            std::ptr::null_mut(),
+            size,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_READWRITE,
+        );
+        if remote_path.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let result = load_library(process.0, remote_path, &path, timeout);
+
+        // The path must stay allocated while LoadLibraryW may still be reading it.
+        if !matches!(result, Err(InjectError::Timeout)) {
+            VirtualFreeEx(process.0, remote_path, 0, MEM_RELEASE);
+        }
+        result
+    }
+
+    unsafe fn load_library(
+        process: *mut u8,
+        remote_path: *mut u8,
+        path: &[u16],
+        timeout: Duration,
+    ) -> Result<(), InjectError> {
+        let size = path.len() * 2;
+        let mut written = 0;
+        if WriteProcessMemory(process, remote_path, path.as_ptr() as _, size, &mut written) == 0
+            || written != size
+        {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // kernel32 is mapped at the same address in every process of a session.
+        let kernel32 = CString::new("kernel32.dll").unwrap();
+        let load_library = CString::new("LoadLibraryW").unwrap();
+        let load_library =
+            GetProcAddress(GetModuleHandleA(kernel32.as_ptr()), load_library.as_ptr());
+        if load_library.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let thread = CreateRemoteThread(
+            process,
+            std::ptr::null_mut(),
+            0,
+            load_library,
+            remote_path,
+            0,
+            std::ptr::null_mut(),
+        );
+        if thread.is_null() {
+            return Err(io::Error::last_os_error().into());
+        }
+        let thread = Handle(thread);
+
+        let milliseconds = timeout.as_millis().min(u32::MAX as u128 - 1) as u32;
+        match WaitForSingleObject(thread.0, milliseconds) {
+            WAIT_OBJECT_0 => {}
+            WAIT_TIMEOUT => return Err(InjectError::Timeout),
+            _ => return Err(io::Error::last_os_error().into()),
+        }
+
+        // The exit code is the low half of the module handle returned by LoadLibraryW, 0 on failure.
+        let mut exit_code = 0;
+        if GetExitCodeThread(thread.0, &mut exit_code) == 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if exit_code == 0 {
+            return Err(InjectError::LoadLibraryFailed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::InjectError;
+
+    pub(super) fn find_process(_executable: &str) -> Option<u32> {
+        None
+    }
+
+    pub(super) unsafe fn inject(
+        _pid: u32,
+        _dll: &Path,
+        _timeout: Duration,
+    ) -> Result<(), InjectError> {
+        Err(InjectError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_dll() {
+        let error = inject(std::process::id(), Path::new("does/not/exist.dll")).unwrap_err();
+        assert!(matches!(error, InjectError::DllNotFound(_)));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unsupported_outside_windows() {
+        let dll = std::env::current_exe().unwrap();
+        let error = inject(std::process::id(), &dll).unwrap_err();
+        assert!(matches!(error, InjectError::Unsupported));
+        assert_eq!(find_process(GAME_EXECUTABLE), None);
+    }
+}
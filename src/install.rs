@@ -0,0 +1,291 @@
+//! Locates the Highfleet installation and the asset folders inside of it.
+//!
+//! `Installation::find` looks at the `HIGHFLEET_DIR` environment variable first, then at every Steam library.
+//! Steam libraries are listed in `libraryfolders.vdf`, and the game's folder in its app manifest.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::dump::Snapshot;
+use crate::general::version::{self, Fingerprint, GameVersion, PeInfo};
+
+/// The name of the game executable.
+pub const EXECUTABLE: &str = "HighFleet.exe";
+
+/// The Steam app id of Highfleet.
+pub const STEAM_APP_ID: u32 = 1434950;
+
+/// The environment variable that overrides where the game is looked for.
+pub const DIR_VARIABLE: &str = "HIGHFLEET_DIR";
+
+/// How many bytes of the executable are read to find its PE headers.
+const HEADER_SIZE: u64 = 0x1000;
+
+/// A folder the game is installed in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installation {
+    root: PathBuf,
+}
+
+impl Installation {
+    /// Uses the given folder, if it holds the game executable.
+    pub fn new(root: impl Into<PathBuf>) -> Option<Self> {
+        let root = root.into();
+        root.join(EXECUTABLE).is_file().then_some(Self { root })
+    }
+
+    /// Finds the installation, from `HIGHFLEET_DIR` or the Steam libraries.
+    pub fn find() -> Option<Self> {
+        if let Some(root) = env::var_os(DIR_VARIABLE) {
+            return Self::new(root);
+        }
+
+        steam_roots()
+            .iter()
+            .flat_map(|steam| steam_libraries(steam))
+            .find_map(|library| Self::find_in_steam_library(&library))
+    }
+
+    /// Finds the installation in a Steam library folder, the one holding `steamapps`.
+    pub fn find_in_steam_library(library: &Path) -> Option<Self> {
+        let steamapps = library.join("steamapps");
+        let manifest =
+            fs::read_to_string(steamapps.join(format!("appmanifest_{STEAM_APP_ID}.acf"))).ok()?;
+        let folder = vdf_values(&manifest, "installdir").next()?;
+        Self::new(steamapps.join("common").join(folder))
+    }
+
+    /// Returns the folder the game is installed in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of the game executable.
+    pub fn executable(&self) -> PathBuf {
+        self.root.join(EXECUTABLE)
+    }
+
+    /// Returns the `Objects` folder.
+    pub fn objects(&self) -> PathBuf {
+        self.root.join("Objects")
+    }
+
+    /// Returns the `Ships` folder.
+    pub fn ships(&self) -> PathBuf {
+        self.root.join("Ships")
+    }
+
+    /// Returns the `Tex` folder, which holds the `.res` files of the images.
+    pub fn tex(&self) -> PathBuf {
+        self.root.join("Tex")
+    }
+
+    /// Returns the `Sounds` folder.
+    pub fn sounds(&self) -> PathBuf {
+        self.root.join("Sounds")
+    }
+
+    /// Reads the identifying PE header values of the executable.
+    ///
+    /// Returns `None` if the executable isn't a PE file.
+    pub fn pe_info(&self) -> io::Result<Option<PeInfo>> {
+        Ok(version::read_pe_info_from(&self.headers()?, 0))
+    }
+
+    /// Detects the installed version using the given fingerprints, such as `version::KNOWN_BUILDS`.
+    pub fn version(&self, fingerprints: &[Fingerprint]) -> io::Result<Option<GameVersion>> {
+        Ok(version::detect_from(&self.headers()?, 0, fingerprints))
+    }
+
+    /// Reads the start of the executable, which holds the same headers as the module mapped from it.
+    fn headers(&self) -> io::Result<Snapshot> {
+        let mut headers = Vec::new();
+        fs::File::open(self.executable())?
+            .take(HEADER_SIZE)
+            .read_to_end(&mut headers)?;
+
+        let mut file = Snapshot::new(0, None);
+        file.insert(0, &headers);
+        Ok(file)
+    }
+}
+
+/// Returns the folders Steam may be installed in, the ones that don't exist left out.
+pub fn steam_roots() -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = sys::registry_steam_path().into_iter().collect();
+
+    if cfg!(windows) {
+        for variable in ["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Some(folder) = env::var_os(variable) {
+                roots.push(PathBuf::from(folder).join("Steam"));
+            }
+        }
+    } else if let Some(home) = env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        roots.push(home.join(".steam/steam"));
+        roots.push(home.join(".local/share/Steam"));
+    }
+
+    roots.retain(|root| root.is_dir());
+    roots.dedup();
+    roots
+}
+
+/// Returns the library folders of a Steam installation, including the installation itself.
+pub fn steam_libraries(steam_root: &Path) -> Vec<PathBuf> {
+    let mut libraries = vec![steam_root.to_path_buf()];
+
+    if let Ok(folders) = fs::read_to_string(steam_root.join("steamapps/libraryfolders.vdf")) {
+        for path in vdf_values(&folders, "path") {
+            let path = PathBuf::from(path);
+            if !libraries.contains(&path) {
+                libraries.push(path);
+            }
+        }
+    }
+    libraries
+}
+
+/// Returns the values of every `"key" "value"` pair with the given key in a Valve KeyValues file.
+fn vdf_values<'a>(text: &'a str, key: &'a str) -> impl Iterator<Item = String> + 'a {
+    text.lines().filter_map(move |line| {
+        let mut strings = line.split('"').skip(1).step_by(2);
+        if strings.next()? != key {
+            return None;
+        }
+        Some(strings.next()?.replace("\\\\", "\\"))
+    })
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::PathBuf;
+
+    const HKEY_CURRENT_USER: isize = 0x8000_0001u32 as i32 as isize;
+    const RRF_RT_REG_SZ: u32 = 0x2;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn RegGetValueW(
+            key: isize,
+            sub_key: *const u16,
+            value: *const u16,
+            flags: u32,
+            kind: *mut u32,
+            data: *mut u16,
+            data_size: *mut u32,
+        ) -> i32;
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        std::ffi::OsStr::new(text)
+            .encode_wide()
+            .chain(Some(0))
+            .collect()
+    }
+
+    /// Reads the folder Steam is installed in from the registry.
+    pub(super) fn registry_steam_path() -> Option<PathBuf> {
+        let sub_key = wide("Software\\Valve\\Steam");
+        let value = wide("SteamPath");
+        let mut buffer = [0u16; 1024];
+        let mut size = (buffer.len() * 2) as u32;
+
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                sub_key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr(),
+                &mut size,
+            )
+        };
+        if status != 0 {
+            return None;
+        }
+
+        // The size includes the terminating nul.
+        let length = (size as usize / 2).saturating_sub(1);
+        Some(OsString::from_wide(&buffer[..length]).into())
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    use std::path::PathBuf;
+
+    pub(super) fn registry_steam_path() -> Option<PathBuf> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY_FOLDERS: &str = r#""libraryfolders"
+{
+	"0"
+	{
+		"path"		"C:\\Program Files (x86)\\Steam"
+		"apps"
+		{
+			"228980"		"0"
+		}
+	}
+	"1"
+	{
+		"path"		"D:\\SteamLibrary"
+	}
+}
+"#;
+
+    #[test]
+    fn parse_vdf() {
+        let paths: Vec<_> = vdf_values(LIBRARY_FOLDERS, "path").collect();
+        assert_eq!(
+            paths,
+            ["C:\\Program Files (x86)\\Steam", "D:\\SteamLibrary"]
+        );
+        assert_eq!(vdf_values(LIBRARY_FOLDERS, "apps").count(), 0);
+    }
+
+    #[test]
+    fn find_in_library() {
+        let library = env::temp_dir().join(format!("highfleet-rs-install-{}", std::process::id()));
+        let game = library.join("steamapps/common/HighFleet");
+        fs::create_dir_all(&game).unwrap();
+        fs::write(
+            library.join(format!("steamapps/appmanifest_{STEAM_APP_ID}.acf")),
+            "\"AppState\"\n{\n\t\"appid\"\t\t\"1434950\"\n\t\"installdir\"\t\t\"HighFleet\"\n}\n",
+        )
+        .unwrap();
+
+        // Not an installation until the executable exists.
+        assert_eq!(Installation::find_in_steam_library(&library), None);
+
+        let mut headers = vec![0u8; 0x200];
+        headers[0..2].copy_from_slice(b"MZ");
+        headers[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        headers[0x80..0x84].copy_from_slice(b"PE\0\0");
+        headers[0x88..0x8c].copy_from_slice(&0x6000_0000u32.to_le_bytes());
+        fs::write(game.join(EXECUTABLE), &headers).unwrap();
+
+        let installation = Installation::find_in_steam_library(&library).unwrap();
+        assert_eq!(installation.root(), game);
+        assert_eq!(installation.ships(), game.join("Ships"));
+        assert_eq!(
+            installation.pe_info().unwrap().unwrap().timestamp,
+            0x6000_0000
+        );
+        assert_eq!(installation.version(version::KNOWN_BUILDS).unwrap(), None);
+
+        fs::remove_dir_all(&library).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+//! A JSON-RPC 2.0 server exposing the live game to external tools, such as overlays, stream widgets, and editors.
+//!
+//! Requests and responses are single lines of JSON over a local TCP connection, after a first line holding
+//! a token chosen by the mod:
+//!
+//! ```text
+//! --> 3f9c2e7a41d8
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "ammos.patch", "params": {"name": "AMMO_57", "patch": {"speed": 1200.0}}}
+//! <-- {"id":1,"jsonrpc":"2.0","result":null}
+//! ```
+//!
+//! A `Router` maps method names to handlers working on the mod's state. `list_ammos`, `get_ammo`, and `patch_ammo`
+//! are ready made handlers for an `AmmoTable`, so tools never touch the game's memory themselves.
+//! Like the `CommandServer` of the console, requests are queued and run by `IpcServer::poll` on the game's main thread.
+//!
+//! ```ignore
+//! let mut router = Router::new();
+//! router.register("ammos.list", |ammos: &mut AmmoTable<Ammo>, params| ipc::list_ammos(ammos, params));
+//! let token = std::fs::read_to_string("ipc_token.txt")?;
+//! let server = IpcServer::bind("127.0.0.1:7879", &token)?;
+//!
+//! // Every frame, from a hook on the game's main loop:
+//! server.poll(&router, &mut ammos, &thread);
+//! ```
+//!
+//! Only available with the `ipc` feature.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::console::LineServer;
+use crate::general::{AmmoFields, AmmoTable, GameThread, Patch};
+
+/// A JSON-RPC error, sent back as the `error` of a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    /// The error code. The codes from -32768 to -32000 are reserved by JSON-RPC.
+    pub code: i64,
+    /// A short description of the error.
+    pub message: String,
+}
+
+impl RpcError {
+    /// The request isn't valid JSON.
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The request isn't a valid JSON-RPC request.
+    pub const INVALID_REQUEST: i64 = -32600;
+    /// No method has the requested name.
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    /// The params don't match the method.
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The method failed.
+    pub const FAILED: i64 = -32000;
+
+    /// Creates an error with the given code.
+    pub fn new(code: i64, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// Creates an `INVALID_PARAMS` error.
+    pub fn invalid_params(message: impl fmt::Display) -> Self {
+        Self::new(Self::INVALID_PARAMS, message)
+    }
+
+    /// Creates a `FAILED` error.
+    pub fn failed(message: impl fmt::Display) -> Self {
+        Self::new(Self::FAILED, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+type Method<S> = Box<dyn Fn(&mut S, Value) -> Result<Value, RpcError> + Send + Sync>;
+
+/// Maps method names to handlers working on the mod's state `S`.
+pub struct Router<S> {
+    methods: BTreeMap<String, Method<S>>,
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self {
+            methods: BTreeMap::new(),
+        }
+    }
+}
+
+impl<S> Router<S> {
+    /// Creates a router with only the built in `rpc.methods` method, which lists every method.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a method, replacing any other method with the same name.
+    ///
+    /// The handler gets the `params` of the request, or `null` if there are none.
+    pub fn register(
+        &mut self,
+        name: &str,
+        handler: impl Fn(&mut S, Value) -> Result<Value, RpcError> + Send + Sync + 'static,
+    ) {
+        self.methods.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Calls a method directly.
+    pub fn call(&self, state: &mut S, method: &str, params: Value) -> Result<Value, RpcError> {
+        match self.methods.get(method) {
+            Some(handler) => handler(state, params),
+            None if method == "rpc.methods" => Ok(json!(self.methods.keys().collect::<Vec<_>>())),
+            None => Err(RpcError::new(
+                RpcError::METHOD_NOT_FOUND,
+                format!("unknown method {method}"),
+            )),
+        }
+    }
+
+    /// Handles a line holding a JSON-RPC request, returning the response line.
+    ///
+    /// Notifications, requests without an `id`, get no response. Batches aren't supported.
+    pub fn handle(&self, state: &mut S, line: &str) -> Option<String> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(error) => {
+                return Some(response(
+                    Value::Null,
+                    Err(RpcError::new(RpcError::PARSE_ERROR, error)),
+                ))
+            }
+        };
+
+        let id = request.get("id").cloned();
+        let result = match (request.get("jsonrpc"), request.get("method")) {
+            (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => {
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                self.call(state, method, params)
+            }
+            _ => Err(RpcError::new(
+                RpcError::INVALID_REQUEST,
+                "expected jsonrpc 2.0 and a method",
+            )),
+        };
+
+        id.map(|id| response(id, result))
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    };
+    response.to_string()
+}
+
+/// Takes JSON-RPC requests over local TCP connections, one per line, and answers them with a `Router`.
+///
+/// The first line of a connection must be the token given to `bind`, otherwise the connection is closed.
+/// The server stops listening when dropped.
+pub struct IpcServer {
+    lines: LineServer,
+}
+
+impl IpcServer {
+    /// Listens on the given address, such as `"127.0.0.1:7879"`, on a background thread.
+    ///
+    /// Clients must send `token` as their first line. Only bind to a loopback address and keep the token secret:
+    /// anyone who can connect and knows it can change the game.
+    /// Returns an `InvalidInput` error if the token is empty.
+    pub fn bind(address: impl ToSocketAddrs, token: &str) -> io::Result<Self> {
+        Ok(Self {
+            lines: LineServer::bind(address, token)?,
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.lines.local_addr()
+    }
+
+    /// Answers every queued request with `router`, returning how many were handled.
+    ///
+    /// Call it regularly from the game's main thread, such as from a hook on a function called every frame.
+    pub fn poll<S>(&self, router: &Router<S>, state: &mut S, _thread: &GameThread) -> usize {
+        self.lines.poll(|line| router.handle(state, line))
+    }
+}
+
+/// Reads a string parameter of an object.
+fn param<'p>(params: &'p Value, name: &str) -> Result<&'p str, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("expected a string {name}")))
+}
+
+/// Handler without params: returns the `index` and `name` of every ammo.
+pub fn list_ammos<A: AmmoFields>(
+    table: &mut AmmoTable<'_, A>,
+    _params: Value,
+) -> Result<Value, RpcError> {
+    Ok(table
+        .iter()
+        .map(|ammo| json!({ "index": ammo.index(), "name": ammo.item_name().to_string() }))
+        .collect())
+}
+
+/// Handler for `{"name": ...}`: returns every field of the named ammo.
+pub fn get_ammo<A: AmmoFields + Serialize>(
+    table: &mut AmmoTable<'_, A>,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let name = param(&params, "name")?;
+    let ammo = table
+        .get_by_name_mut(name)
+        .ok_or_else(|| RpcError::failed(format!("no ammo is named {name}")))?;
+    serde_json::to_value(&*ammo).map_err(RpcError::failed)
+}
+
+/// Handler for `{"name": ..., "patch": {...}}`: applies a `Patch` to the named ammo.
+///
+/// Changed strings are reallocated, so set the `GameCrt` allocator before patching ammos of the game.
+pub fn patch_ammo<A: AmmoFields + Serialize + DeserializeOwned>(
+    table: &mut AmmoTable<'_, A>,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let patch: Patch = params
+        .get("patch")
+        .map(|patch| serde_json::from_value(patch.clone()))
+        .ok_or_else(|| RpcError::invalid_params("expected a patch"))?
+        .map_err(RpcError::invalid_params)?;
+
+    let name = param(&params, "name")?;
+    let ammo = table
+        .get_by_name_mut(name)
+        .ok_or_else(|| RpcError::failed(format!("no ammo is named {name}")))?;
+    patch.apply(ammo).map_err(RpcError::failed)?;
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v1_163::{Ammo, AmmoBuilder};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+
+    fn router<'a>() -> Router<AmmoTable<'a, Ammo>> {
+        let mut router = Router::new();
+        router.register("ammos.list", list_ammos);
+        router.register("ammos.get", get_ammo);
+        router.register("ammos.patch", patch_ammo);
+        router
+    }
+
+    #[test]
+    fn handles_ammo_methods() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let router = router();
+
+        let response = router.handle(
+            &mut table,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "ammos.patch", "params": {"name": "AMMO_57", "patch": {"speed": 1200.0}}}"#,
+        );
+        assert_eq!(
+            response.unwrap(),
+            r#"{"id":1,"jsonrpc":"2.0","result":null}"#
+        );
+        assert_eq!(table.get_by_name("AMMO_57").unwrap().speed, 1200.0);
+
+        let response = router.handle(
+            &mut table,
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "ammos.list"}"#,
+        );
+        assert_eq!(
+            response.unwrap(),
+            r#"{"id":"a","jsonrpc":"2.0","result":[{"index":1,"name":"AMMO_57"},{"index":2,"name":"AMMO_85"}]}"#
+        );
+
+        let speed = router
+            .call(&mut table, "ammos.get", json!({ "name": "AMMO_57" }))
+            .unwrap()["speed"]
+            .clone();
+        assert_eq!(speed, json!(1200.0));
+
+        assert_eq!(
+            router.call(&mut table, "rpc.methods", Value::Null),
+            Ok(json!(["ammos.get", "ammos.list", "ammos.patch"]))
+        );
+    }
+
+    fn error_code<'a>(
+        router: &Router<AmmoTable<'a, Ammo>>,
+        table: &mut AmmoTable<'a, Ammo>,
+        line: &str,
+    ) -> i64 {
+        let response: Value = serde_json::from_str(&router.handle(table, line).unwrap()).unwrap();
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let router = router();
+
+        assert_eq!(error_code(&router, &mut table, "{"), RpcError::PARSE_ERROR);
+        assert_eq!(
+            error_code(&router, &mut table, r#"{"id": 1, "method": "ammos.list"}"#),
+            RpcError::INVALID_REQUEST
+        );
+        assert_eq!(
+            error_code(
+                &router,
+                &mut table,
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "ships.stats"}"#
+            ),
+            RpcError::METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            error_code(
+                &router,
+                &mut table,
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "ammos.get", "params": {}}"#
+            ),
+            RpcError::INVALID_PARAMS
+        );
+        assert_eq!(
+            error_code(
+                &router,
+                &mut table,
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "ammos.patch", "params": {"name": "AMMO_57", "patch": {"warp": 9}}}"#
+            ),
+            RpcError::FAILED
+        );
+
+        // Notifications get no response, even when they fail.
+        assert_eq!(
+            router.handle(&mut table, r#"{"jsonrpc": "2.0", "method": "ammos.list"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn serves_requests_over_tcp() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let router = router();
+
+        let server = IpcServer::bind("127.0.0.1:0", "secret").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"secret\n{\"jsonrpc\": \"2.0\", \"method\": \"ammos.list\"}\n{\"jsonrpc\": \"2.0\", \"id\": 7, \"method\": \"ammos.get\", \"params\": {\"name\": \"AMMO_85\"}}\n")
+            .unwrap();
+
+        let thread = unsafe { GameThread::new() };
+        let mut handled = 0;
+        while handled < 2 {
+            handled += server.poll(&router, &mut table, &thread);
+            std::thread::yield_now();
+        }
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["item_name"], "AMMO_85");
+    }
+
+    #[test]
+    fn ignores_unauthenticated_clients() {
+        let mut ammos = AmmoBuilder::test_ammos();
+        let mut table = AmmoTable::from_slice(&mut ammos);
+        let router = router();
+        let speed = table.get_by_name("AMMO_57").unwrap().speed;
+        assert_ne!(speed, 1200.0);
+
+        let patch = r#"{"jsonrpc": "2.0", "id": 1, "method": "ammos.patch", "params": {"name": "AMMO_57", "patch": {"speed": 1200.0}}}"#;
+        let server = IpcServer::bind("127.0.0.1:0", "secret").unwrap();
+        for request in [
+            format!("{patch}\n"),
+            format!("wrong\n{patch}\n"),
+            format!("POST / HTTP/1.1\r\nContent-Type: text/plain\r\n\r\nsecret\n{patch}\n"),
+        ] {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+
+            let mut response = String::new();
+            let _ = BufReader::new(stream).read_line(&mut response);
+            assert_eq!(response, "");
+        }
+
+        let thread = unsafe { GameThread::new() };
+        assert_eq!(server.poll(&router, &mut table, &thread), 0);
+        assert_eq!(table.get_by_name("AMMO_57").unwrap().speed, speed);
+    }
+}
@@ -0,0 +1,215 @@
+//! Finds which bytes of a struct change between two snapshots, to identify fields like `unknown_180h`.
+//!
+//! Take a `Snapshot` of the struct, make a known change in the game, such as buying ammo, and take another.
+//! `probe_struct` then lists the changed byte ranges relative to the start of the struct,
+//! split at the known fields so each range names the field it falls in.
+//!
+//! ```ignore
+//! let mut before = Snapshot::new(base, Some(GameVersion::V1_163));
+//! before.capture_struct::<Ammo, _>(&game, address)?;
+//! // Buy the ammo in the game.
+//! let mut after = Snapshot::new(base, Some(GameVersion::V1_163));
+//! after.capture_struct::<Ammo, _>(&game, address)?;
+//!
+//! for range in layout_probe::probe_struct::<Ammo, _, _>(&before, &after, address)? {
+//!     println!("{range}");
+//! }
+//! ```
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::size_of;
+
+use crate::general::hexdump::{Annotated, FieldInfo};
+use crate::memory::{MemoryError, MemorySource};
+
+/// A range of bytes that differ between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedRange {
+    /// The offset of the first changed byte from the start of the struct.
+    pub offset: usize,
+    /// The bytes before the change.
+    pub before: Vec<u8>,
+    /// The bytes after the change.
+    pub after: Vec<u8>,
+    /// The name of the known field the bytes belong to, if any.
+    pub field: Option<&'static str>,
+}
+
+impl ChangedRange {
+    /// Returns the number of changed bytes.
+    pub fn len(&self) -> usize {
+        self.before.len()
+    }
+
+    /// Returns true if no bytes changed, which `probe` never returns.
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty()
+    }
+}
+
+impl fmt::Display for ChangedRange {
+    /// Writes a line like `0x180..0x181 unknown_180h: 0a -> 14`.
+    ///
+    /// Ranges of exactly 4 aligned bytes also show their values as `i32` and `f32`, the types most fields have.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}..{:#x}", self.offset, self.offset + self.len())?;
+        if let Some(field) = self.field {
+            write!(f, " {field}")?;
+        }
+        f.write_str(":")?;
+        for byte in &self.before {
+            write!(f, " {byte:02x}")?;
+        }
+        f.write_str(" ->")?;
+        for byte in &self.after {
+            write!(f, " {byte:02x}")?;
+        }
+
+        if let (Ok(before), Ok(after)) = (
+            <[u8; 4]>::try_from(self.before.as_slice()),
+            <[u8; 4]>::try_from(self.after.as_slice()),
+        ) {
+            if self.offset.is_multiple_of(4) {
+                write!(
+                    f,
+                    " (i32 {} -> {}, f32 {} -> {})",
+                    i32::from_le_bytes(before),
+                    i32::from_le_bytes(after),
+                    f32::from_le_bytes(before),
+                    f32::from_le_bytes(after)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the ranges of bytes that differ between `before` and `after`, compared up to the shorter one.
+///
+/// Ranges are split where a field of `fields` starts, and named after the field they fall in.
+pub fn changed_ranges(before: &[u8], after: &[u8], fields: &[FieldInfo]) -> Vec<ChangedRange> {
+    let field_at = |offset: usize| {
+        fields
+            .iter()
+            .find(|field| (field.offset..field.offset + field.kind.size()).contains(&offset))
+    };
+
+    let mut ranges: Vec<ChangedRange> = Vec::new();
+    for (offset, (&old, &new)) in before.iter().zip(after).enumerate() {
+        if old == new {
+            continue;
+        }
+
+        let field = field_at(offset);
+        let continues = ranges.last().is_some_and(|last| {
+            last.offset + last.len() == offset
+                && !fields.iter().any(|field| field.offset == offset)
+                && last.field == field.map(|field| field.name)
+        });
+        match ranges.last_mut() {
+            Some(last) if continues => {
+                last.before.push(old);
+                last.after.push(new);
+            }
+            _ => ranges.push(ChangedRange {
+                offset,
+                before: vec![old],
+                after: vec![new],
+                field: field.map(|field| field.name),
+            }),
+        }
+    }
+    ranges
+}
+
+/// Compares `size` bytes at `address` in two memory sources, usually two snapshots.
+pub fn probe<M: MemorySource, N: MemorySource>(
+    before: &M,
+    after: &N,
+    address: u64,
+    size: usize,
+) -> Result<Vec<ChangedRange>, MemoryError> {
+    let (old, new) = read_both(before, after, address, size)?;
+    Ok(changed_ranges(&old, &new, &[]))
+}
+
+/// Compares the struct at `address` in two memory sources, naming the fields the changed bytes fall in.
+pub fn probe_struct<T: Annotated, M: MemorySource, N: MemorySource>(
+    before: &M,
+    after: &N,
+    address: u64,
+) -> Result<Vec<ChangedRange>, MemoryError> {
+    let (old, new) = read_both(before, after, address, size_of::<T>())?;
+    Ok(changed_ranges(&old, &new, &T::fields()))
+}
+
+fn read_both<M: MemorySource, N: MemorySource>(
+    before: &M,
+    after: &N,
+    address: u64,
+    size: usize,
+) -> Result<(Vec<u8>, Vec<u8>), MemoryError> {
+    let mut old = vec![0u8; size];
+    let mut new = vec![0u8; size];
+    before.read(address, &mut old)?;
+    after.read(address, &mut new)?;
+    Ok((old, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::Snapshot;
+    use crate::general::raw::{from_bytes, to_bytes, PointerPolicy};
+    use crate::v1_163::Ammo;
+    use alloc::string::ToString;
+
+    #[test]
+    fn merges_contiguous_bytes() {
+        let ranges = changed_ranges(&[0, 1, 2, 3, 4, 5], &[0, 9, 9, 3, 4, 9], &[]);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].offset, 1);
+        assert_eq!(ranges[0].after, [9, 9]);
+        assert_eq!(ranges[1].offset, 5);
+        assert_eq!(ranges[1].to_string(), "0x5..0x6: 05 -> 09");
+    }
+
+    #[test]
+    fn probes_struct_fields() {
+        let address = 0x1400_0000;
+        let mut ammo: Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+        ammo.unknown_180h = 10;
+
+        let mut before = Snapshot::new(address, None);
+        before.insert(address, &to_bytes(&ammo, &PointerPolicy::Zero));
+        ammo.unknown_180h = 20;
+        ammo.speed = 1.0;
+        let mut after = Snapshot::new(address, None);
+        after.insert(address, &to_bytes(&ammo, &PointerPolicy::Zero));
+
+        let ranges = probe_struct::<Ammo, _, _>(&before, &after, address).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].field, Some("speed"));
+        assert_eq!(ranges[1].to_string(), "0x180..0x181 unknown_180h: 0a -> 14");
+
+        // Without fields, the same bytes are found by offset only.
+        assert_eq!(probe(&before, &after, address, 0x188).unwrap().len(), 2);
+        assert!(probe(&before, &after, address + 0x100, 0x188).is_err());
+    }
+
+    #[test]
+    fn splits_at_field_boundaries() {
+        let fields = Ammo::fields();
+        let mut before = vec![0u8; 0x188];
+        let mut after = before.clone();
+        after[0x15c..0x164].fill(0xff);
+        before[0x15c] = 1;
+
+        let ranges = changed_ranges(&before, &after, &fields);
+        let names: Vec<_> = ranges.iter().map(|range| range.field.unwrap()).collect();
+        assert_eq!(names, ["ap_drag", "explosive_power"]);
+        assert!(ranges[0].to_string().contains("(i32 1 -> -1"));
+    }
+}
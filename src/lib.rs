@@ -1,7 +1,55 @@
 //! A rust library that defines types for interoperability with the game Highfleet.
 
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+// Lets the code generated by `highfleet-derive` name this crate as `::highfleet` from inside of it too.
+extern crate self as highfleet;
+
+/// Items used by the code generated by `highfleet-derive`, which can't assume the dependent names `alloc`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::string::ToString;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
+
+pub mod any;
+#[cfg(feature = "std")]
+pub mod calc;
+#[cfg(feature = "std")]
+pub mod console;
+#[cfg(feature = "std")]
+pub mod dump;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod general;
+#[cfg(feature = "std")]
+pub mod hook;
+#[cfg(feature = "windows")]
+pub mod inject;
+#[cfg(feature = "std")]
+pub mod install;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+pub mod layout_probe;
+#[cfg(feature = "windows")]
+pub mod loader;
+#[cfg(feature = "std")]
+pub mod localization;
+#[cfg(feature = "log")]
+pub mod logging;
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod modpack;
+#[cfg(feature = "std")]
+pub mod offsets;
+pub mod res;
+pub mod seria;
 pub mod v1_151;
 pub mod v1_163;
+#[cfg(feature = "std")]
+pub mod watch;
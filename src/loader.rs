@@ -0,0 +1,270 @@
+//! Scaffolding for loading a mod as a proxy DLL, without an external injector.
+//!
+//! The game loads DLLs such as `dinput8.dll` from its own directory before the system directory.
+//! A mod built as a `cdylib` named like one of them gets loaded automatically. It has to export every function
+//! the game imports from the real DLL, and forwards those calls to the real DLL in the system directory.
+//!
+//! `proxy_dinput8!` defines the exports of `dinput8.dll`. `proxy_dll!` defines the exports of any other DLL,
+//! such as the `winmm.dll` functions the game imports, given their signatures.
+//! Both define a `DllMain` that calls an init callback once version detection has run.
+//!
+//! ```ignore
+//! fn init(context: &highfleet::loader::LoadContext) {
+//!     println!("Highfleet {:?} loaded at {:#x}", context.version, context.module_base);
+//! }
+//!
+//! highfleet::proxy_dinput8!(init);
+//! ```
+//!
+//! Only available with the `windows` feature.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use crate::general::version::{detect_from, GameVersion, KNOWN_BUILDS};
+use crate::memory::InProcess;
+
+/// The `DllMain` reason for a DLL being loaded into a process.
+pub const DLL_PROCESS_ATTACH: u32 = 1;
+
+/// What is known about the game once the mod is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadContext {
+    /// The base address of the game executable.
+    pub module_base: u64,
+    /// The version of the game, or `None` if it isn't in `KNOWN_BUILDS`.
+    pub version: Option<GameVersion>,
+}
+
+impl LoadContext {
+    /// Finds the game executable of the current process and detects its version.
+    pub fn detect() -> Self {
+        let module_base = sys::game_module_base().unwrap_or(0);
+        let version = (module_base != 0)
+            .then(|| detect_from(&InProcess, module_base, KNOWN_BUILDS))
+            .flatten();
+        Self {
+            module_base,
+            version,
+        }
+    }
+}
+
+/// Runs `init` on a new thread once the DLL is attached, with the detected `LoadContext`.
+///
+/// Called by the `DllMain` defined by `proxy_dll!`.
+/// The callback runs outside of `DllMain`, so it may load libraries and wait on other threads.
+pub fn on_attach(init: fn(&LoadContext)) {
+    std::thread::spawn(move || init(&LoadContext::detect()));
+}
+
+/// The real system DLLs loaded so far, keyed by name.
+static SYSTEM_DLLS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+/// Returns the address of an export of the real DLL with the given name in the system directory.
+///
+/// The DLL is loaded on first use. Returns `None` if it can't be loaded, or doesn't have the export.
+pub fn system_export(dll: &str, export: &str) -> Option<usize> {
+    let module = {
+        let mut dlls = SYSTEM_DLLS.lock().unwrap_or_else(PoisonError::into_inner);
+        let dlls = dlls.get_or_insert_with(HashMap::new);
+        match dlls.get(dll) {
+            Some(&module) => module,
+            None => {
+                let module = unsafe { sys::load_system_dll(dll)? };
+                dlls.insert(dll.to_string(), module);
+                module
+            }
+        }
+    };
+
+    unsafe { sys::get_export(module, export) }
+}
+
+/// Ends the game when an export of the real DLL can't be found, as the call can't be forwarded.
+///
+/// Called by the exports defined by `proxy_dll!`, which can't return a failure value for every signature,
+/// and must not unwind into the game. The reason is logged, and sent to the debugger on Windows.
+pub fn missing_export(dll: &str, export: &str) -> ! {
+    let message = format!("failed to load {export} from the system {dll}, exiting");
+    #[cfg(feature = "log")]
+    {
+        log::error!("{message}");
+        log::logger().flush();
+    }
+    sys::exit_process(&message)
+}
+
+/// Defines a `DllMain` calling `init` with the `LoadContext`, and exports forwarding to the real DLL.
+///
+/// Every export is listed with its signature, and must match the real one.
+/// If the real DLL or one of its exports can't be found, calling the export ends the game, see `missing_export`.
+///
+/// ```ignore
+/// highfleet::proxy_dll!("winmm.dll", init;
+///     timeGetTime() -> u32;
+///     timeBeginPeriod(period: u32) -> u32;
+/// );
+/// ```
+#[macro_export]
+macro_rules! proxy_dll {
+    ($dll:literal, $init:expr; $($export:ident($($arg:ident: $type:ty),* $(,)?) -> $ret:ty;)*) => {
+        #[no_mangle]
+        pub unsafe extern "system" fn DllMain(
+            _module: *mut u8,
+            reason: u32,
+            _reserved: *mut u8,
+        ) -> i32 {
+            if reason == $crate::loader::DLL_PROCESS_ATTACH {
+                $crate::loader::on_attach($init);
+            }
+            1
+        }
+
+        $(
+            #[no_mangle]
+            pub unsafe extern "system" fn $export($($arg: $type),*) -> $ret {
+                let address = $crate::loader::system_export($dll, stringify!($export))
+                    .unwrap_or_else(|| $crate::loader::missing_export($dll, stringify!($export)));
+                let real = ::std::mem::transmute::<usize, unsafe extern "system" fn($($type),*) -> $ret>(address);
+                real($($arg),*)
+            }
+        )*
+    };
+}
+
+/// Defines a `DllMain` calling `init` with the `LoadContext`, and the exports of `dinput8.dll`.
+///
+/// See the module documentation.
+#[macro_export]
+macro_rules! proxy_dinput8 {
+    ($init:expr) => {
+        $crate::proxy_dll!("dinput8.dll", $init;
+            DirectInput8Create(
+                instance: *mut u8,
+                version: u32,
+                riid: *const u8,
+                out: *mut *mut u8,
+                outer: *mut u8,
+            ) -> i32;
+            DllCanUnloadNow() -> i32;
+            DllGetClassObject(clsid: *const u8, riid: *const u8, out: *mut *mut u8) -> i32;
+            DllRegisterServer() -> i32;
+            DllUnregisterServer() -> i32;
+            GetdfDIJoystick() -> *const u8;
+        );
+    };
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::CString;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleW(module_name: *const u16) -> *mut u8;
+        fn GetSystemDirectoryW(buffer: *mut u16, size: u32) -> u32;
+        fn LoadLibraryW(file_name: *const u16) -> *mut u8;
+        fn GetProcAddress(module: *mut u8, proc_name: *const i8) -> *const u8;
+        fn OutputDebugStringW(output_string: *const u16);
+        fn ExitProcess(exit_code: u32) -> !;
+    }
+
+    pub(super) fn exit_process(message: &str) -> ! {
+        let message: Vec<u16> = message.encode_utf16().chain([0]).collect();
+        unsafe {
+            OutputDebugStringW(message.as_ptr());
+            ExitProcess(1)
+        }
+    }
+
+    pub(super) fn game_module_base() -> Option<u64> {
+        let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+        (!module.is_null()).then_some(module as u64)
+    }
+
+    pub(super) unsafe fn load_system_dll(dll: &str) -> Option<usize> {
+        let mut buffer = [0u16; 260];
+        let length = GetSystemDirectoryW(buffer.as_mut_ptr(), buffer.len() as u32) as usize;
+        if length == 0 || length > buffer.len() {
+            return None;
+        }
+
+        let mut path = PathBuf::from(String::from_utf16_lossy(&buffer[..length]));
+        path.push(dll);
+        let path: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+
+        let module = LoadLibraryW(path.as_ptr());
+        (!module.is_null()).then_some(module as usize)
+    }
+
+    pub(super) unsafe fn get_export(module: usize, export: &str) -> Option<usize> {
+        let export = CString::new(export).ok()?;
+        let address = GetProcAddress(module as *mut u8, export.as_ptr());
+        (!address.is_null()).then_some(address as usize)
+    }
+}
+
+#[cfg(not(windows))]
+mod sys {
+    pub(super) fn exit_process(message: &str) -> ! {
+        eprintln!("{message}");
+        std::process::exit(1)
+    }
+
+    pub(super) fn game_module_base() -> Option<u64> {
+        None
+    }
+
+    pub(super) unsafe fn load_system_dll(_dll: &str) -> Option<usize> {
+        None
+    }
+
+    pub(super) unsafe fn get_export(_module: usize, _export: &str) -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::sync::OnceLock;
+
+    static CONTEXT: OnceLock<mpsc::SyncSender<LoadContext>> = OnceLock::new();
+
+    fn init(context: &LoadContext) {
+        CONTEXT.get().unwrap().send(*context).unwrap();
+    }
+
+    mod exports {
+        crate::proxy_dll!("test_proxy.dll", super::init;
+            test_proxy_answer(value: u32) -> u32;
+        );
+    }
+
+    #[test]
+    fn dll_main_runs_init() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        CONTEXT.set(sender).unwrap();
+
+        let result = unsafe {
+            exports::DllMain(
+                std::ptr::null_mut(),
+                DLL_PROCESS_ATTACH,
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(result, 1);
+
+        let context = receiver.recv().unwrap();
+        assert_eq!(context.version, None);
+    }
+
+    #[test]
+    fn missing_system_export() {
+        assert_eq!(system_export("dinput8.dll", "DirectInput8Create"), None);
+    }
+}
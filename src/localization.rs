@@ -0,0 +1,119 @@
+//! Resolves localization tokens, like the `@INCENDIARY` in `shell_kind2`, to the text shown in each language.
+//!
+//! Tokens start with `@`. They can be looked up with or without it, so `"@INCENDIARY"` and `"INCENDIARY"` are the same token.
+//! Mods can add tokens for their own ammos with `insert`.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// The text of every token, per language.
+#[derive(Debug, Clone, Default)]
+pub struct Localization {
+    /// The texts of each language, keyed by token without the `@`.
+    languages: BTreeMap<String, HashMap<String, String>>,
+    /// The language used for tokens missing from the requested one.
+    fallback: Option<String>,
+}
+
+impl Localization {
+    /// Creates a localization without any language.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the language used when a token is missing from the requested language, usually "english".
+    pub fn set_fallback(&mut self, language: impl Into<String>) {
+        self.fallback = Some(language.into());
+    }
+
+    /// Sets the text of a token in a language, returning the text it replaces.
+    pub fn insert(
+        &mut self,
+        language: &str,
+        token: &str,
+        text: impl Into<String>,
+    ) -> Option<String> {
+        self.languages
+            .entry(language.to_string())
+            .or_default()
+            .insert(strip(token).to_string(), text.into())
+    }
+
+    /// Returns the text of a token in a language, or in the fallback language.
+    pub fn get(&self, language: &str, token: &str) -> Option<&str> {
+        let token = strip(token);
+        let lookup = |language: &str| self.languages.get(language)?.get(token).map(String::as_str);
+
+        lookup(language).or_else(|| lookup(self.fallback.as_deref()?))
+    }
+
+    /// Returns the text to show for a string that may be a token.
+    ///
+    /// Strings not starting with `@` are shown as they are, as are tokens without a text.
+    pub fn resolve<'a>(&'a self, language: &str, text: &'a str) -> Cow<'a, str> {
+        if !text.starts_with('@') {
+            return Cow::Borrowed(text);
+        }
+        match self.get(language, text) {
+            Some(resolved) => Cow::Borrowed(resolved),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Returns the languages with at least one token, in order.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.languages.keys().map(String::as_str)
+    }
+
+    /// Returns the tokens of a language, without their `@`.
+    pub fn tokens(&self, language: &str) -> impl Iterator<Item = &str> {
+        self.languages
+            .get(language)
+            .into_iter()
+            .flat_map(|texts| texts.keys().map(String::as_str))
+    }
+}
+
+/// Removes the `@` a token starts with.
+fn strip(token: &str) -> &str {
+    token.strip_prefix('@').unwrap_or(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_tokens() {
+        let mut localization = Localization::new();
+        localization.set_fallback("english");
+        localization.insert("english", "@INCENDIARY", "Incendiary");
+        localization.insert("english", "MOD_SHELL", "Modded shell");
+        localization.insert("russian", "INCENDIARY", "Зажигательный");
+
+        assert_eq!(
+            localization.resolve("russian", "@INCENDIARY"),
+            "Зажигательный"
+        );
+        // Falls back to english.
+        assert_eq!(
+            localization.resolve("russian", "@MOD_SHELL"),
+            "Modded shell"
+        );
+        // Not a token.
+        assert_eq!(localization.resolve("english", "57mm"), "57mm");
+        // Unknown token.
+        assert_eq!(localization.resolve("english", "@MISSING"), "@MISSING");
+
+        assert_eq!(
+            localization.languages().collect::<Vec<_>>(),
+            ["english", "russian"]
+        );
+        assert_eq!(
+            localization
+                .insert("english", "MOD_SHELL", "Shell")
+                .as_deref(),
+            Some("Modded shell")
+        );
+    }
+}
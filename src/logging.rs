@@ -0,0 +1,264 @@
+//! A file logger for injected mods, since the game has no console to print to.
+//!
+//! `init` sends the macros of the `log` crate to a file and installs a panic hook.
+//! Every line is flushed as soon as it's written, so nothing is lost when the game crashes right after.
+//! The panic hook logs the panic, a backtrace, and a hex dump of every struct registered with `watch`,
+//! then runs the previous hook.
+//!
+//! ```ignore
+//! highfleet::logging::init("my_mod.log", log::LevelFilter::Info)?;
+//! highfleet::logging::watch("ammo table", GamePtr::<Ammo>::from_address(address));
+//! log::info!("loaded");
+//! ```
+//!
+//! Only available with the `log` feature.
+
+use std::backtrace::Backtrace;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::panic;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::general::hexdump::{Annotated, HexDump};
+use crate::general::GamePtr;
+use crate::memory::{InProcess, MemorySource};
+
+/// A struct to dump when panicking, registered with `watch`.
+struct Watch {
+    name: &'static str,
+    address: u64,
+    dump: fn(u64) -> String,
+}
+
+static WATCHES: Mutex<Vec<Watch>> = Mutex::new(Vec::new());
+
+/// Error returned when `init` fails.
+#[derive(Debug)]
+pub enum LogError {
+    /// The log file couldn't be created.
+    Io(io::Error),
+    /// A logger was already set.
+    AlreadySet(SetLoggerError),
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "couldn't create the log file: {error}"),
+            Self::AlreadySet(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for LogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::AlreadySet(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for LogError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<SetLoggerError> for LogError {
+    fn from(error: SetLoggerError) -> Self {
+        Self::AlreadySet(error)
+    }
+}
+
+/// A logger writing every record to a file, flushed line by line.
+///
+/// Lines look like `[    12.345s INFO  my_mod] message`, timed from the creation of the logger.
+pub struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+    start: Instant,
+}
+
+impl FileLogger {
+    /// Creates the log file at `path`, replacing an older one, and logs records up to `level`.
+    pub fn create(path: impl AsRef<Path>, level: LevelFilter) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        writeln!(file, "highfleet {}", env!("CARGO_PKG_VERSION"))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            level,
+            start: Instant::now(),
+        })
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // A poisoned lock only means another thread panicked while logging, the file is still fine.
+        let mut file = self.file.lock().unwrap_or_else(|error| error.into_inner());
+        let _ = writeln!(
+            file,
+            "[{:>11.3}s {:<5} {}] {}",
+            self.start.elapsed().as_secs_f64(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let _ = file.flush();
+    }
+
+    fn flush(&self) {
+        let mut file = self.file.lock().unwrap_or_else(|error| error.into_inner());
+        let _ = file.flush();
+    }
+}
+
+/// Logs to the file at `path`, up to `level`, and installs the panic hook with `install_panic_hook`.
+///
+/// Fails if the file can't be created, or a logger was already set.
+pub fn init(path: impl AsRef<Path>, level: LevelFilter) -> Result<(), LogError> {
+    let logger = FileLogger::create(path, level)?;
+    log::set_logger(Box::leak(Box::new(logger)))?;
+    log::set_max_level(level);
+
+    install_panic_hook();
+    Ok(())
+}
+
+/// Installs a panic hook that logs the panic, a backtrace, and the structs registered with `watch`,
+/// then runs the previous hook.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("{info}\n{}", Backtrace::force_capture());
+        for dump in watched_dumps() {
+            log::error!("{dump}");
+        }
+        log::logger().flush();
+
+        previous(info);
+    }));
+}
+
+/// Registers a struct to hex dump when panicking, replacing any other struct registered under `name`.
+///
+/// The struct is read when the panic happens, through `InProcess`, so a bad pointer shows up as unreadable.
+pub fn watch<T: Annotated>(name: &'static str, pointer: GamePtr<T>) {
+    let watch = Watch {
+        name,
+        address: pointer.address(),
+        dump: dump::<T>,
+    };
+
+    let mut watches = WATCHES.lock().unwrap_or_else(|error| error.into_inner());
+    watches.retain(|watch| watch.name != name);
+    watches.push(watch);
+}
+
+/// Stops dumping the struct registered under `name`.
+pub fn unwatch(name: &str) {
+    let mut watches = WATCHES.lock().unwrap_or_else(|error| error.into_inner());
+    watches.retain(|watch| watch.name != name);
+}
+
+/// Returns the dumps of every watched struct, each headed by its name and address.
+fn watched_dumps() -> Vec<String> {
+    // The panic may have happened while the lock was held, so it's not waited on.
+    let Ok(watches) = WATCHES.try_lock() else {
+        return vec!["watched structs are locked, not dumped".to_string()];
+    };
+
+    watches
+        .iter()
+        .map(|watch| {
+            format!(
+                "{} at {:#x}:\n{}",
+                watch.name,
+                watch.address,
+                (watch.dump)(watch.address)
+            )
+        })
+        .collect()
+}
+
+fn dump<T: Annotated>(address: u64) -> String {
+    let mut bytes = vec![0; size_of::<T>()];
+    match InProcess.read(address, &mut bytes) {
+        Ok(()) => HexDump::from_bytes::<T>(&bytes).to_string(),
+        Err(error) => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+
+    #[test]
+    fn writes_records_to_file() {
+        let path = std::env::temp_dir().join(format!("highfleet-log-{}.log", std::process::id()));
+        let logger = FileLogger::create(&path, LevelFilter::Info).unwrap();
+
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .target("my_mod")
+                .args(format_args!("ammo {} is missing", "AMMO_57"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(log::Level::Debug)
+                .args(format_args!("hidden"))
+                .build(),
+        );
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("highfleet "));
+        assert!(lines[1].ends_with("s WARN  my_mod] ammo AMMO_57 is missing"));
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn dumps_watched_structs() {
+        let mut string = EscadraString::from("Banana");
+        watch("banana", GamePtr::new(&mut string as *mut EscadraString));
+        watch("nothing", GamePtr::<EscadraString>::null());
+
+        let dumps = watched_dumps();
+        unwatch("banana");
+        unwatch("nothing");
+
+        let banana = dumps
+            .iter()
+            .find(|dump| dump.starts_with("banana at 0x"))
+            .unwrap();
+        assert!(banana.contains("42 61 6e 61 6e 61"), "{banana}");
+        let nothing = dumps
+            .iter()
+            .find(|dump| dump.starts_with("nothing at 0x0:"))
+            .unwrap();
+        assert!(nothing.contains("could not read"), "{nothing}");
+    }
+}
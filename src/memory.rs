@@ -0,0 +1,203 @@
+//! Defines access to the memory of a running Highfleet process.
+//!
+//! A `MemorySource` reads and writes an address space.
+//! `ExternalProcess` accesses the game from another process, for tools that don't want to be injected.
+//! `InProcess` accesses the game from a mod injected into it, without crashing on invalid addresses.
+//! `find_instances` searches that memory for plausible instances of a struct.
+//! `rtti` identifies the class of C++ objects found in that memory.
+
+use alloc::vec;
+use core::error::Error;
+use core::fmt;
+use core::mem::size_of;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::general::raw::{from_bytes, MemoryReader, PointerPolicy, RawError, RawLayout};
+
+#[cfg(feature = "std")]
+mod external;
+#[cfg(feature = "std")]
+mod in_process;
+pub mod instances;
+pub mod rtti;
+pub mod scan;
+
+#[cfg(feature = "std")]
+pub use external::ExternalProcess;
+#[cfg(feature = "std")]
+pub use in_process::InProcess;
+pub use instances::{find_instances, Heuristics, Plausible};
+
+/// Error returned when accessing memory fails.
+#[derive(Debug)]
+pub enum MemoryError {
+    /// The memory could not be read.
+    Unreadable {
+        /// The first address that was read.
+        address: u64,
+        /// The number of bytes that were read.
+        size: usize,
+    },
+    /// The memory could not be written.
+    Unwritable {
+        /// The first address that was written.
+        address: u64,
+        /// The number of bytes that were written.
+        size: usize,
+    },
+    /// The bytes that were read are not a valid struct.
+    Raw(RawError),
+    /// The operating system failed to open the memory.
+    #[cfg(feature = "std")]
+    Os(io::Error),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable { address, size } => {
+                write!(f, "could not read {size} bytes at {address:#x}")
+            }
+            Self::Unwritable { address, size } => {
+                write!(f, "could not write {size} bytes at {address:#x}")
+            }
+            Self::Raw(error) => write!(f, "{error}"),
+            #[cfg(feature = "std")]
+            Self::Os(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for MemoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Raw(error) => Some(error),
+            #[cfg(feature = "std")]
+            Self::Os(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<RawError> for MemoryError {
+    fn from(error: RawError) -> Self {
+        Self::Raw(error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for MemoryError {
+    fn from(error: io::Error) -> Self {
+        Self::Os(error)
+    }
+}
+
+/// An address space that can be read and written, usually the memory of the game.
+pub trait MemorySource {
+    /// Fills `buffer` with the bytes starting at `address`.
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError>;
+
+    /// Writes `bytes` starting at `address`.
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError>;
+
+    /// Reads the struct at `address`.
+    ///
+    /// Heap buffers owned by the struct, such as those of an `EscadraString`, are read from this source as well
+    /// and copied into memory owned by this process.
+    fn read_struct<T: RawLayout>(&self, address: u64) -> Result<T, MemoryError>
+    where
+        Self: Sized,
+    {
+        let mut bytes = vec![0u8; size_of::<T>()];
+        self.read(address, &mut bytes)?;
+        Ok(from_bytes(&bytes, &PointerPolicy::Resolve(self))?)
+    }
+}
+
+impl<M: MemorySource> MemoryReader for M {
+    fn read_bytes(&self, address: u64, buffer: &mut [u8]) -> bool {
+        self.read(address, buffer).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+    use crate::v1_163::Ammo;
+    use core::cell::RefCell;
+
+    /// A fake address space holding a single block of memory.
+    struct FakeProcess {
+        base: u64,
+        memory: RefCell<Vec<u8>>,
+    }
+
+    impl FakeProcess {
+        fn range(&self, address: u64, size: usize) -> Option<core::ops::Range<usize>> {
+            let start = address.checked_sub(self.base)? as usize;
+            let end = start.checked_add(size)?;
+            (end <= self.memory.borrow().len()).then_some(start..end)
+        }
+    }
+
+    impl MemorySource for FakeProcess {
+        fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+            let size = buffer.len();
+            let range = self
+                .range(address, size)
+                .ok_or(MemoryError::Unreadable { address, size })?;
+            buffer.copy_from_slice(&self.memory.borrow()[range]);
+            Ok(())
+        }
+
+        fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+            let size = bytes.len();
+            let range = self
+                .range(address, size)
+                .ok_or(MemoryError::Unwritable { address, size })?;
+            self.memory.borrow_mut()[range].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_struct_resolves_strings() {
+        let base = 0x1400_0000;
+        let name = "AMMO_57_INCENDIARY_CUSTOM";
+
+        // The ammo at the start, its heap backed name right after it.
+        let mut ammo: Ammo = from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap();
+        ammo.magazine_image.push_str("shell_57");
+        ammo.index = 7;
+        let mut memory = crate::general::raw::to_bytes(&ammo, &PointerPolicy::Zero);
+        let name_address = base + memory.len() as u64;
+        memory[0x08..0x10].copy_from_slice(&name_address.to_le_bytes());
+        memory[0x18..0x20].copy_from_slice(&(name.len() as u64).to_le_bytes());
+        memory[0x20..0x28].copy_from_slice(&(name.len() as u64).to_le_bytes());
+        memory.extend(name.as_bytes());
+        memory.push(0);
+
+        let process = FakeProcess {
+            base,
+            memory: RefCell::new(memory),
+        };
+
+        let ammo: Ammo = process.read_struct(base).unwrap();
+        assert_eq!(ammo.item_name, name);
+        assert_eq!(ammo.magazine_image, "shell_57");
+        assert_eq!(ammo.index, 7);
+
+        let string: EscadraString = process.read_struct(base + 0x08).unwrap();
+        assert_eq!(string, name);
+
+        process.write(base + 0x158, &1500f32.to_le_bytes()).unwrap();
+        assert_eq!(process.read_struct::<Ammo>(base).unwrap().speed, 1500.0);
+
+        assert!(matches!(
+            process.read_struct::<Ammo>(base + 0x100),
+            Err(MemoryError::Unreadable { .. })
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+//! Defines access to the memory of another process.
+
+use std::io;
+
+use super::{MemoryError, MemorySource};
+
+/// A process opened for reading and writing its memory, such as a running Highfleet.
+///
+/// Uses `ReadProcessMemory` and `WriteProcessMemory`, so nothing has to be injected into the game.
+/// Only available on Windows: elsewhere `open` always fails.
+#[derive(Debug)]
+pub struct ExternalProcess {
+    handle: *mut u8,
+    pid: u32,
+}
+
+impl ExternalProcess {
+    /// Opens the process with the given id for reading and writing its memory.
+    pub fn open(pid: u32) -> io::Result<Self> {
+        let handle = unsafe { sys::open(pid)? };
+        Ok(Self { handle, pid })
+    }
+
+    /// Returns the id of the process.
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+}
+
+impl MemorySource for ExternalProcess {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+        let size = buffer.len();
+        unsafe { sys::read(self.handle, address, buffer) }
+            .then_some(())
+            .ok_or(MemoryError::Unreadable { address, size })
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+        let size = bytes.len();
+        unsafe { sys::write(self.handle, address, bytes) }
+            .then_some(())
+            .ok_or(MemoryError::Unwritable { address, size })
+    }
+}
+
+impl Drop for ExternalProcess {
+    fn drop(&mut self) {
+        unsafe { sys::close(self.handle) }
+    }
+}
+
+// The handle may be used from any thread.
+unsafe impl Send for ExternalProcess {}
+unsafe impl Sync for ExternalProcess {}
+
+#[cfg(windows)]
+pub(super) mod sys {
+    use std::io;
+
+    const PROCESS_VM_OPERATION: u32 = 0x0008;
+    const PROCESS_VM_READ: u32 = 0x0010;
+    const PROCESS_VM_WRITE: u32 = 0x0020;
+    const PROCESS_QUERY_INFORMATION: u32 = 0x0400;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut u8;
+        fn ReadProcessMemory(
+            process: *mut u8,
+            base_address: *const u8,
+            buffer: *mut u8,
+            size: usize,
+            bytes_read: *mut usize,
+        ) -> i32;
+        fn WriteProcessMemory(
+            process: *mut u8,
+            base_address: *mut u8,
+            buffer: *const u8,
+            size: usize,
+            bytes_written: *mut usize,
+        ) -> i32;
+        fn CloseHandle(handle: *mut u8) -> i32;
+        fn GetCurrentProcess() -> *mut u8;
+    }
+
+    /// Returns the pseudo handle of the current process, which doesn't have to be closed.
+    pub(in crate::memory) fn current_process() -> *mut u8 {
+        unsafe { GetCurrentProcess() }
+    }
+
+    pub(super) unsafe fn open(pid: u32) -> io::Result<*mut u8> {
+        let access =
+            PROCESS_VM_OPERATION | PROCESS_VM_READ | PROCESS_VM_WRITE | PROCESS_QUERY_INFORMATION;
+        let handle = OpenProcess(access, 0, pid);
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(handle)
+    }
+
+    pub(in crate::memory) unsafe fn read(handle: *mut u8, address: u64, buffer: &mut [u8]) -> bool {
+        let mut read = 0;
+        let success = ReadProcessMemory(
+            handle,
+            address as *const u8,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut read,
+        );
+        success != 0 && read == buffer.len()
+    }
+
+    pub(in crate::memory) unsafe fn write(handle: *mut u8, address: u64, bytes: &[u8]) -> bool {
+        let mut written = 0;
+        let success = WriteProcessMemory(
+            handle,
+            address as *mut u8,
+            bytes.as_ptr(),
+            bytes.len(),
+            &mut written,
+        );
+        success != 0 && written == bytes.len()
+    }
+
+    pub(super) unsafe fn close(handle: *mut u8) {
+        CloseHandle(handle);
+    }
+}
+
+#[cfg(not(windows))]
+pub(super) mod sys {
+    use std::io;
+
+    pub(super) unsafe fn open(_pid: u32) -> io::Result<*mut u8> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "external processes can only be opened on Windows",
+        ))
+    }
+
+    pub(in crate::memory) unsafe fn read(
+        _handle: *mut u8,
+        _address: u64,
+        _buffer: &mut [u8],
+    ) -> bool {
+        false
+    }
+
+    pub(in crate::memory) unsafe fn write(_handle: *mut u8, _address: u64, _bytes: &[u8]) -> bool {
+        false
+    }
+
+    pub(super) unsafe fn close(_handle: *mut u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn unsupported_outside_windows() {
+        let error = ExternalProcess::open(std::process::id()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+}
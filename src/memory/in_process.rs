@@ -0,0 +1,125 @@
+//! Defines access to the memory of the current process, for mods injected into the game.
+
+use super::{MemoryError, MemorySource};
+
+/// The memory of the current process, accessed through addresses.
+///
+/// Unlike dereferencing raw pointers, reading unmapped memory or a guard page returns an error instead of crashing the game.
+/// The accesses go through the operating system, which checks them:
+/// `ReadProcessMemory` on the current process on Windows, and `process_vm_readv` on Linux.
+/// On other platforms every access fails.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InProcess;
+
+impl MemorySource for InProcess {
+    fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+        let size = buffer.len();
+        unsafe { sys::read(address, buffer) }
+            .then_some(())
+            .ok_or(MemoryError::Unreadable { address, size })
+    }
+
+    fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+        let size = bytes.len();
+        unsafe { sys::write(address, bytes) }
+            .then_some(())
+            .ok_or(MemoryError::Unwritable { address, size })
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use crate::memory::external::sys;
+
+    pub(super) unsafe fn read(address: u64, buffer: &mut [u8]) -> bool {
+        sys::read(sys::current_process(), address, buffer)
+    }
+
+    pub(super) unsafe fn write(address: u64, bytes: &[u8]) -> bool {
+        sys::write(sys::current_process(), address, bytes)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use libc::{c_void, iovec};
+
+    pub(super) unsafe fn read(address: u64, buffer: &mut [u8]) -> bool {
+        let local = iovec {
+            iov_base: buffer.as_mut_ptr() as *mut c_void,
+            iov_len: buffer.len(),
+        };
+        let remote = iovec {
+            iov_base: address as *mut c_void,
+            iov_len: buffer.len(),
+        };
+        let read = libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0);
+        read == buffer.len() as isize
+    }
+
+    pub(super) unsafe fn write(address: u64, bytes: &[u8]) -> bool {
+        let local = iovec {
+            iov_base: bytes.as_ptr() as *mut c_void,
+            iov_len: bytes.len(),
+        };
+        let remote = iovec {
+            iov_base: address as *mut c_void,
+            iov_len: bytes.len(),
+        };
+        let written = libc::process_vm_writev(libc::getpid(), &local, 1, &remote, 1, 0);
+        written == bytes.len() as isize
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod sys {
+    pub(super) unsafe fn read(_address: u64, _buffer: &mut [u8]) -> bool {
+        false
+    }
+
+    pub(super) unsafe fn write(_address: u64, _bytes: &[u8]) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, any(windows, target_os = "linux")))]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+
+    #[test]
+    fn reads_and_writes_own_memory() {
+        let mut value = [1u8, 2, 3, 4];
+        let address = value.as_mut_ptr() as u64;
+
+        let mut buffer = [0u8; 4];
+        InProcess.read(address, &mut buffer).unwrap();
+        assert_eq!(buffer, [1, 2, 3, 4]);
+
+        InProcess.write(address + 2, &[9, 9]).unwrap();
+        assert_eq!(value, [1, 2, 9, 9]);
+    }
+
+    #[test]
+    fn read_struct_copies_heap_strings() {
+        let string = EscadraString::from("Banana Banana Banana Banana");
+        let address = &string as *const EscadraString as u64;
+
+        let copy: EscadraString = InProcess.read_struct(address).unwrap();
+        assert_eq!(copy, string);
+        assert_ne!(copy.get_bytes().as_ptr(), string.get_bytes().as_ptr());
+    }
+
+    #[test]
+    fn unmapped_memory_is_an_error() {
+        let mut buffer = [0u8; 8];
+        assert!(matches!(
+            InProcess.read(0x10, &mut buffer),
+            Err(MemoryError::Unreadable {
+                address: 0x10,
+                size: 8
+            })
+        ));
+        assert!(InProcess.write(0x10, &buffer).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Finds plausible instances of a game struct in memory, to locate registries again after a patch moves them.
+//!
+//! Every aligned address of the scanned ranges is read as the struct and kept if it passes the checks of `Heuristics`:
+//! the `RawLayout` invariants, such as those of `EscadraString`, heap strings that can be read,
+//! and few enough values outside of the ranges the vanilla game uses.
+//!
+//! `MemorySource` can't list the regions of the game's heap, so the ranges to scan are given by the caller.
+
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::ops::Range;
+
+use super::MemorySource;
+use crate::general::raw::{from_bytes, PointerPolicy, RawLayout};
+
+/// How many bytes are read at once while searching.
+const CHUNK_SIZE: u64 = 0x1000;
+
+/// A struct that can tell how plausible its values are.
+pub trait Plausible: RawLayout {
+    /// Returns how many values of the struct the game would not use, zero for a struct that looks fine.
+    ///
+    /// This is called on arbitrary memory, so it must not panic on any value that passes `RawLayout::validate`,
+    /// such as strings that aren't valid UTF-8.
+    fn implausible_values(&self) -> usize;
+}
+
+impl Plausible for crate::v1_151::Ammo {
+    fn implausible_values(&self) -> usize {
+        self.validate().len()
+    }
+}
+
+impl Plausible for crate::v1_163::Ammo {
+    fn implausible_values(&self) -> usize {
+        self.validate().len()
+    }
+}
+
+/// The checks a candidate has to pass to be returned by `find_instances`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heuristics {
+    /// The alignment of the candidate addresses.
+    ///
+    /// 8 finds structs inside of arrays as well as on their own.
+    pub alignment: u64,
+    /// Whether the heap buffers of strings must be readable.
+    pub readable_strings: bool,
+    /// The largest number of implausible values a candidate may have.
+    pub max_implausible: usize,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self {
+            alignment: 8,
+            readable_strings: true,
+            max_implausible: 0,
+        }
+    }
+}
+
+/// Returns the addresses inside of `ranges` that hold a plausible `T`, in increasing order for sorted ranges.
+///
+/// Memory is read a page at a time. Pages that can't be read are skipped.
+pub fn find_instances<T: Plausible, M: MemorySource>(
+    memory: &M,
+    ranges: impl IntoIterator<Item = Range<u64>>,
+    heuristics: &Heuristics,
+) -> Vec<u64> {
+    let policy = if heuristics.readable_strings {
+        PointerPolicy::Resolve(memory)
+    } else {
+        PointerPolicy::Zero
+    };
+    let alignment = heuristics.alignment.max(1);
+    let size = size_of::<T>() as u64;
+
+    let mut found = Vec::new();
+    let mut buffer = Vec::new();
+    for range in ranges {
+        let mut chunk = range.start.next_multiple_of(alignment);
+        while chunk < range.end {
+            // Align chunks to pages, so an unreadable page only loses that page.
+            let chunk_end = ((chunk / CHUNK_SIZE + 1) * CHUNK_SIZE).min(range.end);
+            let tail_end = (chunk_end + size - 1).min(range.end);
+
+            buffer.resize((tail_end - chunk) as usize, 0);
+            if memory.read(chunk, &mut buffer).is_err() {
+                // The tail may lie on an unreadable page, try without it.
+                buffer.truncate((chunk_end - chunk) as usize);
+                if memory.read(chunk, &mut buffer).is_err() {
+                    buffer.clear();
+                }
+            }
+
+            let mut address = chunk;
+            while address < chunk_end {
+                let start = (address - chunk) as usize;
+                if let Some(bytes) = buffer.get(start..start + size as usize) {
+                    let plausible = from_bytes::<T>(bytes, &policy).is_ok_and(|value| {
+                        value.implausible_values() <= heuristics.max_implausible
+                    });
+                    if plausible {
+                        found.push(address);
+                    }
+                }
+                address += alignment;
+            }
+            chunk = address;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::Snapshot;
+    use crate::general::raw::to_bytes;
+    use crate::v1_163::{Ammo, AmmoBuilder};
+
+    const BASE: u64 = 0x1400_0000;
+    const LONG_NAME: &str = "AMMO_57_INCENDIARY_CUSTOM";
+
+    /// An ammo whose strings all fit inline, so `PointerPolicy::Zero` keeps them.
+    fn inline_ammo() -> Ammo {
+        AmmoBuilder::new()
+            .item_name("AMMO_57")
+            .index(0)
+            .magazine_image("shell_57")
+            .speed(1000.0)
+            .shell_enemy("shell_57")
+            .shell_far("shell_57")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_plausible_ammos() {
+        assert_eq!(inline_ammo().validate(), []);
+
+        // An ammo across the first page boundary, and one with a heap name stored past the scanned range.
+        let first = BASE + 0xf00;
+        let second = BASE + 0x1800;
+        let name = BASE + 0x4000;
+        let mut bytes = to_bytes(&inline_ammo(), &PointerPolicy::Zero);
+        bytes[0x08..0x10].copy_from_slice(&name.to_le_bytes());
+        bytes[0x18..0x20].copy_from_slice(&(LONG_NAME.len() as u64).to_le_bytes());
+        bytes[0x20..0x28].copy_from_slice(&(LONG_NAME.len() as u64).to_le_bytes());
+
+        let mut memory = Snapshot::new(BASE, None);
+        memory.insert(BASE, &[0u8; 0x3000]);
+        memory.insert(first, &to_bytes(&inline_ammo(), &PointerPolicy::Zero));
+        memory.insert(second, &bytes);
+        memory.insert(name, LONG_NAME.as_bytes());
+
+        let heuristics = Heuristics::default();
+        let search = |memory: &Snapshot| {
+            find_instances::<Ammo, _>(memory, Some(BASE..BASE + 0x3000), &heuristics)
+        };
+        assert_eq!(search(&memory), [first, second]);
+
+        // Without it, the heap name can't be read.
+        // Unless strings are checked, it's zeroed instead, which only makes the ammo a little less plausible.
+        let mut memory = Snapshot::new(BASE, None);
+        memory.insert(BASE, &[0u8; 0x3000]);
+        memory.insert(second, &bytes);
+        assert!(search(&memory).is_empty());
+        let lenient = Heuristics {
+            readable_strings: false,
+            max_implausible: 1,
+            ..heuristics
+        };
+        assert_eq!(
+            find_instances::<Ammo, _>(&memory, Some(BASE..BASE + 0x3000), &lenient),
+            [second]
+        );
+    }
+
+    #[test]
+    fn non_utf8_strings_are_plausible() {
+        // The game writes CP1251, so most inline strings found in the heap aren't valid UTF-8.
+        let mut bytes = to_bytes(&inline_ammo(), &PointerPolicy::Zero);
+        for offset in [0x08, 0x28, 0x48, 0x68, 0x88] {
+            bytes[offset] = 0xCF;
+        }
+
+        let mut memory = Snapshot::new(BASE, None);
+        memory.insert(BASE, &[0xCF; 0x1000]);
+        memory.insert(BASE + 0x100, &bytes);
+
+        let found =
+            find_instances::<Ammo, _>(&memory, Some(BASE..BASE + 0x1000), &Heuristics::default());
+        assert_eq!(found, [BASE + 0x100]);
+    }
+}
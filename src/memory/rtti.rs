@@ -0,0 +1,319 @@
+//! Identifies C++ objects of the game by walking the RTTI the MSVC compiler emits for polymorphic classes.
+//!
+//! A polymorphic object starts with a pointer to its vtable.
+//! The pointer just before the vtable leads to a complete object locator, which holds the offsets,
+//! relative to the module base, of the class's type descriptor and of its hierarchy.
+//! The type descriptor holds the mangled class name, like `.?AVShip@@`.
+
+use super::MemorySource;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Signature of a complete object locator in a 64 bit module.
+const COL_SIGNATURE_X64: u32 = 1;
+
+/// The longest mangled name that is read.
+const MAX_NAME_LENGTH: u64 = 1024;
+
+/// Upper bound on the base classes read, to stop on garbage.
+const MAX_BASE_CLASSES: u32 = 256;
+
+/// What the RTTI of an object tells about its class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeInfo {
+    /// The demangled class name, like `Ship` or `Escadra::Module`.
+    ///
+    /// Names the demangler doesn't understand, such as templates, are kept mangled.
+    pub name: String,
+    /// The mangled name from the type descriptor, like `.?AVShip@@`.
+    pub mangled: String,
+    /// The demangled names of every base class, direct or not, in the order MSVC lists them.
+    pub base_classes: Vec<String>,
+    /// The offset of the vtable pointer within the complete object.
+    ///
+    /// Not zero when the pointer leads into a base class subobject.
+    pub offset: u32,
+    /// The address of the vtable.
+    pub vtable: u64,
+}
+
+impl TypeInfo {
+    /// Returns true if the class is `name` or derives from it.
+    pub fn is_a(&self, name: &str) -> bool {
+        self.name == name || self.base_classes.iter().any(|base| base == name)
+    }
+}
+
+/// Returns the demangled class name of the object at `object`.
+///
+/// Returns `None` if the object doesn't start with a pointer to a vtable that has MSVC RTTI.
+pub fn class_name<M: MemorySource>(memory: &M, object: u64) -> Option<String> {
+    type_info(memory, object).map(|info| info.name)
+}
+
+/// Walks the RTTI of the object at `object`.
+///
+/// Returns `None` if the object doesn't start with a pointer to a vtable that has MSVC RTTI.
+/// Since garbage rarely passes the checks on the way, this can be used on pointers of unknown purpose.
+pub fn type_info<M: MemorySource>(memory: &M, object: u64) -> Option<TypeInfo> {
+    let vtable = read_u64(memory, object)?;
+    type_info_of_vtable(memory, vtable)
+}
+
+/// Walks the RTTI of the vtable at `vtable`.
+pub fn type_info_of_vtable<M: MemorySource>(memory: &M, vtable: u64) -> Option<TypeInfo> {
+    let locator = read_u64(memory, vtable.checked_sub(8)?)?;
+    if read_u32(memory, locator)? != COL_SIGNATURE_X64 {
+        return None;
+    }
+
+    let offset = read_u32(memory, locator + 4)?;
+    let type_descriptor = read_u32(memory, locator + 0xc)? as u64;
+    let hierarchy = read_u32(memory, locator + 0x10)? as u64;
+    // The locator holds its own offset, which gives the module base.
+    let module_base = locator.checked_sub(read_u32(memory, locator + 0x14)? as u64)?;
+
+    let mangled = type_name(memory, module_base + type_descriptor)?;
+    let base_classes = base_classes(memory, module_base, module_base + hierarchy)
+        .unwrap_or_default()
+        .iter()
+        .map(|mangled| demangle_or_keep(mangled))
+        .collect();
+
+    Some(TypeInfo {
+        name: demangle_or_keep(&mangled),
+        mangled,
+        base_classes,
+        offset,
+        vtable,
+    })
+}
+
+/// Returns the mangled names of the base classes listed by a class hierarchy descriptor.
+fn base_classes<M: MemorySource>(
+    memory: &M,
+    module_base: u64,
+    hierarchy: u64,
+) -> Option<Vec<String>> {
+    let count = read_u32(memory, hierarchy + 8)?.min(MAX_BASE_CLASSES);
+    let array = module_base + read_u32(memory, hierarchy + 0xc)? as u64;
+
+    // The first entry is the class itself.
+    (1..count as u64)
+        .map(|i| {
+            let descriptor = module_base + read_u32(memory, array + i * 4)? as u64;
+            let type_descriptor = module_base + read_u32(memory, descriptor)? as u64;
+            type_name(memory, type_descriptor)
+        })
+        .collect()
+}
+
+/// Reads the mangled name of a type descriptor, checking it looks like one.
+fn type_name<M: MemorySource>(memory: &M, type_descriptor: u64) -> Option<String> {
+    let name = read_c_string(memory, type_descriptor + 0x10)?;
+    name.starts_with(".?A").then_some(name)
+}
+
+/// Demangles an MSVC type descriptor name, like `.?AVModule@Escadra@@` into `Escadra::Module`.
+///
+/// Handles classes, structs, unions and enums in namespaces, including back references.
+/// Returns `None` for names using anything else, such as templates.
+pub fn demangle(mangled: &str) -> Option<String> {
+    let rest = mangled.strip_prefix(".?A")?;
+    let rest = match rest.as_bytes().first()? {
+        b'V' | b'U' | b'T' => &rest[1..],
+        // The underlying type of an enum follows the `W`, like `W4` for `int`.
+        b'W' => rest.get(2..)?,
+        _ => return None,
+    };
+
+    // Names end with `@`, back references are a single digit, and the list ends with a lone `@`.
+    let mut names: Vec<&str> = Vec::new();
+    let mut remaining = rest;
+    loop {
+        let first = *remaining.as_bytes().first()?;
+        if first == b'@' {
+            if remaining.len() != 1 {
+                return None;
+            }
+            break;
+        }
+        if first.is_ascii_digit() {
+            names.push(names.get((first - b'0') as usize)?);
+            remaining = &remaining[1..];
+            continue;
+        }
+
+        let (name, tail) = remaining.split_once('@')?;
+        if name.is_empty() || !name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+            return None;
+        }
+        names.push(name);
+        remaining = tail;
+    }
+
+    if names.is_empty() {
+        return None;
+    }
+    names.reverse();
+    Some(names.join("::"))
+}
+
+/// Demangles the name, keeping it mangled if that fails.
+fn demangle_or_keep(mangled: &str) -> String {
+    demangle(mangled).unwrap_or_else(|| mangled.to_string())
+}
+
+fn read_u32<M: MemorySource>(memory: &M, address: u64) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    memory.read(address, &mut bytes).ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<M: MemorySource>(memory: &M, address: u64) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    memory.read(address, &mut bytes).ok()?;
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Reads a nul terminated ASCII string.
+///
+/// Reads are aligned to 16 bytes so none of them crosses into a page that isn't mapped.
+fn read_c_string<M: MemorySource>(memory: &M, address: u64) -> Option<String> {
+    let mut name = Vec::new();
+    let mut chunk_start = address & !0xf;
+    while name.len() < MAX_NAME_LENGTH as usize {
+        let mut chunk = [0u8; 16];
+        memory.read(chunk_start, &mut chunk).ok()?;
+
+        let skip = address.saturating_sub(chunk_start) as usize;
+        for &byte in &chunk[skip..] {
+            if byte == 0 {
+                return String::from_utf8(name).ok().filter(|name| name.is_ascii());
+            }
+            name.push(byte);
+        }
+        chunk_start += 16;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryError;
+
+    /// A fake module, mapped at `BASE`.
+    struct FakeModule {
+        memory: Vec<u8>,
+    }
+
+    const BASE: u64 = 0x1_4000_0000;
+
+    impl FakeModule {
+        fn put(&mut self, rva: usize, bytes: &[u8]) {
+            self.memory[rva..rva + bytes.len()].copy_from_slice(bytes);
+        }
+
+        /// Adds a type descriptor at `rva`.
+        fn type_descriptor(&mut self, rva: usize, name: &str) {
+            self.put(rva + 0x10, name.as_bytes());
+            self.put(rva + 0x10 + name.len(), &[0]);
+        }
+    }
+
+    impl MemorySource for FakeModule {
+        fn read(&self, address: u64, buffer: &mut [u8]) -> Result<(), MemoryError> {
+            let size = buffer.len();
+            let start = address
+                .checked_sub(BASE)
+                .map(|start| start as usize)
+                .filter(|start| start + size <= self.memory.len())
+                .ok_or(MemoryError::Unreadable { address, size })?;
+            buffer.copy_from_slice(&self.memory[start..start + size]);
+            Ok(())
+        }
+
+        fn write(&self, address: u64, bytes: &[u8]) -> Result<(), MemoryError> {
+            Err(MemoryError::Unwritable {
+                address,
+                size: bytes.len(),
+            })
+        }
+    }
+
+    /// Builds a module holding an object of class `Escadra::Cannon`, deriving from `Module`.
+    fn module() -> FakeModule {
+        let mut module = FakeModule {
+            memory: vec![0; 0x1000],
+        };
+
+        // Type descriptors.
+        module.type_descriptor(0x100, ".?AVCannon@Escadra@@");
+        module.type_descriptor(0x140, ".?AVModule@@");
+        // Base class descriptors, then the array pointing to them.
+        module.put(0x200, &0x100u32.to_le_bytes());
+        module.put(0x220, &0x140u32.to_le_bytes());
+        module.put(0x240, &0x200u32.to_le_bytes());
+        module.put(0x244, &0x220u32.to_le_bytes());
+        // Class hierarchy descriptor.
+        module.put(0x288, &2u32.to_le_bytes());
+        module.put(0x28c, &0x240u32.to_le_bytes());
+        // Complete object locator.
+        module.put(0x300, &COL_SIGNATURE_X64.to_le_bytes());
+        module.put(0x30c, &0x100u32.to_le_bytes());
+        module.put(0x310, &0x280u32.to_le_bytes());
+        module.put(0x314, &0x300u32.to_le_bytes());
+        // The vtable, preceded by the locator.
+        module.put(0x408, &(BASE + 0x300).to_le_bytes());
+        // The object.
+        module.put(0x800, &(BASE + 0x410).to_le_bytes());
+
+        module
+    }
+
+    #[test]
+    fn demangle_names() {
+        assert_eq!(demangle(".?AVShip@@").as_deref(), Some("Ship"));
+        assert_eq!(demangle(".?AUPoint@@").as_deref(), Some("Point"));
+        assert_eq!(
+            demangle(".?AVModule@Escadra@@").as_deref(),
+            Some("Escadra::Module")
+        );
+        assert_eq!(demangle(".?AW4Kind@Ammo@@").as_deref(), Some("Ammo::Kind"));
+        assert_eq!(demangle(".?AVA@0@").as_deref(), Some("A::A"));
+        assert_eq!(demangle(".?AV?$vector@HV?$allocator@H@std@@@std@@"), None);
+        assert_eq!(demangle("Ship"), None);
+    }
+
+    #[test]
+    fn walk_rtti() {
+        let module = module();
+
+        let info = type_info(&module, BASE + 0x800).unwrap();
+        assert_eq!(info.name, "Escadra::Cannon");
+        assert_eq!(info.mangled, ".?AVCannon@Escadra@@");
+        assert_eq!(info.base_classes, ["Module"]);
+        assert_eq!(info.offset, 0);
+        assert_eq!(info.vtable, BASE + 0x410);
+        assert!(info.is_a("Module"));
+        assert!(!info.is_a("Ship"));
+
+        assert_eq!(
+            class_name(&module, BASE + 0x800).as_deref(),
+            Some("Escadra::Cannon")
+        );
+    }
+
+    #[test]
+    fn reject_non_objects() {
+        let module = module();
+
+        // Points to zeroes.
+        assert_eq!(type_info(&module, BASE + 0x900), None);
+        // Points outside the module.
+        assert_eq!(type_info(&module, 0x10), None);
+        // The vtable pointer itself isn't an object.
+        assert_eq!(type_info(&module, BASE + 0x408), None);
+    }
+}
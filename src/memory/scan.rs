@@ -0,0 +1,357 @@
+//! Finds byte patterns in memory, so addresses can be located without hardcoding them for every patch.
+//!
+//! Patterns are written like in IDA: hex bytes separated by spaces, with `??` or `?` matching any byte.
+//! For example `"48 8B 05 ?? ?? ?? ?? 89 05"`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::str::FromStr;
+
+use super::MemorySource;
+
+/// How many bytes are read at once while scanning.
+const CHUNK_SIZE: u64 = 0x1000;
+
+/// Characteristics flag of a PE section holding executable code.
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+
+/// A byte pattern with wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// The bytes to match, `None` matching any byte.
+    bytes: Vec<Option<u8>>,
+}
+
+/// Returned when parsing a `Pattern` fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// The pattern has no bytes.
+    Empty,
+    /// A token is neither a hex byte nor a wildcard.
+    InvalidToken(String),
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "pattern is empty"),
+            Self::InvalidToken(token) => write!(f, "invalid pattern byte {token:?}"),
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+impl Pattern {
+    /// Parses an IDA style pattern, like `"48 8B ?? ?? 89 05"`.
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let bytes = pattern
+            .split_whitespace()
+            .map(|token| match token {
+                "?" | "??" => Ok(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16)
+                    .map(Some)
+                    .map_err(|_| PatternError::InvalidToken(token.to_string())),
+                _ => Err(PatternError::InvalidToken(token.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if bytes.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        Ok(Self { bytes })
+    }
+
+    /// Returns the number of bytes the pattern matches.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Always false, as parsing rejects empty patterns.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns true if `bytes` starts with the pattern.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(bytes)
+                .all(|(pattern, byte)| pattern.is_none_or(|pattern| pattern == *byte))
+    }
+
+    /// Returns the offsets of every match inside of `haystack`, in order.
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        haystack
+            .windows(self.len())
+            .enumerate()
+            .filter(|(_, window)| self.matches(window))
+            .map(|(offset, _)| offset)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Self::parse(pattern)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match byte {
+                Some(byte) => write!(f, "{byte:02X}")?,
+                None => f.write_str("??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the addresses of every match of a pattern inside of a memory range.
+///
+/// Memory is read a page at a time. Pages that can't be read are skipped.
+pub struct Scan<'a, M> {
+    memory: &'a M,
+    pattern: &'a Pattern,
+    /// The start of the next chunk to read.
+    next_chunk: u64,
+    /// The end of the scanned range.
+    end: u64,
+    /// The current chunk, followed by the bytes needed to match patterns starting near its end.
+    buffer: Vec<u8>,
+    /// The address of the first byte of `buffer`.
+    buffer_address: u64,
+    /// The number of positions in `buffer` where a match may start.
+    starts: usize,
+    /// The next position in `buffer` to check.
+    position: usize,
+}
+
+impl<'a, M: MemorySource> Scan<'a, M> {
+    /// Reads the next chunk into the buffer, returning false once the range is exhausted.
+    fn load_next_chunk(&mut self) -> bool {
+        if self.next_chunk >= self.end {
+            return false;
+        }
+
+        let start = self.next_chunk;
+        // Align chunks to pages, so an unreadable page only loses that page.
+        let chunk_end = ((start / CHUNK_SIZE + 1) * CHUNK_SIZE).min(self.end);
+        let tail_end = (chunk_end + self.pattern.len() as u64 - 1).min(self.end);
+        self.next_chunk = chunk_end;
+
+        self.buffer_address = start;
+        self.position = 0;
+        self.buffer.resize((tail_end - start) as usize, 0);
+        if self.memory.read(start, &mut self.buffer).is_ok() {
+            self.starts = (chunk_end - start) as usize;
+            return true;
+        }
+
+        // The tail may lie on an unreadable page, try without it.
+        self.buffer.truncate((chunk_end - start) as usize);
+        self.starts = if self.memory.read(start, &mut self.buffer).is_ok() {
+            self.buffer.len()
+        } else {
+            0
+        };
+        true
+    }
+}
+
+impl<M: MemorySource> Iterator for Scan<'_, M> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while self.position < self.starts {
+                let position = self.position;
+                self.position += 1;
+                if self.pattern.matches(&self.buffer[position..]) {
+                    return Some(self.buffer_address + position as u64);
+                }
+            }
+
+            if !self.load_next_chunk() {
+                return None;
+            }
+        }
+    }
+}
+
+/// Scans the `size` bytes of memory starting at `start` for the pattern.
+pub fn scan<'a, M: MemorySource>(
+    memory: &'a M,
+    start: u64,
+    size: u64,
+    pattern: &'a Pattern,
+) -> Scan<'a, M> {
+    Scan {
+        memory,
+        pattern,
+        next_chunk: start,
+        end: start.saturating_add(size),
+        buffer: Vec::new(),
+        buffer_address: start,
+        starts: 0,
+        position: 0,
+    }
+}
+
+/// Returns the address ranges of the executable sections of the PE module mapped at `module_base`.
+///
+/// Returns `None` if the headers can't be read, or aren't valid DOS and PE headers.
+pub fn executable_sections<M: MemorySource>(
+    memory: &M,
+    module_base: u64,
+) -> Option<Vec<core::ops::Range<u64>>> {
+    let read_u16 = |offset: u64| {
+        let mut bytes = [0u8; 2];
+        memory.read(module_base + offset, &mut bytes).ok()?;
+        Some(u16::from_le_bytes(bytes))
+    };
+    let read_u32 = |offset: u64| {
+        let mut bytes = [0u8; 4];
+        memory.read(module_base + offset, &mut bytes).ok()?;
+        Some(u32::from_le_bytes(bytes))
+    };
+
+    // "MZ"
+    if read_u16(0)? != 0x5a4d {
+        return None;
+    }
+    let pe_offset = read_u32(0x3c)? as u64;
+    // "PE\0\0"
+    if read_u32(pe_offset)? != 0x4550 {
+        return None;
+    }
+
+    let coff_header = pe_offset + 4;
+    let section_count = read_u16(coff_header + 2)? as u64;
+    let optional_header_size = read_u16(coff_header + 16)? as u64;
+    let section_table = coff_header + 20 + optional_header_size;
+
+    let mut sections = Vec::new();
+    for i in 0..section_count {
+        let section = section_table + i * 40;
+        let virtual_size = read_u32(section + 8)? as u64;
+        let virtual_address = read_u32(section + 12)? as u64;
+        let characteristics = read_u32(section + 36)?;
+
+        if characteristics & IMAGE_SCN_MEM_EXECUTE != 0 {
+            let start = module_base + virtual_address;
+            sections.push(start..start + virtual_size);
+        }
+    }
+    Some(sections)
+}
+
+/// Scans the executable sections of the PE module mapped at `module_base` for the pattern.
+///
+/// Finds nothing if the module's headers can't be read.
+pub fn scan_module<'a, M: MemorySource>(
+    memory: &'a M,
+    module_base: u64,
+    pattern: &'a Pattern,
+) -> impl Iterator<Item = u64> + 'a {
+    executable_sections(memory, module_base)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(move |section| scan(memory, section.start, section.end - section.start, pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pattern() {
+        let pattern = Pattern::parse("48 8b ?? ? 89 05").unwrap();
+        assert_eq!(pattern.len(), 6);
+        assert_eq!(pattern.to_string(), "48 8B ?? ?? 89 05");
+
+        assert_eq!(Pattern::parse("  "), Err(PatternError::Empty));
+        assert_eq!(
+            Pattern::parse("48 8G"),
+            Err(PatternError::InvalidToken("8G".to_string()))
+        );
+        assert!("488B".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn find_in_bytes() {
+        let pattern = Pattern::parse("AA ?? CC").unwrap();
+        let haystack = [0xaa, 0x00, 0xcc, 0xaa, 0xbb, 0xcc, 0xaa, 0xcc];
+
+        assert_eq!(pattern.find_iter(&haystack).collect::<Vec<_>>(), [0, 3]);
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn scan_across_pages() {
+        use crate::memory::InProcess;
+
+        let mut allocation = vec![0u8; 0x4000];
+        // Start the scanned range on a page, so chunks line up with the offsets below.
+        let skip = allocation.as_ptr().align_offset(0x1000);
+        let memory = &mut allocation[skip..skip + 0x3000];
+        let pattern = Pattern::parse("DE AD ?? EF").unwrap();
+        // The second match straddles two chunks.
+        for offset in [0x10, 0xffe, 0x2ff0] {
+            memory[offset..offset + 4].copy_from_slice(&[0xde, 0xad, 0x42, 0xef]);
+        }
+
+        let base = memory.as_ptr() as u64;
+        let found: Vec<_> = scan(&InProcess, base, memory.len() as u64, &pattern)
+            .map(|address| address - base)
+            .collect();
+        assert_eq!(found, [0x10, 0xffe, 0x2ff0]);
+
+        // A match cut off by the end of the range isn't found.
+        let found = scan(&InProcess, base, 0x1000, &pattern).count();
+        assert_eq!(found, 1);
+    }
+
+    #[cfg(any(windows, target_os = "linux"))]
+    #[test]
+    fn scan_executable_sections() {
+        use crate::memory::InProcess;
+
+        // A PE image with a data section at 0x200 and a code section at 0x400.
+        let mut image = vec![0u8; 0x600];
+        image[0..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        image[0x86..0x88].copy_from_slice(&2u16.to_le_bytes());
+        image[0x94..0x96].copy_from_slice(&0xf0u16.to_le_bytes());
+        let section_table = 0x84 + 20 + 0xf0;
+        for (i, (address, characteristics)) in [(0x200u32, 0x4000_0040u32), (0x400, 0x6000_0020)]
+            .into_iter()
+            .enumerate()
+        {
+            let section = section_table + i * 40;
+            image[section + 8..section + 12].copy_from_slice(&0x200u32.to_le_bytes());
+            image[section + 12..section + 16].copy_from_slice(&address.to_le_bytes());
+            image[section + 36..section + 40].copy_from_slice(&characteristics.to_le_bytes());
+        }
+        image[0x210..0x213].copy_from_slice(&[0x48, 0x8b, 0x05]);
+        image[0x420..0x423].copy_from_slice(&[0x48, 0x8b, 0x05]);
+
+        let base = image.as_ptr() as u64;
+        let pattern = Pattern::parse("48 8B 05").unwrap();
+        let found: Vec<_> = scan_module(&InProcess, base, &pattern)
+            .map(|address| address - base)
+            .collect();
+        assert_eq!(found, [0x420]);
+    }
+}
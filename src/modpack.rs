@@ -0,0 +1,409 @@
+//! Defines the mod package format, so mod managers can share one format instead of each inventing their own.
+//!
+//! A mod package is a folder holding a `mod.json` manifest.
+//! The manifest names the mod, lists the game versions it supports, and holds its changes:
+//! patches of ammos keyed by `item_name`, and entries to set in `.seria` files of the game.
+//!
+//! ```json
+//! {
+//!     "name": "Faster shells",
+//!     "version": "1.0.0",
+//!     "game_versions": ["1.163"],
+//!     "ammo_patches": { "AMMO_57": { "speed": 1200.0 } },
+//!     "seria_overrides": [{ "file": "Objects/example.seria", "set": { "m_price": "100" } }]
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::general::version::GameVersion;
+use crate::general::{AmmoFields, AmmoTable, Patch, PatchError};
+use crate::install::Installation;
+use crate::seria::{Container, Document};
+
+mod merge;
+pub use merge::{merge, merge_patches, Conflict, ConflictTarget, Merged};
+
+/// The name of the manifest file inside of a mod package.
+pub const MANIFEST_FILE: &str = "mod.json";
+
+/// Entries to set in a `.seria` file of the game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SeriaOverride {
+    /// The path of the file, relative to the game folder.
+    pub file: PathBuf,
+    /// The values to set, keyed by the keys of top level entries.
+    pub set: BTreeMap<String, String>,
+}
+
+/// The manifest of a mod package.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Manifest {
+    /// The name of the mod.
+    pub name: String,
+    /// The version of the mod.
+    pub version: String,
+    /// The authors of the mod.
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// A description of the mod.
+    #[serde(default)]
+    pub description: String,
+    /// The game versions the mod supports, like "1.163". Empty if it supports every version.
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+    /// Patches of ammos, keyed by `item_name`.
+    #[serde(default)]
+    pub ammo_patches: BTreeMap<String, Patch>,
+    /// Entries to set in `.seria` files.
+    #[serde(default)]
+    pub seria_overrides: Vec<SeriaOverride>,
+}
+
+/// Error returned when loading, validating, or applying a mod package fails.
+#[derive(Debug)]
+pub enum ModError {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// The manifest is not valid JSON, or misses fields.
+    Manifest(serde_json::Error),
+    /// The manifest has no name.
+    MissingName,
+    /// The manifest lists a game version this library doesn't know.
+    UnknownGameVersion(String),
+    /// The mod doesn't support the game version it is applied to.
+    UnsupportedGameVersion(GameVersion),
+    /// The merged mods have no game version in common.
+    NoCommonGameVersion,
+    /// A path is absolute, or leaves the game folder.
+    InvalidPath(PathBuf),
+    /// An ammo patch applies to an ammo the table doesn't have.
+    MissingAmmo(String),
+    /// An ammo patch is not valid for the ammo.
+    Patch {
+        /// The `item_name` of the patched ammo.
+        item_name: String,
+        /// Why the patch is not valid.
+        error: PatchError,
+    },
+    /// A `.seria` file couldn't be parsed.
+    Seria {
+        /// The path of the file.
+        file: PathBuf,
+        /// Why parsing failed.
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for ModError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Manifest(error) => write!(f, "invalid manifest: {error}"),
+            Self::MissingName => write!(f, "the manifest has no name"),
+            Self::UnknownGameVersion(version) => write!(f, "unknown game version {version:?}"),
+            Self::UnsupportedGameVersion(version) => {
+                write!(f, "the mod doesn't support game version {version}")
+            }
+            Self::NoCommonGameVersion => write!(f, "the mods have no game version in common"),
+            Self::InvalidPath(path) => {
+                write!(f, "{} leaves the game folder", path.display())
+            }
+            Self::MissingAmmo(item_name) => write!(f, "no ammo is named {item_name}"),
+            Self::Patch { item_name, error } => write!(f, "patch of {item_name}: {error}"),
+            Self::Seria { file, error } => write!(f, "{}: {error}", file.display()),
+        }
+    }
+}
+
+impl Error for ModError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) | Self::Seria { error, .. } => Some(error),
+            Self::Manifest(error) => Some(error),
+            Self::Patch { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// A mod package loaded from a folder.
+#[derive(Debug, Clone)]
+pub struct ModPackage {
+    /// The folder of the package.
+    pub root: PathBuf,
+    /// The manifest of the package.
+    pub manifest: Manifest,
+}
+
+impl ModPackage {
+    /// Loads the package in the given folder.
+    pub fn load(root: impl Into<PathBuf>) -> Result<Self, ModError> {
+        let root = root.into();
+        let manifest = std::fs::read_to_string(root.join(MANIFEST_FILE))?;
+        let manifest = serde_json::from_str(&manifest).map_err(ModError::Manifest)?;
+        Ok(Self { root, manifest })
+    }
+
+    /// Writes the manifest into the package folder.
+    pub fn save(&self) -> Result<(), ModError> {
+        let manifest = serde_json::to_string_pretty(&self.manifest).map_err(ModError::Manifest)?;
+        std::fs::write(self.root.join(MANIFEST_FILE), manifest)?;
+        Ok(())
+    }
+
+    /// Returns the game versions the mod supports, failing on a version this library doesn't know.
+    ///
+    /// Empty if the mod supports every version.
+    pub fn game_versions(&self) -> Result<Vec<GameVersion>, ModError> {
+        self.manifest
+            .game_versions
+            .iter()
+            .map(|name| {
+                GameVersion::ALL
+                    .into_iter()
+                    .find(|version| version.name() == name)
+                    .ok_or_else(|| ModError::UnknownGameVersion(name.clone()))
+            })
+            .collect()
+    }
+
+    /// Returns true if the mod supports the given game version.
+    pub fn supports(&self, version: GameVersion) -> bool {
+        self.game_versions()
+            .is_ok_and(|versions| versions.is_empty() || versions.contains(&version))
+    }
+
+    /// Checks the manifest without applying it, returning every problem found.
+    ///
+    /// `sample` is an ammo of the targeted version, used to check that patched fields exist.
+    pub fn validate<A: Serialize + DeserializeOwned>(&self, sample: &A) -> Vec<ModError> {
+        let mut errors = Vec::new();
+
+        if self.manifest.name.trim().is_empty() {
+            errors.push(ModError::MissingName);
+        }
+        if let Err(error) = self.game_versions() {
+            errors.push(error);
+        }
+
+        for (item_name, patch) in &self.manifest.ammo_patches {
+            let checked = serde_json::to_value(sample)
+                .map_err(PatchError::from)
+                .and_then(|value| {
+                    let mut copy: A = serde_json::from_value(value)?;
+                    patch.apply(&mut copy)
+                });
+            if let Err(error) = checked {
+                errors.push(ModError::Patch {
+                    item_name: item_name.clone(),
+                    error,
+                });
+            }
+        }
+
+        for seria in &self.manifest.seria_overrides {
+            if !is_inside(&seria.file) {
+                errors.push(ModError::InvalidPath(seria.file.clone()));
+            }
+        }
+
+        errors
+    }
+
+    /// Applies the ammo patches to a table.
+    ///
+    /// Stops at the first patch that fails. Patches applied before it stay applied.
+    pub fn apply_ammo<A>(&self, table: &mut AmmoTable<'_, A>) -> Result<(), ModError>
+    where
+        A: AmmoFields + Serialize + DeserializeOwned,
+    {
+        for (item_name, patch) in &self.manifest.ammo_patches {
+            let ammo = table
+                .get_by_name_mut(item_name)
+                .ok_or_else(|| ModError::MissingAmmo(item_name.clone()))?;
+            patch.apply(ammo).map_err(|error| ModError::Patch {
+                item_name: item_name.clone(),
+                error,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Sets the `.seria` entries in the files of the given game folder.
+    ///
+    /// Every file is checked before any is written, so a bad override leaves the game untouched.
+    pub fn apply_seria(&self, game: &Path) -> Result<(), ModError> {
+        let mut documents = Vec::new();
+        for seria in &self.manifest.seria_overrides {
+            if !is_inside(&seria.file) {
+                return Err(ModError::InvalidPath(seria.file.clone()));
+            }
+
+            let path = game.join(&seria.file);
+            let mut document = Document::load(&path).map_err(|error| ModError::Seria {
+                file: seria.file.clone(),
+                error,
+            })?;
+            for (key, value) in &seria.set {
+                document.set(key, value.as_str());
+            }
+            document.normalize_new_lines();
+            documents.push((path, document));
+        }
+
+        for (path, document) in documents {
+            document.save(path)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the `.seria` overrides to an installation, after checking the mod supports its version.
+    ///
+    /// Installations of an unknown version are accepted.
+    pub fn install(
+        &self,
+        installation: &Installation,
+        version: Option<GameVersion>,
+    ) -> Result<(), ModError> {
+        if let Some(version) = version {
+            if !self.supports(version) {
+                return Err(ModError::UnsupportedGameVersion(version));
+            }
+        }
+        self.apply_seria(installation.root())
+    }
+}
+
+/// Returns true if a relative path stays inside of the folder it is relative to.
+fn is_inside(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v1_163::AmmoBuilder;
+
+    fn package(manifest: &str) -> ModPackage {
+        ModPackage {
+            root: PathBuf::new(),
+            manifest: serde_json::from_str(manifest).unwrap(),
+        }
+    }
+
+    #[test]
+    fn validate_manifest() {
+        let package = package(
+            r#"{
+                "name": "",
+                "version": "1.0",
+                "game_versions": ["1.163", "2.0"],
+                "ammo_patches": {
+                    "AMMO_57": { "speed": 1200.0 },
+                    "AMMO_85": { "sped": 1200.0 }
+                },
+                "seria_overrides": [{ "file": "../outside.seria", "set": {} }]
+            }"#,
+        );
+
+        let errors: Vec<_> = package
+            .validate(&AmmoBuilder::test_ammo("AMMO_57", 1))
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "the manifest has no name",
+                "unknown game version \"2.0\"",
+                "patch of AMMO_85: unknown field sped",
+                "../outside.seria leaves the game folder",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_ammo_patches() {
+        let package = package(
+            r#"{ "name": "Fast", "version": "1.0", "ammo_patches": { "AMMO_57": { "speed": 1200.0 } } }"#,
+        );
+        assert!(package
+            .validate(&AmmoBuilder::test_ammo("AMMO_57", 1))
+            .is_empty());
+        assert!(package.supports(GameVersion::V1_151));
+
+        let mut ammos = [
+            AmmoBuilder::test_ammo("AMMO_37", 1),
+            AmmoBuilder::test_ammo("AMMO_57", 1),
+        ];
+        package
+            .apply_ammo(&mut AmmoTable::from_slice(&mut ammos))
+            .unwrap();
+        assert_eq!(ammos[1].speed, 1200.0);
+
+        let mut ammos = [AmmoBuilder::test_ammo("AMMO_37", 1)];
+        let error = package
+            .apply_ammo(&mut AmmoTable::from_slice(&mut ammos))
+            .unwrap_err();
+        assert!(matches!(error, ModError::MissingAmmo(name) if name == "AMMO_57"));
+    }
+
+    #[test]
+    fn apply_seria_overrides() {
+        let game =
+            std::env::temp_dir().join(format!("highfleet-rs-modpack-{}", std::process::id()));
+        std::fs::create_dir_all(game.join("Objects")).unwrap();
+        std::fs::write(
+            game.join("Objects/gun.seria"),
+            "m_name=Gun\r\nm_price=50\r\n",
+        )
+        .unwrap();
+
+        let package = package(
+            r#"{
+                "name": "Cheap", "version": "1.0",
+                "seria_overrides": [{ "file": "Objects/gun.seria", "set": { "m_price": "10", "m_new": "1" } }]
+            }"#,
+        );
+        package.apply_seria(&game).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(game.join("Objects/gun.seria")).unwrap(),
+            "m_name=Gun\r\nm_price=10\r\nm_new=1\r\n"
+        );
+
+        std::fs::remove_dir_all(&game).unwrap();
+    }
+
+    #[test]
+    fn paths_stay_inside() {
+        assert!(is_inside(Path::new("Objects/../Ships/a.seria")));
+        assert!(!is_inside(Path::new("Objects/../../a.seria")));
+        assert!(!is_inside(Path::new("/etc/passwd")));
+    }
+}
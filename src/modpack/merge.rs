@@ -0,0 +1,272 @@
+//! Merges mod packages into one, reporting the fields they disagree on.
+//!
+//! Packages are merged in priority order: when several set the same field to different values,
+//! the one merged last wins, and the disagreement is recorded as a `Conflict`.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{Manifest, ModError, ModPackage, SeriaOverride};
+use crate::general::Patch;
+
+/// What a conflicting field belongs to.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConflictTarget {
+    /// An ammo, by `item_name`.
+    Ammo {
+        /// The `item_name` of the ammo.
+        item_name: String,
+    },
+    /// A `.seria` file.
+    Seria {
+        /// The path of the file, relative to the game folder.
+        file: PathBuf,
+    },
+}
+
+/// A field set to different values by several mods.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// What the field belongs to.
+    pub target: ConflictTarget,
+    /// The name of the field, or the key of the `.seria` entry.
+    pub field: String,
+    /// The name of every mod setting the field and its value, in priority order.
+    pub values: Vec<(String, Value)>,
+    /// The name of the mod whose value is kept.
+    pub winner: String,
+}
+
+/// The result of merging mods: one manifest holding every change, and the conflicts found on the way.
+///
+/// Serializing the conflicts gives a machine readable report.
+#[derive(Debug, Clone)]
+pub struct Merged {
+    /// The merged changes.
+    pub manifest: Manifest,
+    /// The fields mods disagree on, ordered by target and field.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// The values a field is set to, in priority order.
+type Contributions = BTreeMap<(ConflictTarget, String), Vec<(String, Value)>>;
+
+/// Merges named ammo patch sets, lowest priority first.
+///
+/// Use this for patches that don't come from a `ModPackage`.
+pub fn merge_patches<'a>(
+    sets: impl IntoIterator<Item = (&'a str, &'a BTreeMap<String, Patch>)>,
+) -> (BTreeMap<String, Patch>, Vec<Conflict>) {
+    let mut contributions = Contributions::new();
+    for (name, patches) in sets {
+        for (item_name, patch) in patches {
+            let target = ConflictTarget::Ammo {
+                item_name: item_name.clone(),
+            };
+            for (field, value) in patch.fields() {
+                contributions
+                    .entry((target.clone(), field.to_string()))
+                    .or_default()
+                    .push((name.to_string(), value.clone()));
+            }
+        }
+    }
+
+    let mut merged: BTreeMap<String, Patch> = BTreeMap::new();
+    let conflicts = resolve(contributions, |target, field, value| {
+        if let ConflictTarget::Ammo { item_name } = target {
+            merged
+                .entry(item_name.clone())
+                .or_default()
+                .insert(field, value);
+        }
+    });
+    (merged, conflicts)
+}
+
+/// Merges mod packages, lowest priority first.
+///
+/// The merged manifest is named after the packages, and supports the game versions every package supports.
+/// Fails with `ModError::NoCommonGameVersion` if there is none.
+pub fn merge(packages: &[&ModPackage]) -> Result<Merged, ModError> {
+    let game_versions = common_game_versions(packages)?;
+
+    let (ammo_patches, mut conflicts) = merge_patches(packages.iter().map(|package| {
+        (
+            package.manifest.name.as_str(),
+            &package.manifest.ammo_patches,
+        )
+    }));
+
+    let mut contributions = Contributions::new();
+    for package in packages {
+        for seria in &package.manifest.seria_overrides {
+            let target = ConflictTarget::Seria {
+                file: seria.file.clone(),
+            };
+            for (key, value) in &seria.set {
+                contributions
+                    .entry((target.clone(), key.clone()))
+                    .or_default()
+                    .push((package.manifest.name.clone(), Value::String(value.clone())));
+            }
+        }
+    }
+
+    let mut files: BTreeMap<PathBuf, BTreeMap<String, String>> = BTreeMap::new();
+    conflicts.extend(resolve(contributions, |target, key, value| {
+        if let (ConflictTarget::Seria { file }, Value::String(value)) = (target, value) {
+            files
+                .entry(file.clone())
+                .or_default()
+                .insert(key.to_string(), value);
+        }
+    }));
+
+    let manifest = Manifest {
+        name: packages
+            .iter()
+            .map(|package| package.manifest.name.as_str())
+            .collect::<Vec<_>>()
+            .join(" + "),
+        game_versions,
+        ammo_patches,
+        seria_overrides: files
+            .into_iter()
+            .map(|(file, set)| SeriaOverride { file, set })
+            .collect(),
+        ..Manifest::default()
+    };
+
+    Ok(Merged {
+        manifest,
+        conflicts,
+    })
+}
+
+/// Keeps the value of the highest priority for every field, returning the fields set to different values.
+fn resolve(
+    contributions: Contributions,
+    mut keep: impl FnMut(&ConflictTarget, &str, Value),
+) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for ((target, field), values) in contributions {
+        let (winner, value) = values.last().cloned().unwrap();
+        keep(&target, &field, value.clone());
+
+        if values.iter().any(|(_, other)| *other != value) {
+            conflicts.push(Conflict {
+                target,
+                field,
+                values,
+                winner,
+            });
+        }
+    }
+    conflicts
+}
+
+/// Returns the game versions every package supports, empty meaning every version.
+fn common_game_versions(packages: &[&ModPackage]) -> Result<Vec<String>, ModError> {
+    let mut common: Option<Vec<String>> = None;
+    for package in packages {
+        let versions = &package.manifest.game_versions;
+        if versions.is_empty() {
+            continue;
+        }
+        common = Some(match common {
+            None => versions.clone(),
+            Some(common) => common
+                .into_iter()
+                .filter(|version| versions.contains(version))
+                .collect(),
+        });
+    }
+    match common {
+        Some(common) if common.is_empty() => Err(ModError::NoCommonGameVersion),
+        common => Ok(common.unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(manifest: &str) -> ModPackage {
+        ModPackage {
+            root: PathBuf::new(),
+            manifest: serde_json::from_str(manifest).unwrap(),
+        }
+    }
+
+    #[test]
+    fn merge_with_priority() {
+        let base = package(
+            r#"{
+                "name": "Base", "version": "1", "game_versions": ["1.151", "1.163"],
+                "ammo_patches": { "AMMO_57": { "speed": 1200.0, "ttl": 5.0 } },
+                "seria_overrides": [{ "file": "Objects/gun.seria", "set": { "m_price": "10" } }]
+            }"#,
+        );
+        let addon = package(
+            r#"{
+                "name": "Addon", "version": "1", "game_versions": ["1.163"],
+                "ammo_patches": { "AMMO_57": { "speed": 1500.0, "ttl": 5.0 }, "AMMO_85": { "ttl": 9.0 } },
+                "seria_overrides": [{ "file": "Objects/gun.seria", "set": { "m_price": "20", "m_name": "Gun" } }]
+            }"#,
+        );
+
+        let merged = merge(&[&base, &addon]).unwrap();
+        let manifest = &merged.manifest;
+        assert_eq!(manifest.name, "Base + Addon");
+        assert_eq!(manifest.game_versions, ["1.163"]);
+        assert_eq!(
+            manifest.ammo_patches["AMMO_57"].get("speed"),
+            Some(&1500.0.into())
+        );
+        assert_eq!(
+            manifest.ammo_patches["AMMO_85"].get("ttl"),
+            Some(&9.0.into())
+        );
+        assert_eq!(manifest.seria_overrides[0].set["m_price"], "20");
+        assert_eq!(manifest.seria_overrides[0].set["m_name"], "Gun");
+
+        // Agreeing on `ttl` isn't a conflict.
+        assert_eq!(merged.conflicts.len(), 2);
+        let report = serde_json::to_value(&merged.conflicts).unwrap();
+        assert_eq!(report[0]["target"]["kind"], "ammo");
+        assert_eq!(report[0]["field"], "speed");
+        assert_eq!(report[0]["winner"], "Addon");
+        assert_eq!(report[0]["values"][0], serde_json::json!(["Base", 1200.0]));
+        assert_eq!(report[1]["target"]["file"], "Objects/gun.seria");
+
+        // Reversing the priority reverses the winner.
+        let merged = merge(&[&addon, &base]).unwrap();
+        assert_eq!(
+            merged.manifest.ammo_patches["AMMO_57"].get("speed"),
+            Some(&1200.0.into())
+        );
+        assert_eq!(merged.conflicts[0].winner, "Base");
+    }
+
+    #[test]
+    fn merge_fails_without_common_game_version() {
+        let old = package(r#"{ "name": "Old", "version": "1", "game_versions": ["1.151"] }"#);
+        let new = package(r#"{ "name": "New", "version": "1", "game_versions": ["1.163"] }"#);
+        let any = package(r#"{ "name": "Any", "version": "1" }"#);
+
+        assert!(matches!(
+            merge(&[&old, &any, &new]),
+            Err(ModError::NoCommonGameVersion)
+        ));
+
+        let merged = merge(&[&old, &any]).unwrap();
+        assert_eq!(merged.manifest.game_versions, ["1.151"]);
+        let merged = merge(&[&any]).unwrap();
+        assert!(merged.manifest.game_versions.is_empty());
+    }
+}
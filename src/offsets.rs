@@ -0,0 +1,373 @@
+//! Defines a database of named game addresses, per game version.
+//!
+//! Addresses are stored relative to the module base (RVAs), so they survive ASLR.
+//! Objects on the heap are found through a `PointerChain` from such an address instead.
+//! When no address is known for the running version, a byte pattern can be scanned for instead,
+//! which keeps working across patches as long as the surrounding code doesn't change.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::general::version::GameVersion;
+use crate::hook::FnPtr;
+use crate::memory::scan::{scan_module, Pattern};
+use crate::memory::{MemoryError, MemorySource};
+
+mod chain;
+pub use chain::{ChainError, ChainParseError, KnownChain, PointerChain, KNOWN_CHAINS};
+
+/// The names of the addresses looked up by this library.
+pub mod names {
+    /// The array of every ammo.
+    pub const AMMO_TABLE: &str = "ammo_table";
+    /// The registry of every module.
+    pub const MODULE_REGISTRY: &str = "module_registry";
+    /// The profile of the player.
+    pub const PLAYER_PROFILE: &str = "player_profile";
+}
+
+/// A known address of a game version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownOffset {
+    /// The name of the address, see `names`.
+    pub name: &'static str,
+    /// The version the address belongs to.
+    pub version: GameVersion,
+    /// The address relative to the module base.
+    pub rva: u64,
+}
+
+/// The addresses known to this library.
+///
+/// No address has been verified against a game build yet. Add your own with `Offsets::insert`
+/// or `Offsets::insert_signature` until then.
+pub const KNOWN_OFFSETS: &[KnownOffset] = &[];
+
+/// How the address is computed from the location of a signature match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureTarget {
+    /// The address is at a fixed distance from the match.
+    Match {
+        /// The distance from the start of the match.
+        offset: i64,
+    },
+    /// The match is an instruction with a RIP-relative operand, like `mov rax, [rip + x]`, pointing to the address.
+    RipRelative {
+        /// The offset of the 32-bit displacement from the start of the match.
+        displacement: u64,
+        /// The offset of the end of the instruction from the start of the match.
+        instruction_end: u64,
+    },
+}
+
+/// A byte pattern that locates an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The pattern to scan the executable sections of the game for. It must match exactly once.
+    pub pattern: Pattern,
+    /// How the address is computed from the match.
+    pub target: SignatureTarget,
+}
+
+/// Returned when resolving an address fails.
+#[derive(Debug)]
+pub enum OffsetError {
+    /// Neither an address nor a signature is known for the name.
+    Unknown(String),
+    /// The signature doesn't match anything.
+    NotFound(String),
+    /// The signature matches more than once.
+    Ambiguous {
+        /// The name of the address.
+        name: String,
+        /// The number of matches.
+        matches: usize,
+    },
+    /// Memory around a match could not be read.
+    Memory(MemoryError),
+    /// The pointer chain could not be followed.
+    Chain(ChainError),
+}
+
+impl fmt::Display for OffsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "no address or signature known for {name}"),
+            Self::NotFound(name) => write!(f, "signature of {name} not found"),
+            Self::Ambiguous { name, matches } => {
+                write!(f, "signature of {name} matches {matches} times")
+            }
+            Self::Memory(error) => write!(f, "{error}"),
+            Self::Chain(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for OffsetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Memory(error) => Some(error),
+            Self::Chain(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<MemoryError> for OffsetError {
+    fn from(error: MemoryError) -> Self {
+        Self::Memory(error)
+    }
+}
+
+impl From<ChainError> for OffsetError {
+    fn from(error: ChainError) -> Self {
+        Self::Chain(error)
+    }
+}
+
+/// A database of named addresses per game version, with signatures as fallback.
+#[derive(Debug, Clone, Default)]
+pub struct Offsets {
+    rvas: HashMap<(GameVersion, String), u64>,
+    chains: HashMap<(GameVersion, String), PointerChain>,
+    signatures: HashMap<String, Signature>,
+}
+
+impl Offsets {
+    /// Creates an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a database holding `KNOWN_OFFSETS` and `KNOWN_CHAINS`.
+    pub fn builtin() -> Self {
+        let mut offsets = Self::new();
+        for known in KNOWN_OFFSETS {
+            offsets.insert(known.version, known.name, known.rva);
+        }
+        for known in KNOWN_CHAINS {
+            offsets.insert_chain(known.version, known.name, known.chain());
+        }
+        offsets
+    }
+
+    /// Sets the address of a name for a version, relative to the module base.
+    pub fn insert(&mut self, version: GameVersion, name: &str, rva: u64) {
+        self.rvas.insert((version, name.to_string()), rva);
+    }
+
+    /// Sets the pointer chain leading to a name for a version, used when its address isn't known.
+    pub fn insert_chain(&mut self, version: GameVersion, name: &str, chain: PointerChain) {
+        self.chains.insert((version, name.to_string()), chain);
+    }
+
+    /// Sets the signature used for a name when its address isn't known for the running version.
+    pub fn insert_signature(&mut self, name: &str, signature: Signature) {
+        self.signatures.insert(name.to_string(), signature);
+    }
+
+    /// Returns the address of a name for a version, relative to the module base.
+    pub fn rva(&self, version: GameVersion, name: &str) -> Option<u64> {
+        self.rvas.get(&(version, name.to_string())).copied()
+    }
+
+    /// Returns the pointer chain leading to a name for a version.
+    pub fn chain(&self, version: GameVersion, name: &str) -> Option<&PointerChain> {
+        self.chains.get(&(version, name.to_string()))
+    }
+
+    /// Returns the signature of a name.
+    pub fn signature(&self, name: &str) -> Option<&Signature> {
+        self.signatures.get(name)
+    }
+
+    /// Returns the absolute address of a name in the game module mapped at `module_base`.
+    ///
+    /// Uses the known address for the version, then the pointer chain for the version,
+    /// and scans for the signature otherwise.
+    pub fn resolve<M: MemorySource>(
+        &self,
+        memory: &M,
+        version: GameVersion,
+        module_base: u64,
+        name: &str,
+    ) -> Result<u64, OffsetError> {
+        if let Some(rva) = self.rva(version, name) {
+            return Ok(module_base + rva);
+        }
+        if let Some(chain) = self.chain(version, name) {
+            return Ok(chain.resolve(memory, module_base)?);
+        }
+
+        let signature = self
+            .signature(name)
+            .ok_or_else(|| OffsetError::Unknown(name.to_string()))?;
+        let matches: Vec<_> = scan_module(memory, module_base, &signature.pattern)
+            .take(2)
+            .collect();
+        let found = match matches[..] {
+            [found] => found,
+            [] => return Err(OffsetError::NotFound(name.to_string())),
+            _ => {
+                return Err(OffsetError::Ambiguous {
+                    name: name.to_string(),
+                    matches: scan_module(memory, module_base, &signature.pattern).count(),
+                })
+            }
+        };
+
+        match signature.target {
+            SignatureTarget::Match { offset } => Ok(found.wrapping_add_signed(offset)),
+            SignatureTarget::RipRelative {
+                displacement,
+                instruction_end,
+            } => {
+                let mut bytes = [0u8; 4];
+                memory.read(found + displacement, &mut bytes)?;
+                let displacement = i32::from_le_bytes(bytes) as i64;
+                Ok((found + instruction_end).wrapping_add_signed(displacement))
+            }
+        }
+    }
+
+    /// Resolves the address of a game function as a typed function pointer.
+    ///
+    /// See `resolve`.
+    ///
+    /// # Safety
+    ///
+    /// The function with the given name must have the signature `F` in the running version.
+    pub unsafe fn resolve_fn<F: FnPtr, M: MemorySource>(
+        &self,
+        memory: &M,
+        version: GameVersion,
+        module_base: u64,
+        name: &str,
+    ) -> Result<F, OffsetError> {
+        let address = self.resolve(memory, version, module_base, name)?;
+        Ok(F::from_address(address as usize))
+    }
+}
+
+#[cfg(all(test, any(windows, target_os = "linux")))]
+mod tests {
+    use super::*;
+    use crate::memory::InProcess;
+
+    /// Builds a PE image with a single code section at 0x200, holding `code` at its start.
+    fn image(code: &[u8]) -> Vec<u8> {
+        let mut image = vec![0u8; 0x400];
+        image[0..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        image[0x86..0x88].copy_from_slice(&1u16.to_le_bytes());
+        let section = 0x84 + 20;
+        image[section + 8..section + 12].copy_from_slice(&0x200u32.to_le_bytes());
+        image[section + 12..section + 16].copy_from_slice(&0x200u32.to_le_bytes());
+        image[section + 36..section + 40].copy_from_slice(&0x6000_0020u32.to_le_bytes());
+        image[0x200..0x200 + code.len()].copy_from_slice(code);
+        image
+    }
+
+    #[test]
+    fn known_rva_wins() {
+        let mut offsets = Offsets::new();
+        offsets.insert(GameVersion::V1_163, names::AMMO_TABLE, 0x1234);
+
+        let address = offsets.resolve(&InProcess, GameVersion::V1_163, 0x1000, names::AMMO_TABLE);
+        assert_eq!(address.unwrap(), 0x2234);
+
+        let error = offsets
+            .resolve(&InProcess, GameVersion::V1_151, 0x1000, names::AMMO_TABLE)
+            .unwrap_err();
+        assert!(matches!(error, OffsetError::Unknown(_)));
+    }
+
+    #[test]
+    fn falls_back_to_pointer_chain() {
+        let object = 0x1234u64;
+        let pointer = Box::new(object);
+        let base = &*pointer as *const u64 as u64 - 0x10;
+
+        let mut offsets = Offsets::new();
+        offsets.insert_chain(
+            GameVersion::V1_163,
+            names::PLAYER_PROFILE,
+            PointerChain::new(0x10, vec![0x8]),
+        );
+
+        let address = offsets.resolve(&InProcess, GameVersion::V1_163, base, names::PLAYER_PROFILE);
+        assert_eq!(address.unwrap(), object + 0x8);
+        assert!(Offsets::builtin()
+            .chain(GameVersion::V1_163, names::PLAYER_PROFILE)
+            .is_none());
+    }
+
+    #[test]
+    fn falls_back_to_rip_relative_signature() {
+        // mov rax, [rip + 0x100]
+        let image = image(&[0x90, 0x48, 0x8b, 0x05, 0x00, 0x01, 0x00, 0x00, 0xc3]);
+        let base = image.as_ptr() as u64;
+
+        let mut offsets = Offsets::new();
+        offsets.insert_signature(
+            names::AMMO_TABLE,
+            Signature {
+                pattern: Pattern::parse("48 8B 05 ?? ?? ?? ?? C3").unwrap(),
+                target: SignatureTarget::RipRelative {
+                    displacement: 3,
+                    instruction_end: 7,
+                },
+            },
+        );
+
+        let address = offsets
+            .resolve(&InProcess, GameVersion::V1_163, base, names::AMMO_TABLE)
+            .unwrap();
+        assert_eq!(address, base + 0x201 + 7 + 0x100);
+    }
+
+    #[test]
+    fn resolve_typed_function() {
+        extern "C" fn answer() -> i32 {
+            42
+        }
+
+        let base = 0x1000;
+        let mut offsets = Offsets::new();
+        offsets.insert(
+            GameVersion::V1_151,
+            "answer",
+            (answer as extern "C" fn() -> i32).to_address() as u64 - base,
+        );
+
+        let function: extern "C" fn() -> i32 =
+            unsafe { offsets.resolve_fn(&InProcess, GameVersion::V1_151, base, "answer") }.unwrap();
+        assert_eq!(function(), 42);
+    }
+
+    #[test]
+    fn ambiguous_signature() {
+        let image = image(&[0xc3, 0x90, 0xc3]);
+
+        let mut offsets = Offsets::new();
+        offsets.insert_signature(
+            names::PLAYER_PROFILE,
+            Signature {
+                pattern: Pattern::parse("C3").unwrap(),
+                target: SignatureTarget::Match { offset: 0 },
+            },
+        );
+
+        let error = offsets
+            .resolve(
+                &InProcess,
+                GameVersion::V1_163,
+                image.as_ptr() as u64,
+                names::PLAYER_PROFILE,
+            )
+            .unwrap_err();
+        assert!(matches!(error, OffsetError::Ambiguous { matches: 2, .. }));
+    }
+}
@@ -0,0 +1,231 @@
+//! Defines pointer chains, the paths of pointers leading from a static address to a heap object.
+//!
+//! A chain is written like `0x1234 -> +0x18 -> +0x120 -> +0x8`:
+//! starting at the module base plus `0x1234`, every step reads the pointer at the current address and adds its offset.
+//! The result is the address of the object, not the value stored there.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::general::version::GameVersion;
+use crate::memory::{MemoryError, MemorySource};
+
+/// A path of pointers from an address relative to the module base.
+///
+/// Serialized as its text form, like `"0x1234 -> +0x18 -> -0x8"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PointerChain {
+    /// The address of the first pointer, relative to the module base.
+    pub base: u64,
+    /// The offset added to every pointer read along the chain.
+    pub offsets: Vec<i64>,
+}
+
+/// A pointer chain known for a game version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownChain {
+    /// The name of the address the chain leads to, see `names`.
+    pub name: &'static str,
+    /// The version the chain belongs to.
+    pub version: GameVersion,
+    /// The address of the first pointer, relative to the module base.
+    pub base: u64,
+    /// The offset added to every pointer read along the chain.
+    pub offsets: &'static [i64],
+}
+
+impl KnownChain {
+    /// Returns the chain.
+    pub fn chain(&self) -> PointerChain {
+        PointerChain::new(self.base, self.offsets.to_vec())
+    }
+}
+
+/// The pointer chains known to this library.
+///
+/// No chain has been verified against a game build yet. Add your own with `Offsets::insert_chain` until then.
+pub const KNOWN_CHAINS: &[KnownChain] = &[];
+
+/// Returned when resolving a pointer chain fails.
+#[derive(Debug)]
+pub enum ChainError {
+    /// A pointer along the chain is null, usually because the object doesn't exist yet.
+    Null {
+        /// The index of the step whose pointer is null.
+        step: usize,
+    },
+    /// A pointer along the chain could not be read.
+    Memory {
+        /// The index of the step whose pointer could not be read.
+        step: usize,
+        /// The error returned by the memory.
+        error: MemoryError,
+    },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null { step } => write!(f, "pointer at step {step} is null"),
+            Self::Memory { step, error } => write!(f, "step {step}: {error}"),
+        }
+    }
+}
+
+impl Error for ChainError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Memory { error, .. } => Some(error),
+            Self::Null { .. } => None,
+        }
+    }
+}
+
+/// Returned when parsing a `PointerChain` fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainParseError(pub String);
+
+impl fmt::Display for ChainParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pointer chain step: {:?}", self.0)
+    }
+}
+
+impl Error for ChainParseError {}
+
+impl PointerChain {
+    /// Creates a chain starting at `base`, relative to the module base.
+    pub fn new(base: u64, offsets: Vec<i64>) -> Self {
+        Self { base, offsets }
+    }
+
+    /// Returns the address the chain leads to in the game module mapped at `module_base`.
+    pub fn resolve<M: MemorySource>(
+        &self,
+        memory: &M,
+        module_base: u64,
+    ) -> Result<u64, ChainError> {
+        let mut address = module_base.wrapping_add(self.base);
+        for (step, offset) in self.offsets.iter().enumerate() {
+            let mut bytes = [0u8; 8];
+            memory
+                .read(address, &mut bytes)
+                .map_err(|error| ChainError::Memory { step, error })?;
+            let pointer = u64::from_le_bytes(bytes);
+            if pointer == 0 {
+                return Err(ChainError::Null { step });
+            }
+            address = pointer.wrapping_add_signed(*offset);
+        }
+        Ok(address)
+    }
+}
+
+impl fmt::Display for PointerChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.base)?;
+        for offset in &self.offsets {
+            let sign = if *offset < 0 { '-' } else { '+' };
+            write!(f, " -> {sign}{:#x}", offset.unsigned_abs())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PointerChain {
+    type Err = ChainParseError;
+
+    /// Parses the form written by `Display`. Offsets without a sign are positive.
+    fn from_str(chain: &str) -> Result<Self, Self::Err> {
+        let hex = |step: &str| {
+            let digits = step.strip_prefix("0x").unwrap_or(step);
+            u64::from_str_radix(digits, 16).map_err(|_| ChainParseError(step.to_string()))
+        };
+
+        let mut steps = chain.split("->").map(str::trim);
+        let base = hex(steps.next().unwrap_or_default())?;
+        let offsets = steps
+            .map(|step| {
+                let (negative, magnitude) = match step.strip_prefix('-') {
+                    Some(magnitude) => (true, magnitude),
+                    None => (false, step.strip_prefix('+').unwrap_or(step)),
+                };
+                let magnitude = hex(magnitude)
+                    .ok()
+                    .and_then(|magnitude| i64::try_from(magnitude).ok())
+                    .ok_or_else(|| ChainParseError(step.to_string()))?;
+                Ok(if negative { -magnitude } else { magnitude })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { base, offsets })
+    }
+}
+
+impl Serialize for PointerChain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PointerChain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chain = String::deserialize(deserializer)?;
+        chain.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::Snapshot;
+
+    const BASE: u64 = 0x1400_0000;
+
+    #[test]
+    fn parse_and_format() {
+        let chain: PointerChain = "0x1234 -> +0x18 -> 120 -> -0x8".parse().unwrap();
+        assert_eq!(chain, PointerChain::new(0x1234, vec![0x18, 0x120, -0x8]));
+        assert_eq!(chain.to_string(), "0x1234 -> +0x18 -> +0x120 -> -0x8");
+        assert_eq!(
+            "0x1234 -> +0x1g".parse::<PointerChain>(),
+            Err(ChainParseError("+0x1g".to_string()))
+        );
+
+        let json = serde_json::to_string(&chain).unwrap();
+        assert_eq!(json, "\"0x1234 -> +0x18 -> +0x120 -> -0x8\"");
+        assert_eq!(serde_json::from_str::<PointerChain>(&json).unwrap(), chain);
+    }
+
+    #[test]
+    fn resolve_follows_pointers() {
+        let object = BASE + 0x2000;
+        let mut memory = Snapshot::new(BASE, None);
+        memory.insert(BASE + 0x100, &(BASE + 0x1000).to_le_bytes());
+        memory.insert(BASE + 0x1018, &object.to_le_bytes());
+        memory.insert(BASE + 0x1020, &0u64.to_le_bytes());
+
+        let chain = PointerChain::new(0x100, vec![0x18, 0x8]);
+        assert_eq!(chain.resolve(&memory, BASE).unwrap(), object + 0x8);
+        assert_eq!(
+            PointerChain::new(0x100, vec![])
+                .resolve(&memory, BASE)
+                .unwrap(),
+            BASE + 0x100
+        );
+
+        let null = PointerChain::new(0x100, vec![0x20, 0x8]);
+        assert!(matches!(
+            null.resolve(&memory, BASE),
+            Err(ChainError::Null { step: 1 })
+        ));
+        let unreadable = PointerChain::new(0x100, vec![0x18, 0x8, 0]);
+        assert!(matches!(
+            unreadable.resolve(&memory, BASE),
+            Err(ChainError::Memory { step: 2, .. })
+        ));
+    }
+}
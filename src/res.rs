@@ -0,0 +1,390 @@
+//! Defines an index of the resources the game has installed, such as images and sound sets.
+//!
+//! Ammos refer to resources by name: `magazine_image` to an image or animation of a `.res` file in the Tex folder,
+//! and the `shell_*` fields to sound sets of `sound.res`.
+//! A `ResourceIndex` holds the known names, so `validate_resources` can tell when a name doesn't exist.
+//!
+//! A sound set is named after its sounds without their number: the sounds "crowd_01" to "crowd_03" make the set "crowd".
+//! `SoundSet` holds a name following that rule, and `AmmoSounds` the sound sets of an ammo.
+//! Images are the other way around: an animation is named by its first frame, like "animation_name_01",
+//! which `SpriteRef::resolve` tells apart from static images.
+
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use crate::general::{AmmoFields, AmmoWarning, EscadraString};
+
+/// The names of the images and sound sets available to the game.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ResourceIndex {
+    images: BTreeSet<String>,
+    sound_sets: BTreeSet<String>,
+    sounds: BTreeSet<String>,
+}
+
+impl ResourceIndex {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the name of an image or of an animation frame, like "animation_name_01".
+    pub fn insert_image(&mut self, name: impl Into<String>) {
+        self.images.insert(name.into());
+    }
+
+    /// Adds the name of a sound set, like "shell_in_small".
+    pub fn insert_sound_set(&mut self, name: impl Into<String>) {
+        self.sound_sets.insert(name.into());
+    }
+
+    /// Adds the name of a sound of `sound.res`, like "crowd_01", and the sound set it belongs to.
+    pub fn insert_sound(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.insert_sound_set(SoundSet::of_sound(&name).unwrap_or(&name));
+        self.sounds.insert(name);
+    }
+
+    /// Returns true if an image or animation frame with the name exists.
+    pub fn has_image(&self, name: &str) -> bool {
+        self.images.contains(name)
+    }
+
+    /// Returns true if a sound set with the name exists.
+    pub fn has_sound_set(&self, name: &str) -> bool {
+        self.sound_sets.contains(name)
+    }
+
+    /// Returns the names of every image, in order.
+    pub fn images(&self) -> impl Iterator<Item = &str> {
+        self.images.iter().map(String::as_str)
+    }
+
+    /// Returns the names of every sound set, in order.
+    pub fn sound_sets(&self) -> impl Iterator<Item = &str> {
+        self.sound_sets.iter().map(String::as_str)
+    }
+
+    /// Returns the sounds added with `insert_sound` that belong to `set`, in order.
+    pub fn sound_set_members<'a>(&'a self, set: &'a SoundSet) -> impl Iterator<Item = &'a str> {
+        self.sounds
+            .iter()
+            .map(String::as_str)
+            .filter(move |sound| SoundSet::of_sound(sound).unwrap_or(sound) == set.as_str())
+    }
+}
+
+impl<S: Into<String>> Extend<S> for ResourceIndex {
+    /// Adds image names.
+    fn extend<I: IntoIterator<Item = S>>(&mut self, names: I) {
+        self.images.extend(names.into_iter().map(Into::into));
+    }
+}
+
+/// The name of a sound set, checked not to be the name of one of its sounds.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SoundSet(String);
+
+/// Error returned when a name can't be a sound set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoundSetError {
+    /// The name is empty.
+    Empty,
+    /// The name ends with a number, like "crowd_01", so it names a sound of the set instead.
+    NumberedSound {
+        /// The name of the set the sound belongs to, like "crowd".
+        set: String,
+    },
+}
+
+impl fmt::Display for SoundSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the sound set name is empty"),
+            Self::NumberedSound { set } => {
+                write!(f, "the name is a sound of the set {set:?}, not a sound set")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SoundSetError {}
+
+impl SoundSet {
+    /// Checks that `name` is a sound set name, like "shell_in_small" rather than "shell_in_small_01".
+    ///
+    /// Names ending with a digit without an underscore before it, like "shell_out_small2", are sound sets.
+    pub fn new(name: impl Into<String>) -> Result<Self, SoundSetError> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(SoundSetError::Empty);
+        }
+        if let Some(set) = Self::of_sound(&name) {
+            return Err(SoundSetError::NumberedSound {
+                set: set.to_string(),
+            });
+        }
+        Ok(Self(name))
+    }
+
+    /// Returns the name of the sound set of a sound, like "crowd" for "crowd_01",
+    /// or `None` if the name doesn't end with an underscore and a number.
+    pub fn of_sound(name: &str) -> Option<&str> {
+        split_number(name).map(|(set, _)| set)
+    }
+
+    /// Returns the name of the sound set.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for SoundSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The sound sets of an ammo, each checked with `SoundSet::new`.
+///
+/// Covers the fields every version has, the `shell_enemy` of v1.163 can be checked with `SoundSet::new` too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmmoSounds {
+    /// The sound set played when a shell is loaded into the magazine.
+    pub shell_in: SoundSet,
+    /// The sound set played when firing the gun.
+    pub shell_out: SoundSet,
+    /// The sound set played when the gun is fired from far away.
+    pub shell_far: SoundSet,
+}
+
+impl AmmoSounds {
+    /// Checks the sound sets of `ammo`, returning a warning for the first field that isn't a sound set name.
+    pub fn new(ammo: &dyn AmmoFields) -> Result<Self, AmmoWarning> {
+        let check = |field: &'static str, name: &EscadraString| {
+            let name = name.get_string_lossy();
+            SoundSet::new(&*name).map_err(|error| match error {
+                SoundSetError::Empty => AmmoWarning::Empty(field),
+                SoundSetError::NumberedSound { set } => AmmoWarning::NotASoundSet {
+                    field,
+                    name: name.to_string(),
+                    set,
+                },
+            })
+        };
+
+        Ok(Self {
+            shell_in: check("shell_in", ammo.shell_in())?,
+            shell_out: check("shell_out", ammo.shell_out())?,
+            shell_far: check("shell_far", ammo.shell_far())?,
+        })
+    }
+}
+
+/// Splits a name like "crowd_01" into "crowd" and 1.
+fn split_number(name: &str) -> Option<(&str, u32)> {
+    let (base, number) = name.rsplit_once('_')?;
+    if base.is_empty() || number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((base, number.parse().ok()?))
+}
+
+/// An installed image an ammo can show, either a static image or an animation named by one of its frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteRef<'a> {
+    /// A static image, like "shell_57".
+    Static(&'a str),
+    /// An animation, like "animation_name_01".
+    Animation {
+        /// The name without the frame number, like "animation_name".
+        base: &'a str,
+        /// The number of the frame named, 1 for "_01".
+        frame: u32,
+        /// How many frames follow from that one, including it.
+        frames: u32,
+    },
+}
+
+/// Error returned when a name doesn't match the installed images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpriteError {
+    /// The name is empty.
+    Empty,
+    /// No image has the name.
+    Missing(String),
+    /// The name is an animation without its frame number, like "animation_name" for "animation_name_01".
+    MissingFrame {
+        /// The name given.
+        name: String,
+        /// The name of the first frame, which should be used instead.
+        first_frame: String,
+    },
+}
+
+impl fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the image name is empty"),
+            Self::Missing(name) => write!(f, "no image or animation frame is named {name:?}"),
+            Self::MissingFrame { name, first_frame } => write!(
+                f,
+                "{name:?} is an animation, name its first frame {first_frame:?} instead"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SpriteError {}
+
+impl<'a> SpriteRef<'a> {
+    /// Looks `name` up in the installed images.
+    ///
+    /// Names like "shell_57" are ambiguous, so an installed name ending with `_` and a number is an animation frame
+    /// only if it's the first frame, "_01", or the next frame is installed too.
+    /// The game silently shows nothing for a missing image, the error says why instead.
+    pub fn resolve(name: &'a str, resources: &ResourceIndex) -> Result<Self, SpriteError> {
+        if name.is_empty() {
+            return Err(SpriteError::Empty);
+        }
+        if !resources.has_image(name) {
+            let first_frame = alloc::format!("{name}_01");
+            return Err(if resources.has_image(&first_frame) {
+                SpriteError::MissingFrame {
+                    name: name.to_string(),
+                    first_frame,
+                }
+            } else {
+                SpriteError::Missing(name.to_string())
+            });
+        }
+
+        let frame_name = |base: &str, frame: u32| alloc::format!("{base}_{frame:02}");
+        match split_number(name) {
+            Some((base, frame))
+                if name == frame_name(base, frame)
+                    && (frame == 1 || resources.has_image(&frame_name(base, frame + 1))) =>
+            {
+                let frames = (frame..)
+                    .take_while(|frame| resources.has_image(&frame_name(base, *frame)))
+                    .count() as u32;
+                Ok(Self::Animation {
+                    base,
+                    frame,
+                    frames,
+                })
+            }
+            _ => Ok(Self::Static(name)),
+        }
+    }
+
+    /// Returns how many frames the image has, 1 for a static image.
+    pub fn frames(&self) -> u32 {
+        match self {
+            Self::Static(_) => 1,
+            Self::Animation { frames, .. } => *frames,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::EscadraString;
+    use crate::v1_163::AmmoBuilder;
+
+    #[test]
+    fn checks_sound_set_names() {
+        assert_eq!(SoundSet::new("crowd").unwrap().as_str(), "crowd");
+        assert!(SoundSet::new("shell_out_small2").is_ok());
+        assert_eq!(SoundSet::new(""), Err(SoundSetError::Empty));
+        assert_eq!(
+            SoundSet::new("crowd_01"),
+            Err(SoundSetError::NumberedSound {
+                set: "crowd".to_string()
+            })
+        );
+        assert_eq!(SoundSet::of_sound("_01"), None);
+        assert_eq!(SoundSet::of_sound("crowd_"), None);
+    }
+
+    #[test]
+    fn lists_sound_set_members() {
+        let mut resources = ResourceIndex::new();
+        for sound in ["crowd_02", "crowd_01", "crowd_big_01", "engine"] {
+            resources.insert_sound(sound);
+        }
+
+        assert!(resources.has_sound_set("crowd"));
+        assert!(resources.has_sound_set("engine"));
+        let crowd = SoundSet::new("crowd").unwrap();
+        assert!(resources
+            .sound_set_members(&crowd)
+            .eq(["crowd_01", "crowd_02"]));
+    }
+
+    #[test]
+    fn resolves_sprites() {
+        let mut resources = ResourceIndex::new();
+        resources.extend(["shell_57", "flare_01", "flare_02", "flare_03"]);
+
+        assert_eq!(
+            SpriteRef::resolve("shell_57", &resources),
+            Ok(SpriteRef::Static("shell_57"))
+        );
+        assert_eq!(
+            SpriteRef::resolve("flare_01", &resources),
+            Ok(SpriteRef::Animation {
+                base: "flare",
+                frame: 1,
+                frames: 3
+            })
+        );
+        assert_eq!(
+            SpriteRef::resolve("flare_02", &resources).unwrap().frames(),
+            2
+        );
+        assert_eq!(
+            SpriteRef::resolve("flare", &resources),
+            Err(SpriteError::MissingFrame {
+                name: "flare".to_string(),
+                first_frame: "flare_01".to_string()
+            })
+        );
+        assert_eq!(
+            SpriteRef::resolve("flare_04", &resources),
+            Err(SpriteError::Missing("flare_04".to_string()))
+        );
+        assert_eq!(SpriteRef::resolve("", &resources), Err(SpriteError::Empty));
+    }
+
+    #[test]
+    fn checks_ammo_sounds() {
+        let mut ammo = AmmoBuilder::test_ammo("AMMO_57", 5);
+        let sounds = AmmoSounds::new(&ammo).unwrap();
+        assert_eq!(sounds.shell_in.as_str(), ammo.shell_in.to_string());
+
+        ammo.shell_out = EscadraString::from("shell_out_med_01");
+        assert_eq!(
+            AmmoSounds::new(&ammo),
+            Err(AmmoWarning::NotASoundSet {
+                field: "shell_out",
+                name: "shell_out_med_01".to_string(),
+                set: "shell_out_med".to_string(),
+            })
+        );
+        assert!(ammo
+            .validate_resources(&ResourceIndex::new())
+            .iter()
+            .any(|warning| matches!(
+                warning,
+                AmmoWarning::NotASoundSet {
+                    field: "shell_out",
+                    ..
+                }
+            )));
+    }
+}
@@ -0,0 +1,618 @@
+//! Reads and writes the text based `.seria` format Highfleet stores ship designs, saves, and OL files in.
+//!
+//! A `.seria` file is made of lines.
+//! A line holding `key=value` is an `Entry`, a line ending with `{` opens a `Block`, and a line holding `}` closes it.
+//! Blank lines and lines that are none of these are kept as they are.
+//!
+//! Parsing keeps the indentation, trailing whitespace, and line endings of every line,
+//! so writing an unmodified `Document` gives back the exact same bytes.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// Error returned when parsing a `.seria` file fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeriaError {
+    /// The file is not valid UTF-8.
+    InvalidUtf8 {
+        /// The line holding the invalid bytes, starting at 1.
+        line: usize,
+    },
+    /// A `}` has no matching `{`.
+    UnexpectedClose {
+        /// The line of the `}`, starting at 1.
+        line: usize,
+    },
+    /// A `{` has no matching `}`.
+    UnclosedBlock {
+        /// The line of the `{`, starting at 1.
+        line: usize,
+    },
+}
+
+impl fmt::Display for SeriaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUtf8 { line } => write!(f, "line {line} is not valid UTF-8"),
+            Self::UnexpectedClose { line } => {
+                write!(f, "line {line} closes a block that isn't open")
+            }
+            Self::UnclosedBlock { line } => {
+                write!(f, "block opened on line {line} is never closed")
+            }
+        }
+    }
+}
+
+impl Error for SeriaError {}
+
+/// How a line ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\n`.
+    Lf,
+    /// `\r\n`.
+    CrLf,
+    /// No line break, as for the last line of some files.
+    ///
+    /// Nodes created in code start out without a line break, until `Document::normalize_new_lines` gives them one.
+    #[default]
+    None,
+}
+
+impl LineEnding {
+    /// Returns the characters ending the line.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::None => "",
+        }
+    }
+}
+
+/// The whitespace around the content of a line, kept so lines are written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LineFormat {
+    /// The whitespace before the content.
+    pub indent: String,
+    /// The whitespace after the content.
+    pub trailing: String,
+    /// How the line ends.
+    pub ending: LineEnding,
+}
+
+/// A `key=value` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The text before the first `=`.
+    pub key: String,
+    /// The text after the first `=`.
+    pub value: String,
+    /// The whitespace around the line.
+    pub format: LineFormat,
+}
+
+impl Entry {
+    /// Creates an entry on a line of its own.
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            format: LineFormat::default(),
+        }
+    }
+}
+
+/// A block of nodes between a line ending with `{` and a line holding `}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The text before the `{`, often empty.
+    pub header: String,
+    /// The nodes inside of the block.
+    pub children: Vec<Node>,
+    /// The whitespace around the `{` line.
+    pub open_format: LineFormat,
+    /// The whitespace around the `}` line.
+    pub close_format: LineFormat,
+}
+
+impl Block {
+    /// Creates an empty block.
+    pub fn new(header: impl Into<String>) -> Self {
+        Self {
+            header: header.into(),
+            children: Vec::new(),
+            open_format: LineFormat::default(),
+            close_format: LineFormat::default(),
+        }
+    }
+}
+
+/// A node of a `.seria` document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// A `key=value` line.
+    Entry(Entry),
+    /// A nested block.
+    Block(Block),
+    /// A blank line, or a line that is neither an entry nor a brace.
+    Text {
+        /// The content of the line, without the whitespace around it.
+        text: String,
+        /// The whitespace around the line.
+        format: LineFormat,
+    },
+}
+
+/// Methods shared by the `Document` and its `Block`s, which both hold a list of nodes.
+pub trait Container {
+    /// Returns the nodes held.
+    fn children(&self) -> &[Node];
+
+    /// Returns the nodes held mutably.
+    fn children_mut(&mut self) -> &mut Vec<Node>;
+
+    /// Returns the entries held directly, in order.
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.children().iter().filter_map(|node| match node {
+            Node::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Returns the blocks held directly, in order.
+    fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.children().iter().filter_map(|node| match node {
+            Node::Block(block) => Some(block),
+            _ => None,
+        })
+    }
+
+    /// Returns the blocks held directly mutably, in order.
+    fn blocks_mut(&mut self) -> impl Iterator<Item = &mut Block> {
+        self.children_mut()
+            .iter_mut()
+            .filter_map(|node| match node {
+                Node::Block(block) => Some(block),
+                _ => None,
+            })
+    }
+
+    /// Returns every entry held, including those in nested blocks, in the order they appear in the file.
+    fn all_entries(&self) -> Vec<&Entry> {
+        fn visit<'a>(nodes: &'a [Node], entries: &mut Vec<&'a Entry>) {
+            for node in nodes {
+                match node {
+                    Node::Entry(entry) => entries.push(entry),
+                    Node::Block(block) => visit(&block.children, entries),
+                    Node::Text { .. } => {}
+                }
+            }
+        }
+
+        let mut entries = Vec::new();
+        visit(self.children(), &mut entries);
+        entries
+    }
+
+    /// Returns the value of the first entry held directly with the given key.
+    ///
+    /// Whitespace around the key in the file is ignored.
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries()
+            .find(|entry| entry.key.trim() == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Returns the values of every entry held directly with the given key, in order.
+    fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries()
+            .filter(move |entry| entry.key.trim() == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Sets the value of the first entry held directly with the given key, or appends a new entry.
+    ///
+    /// A new entry copies the whitespace of the last entry. If that entry ends the file without a line break,
+    /// call `Document::normalize_new_lines` afterwards.
+    fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        let children = self.children_mut();
+        for node in children.iter_mut() {
+            if let Node::Entry(entry) = node {
+                if entry.key.trim() == key {
+                    entry.value = value;
+                    return;
+                }
+            }
+        }
+
+        // New lines copy the whitespace of the last line, to blend in.
+        let format = children
+            .iter()
+            .rev()
+            .find_map(|node| match node {
+                Node::Entry(entry) => Some(entry.format.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        children.push(Node::Entry(Entry {
+            key: key.to_string(),
+            value,
+            format,
+        }));
+    }
+
+    /// Removes every entry held directly with the given key, returning the value of the first.
+    fn remove(&mut self, key: &str) -> Option<String> {
+        let mut removed = None;
+        self.children_mut().retain(|node| match node {
+            Node::Entry(entry) if entry.key.trim() == key => {
+                removed.get_or_insert_with(|| entry.value.clone());
+                false
+            }
+            _ => true,
+        });
+        removed
+    }
+}
+
+impl Container for Block {
+    fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.children
+    }
+}
+
+/// A parsed `.seria` file.
+///
+/// Formatting the document with `Display` writes it back.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Document {
+    /// The top level nodes.
+    pub nodes: Vec<Node>,
+}
+
+impl Container for Document {
+    fn children(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.nodes
+    }
+}
+
+impl Document {
+    /// Parses the text of a `.seria` file.
+    pub fn parse(text: &str) -> Result<Self, SeriaError> {
+        // The blocks being parsed, with the line they were opened on.
+        let mut stack: Vec<(Block, usize)> = Vec::new();
+        let mut nodes = Vec::new();
+
+        for (number, raw) in lines(text).enumerate() {
+            let number = number + 1;
+            let (line, format) = split_line(raw);
+
+            if line == "}" {
+                let (mut block, _) = stack
+                    .pop()
+                    .ok_or(SeriaError::UnexpectedClose { line: number })?;
+                block.close_format = format;
+                push(&mut stack, &mut nodes, Node::Block(block));
+            } else if let Some(header) = line.strip_suffix('{') {
+                let mut block = Block::new(header);
+                block.open_format = format;
+                stack.push((block, number));
+            } else if let Some((key, value)) = line.split_once('=') {
+                let entry = Entry {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    format,
+                };
+                push(&mut stack, &mut nodes, Node::Entry(entry));
+            } else {
+                let text = Node::Text {
+                    text: line.to_string(),
+                    format,
+                };
+                push(&mut stack, &mut nodes, text);
+            }
+        }
+
+        match stack.first() {
+            Some((_, line)) => Err(SeriaError::UnclosedBlock { line: *line }),
+            None => Ok(Self { nodes }),
+        }
+    }
+
+    /// Parses the bytes of a `.seria` file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SeriaError> {
+        match core::str::from_utf8(bytes) {
+            Ok(text) => Self::parse(text),
+            Err(error) => {
+                let valid = &bytes[..error.valid_up_to()];
+                let line = valid.iter().filter(|&&byte| byte == b'\n').count() + 1;
+                Err(SeriaError::InvalidUtf8 { line })
+            }
+        }
+    }
+
+    /// Reads and parses a `.seria` file.
+    ///
+    /// Parse errors are returned as `io::ErrorKind::InvalidData`, holding the `SeriaError`.
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Writes the document to a file, replacing it.
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Returns the line ending of the first line, for new lines to match.
+    ///
+    /// Defaults to `LineEnding::Lf` if no line has one.
+    pub fn line_ending(&self) -> LineEnding {
+        fn find(nodes: &[Node]) -> Option<LineEnding> {
+            nodes.iter().find_map(|node| {
+                let ending = match node {
+                    Node::Entry(entry) => entry.format.ending,
+                    Node::Block(block) => block.open_format.ending,
+                    Node::Text { format, .. } => format.ending,
+                };
+                (ending != LineEnding::None).then_some(ending)
+            })
+        }
+        find(&self.nodes).unwrap_or(LineEnding::Lf)
+    }
+
+    /// Gives every line without a line break the document's line ending, so the lines don't run into each other.
+    ///
+    /// Call this after adding nodes. Lines that also have no indentation are indented with a tab per level of nesting.
+    /// Afterwards the document ends with a line break.
+    pub fn normalize_new_lines(&mut self) {
+        fn visit(nodes: &mut [Node], depth: usize, ending: LineEnding) {
+            for node in nodes {
+                match node {
+                    Node::Entry(entry) => fix(&mut entry.format, depth, ending),
+                    Node::Text { format, .. } => fix(format, depth, ending),
+                    Node::Block(block) => {
+                        fix(&mut block.open_format, depth, ending);
+                        visit(&mut block.children, depth + 1, ending);
+                        fix(&mut block.close_format, depth, ending);
+                    }
+                }
+            }
+        }
+
+        fn fix(format: &mut LineFormat, depth: usize, ending: LineEnding) {
+            if format.ending != LineEnding::None {
+                return;
+            }
+            format.ending = ending;
+            if format.indent.is_empty() {
+                format.indent = "\t".repeat(depth);
+            }
+        }
+
+        let ending = self.line_ending();
+        visit(&mut self.nodes, 0, ending);
+    }
+}
+
+impl core::str::FromStr for Document {
+    type Err = SeriaError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Self::parse(text)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_line(
+            f: &mut fmt::Formatter<'_>,
+            content: &str,
+            format: &LineFormat,
+        ) -> fmt::Result {
+            write!(
+                f,
+                "{}{content}{}{}",
+                format.indent,
+                format.trailing,
+                format.ending.as_str()
+            )
+        }
+
+        fn write_nodes(f: &mut fmt::Formatter<'_>, nodes: &[Node]) -> fmt::Result {
+            for node in nodes {
+                match node {
+                    Node::Entry(entry) => {
+                        write!(f, "{}", entry.format.indent)?;
+                        write!(f, "{}={}", entry.key, entry.value)?;
+                        write!(
+                            f,
+                            "{}{}",
+                            entry.format.trailing,
+                            entry.format.ending.as_str()
+                        )?;
+                    }
+                    Node::Block(block) => {
+                        write!(f, "{}{}{{", block.open_format.indent, block.header)?;
+                        write!(
+                            f,
+                            "{}{}",
+                            block.open_format.trailing,
+                            block.open_format.ending.as_str()
+                        )?;
+                        write_nodes(f, &block.children)?;
+                        write_line(f, "}", &block.close_format)?;
+                    }
+                    Node::Text { text, format } => write_line(f, text, format)?,
+                }
+            }
+            Ok(())
+        }
+
+        write_nodes(f, &self.nodes)
+    }
+}
+
+/// Adds a node to the innermost open block, or to the top level.
+fn push(stack: &mut [(Block, usize)], nodes: &mut Vec<Node>, node: Node) {
+    match stack.last_mut() {
+        Some((block, _)) => block.children.push(node),
+        None => nodes.push(node),
+    }
+}
+
+/// Splits text into lines, keeping their line breaks.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive('\n')
+}
+
+/// Splits a line into its content and the whitespace around it.
+fn split_line(raw: &str) -> (&str, LineFormat) {
+    let (raw, ending) = if let Some(raw) = raw.strip_suffix("\r\n") {
+        (raw, LineEnding::CrLf)
+    } else if let Some(raw) = raw.strip_suffix('\n') {
+        (raw, LineEnding::Lf)
+    } else {
+        (raw, LineEnding::None)
+    };
+
+    let content = raw.trim();
+    let indent_length = raw.len() - raw.trim_start().len();
+    let indent = &raw[..indent_length];
+    let trailing = &raw[indent_length + content.len()..];
+
+    let format = LineFormat {
+        indent: indent.to_string(),
+        trailing: trailing.to_string(),
+        ending,
+    };
+    (content, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIP: &str = "m_name=Sevastopol\r\n\
+        m_classname=Ship\r\n\
+        {\r\n\
+        \tm_classname=Node\r\n\
+        \tm_pos=10 20  \r\n\
+        \t{\r\n\
+        \t\tm_oid=ENGINE\r\n\
+        \t}\r\n\
+        }\r\n\
+        \r\n\
+        trailing text";
+
+    #[test]
+    fn parse_tree() {
+        let document = Document::parse(SHIP).unwrap();
+
+        assert_eq!(document.get("m_name"), Some("Sevastopol"));
+        assert_eq!(document.line_ending(), LineEnding::CrLf);
+
+        let block = document.blocks().next().unwrap();
+        assert_eq!(block.header, "");
+        assert_eq!(block.get("m_classname"), Some("Node"));
+        assert_eq!(block.get("m_pos"), Some("10 20"));
+        assert_eq!(block.blocks().next().unwrap().get("m_oid"), Some("ENGINE"));
+
+        let keys: Vec<_> = document
+            .all_entries()
+            .iter()
+            .map(|entry| entry.key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            ["m_name", "m_classname", "m_classname", "m_pos", "m_oid"]
+        );
+
+        assert!(matches!(
+            document.nodes.last(),
+            Some(Node::Text { text, format }) if text == "trailing text" && format.ending == LineEnding::None
+        ));
+    }
+
+    #[test]
+    fn round_trip_is_identical() {
+        for text in [
+            SHIP,
+            "",
+            "\n\n",
+            "a=b",
+            "  key = value with = signs \t\nheader {\n}\n",
+        ] {
+            let document = Document::parse(text).unwrap();
+            assert_eq!(document.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn edit_entries() {
+        let mut document = Document::parse("a=1\n{\n\tb=2\n}\n").unwrap();
+
+        document.set("a", "3");
+        let block = document.blocks_mut().next().unwrap();
+        block.set("c", "4");
+        assert_eq!(block.remove("b").as_deref(), Some("2"));
+        assert_eq!(document.to_string(), "a=3\n{\n\tc=4\n}\n");
+
+        let mut block = Block::new("new");
+        block.children.push(Node::Entry(Entry::new("d", "5")));
+        document.nodes.push(Node::Block(block));
+        document.normalize_new_lines();
+        assert_eq!(document.to_string(), "a=3\n{\n\tc=4\n}\nnew{\n\td=5\n}\n");
+    }
+
+    #[test]
+    fn load_and_save() {
+        let path =
+            std::env::temp_dir().join(format!("highfleet-rs-seria-{}.seria", std::process::id()));
+
+        Document::parse(SHIP).unwrap().save(&path).unwrap();
+        let loaded = Document::load(&path).unwrap();
+        assert_eq!(loaded.to_string(), SHIP);
+
+        std::fs::write(&path, "}\n").unwrap();
+        let error = Document::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reject_unbalanced_braces() {
+        assert_eq!(
+            Document::parse("a=1\n}\n"),
+            Err(SeriaError::UnexpectedClose { line: 2 })
+        );
+        assert_eq!(
+            Document::parse("{\n{\n}\n"),
+            Err(SeriaError::UnclosedBlock { line: 1 })
+        );
+        assert_eq!(
+            Document::from_bytes(b"a=1\nb=\xff\n"),
+            Err(SeriaError::InvalidUtf8 { line: 2 })
+        );
+    }
+}
@@ -1,12 +1,24 @@
 //! v1.151
 
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
 use crate::general::escadra_string::EscadraString;
+use crate::general::traits::impl_ammo_fields;
+#[cfg(feature = "strict")]
+use crate::general::warning::impl_strict_serde;
+use crate::general::warning::{AmmoChecker, AmmoWarning};
+use crate::general::GameStruct;
+use crate::res::ResourceIndex;
+
+mod builder;
+pub use builder::AmmoBuilder;
 
 /// Represents an Ammo object in Highfleet
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, GameStruct)]
+#[cfg_attr(feature = "strict", serde(remote = "Self"))]
+#[size(0x168)]
 pub struct Ammo {
     /// What reticle to use when firing the ammo.
     ///
@@ -15,23 +27,29 @@ pub struct Ammo {
     /// - 2: Used by aircraft bombs.
     /// - 3: Used mostly by rockets.
     /// - 4: Used by aircraft ammos.
+    #[offset(0x00)]
     pub reticle: i32,
     /// Unused padding bytes?
     /// Not always set to 0.
+    #[offset(0x04)]
     pub padding_4h: u32,
     /// The internal name for the item within Highfleet.
+    #[offset(0x08)]
     pub item_name: EscadraString,
     /// The text that displays the shell's kind in the shop.
     ///
     /// For example: "Incendiary".
+    #[offset(0x28)]
     pub shell_kind: EscadraString,
     /// The internal text to determine the shell's kind.
     ///
     /// For example: "@INCENDIARY".
+    #[offset(0x48)]
     pub shell_kind2: EscadraString,
     /// The text to display for the ammo's milimeter in the shop.
     ///
     /// For example: "57mm".
+    #[offset(0x68)]
     pub milimeterage: EscadraString,
     /// The image to use for the ammo in the magazine.
     /// These are defined in the .res files inside of the Tex folder.
@@ -41,6 +59,7 @@ pub struct Ammo {
     ///
     /// Note that this doesn't have to be an actual image, it can be an animation.
     /// When setting it to an animation include the full name. E.g. "animation_name_01"
+    #[offset(0x88)]
     pub magazine_image: EscadraString,
     /// What sign to use for the reticle?
     ///
@@ -52,12 +71,15 @@ pub struct Ammo {
     /// - "sign_ammo_inc" for standard incendiary rounds.
     /// - "sign_ammo_guided" for lazer guided rounds.
     /// - "sign_ammo_craft" for rounds (bombs, or rockets) used by aircraft.
+    #[offset(0xa8)]
     pub sign_ammo: EscadraString,
     /// How tall the bullet is in the magazine.
     ///
     /// In the vanilla game it ranges from 16 to 38.
+    #[offset(0xc8)]
     pub bullet_height: f32,
     /// Unused padding bytes.
+    #[offset(0xcc)]
     pub padding_cch: u32,
     /// The sound set to play when a shell is loaded into the magazine.
     ///
@@ -80,6 +102,7 @@ pub struct Ammo {
     /// In vanilla it is one of these two values:
     /// - "shell_in_small"
     /// - "shell_in_med"
+    #[offset(0xd0)]
     pub shell_in: EscadraString,
     /// The sound set to play when firing the gun.
     ///
@@ -92,6 +115,7 @@ pub struct Ammo {
     /// - "shell_out_med"
     /// - "shell_out_big"
     /// - "shell_out_big3"
+    #[offset(0xf0)]
     pub shell_out: EscadraString,
     /// The sound set to play when the gun is fired from far away.
     ///
@@ -102,6 +126,7 @@ pub struct Ammo {
     /// - "shell_out_small_far"
     /// - "shell_out_med_far"
     /// - "shell_out_big_far"
+    #[offset(0x110)]
     pub shell_far: EscadraString,
     /// Determines if the shell behaves like HE, AP, INC, or LG?
     ///
@@ -110,33 +135,43 @@ pub struct Ammo {
     /// - 130: Rocket and Incendiary?
     /// - 140: Laser Guided
     /// - 160: Proxy
+    #[offset(0x130)]
     pub caliber: i32,
     /// The index of the ammo.
     /// A weapon's m_weapon_caliber should match with an ammo index.
+    #[offset(0x134)]
     pub index: i32,
     /// The speed of the shell.
+    #[offset(0x138)]
     pub speed: f32,
     /// The drag the shell experiences?
     ///
     /// A value between 0 and 1.
     /// In the vanilla game it's either set to 0 or to 0.0007
+    #[offset(0x13c)]
     pub ap_drag: f32,
     /// The shell's explosive power.
     /// Higher is better.
+    #[offset(0x140)]
     pub explosive_power: f32,
     /// The shell's penetrative power.
     /// Higher is better.
+    #[offset(0x144)]
     pub penetrative_power: f32,
     /// The shell's incendiary power.
     /// Higher is better.
     ///
     /// By default it is 100.0, where incendiary rounds having it set to 1000.0
+    #[offset(0x148)]
     pub incendiary_power: f32,
     /// The price of the ammo inside of city shops.
+    #[offset(0x14c)]
     pub shop_price: i32,
     /// Value with unknown purpose.
+    #[offset(0x150)]
     pub unknown_150h: f32,
     /// Value with unknown purpose.
+    #[offset(0x154)]
     pub unknown_154h: f32,
     /// Value between 0 and 1.
     /// By default it is 0.5.
@@ -145,11 +180,13 @@ pub struct Ammo {
     /// - The NAR122 where it's 0.2
     /// - The 37MM aircraft rounds where it's 0.1
     /// - The 57MM aircraft rounds where it's 0.2
+    #[offset(0x158)]
     pub unknown_158h: f32,
     /// Value with unknown purpose.
     /// By default it is 10.
     ///
     /// The only exception being the 57MM aircraft rounds where it's 7.
+    #[offset(0x15c)]
     pub unknown_15ch: i32,
     /// Value with unknown purpose.
     /// By default it is 0.0.
@@ -162,7 +199,34 @@ pub struct Ammo {
     /// - The FAB500 where it's 8.0
     /// - The 37MM aircraft where it's 1.0
     /// - The 57MM aircraft where it's 2.0
+    #[offset(0x160)]
     pub unknown_160h: f32,
     /// Unused padding bytes
+    #[offset(0x164)]
     pub padding_164h: u32,
 }
+
+impl_ammo_fields!(Ammo);
+
+#[cfg(feature = "strict")]
+impl_strict_serde!(Ammo);
+
+impl Ammo {
+    /// Checks the fields against the values the vanilla ammos use.
+    ///
+    /// Returns a warning for every field the game may not handle as expected, or nothing if the ammo looks fine.
+    pub fn validate(&self) -> Vec<AmmoWarning> {
+        let mut checker = AmmoChecker::new(self);
+
+        checker.range("unknown_158h", self.unknown_158h, 0.0, 1.0);
+
+        checker.warnings
+    }
+
+    /// Checks that the images and sound sets the ammo refers to are installed.
+    ///
+    /// Returns a warning for every missing one, or nothing if they all exist.
+    pub fn validate_resources(&self, resources: &ResourceIndex) -> Vec<AmmoWarning> {
+        AmmoChecker::resources(self, resources).warnings
+    }
+}
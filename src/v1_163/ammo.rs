@@ -1,12 +1,26 @@
 //! v1.163
 
+use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
+use crate::general::convert::LossyConversion;
 use crate::general::escadra_string::EscadraString;
+use crate::general::traits::impl_ammo_fields;
+#[cfg(feature = "strict")]
+use crate::general::warning::impl_strict_serde;
+use crate::general::warning::{AmmoChecker, AmmoWarning};
+use crate::general::GameStruct;
+use crate::res::ResourceIndex;
+use crate::v1_151;
+
+mod builder;
+pub use builder::AmmoBuilder;
 
 /// Represents an Ammo object in Highfleet
 #[repr(C)]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, GameStruct)]
+#[cfg_attr(feature = "strict", serde(remote = "Self"))]
+#[size(0x188)]
 pub struct Ammo {
     /// What reticle to use when firing the ammo.
     ///
@@ -15,23 +29,29 @@ pub struct Ammo {
     /// - 2: Used by aircraft bombs.
     /// - 3: Used mostly by rockets.
     /// - 4: Used by aircraft ammos.
+    #[offset(0x00)]
     pub reticle: i32,
     /// Unused padding bytes?
     /// Not always set to 0.
+    #[offset(0x04)]
     pub padding_4h: u32,
     /// The internal name for the item within Highfleet.
+    #[offset(0x08)]
     pub item_name: EscadraString,
     /// The text that displays the shell's kind in the shop.
     ///
     /// For example: "Incendiary".
+    #[offset(0x28)]
     pub shell_kind: EscadraString,
     /// The internal text to determine the shell's kind.
     ///
     /// For example: "@INCENDIARY".
+    #[offset(0x48)]
     pub shell_kind2: EscadraString,
     /// The text to display for the ammo's milimeter in the shop.
     ///
     /// For example: "57mm".
+    #[offset(0x68)]
     pub milimeterage: EscadraString,
     /// The image to use for the ammo in the magazine.
     /// These are defined in the .res files inside of the Tex folder.
@@ -41,6 +61,7 @@ pub struct Ammo {
     ///
     /// Note that this doesn't have to be an actual image, it can be an animation.
     /// When setting it to an animation include the full name. E.g. "animation_name_01"
+    #[offset(0x88)]
     pub magazine_image: EscadraString,
     /// What sign to use for the reticle?
     ///
@@ -52,15 +73,18 @@ pub struct Ammo {
     /// - "sign_ammo_inc" for standard incendiary rounds.
     /// - "sign_ammo_guided" for lazer guided rounds.
     /// - "sign_ammo_craft" for rounds (bombs, or rockets) used by aircraft.
+    #[offset(0xa8)]
     pub sign_ammo: EscadraString,
     /// How tall the bullet is in the magazine.
     ///
     /// In the vanilla game it ranges from 16 to 38.
+    #[offset(0xc8)]
     pub bullet_height: f32,
     /// Unused padding bytes by the game.
     ///
     /// Ammo Extended hijacks this value to determine shell behaviour.
     /// It must be a vanilla value.
+    #[offset(0xcc)]
     pub padding_cch: u32,
     /// The sound set to play when a shell is loaded into the magazine.
     ///
@@ -83,6 +107,7 @@ pub struct Ammo {
     /// In vanilla it is one of these two values:
     /// - "shell_in_small"
     /// - "shell_in_med"
+    #[offset(0xd0)]
     pub shell_in: EscadraString,
     /// The sound set to play when firing the gun.
     ///
@@ -95,6 +120,7 @@ pub struct Ammo {
     /// - "shell_out_med"
     /// - "shell_out_big"
     /// - "shell_out_big3"
+    #[offset(0xf0)]
     pub shell_out: EscadraString,
     /// The sound set to play when an enemy is firing the gun.
     ///
@@ -104,6 +130,7 @@ pub struct Ammo {
     /// - "shell_out_enemy_tiny"
     /// - "shell_out_enemy_med"
     /// - "shell_out_enemy_big"
+    #[offset(0x110)]
     pub shell_enemy: EscadraString,
     /// The sound set to play when the gun is fired from far away.
     ///
@@ -114,6 +141,7 @@ pub struct Ammo {
     /// - "shell_out_small_far"
     /// - "shell_out_med_far"
     /// - "shell_out_big_far"
+    #[offset(0x130)]
     pub shell_far: EscadraString,
     /// Determines if the shell behaves like HE, AP, INC, or LG?
     ///
@@ -122,46 +150,57 @@ pub struct Ammo {
     /// - 130: Rocket and Incendiary?
     /// - 140: Laser Guided
     /// - 160: Proxy
+    #[offset(0x150)]
     pub caliber: i32,
     /// The index of the ammo.
     /// A weapon's m_weapon_caliber should match with an ammo index.
+    #[offset(0x154)]
     pub index: i32,
     /// The speed of the shell.
+    #[offset(0x158)]
     pub speed: f32,
     /// The drag the shell experiences?
     ///
     /// A value between 0 and 1.
     /// In the vanilla game it's either set to 0 or to 0.0007
+    #[offset(0x15c)]
     pub ap_drag: f32,
     /// The shell's explosive power.
     /// Higher is better.
+    #[offset(0x160)]
     pub explosive_power: f32,
     /// The shell's penetrative power.
     /// Higher is better.
+    #[offset(0x164)]
     pub penetrative_power: f32,
     /// The shell's incendiary power.
     /// Higher is better.
     ///
     /// By default it is 100.0, with incendiary rounds having it set to 1000.0
+    #[offset(0x168)]
     pub incendiary_power: f32,
     /// Determines how long a shell will last in the air.
     ///
     /// In vanilla ranges from 30 to 1.
     #[serde(alias = "unknown_16ch")]
+    #[offset(0x16c)]
     pub ttl: f32,
     /// The price of the ammo inside of city shops.
+    #[offset(0x170)]
     pub shop_price: i32,
     /// Determines how rare the ammo is in the shop.
     ///
     /// Percentage value between 0 and 1.
     /// Non special ammos have it set to 0.0.
     #[serde(alias = "unknown_174h")]
+    #[offset(0x174)]
     pub shop_rarity: f32,
     /// On average, how much of the ammo is available in the shop.
     /// Ranges from 0.0 to 500.0 in vanilla.
     ///
     /// Non special ammos have it set to 0.0.
     #[serde(alias = "unknown_178h")]
+    #[offset(0x178)]
     pub shop_ammount: f32,
     /// How long it takes from "pulling the trigger" to the bullet being fired.
     /// Standard guns are unaffected by this value.
@@ -175,6 +214,7 @@ pub struct Ammo {
     /// - The 37MM aircraft rounds where it's 0.05
     /// - The 57MM aircraft rounds where it's 0.2
     #[serde(alias = "unknown_17ch")]
+    #[offset(0x17c)]
     pub fire_delay: f32,
     /// Value with unknown purpose.
     /// By default it is 10.
@@ -182,7 +222,323 @@ pub struct Ammo {
     /// The only exception are:
     /// - The 37MM aircraft rounds where it's 20.
     /// - The 57MM aircraft rounds where it's 7.
+    #[offset(0x180)]
     pub unknown_180h: i32,
     /// Unused padding bytes
+    #[offset(0x184)]
     pub padding_184h: u32,
 }
+
+impl_ammo_fields!(Ammo);
+
+#[cfg(feature = "strict")]
+impl_strict_serde!(Ammo);
+
+impl Ammo {
+    /// Checks the fields against the values the vanilla ammos use.
+    ///
+    /// Returns a warning for every field the game may not handle as expected, or nothing if the ammo looks fine.
+    pub fn validate(&self) -> Vec<AmmoWarning> {
+        let mut checker = AmmoChecker::new(self);
+
+        checker.non_empty("shell_enemy", &self.shell_enemy);
+        checker.range("ttl", self.ttl, 1.0, 30.0);
+        checker.range("shop_rarity", self.shop_rarity, 0.0, 1.0);
+        checker.range("shop_ammount", self.shop_ammount, 0.0, 500.0);
+        checker.range("fire_delay", self.fire_delay, 0.0, 1.0);
+
+        checker.warnings
+    }
+
+    /// Checks that the images and sound sets the ammo refers to are installed.
+    ///
+    /// Returns a warning for every missing one, or nothing if they all exist.
+    pub fn validate_resources(&self, resources: &ResourceIndex) -> Vec<AmmoWarning> {
+        let mut checker = AmmoChecker::resources(self, resources);
+        checker.sound_set("shell_enemy", &self.shell_enemy, resources);
+
+        checker.warnings
+    }
+}
+
+impl Ammo {
+    /// The `ttl` given to ammos converted from v1.151, which doesn't have it.
+    /// The longest `ttl` of the vanilla ammos, so converted shells are never cut short.
+    pub const V1_151_TTL: f32 = 30.0;
+
+    /// Returns the fields of `ammo` that `From<v1_151::Ammo>` drops while they hold anything but 0.0.
+    pub fn lost_from_v1_151(ammo: &v1_151::Ammo) -> Vec<&'static str> {
+        [
+            ("unknown_150h", ammo.unknown_150h),
+            ("unknown_154h", ammo.unknown_154h),
+            ("unknown_160h", ammo.unknown_160h),
+        ]
+        .into_iter()
+        .filter(|(_, value)| *value != 0.0)
+        .map(|(field, _)| field)
+        .collect()
+    }
+}
+
+impl From<v1_151::Ammo> for Ammo {
+    /// Converts an ammo from v1.151.
+    ///
+    /// `unknown_158h` and `unknown_15ch` are assumed to be the fields that became `fire_delay` and `unknown_180h`,
+    /// as they sit at the same place among their neighbours and most vanilla values match.
+    /// The 37MM aircraft rounds differ, with 0.1 and 10 in v1.151 against 0.05 and 20 in v1.163.
+    ///
+    /// `unknown_150h`, `unknown_154h`, and `unknown_160h` have no counterpart and are lost.
+    /// `unknown_160h` is non zero for the NAR122, NAR340, FAB100, FAB250, FAB500, and aircraft rounds,
+    /// use `Ammo::lost_from_v1_151` to tell whether an ammo loses anything.
+    ///
+    /// Fields that are new in v1.163 get these values:
+    /// - `shell_enemy`: empty.
+    /// - `ttl`: `Ammo::V1_151_TTL`.
+    /// - `shop_rarity` and `shop_ammount`: 0.0, like non special ammos.
+    fn from(ammo: v1_151::Ammo) -> Self {
+        Self {
+            reticle: ammo.reticle,
+            padding_4h: ammo.padding_4h,
+            item_name: ammo.item_name,
+            shell_kind: ammo.shell_kind,
+            shell_kind2: ammo.shell_kind2,
+            milimeterage: ammo.milimeterage,
+            magazine_image: ammo.magazine_image,
+            sign_ammo: ammo.sign_ammo,
+            bullet_height: ammo.bullet_height,
+            padding_cch: ammo.padding_cch,
+            shell_in: ammo.shell_in,
+            shell_out: ammo.shell_out,
+            shell_enemy: EscadraString::new(),
+            shell_far: ammo.shell_far,
+            caliber: ammo.caliber,
+            index: ammo.index,
+            speed: ammo.speed,
+            ap_drag: ammo.ap_drag,
+            explosive_power: ammo.explosive_power,
+            penetrative_power: ammo.penetrative_power,
+            incendiary_power: ammo.incendiary_power,
+            ttl: Self::V1_151_TTL,
+            shop_price: ammo.shop_price,
+            shop_rarity: 0.0,
+            shop_ammount: 0.0,
+            fire_delay: ammo.unknown_158h,
+            unknown_180h: ammo.unknown_15ch,
+            padding_184h: ammo.padding_164h,
+        }
+    }
+}
+
+impl TryFrom<Ammo> for v1_151::Ammo {
+    type Error = LossyConversion<v1_151::Ammo>;
+
+    /// Converts an ammo to v1.151, the reverse of `From<v1_151::Ammo>`.
+    ///
+    /// Fails if `shell_enemy`, `ttl`, `shop_rarity`, or `shop_ammount` hold anything but the values `From` fills in.
+    ///
+    /// `unknown_150h`, `unknown_154h`, and `unknown_160h` don't exist in v1.163 and are set to 0.0,
+    /// their most common vanilla value. As nothing of the source is lost, they are not listed in `lost`.
+    fn try_from(ammo: Ammo) -> Result<Self, Self::Error> {
+        let mut lost = Vec::new();
+        if !ammo.shell_enemy.get_bytes().is_empty() {
+            lost.push("shell_enemy");
+        }
+        if ammo.ttl != Ammo::V1_151_TTL {
+            lost.push("ttl");
+        }
+        if ammo.shop_rarity != 0.0 {
+            lost.push("shop_rarity");
+        }
+        if ammo.shop_ammount != 0.0 {
+            lost.push("shop_ammount");
+        }
+
+        let value = v1_151::Ammo {
+            reticle: ammo.reticle,
+            padding_4h: ammo.padding_4h,
+            item_name: ammo.item_name,
+            shell_kind: ammo.shell_kind,
+            shell_kind2: ammo.shell_kind2,
+            milimeterage: ammo.milimeterage,
+            magazine_image: ammo.magazine_image,
+            sign_ammo: ammo.sign_ammo,
+            bullet_height: ammo.bullet_height,
+            padding_cch: ammo.padding_cch,
+            shell_in: ammo.shell_in,
+            shell_out: ammo.shell_out,
+            shell_far: ammo.shell_far,
+            caliber: ammo.caliber,
+            index: ammo.index,
+            speed: ammo.speed,
+            ap_drag: ammo.ap_drag,
+            explosive_power: ammo.explosive_power,
+            penetrative_power: ammo.penetrative_power,
+            incendiary_power: ammo.incendiary_power,
+            shop_price: ammo.shop_price,
+            unknown_150h: 0.0,
+            unknown_154h: 0.0,
+            unknown_158h: ammo.fire_delay,
+            unknown_15ch: ammo.unknown_180h,
+            unknown_160h: 0.0,
+            padding_164h: ammo.padding_184h,
+        };
+
+        if lost.is_empty() {
+            Ok(value)
+        } else {
+            Err(LossyConversion { value, lost })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::raw::{from_bytes, to_bytes, PointerPolicy};
+
+    #[test]
+    fn validate_warns() {
+        let mut ammo = AmmoBuilder::test_ammo("AMMO_57_INC", 7);
+        assert_eq!(ammo.validate(), []);
+
+        ammo.bullet_height = 50.0;
+        ammo.shell_enemy.clear();
+        ammo.caliber = 150;
+
+        assert_eq!(
+            ammo.validate(),
+            [
+                AmmoWarning::OutOfRange {
+                    field: "bullet_height",
+                    value: 50.0,
+                    min: 16.0,
+                    max: 38.0
+                },
+                AmmoWarning::UnknownValue {
+                    field: "caliber",
+                    value: "150".to_string()
+                },
+                AmmoWarning::Empty("shell_enemy"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_resources_warns() {
+        let ammo = AmmoBuilder::test_ammo("AMMO_57_INC", 7);
+
+        let mut resources = ResourceIndex::new();
+        resources.insert_image("shell_57");
+        for name in [&ammo.shell_in, &ammo.shell_out, &ammo.shell_far] {
+            resources.insert_sound_set(name.to_string());
+        }
+
+        assert_eq!(
+            ammo.validate_resources(&resources),
+            [AmmoWarning::MissingResource {
+                field: "shell_enemy",
+                name: ammo.shell_enemy.to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn validate_non_utf8_strings() {
+        let ammo = AmmoBuilder::test_ammo("AMMO_57_INC", 7);
+        let mut bytes = to_bytes(&ammo, &PointerPolicy::Zero);
+        // A CP1251 string written by the game, which isn't valid UTF-8.
+        bytes[core::mem::offset_of!(Ammo, item_name)] = 0xCF;
+        bytes[core::mem::offset_of!(Ammo, magazine_image)] = 0xCF;
+        bytes[core::mem::offset_of!(Ammo, shell_in)] = 0xCF;
+
+        let ammo: Ammo = from_bytes(&bytes, &PointerPolicy::Zero).unwrap();
+        assert!(!ammo.validate().contains(&AmmoWarning::Empty("item_name")));
+
+        let warnings = ammo.validate_resources(&ResourceIndex::new());
+        assert!(warnings.contains(&AmmoWarning::MissingResource {
+            field: "shell_in",
+            name: ammo.shell_in.get_string_lossy().into_owned()
+        }));
+    }
+
+    #[test]
+    fn diff_lists_changed_fields() {
+        let old = Ammo::from(old_ammo());
+        let mut new = Ammo::from(old_ammo());
+        assert_eq!(crate::general::diff(&old, &new), []);
+
+        new.item_name.push_str("_MK2");
+        new.speed = 1250.5;
+
+        let diffs = crate::general::diff(&old, &new);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(
+            diffs[0].to_string(),
+            "item_name: AMMO_57_INC -> AMMO_57_INC_MK2"
+        );
+        assert_eq!(diffs[1].to_string(), "speed: 0 -> 1250.5");
+    }
+
+    fn old_ammo() -> v1_151::Ammo {
+        let mut ammo: v1_151::Ammo = from_bytes(&[0u8; 0x168], &PointerPolicy::Zero).unwrap();
+        ammo.item_name.set_string(&"AMMO_57_INC".to_string());
+        ammo.index = 7;
+        ammo.unknown_158h = 0.5;
+        ammo.unknown_15ch = 10;
+        ammo
+    }
+
+    #[test]
+    fn convert_from_v1_151() {
+        let ammo = Ammo::from(old_ammo());
+
+        assert_eq!(ammo.item_name, "AMMO_57_INC");
+        assert_eq!(ammo.index, 7);
+        assert_eq!(ammo.fire_delay, 0.5);
+        assert_eq!(ammo.unknown_180h, 10);
+        assert_eq!(ammo.ttl, Ammo::V1_151_TTL);
+        assert_eq!(ammo.shell_enemy, "");
+    }
+
+    #[test]
+    fn dropped_fields_are_reported() {
+        let mut old = old_ammo();
+        assert!(Ammo::lost_from_v1_151(&old).is_empty());
+
+        // The FAB250.
+        old.unknown_160h = 5.0;
+        assert_eq!(Ammo::lost_from_v1_151(&old), ["unknown_160h"]);
+    }
+
+    #[test]
+    fn round_trip_is_lossless() {
+        let ammo = v1_151::Ammo::try_from(Ammo::from(old_ammo())).unwrap();
+
+        assert_eq!(ammo.item_name, "AMMO_57_INC");
+        assert_eq!(ammo.unknown_158h, 0.5);
+    }
+
+    #[test]
+    fn lost_fields_are_reported() {
+        let mut ammo = Ammo::from(old_ammo());
+        ammo.shell_enemy
+            .set_string(&"shell_out_enemy_med".to_string());
+        ammo.ttl = 6.0;
+
+        let error = v1_151::Ammo::try_from(ammo).unwrap_err();
+        assert_eq!(error.lost, ["shell_enemy", "ttl"]);
+        assert_eq!(error.into_value().item_name, "AMMO_57_INC");
+    }
+
+    #[cfg(feature = "strict")]
+    #[test]
+    fn strict_rejects_out_of_range() {
+        let ammo = AmmoBuilder::test_ammo("AMMO_57", 5);
+        let mut json = serde_json::to_value(&ammo).unwrap();
+        assert!(serde_json::from_value::<Ammo>(json.clone()).is_ok());
+
+        json["ap_drag"] = 2.0.into();
+        let error = serde_json::from_value::<Ammo>(json).unwrap_err();
+        assert!(error.to_string().starts_with("ap_drag is 2"), "{error}");
+    }
+}
@@ -0,0 +1,255 @@
+//! Defines a builder for v1.163 ammos, filled with vanilla defaults.
+
+#[cfg(test)]
+use crate::general::build::impl_test_ammo;
+use crate::general::build::{check_range, AmmoBuildError};
+use crate::general::EscadraString;
+
+use super::Ammo;
+
+/// Defines a builder method for each given field of `Ammo`.
+macro_rules! setters {
+    ($($(#[$meta:meta])* $field:ident: $type:ty,)*) => {
+        $(
+            $(#[$meta])*
+            pub fn $field(mut self, $field: $type) -> Self {
+                self.ammo.$field = $field.into();
+                self
+            }
+        )*
+    };
+}
+
+/// Builds an `Ammo` out of vanilla defaults, so only the fields that differ have to be set.
+///
+/// `item_name`, `index`, `magazine_image` and `speed` have no sensible default and must be set.
+///
+/// ```
+/// # use highfleet::v1_163::AmmoBuilder;
+/// let ammo = AmmoBuilder::new()
+///     .item_name("AMMO_57_CUSTOM")
+///     .index(30)
+///     .magazine_image("shell_57")
+///     .speed(1200.0)
+///     .explosive_power(40.0)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(ammo.caliber, 100);
+/// ```
+#[derive(Debug)]
+pub struct AmmoBuilder {
+    ammo: Ammo,
+    index: Option<i32>,
+    speed: Option<f32>,
+}
+
+impl AmmoBuilder {
+    /// Creates a builder holding the values shared by most vanilla ammos.
+    pub fn new() -> Self {
+        Self {
+            ammo: Ammo {
+                reticle: 1,
+                padding_4h: 0,
+                item_name: EscadraString::new(),
+                shell_kind: EscadraString::new(),
+                shell_kind2: EscadraString::new(),
+                milimeterage: EscadraString::new(),
+                magazine_image: EscadraString::new(),
+                sign_ammo: "sign_ammo_unset".into(),
+                bullet_height: 16.0,
+                padding_cch: 0,
+                shell_in: "shell_in_small".into(),
+                shell_out: "shell_out_small".into(),
+                shell_enemy: "shell_out_enemy_tiny".into(),
+                shell_far: "shell_out_small_far".into(),
+                caliber: 100,
+                index: 0,
+                speed: 0.0,
+                ap_drag: 0.0,
+                explosive_power: 0.0,
+                penetrative_power: 0.0,
+                incendiary_power: 100.0,
+                ttl: 30.0,
+                shop_price: 0,
+                shop_rarity: 0.0,
+                shop_ammount: 0.0,
+                fire_delay: 0.5,
+                unknown_180h: 10,
+                padding_184h: 0,
+            },
+            index: None,
+            speed: None,
+        }
+    }
+
+    /// Sets the index of the ammo, which weapons refer to by their `m_weapon_caliber`.
+    pub fn index(mut self, index: i32) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Sets the speed of the shell.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    setters! {
+        /// Sets what reticle to use when firing the ammo.
+        reticle: impl Into<i32>,
+        /// Sets the internal name for the item within Highfleet.
+        item_name: &str,
+        /// Sets the text that displays the shell's kind in the shop.
+        shell_kind: &str,
+        /// Sets the internal text to determine the shell's kind.
+        shell_kind2: &str,
+        /// Sets the text to display for the ammo's milimeter in the shop.
+        milimeterage: &str,
+        /// Sets the image to use for the ammo in the magazine.
+        magazine_image: &str,
+        /// Sets what sign to use for the reticle, such as `AmmoSign::ArmourPiercing.as_str()`.
+        sign_ammo: &str,
+        /// Sets how tall the bullet is in the magazine.
+        bullet_height: f32,
+        /// Sets the sound set to play when a shell is loaded into the magazine.
+        shell_in: &str,
+        /// Sets the sound set to play when firing the gun.
+        shell_out: &str,
+        /// Sets the sound set to play when an enemy is firing the gun.
+        shell_enemy: &str,
+        /// Sets the sound set to play when the gun is fired from far away.
+        shell_far: &str,
+        /// Sets if the shell behaves like HE, AP, INC, or LG.
+        caliber: impl Into<i32>,
+        /// Sets the drag the shell experiences.
+        ap_drag: f32,
+        /// Sets the shell's explosive power.
+        explosive_power: f32,
+        /// Sets the shell's penetrative power.
+        penetrative_power: f32,
+        /// Sets the shell's incendiary power.
+        incendiary_power: f32,
+        /// Sets how long a shell will last in the air.
+        ttl: f32,
+        /// Sets the price of the ammo inside of city shops.
+        shop_price: i32,
+        /// Sets how rare the ammo is in the shop.
+        shop_rarity: f32,
+        /// Sets how much of the ammo is available in the shop on average.
+        shop_ammount: f32,
+        /// Sets how long it takes from "pulling the trigger" to the bullet being fired.
+        fire_delay: f32,
+        /// Sets the value of `unknown_180h`.
+        unknown_180h: i32,
+    }
+
+    /// Builds the ammo, checking that the required fields are set and the ranged fields are in range.
+    pub fn build(self) -> Result<Ammo, AmmoBuildError> {
+        let mut ammo = self.ammo;
+
+        if ammo.item_name.is_empty() {
+            return Err(AmmoBuildError::MissingField("item_name"));
+        }
+        if ammo.magazine_image.is_empty() {
+            return Err(AmmoBuildError::MissingField("magazine_image"));
+        }
+        ammo.index = self.index.ok_or(AmmoBuildError::MissingField("index"))?;
+        ammo.speed = self.speed.ok_or(AmmoBuildError::MissingField("speed"))?;
+
+        check_range("speed", ammo.speed, 0.0, f32::MAX)?;
+        check_range("bullet_height", ammo.bullet_height, 0.0, f32::MAX)?;
+        check_range("ap_drag", ammo.ap_drag, 0.0, 1.0)?;
+        check_range("ttl", ammo.ttl, 0.0, f32::MAX)?;
+        check_range("shop_rarity", ammo.shop_rarity, 0.0, 1.0)?;
+        check_range("fire_delay", ammo.fire_delay, 0.0, 1.0)?;
+
+        Ok(ammo)
+    }
+}
+
+#[cfg(test)]
+impl_test_ammo!(Ammo);
+
+#[cfg(test)]
+impl AmmoBuilder {
+    /// Builds "AMMO_57" and "AMMO_85" at indices 1 and 2, for the tests of tables.
+    pub(crate) fn test_ammos() -> alloc::vec::Vec<Ammo> {
+        alloc::vec![Self::test_ammo("AMMO_57", 1), Self::test_ammo("AMMO_85", 2)]
+    }
+}
+
+impl Default for AmmoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::{AmmoFields, AmmoSign, Reticle, ShellBehavior};
+
+    fn required() -> AmmoBuilder {
+        AmmoBuilder::new()
+            .item_name("AMMO_CUSTOM")
+            .index(40)
+            .magazine_image("shell_57")
+            .speed(1000.0)
+    }
+
+    #[test]
+    fn build_with_defaults() {
+        let ammo = required().penetrative_power(20.0).build().unwrap();
+
+        assert_eq!(ammo.item_name, "AMMO_CUSTOM");
+        assert_eq!(ammo.index, 40);
+        assert_eq!(ammo.speed, 1000.0);
+        assert_eq!(ammo.penetrative_power, 20.0);
+        assert_eq!(ammo.reticle, 1);
+        assert_eq!(ammo.sign_ammo, "sign_ammo_unset");
+        assert_eq!(ammo.fire_delay, 0.5);
+        assert_eq!(ammo.padding_cch, 0);
+    }
+
+    #[test]
+    fn typed_setters() {
+        let ammo = required()
+            .reticle(Reticle::Rocket)
+            .caliber(ShellBehavior::Incendiary)
+            .sign_ammo(AmmoSign::Incendiary.as_str())
+            .build()
+            .unwrap();
+
+        assert_eq!(ammo.reticle, 3);
+        assert_eq!(ammo.shell_behavior(), ShellBehavior::Incendiary);
+        assert_eq!(ammo.ammo_sign(), AmmoSign::Incendiary);
+    }
+
+    #[test]
+    fn missing_fields() {
+        let result = AmmoBuilder::new().item_name("AMMO_CUSTOM").build();
+        assert_eq!(
+            result.unwrap_err(),
+            AmmoBuildError::MissingField("magazine_image")
+        );
+
+        let result = AmmoBuilder::new()
+            .item_name("AMMO_CUSTOM")
+            .magazine_image("shell_57")
+            .build();
+        assert_eq!(result.unwrap_err(), AmmoBuildError::MissingField("index"));
+    }
+
+    #[test]
+    fn out_of_range() {
+        let result = required().ap_drag(1.5).build();
+        assert_eq!(
+            result.unwrap_err(),
+            AmmoBuildError::OutOfRange {
+                field: "ap_drag",
+                value: 1.5
+            }
+        );
+    }
+}
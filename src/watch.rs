@@ -0,0 +1,267 @@
+//! Polls addresses of the game and reports their changes through channels,
+//! so overlays can react to money, fuel, or ammo changing without hooking the functions that change them.
+//!
+//! Every watch registered on a `Watcher` gets its own `Receiver`.
+//! `Watcher::poll` reads every watched range once, sending a `Change` for each one that differs from the last read.
+//! `Watcher::spawn` polls at a fixed interval on a background thread instead.
+//!
+//! ```ignore
+//! let mut watcher = Watcher::new(InProcess);
+//! let speed = watcher.watch_field::<Ammo>(ammo_address, "speed").unwrap();
+//! let watcher = watcher.spawn(Duration::from_millis(100));
+//!
+//! for change in speed.iter() {
+//!     println!("speed: {} -> {}", change.old_value(), change.new_value());
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::general::hexdump::{Annotated, FieldKind};
+use crate::memory::MemorySource;
+
+/// A change of a watched range of memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The name the range was watched under.
+    pub name: String,
+    /// The address of the range.
+    pub address: u64,
+    /// How the bytes are decoded, `FieldKind::Bytes` for ranges watched with `Watcher::watch`.
+    pub kind: FieldKind,
+    /// The bytes read by the previous poll.
+    pub old: Vec<u8>,
+    /// The bytes read by this poll.
+    pub new: Vec<u8>,
+    /// When the change was seen.
+    pub timestamp: SystemTime,
+}
+
+impl Change {
+    /// Returns the old value decoded according to `kind`, or nothing for plain bytes.
+    pub fn old_value(&self) -> String {
+        self.kind.decode(&self.old)
+    }
+
+    /// Returns the new value decoded according to `kind`, or nothing for plain bytes.
+    pub fn new_value(&self) -> String {
+        self.kind.decode(&self.new)
+    }
+}
+
+/// A watched range of memory.
+struct Watch {
+    name: String,
+    address: u64,
+    kind: FieldKind,
+    /// The bytes read by the last successful poll, `None` before the first one.
+    last: Option<Vec<u8>>,
+    sender: Sender<Change>,
+}
+
+/// Polls watched ranges of a `MemorySource`, sending their changes to the receivers returned when watching them.
+pub struct Watcher<M> {
+    memory: M,
+    watches: Vec<Watch>,
+}
+
+impl<M: MemorySource> Watcher<M> {
+    /// Creates a watcher without watches.
+    pub fn new(memory: M) -> Self {
+        Self {
+            memory,
+            watches: Vec::new(),
+        }
+    }
+
+    /// Watches the `size` bytes at `address`.
+    pub fn watch(&mut self, name: &str, address: u64, size: usize) -> Receiver<Change> {
+        self.insert(name, address, FieldKind::Bytes(size))
+    }
+
+    /// Watches the field named `field` of the `T` at `address`, so changes can be decoded.
+    ///
+    /// Returns `None` if `T` has no such field.
+    pub fn watch_field<T: Annotated>(
+        &mut self,
+        address: u64,
+        field: &str,
+    ) -> Option<Receiver<Change>> {
+        let info = T::fields().into_iter().find(|info| info.name == field)?;
+        Some(self.insert(info.name, address + info.offset as u64, info.kind))
+    }
+
+    fn insert(&mut self, name: &str, address: u64, kind: FieldKind) -> Receiver<Change> {
+        let (sender, receiver) = mpsc::channel();
+        self.watches.push(Watch {
+            name: name.to_string(),
+            address,
+            kind,
+            last: None,
+            sender,
+        });
+        receiver
+    }
+
+    /// Returns the number of watches, including those whose receiver was dropped but haven't changed since.
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    /// Returns true if nothing is watched.
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Reads every watched range, sending a `Change` for each one that differs from the previous read.
+    ///
+    /// The first read of a range only records it. Ranges that can't be read are skipped,
+    /// and compared against their last readable value once they can be read again.
+    /// A watch whose receiver was dropped is removed the next time its range changes,
+    /// as a channel can only tell that its receiver is gone when sending to it.
+    ///
+    /// Returns the number of changes sent.
+    pub fn poll(&mut self) -> usize {
+        let mut sent = 0;
+        self.watches.retain_mut(|watch| {
+            let mut new = vec![0u8; watch.kind.size()];
+            if self.memory.read(watch.address, &mut new).is_err() {
+                return true;
+            }
+
+            let Some(old) = watch.last.replace(new.clone()) else {
+                return true;
+            };
+            if old == new {
+                return true;
+            }
+
+            let change = Change {
+                name: watch.name.clone(),
+                address: watch.address,
+                kind: watch.kind,
+                old,
+                new,
+                timestamp: SystemTime::now(),
+            };
+            let received = watch.sender.send(change).is_ok();
+            if received {
+                sent += 1;
+            }
+            received
+        });
+        sent
+    }
+
+    /// Polls every `interval` on a background thread, until the returned handle is stopped or dropped.
+    pub fn spawn(mut self, interval: Duration) -> WatcherHandle
+    where
+        M: Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                self.poll();
+                std::thread::sleep(interval);
+            }
+        });
+
+        WatcherHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// A `Watcher` polling on a background thread, stopped when dropped.
+pub struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatcherHandle {
+    /// Stops polling, waiting for the current poll to finish.
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(all(test, any(windows, target_os = "linux")))]
+mod tests {
+    use super::*;
+    use crate::general::raw::{from_bytes, PointerPolicy};
+    use crate::memory::InProcess;
+    use crate::v1_163::Ammo;
+    use std::sync::atomic::AtomicU32;
+
+    #[test]
+    fn poll_sends_changes() {
+        let mut ammo: Box<Ammo> =
+            Box::new(from_bytes(&[0u8; 0x188], &PointerPolicy::Zero).unwrap());
+        ammo.speed = 1000.0;
+        let address = &*ammo as *const Ammo as u64;
+
+        let mut watcher = Watcher::new(InProcess);
+        let speed = watcher.watch_field::<Ammo>(address, "speed").unwrap();
+        let bytes = watcher.watch("ammo", address, 0x188);
+        assert!(watcher.watch_field::<Ammo>(address, "fuel").is_none());
+
+        // The first poll only records the values.
+        assert_eq!(watcher.poll(), 0);
+        ammo.speed = 1250.5;
+        assert_eq!(watcher.poll(), 2);
+        assert_eq!(watcher.poll(), 0);
+
+        let change = speed.try_recv().unwrap();
+        assert_eq!(change.name, "speed");
+        assert_eq!(
+            (change.old_value(), change.new_value()),
+            ("1000".to_string(), "1250.5".to_string())
+        );
+        assert!(speed.try_recv().is_err());
+        assert_eq!(bytes.try_recv().unwrap().new.len(), 0x188);
+
+        // Dropping a receiver removes its watch once its range changes.
+        drop(bytes);
+        assert_eq!(watcher.poll(), 0);
+        assert_eq!(watcher.len(), 2);
+        ammo.speed = 0.0;
+        assert_eq!(watcher.poll(), 1);
+        assert_eq!(watcher.len(), 1);
+    }
+
+    #[test]
+    fn spawned_watcher_polls_in_background() {
+        let value: &'static AtomicU32 = Box::leak(Box::new(AtomicU32::new(1)));
+        let mut watcher = Watcher::new(InProcess);
+        let changes = watcher.watch("value", value.as_ptr() as u64, 4);
+        let handle = watcher.spawn(Duration::from_millis(1));
+
+        // Let the first poll record the value before changing it.
+        std::thread::sleep(Duration::from_millis(50));
+        value.store(2, Ordering::Relaxed);
+        let change = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change.new, 2u32.to_le_bytes());
+
+        handle.stop();
+        assert!(changes.recv().is_err());
+    }
+}